@@ -3,15 +3,21 @@
 //! This module provides subcommands and a command matcher related to completion.
 
 use anyhow::Result;
-use clap::{self, App, Arg, ArgMatches, Shell, SubCommand};
+use clap::{self, App, AppSettings, Arg, ArgMatches, Shell, SubCommand};
 use log::debug;
 
+/// Represents the Nushell shell, which is not part of the clap's shells.
+pub const NUSHELL: &str = "nushell";
+
 type OptionShell<'a> = Option<&'a str>;
+type ValuesKind<'a> = &'a str;
 
 /// Completion commands.
 pub enum Command<'a> {
     /// Generate completion script for the given shell slice.
     Generate(OptionShell<'a>),
+    /// Print the dynamic completion values of the given kind (accounts or mailboxes).
+    Values(ValuesKind<'a>),
 }
 
 /// Completion command matcher.
@@ -23,15 +29,31 @@ pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Command<'a>>> {
         return Ok(Some(Command::Generate(shell)));
     };
 
+    if let Some(m) = m.subcommand_matches("__complete") {
+        debug!("complete command matched");
+        let kind = m.value_of("kind").unwrap();
+        debug!("kind: `{}`", kind);
+        return Ok(Some(Command::Values(kind)));
+    };
+
     Ok(None)
 }
 
 /// Completion subcommands.
 pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
-    vec![SubCommand::with_name("completion")
-        .aliases(&["completions", "compl", "compe", "comp"])
-        .about("Generates the completion script for the given shell")
-        .args(&[Arg::with_name("shell")
-            .possible_values(&Shell::variants()[..])
-            .required(true)])]
+    vec![
+        SubCommand::with_name("completion")
+            .aliases(&["completions", "compl", "compe", "comp"])
+            .about("Generates the completion script for the given shell")
+            .args(&[Arg::with_name("shell")
+                .possible_values(&[&Shell::variants()[..], &[NUSHELL]].concat())
+                .required(true)]),
+        // Used by the completion scripts to complete values that depend on the user config.
+        SubCommand::with_name("__complete")
+            .setting(AppSettings::Hidden)
+            .about("Prints dynamic completion values")
+            .args(&[Arg::with_name("kind")
+                .possible_values(&["accounts", "mailboxes"])
+                .required(true)]),
+    ]
 }
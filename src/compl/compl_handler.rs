@@ -1,16 +1,327 @@
 //! Module related to completion handling.
 //!
-//! This module gathers all completion commands.  
+//! This module gathers all completion commands.
 
 use anyhow::{anyhow, Context, Result};
-use clap::{App, Shell};
-use std::{io, str::FromStr};
+use clap::{self, App, AppSettings, Shell};
+use std::{
+    io::{self, Write},
+    str::FromStr,
+};
+
+use crate::{
+    compl::compl_arg::NUSHELL, config::Config, domain::imap::ImapServiceInterface,
+    output::PrinterService,
+};
+
+/// Bash hook completing accounts and mailboxes by calling back `himalaya __complete`.
+const BASH_HOOK: &str = r#"
+_himalaya_dynamic() {
+    local cur prev account i
+    cur="${COMP_WORDS[COMP_CWORD]}"
+    prev="${COMP_WORDS[COMP_CWORD-1]}"
+    account=""
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${COMP_WORDS[i]}" in
+            -a|--account) account="${COMP_WORDS[i+1]}" ;;
+        esac
+    done
+
+    case "${prev}" in
+        -a|--account)
+            COMPREPLY=($(compgen -W "$(himalaya __complete accounts 2>/dev/null)" -- "${cur}"))
+            return 0
+            ;;
+        -m|--mailbox)
+            COMPREPLY=($(compgen -W "$(himalaya ${account:+--account "${account}"} __complete mailboxes 2>/dev/null)" -- "${cur}"))
+            return 0
+            ;;
+    esac
+
+    _himalaya "$@"
+}
+
+complete -F _himalaya_dynamic -o bashdefault -o default himalaya
+"#;
+
+/// Zsh hook completing accounts and mailboxes by calling back `himalaya __complete`.
+const ZSH_HOOK: &str = r#"
+_himalaya_dynamic() {
+    local -a values account
+    local i
+    for ((i = 2; i < CURRENT; i++)); do
+        case "${words[i]}" in
+            -a|--account) account=(--account "${words[i+1]}") ;;
+        esac
+    done
+
+    case "${words[CURRENT-1]}" in
+        -a|--account)
+            values=(${(f)"$(himalaya __complete accounts 2>/dev/null)"})
+            compadd -a values
+            return
+            ;;
+        -m|--mailbox)
+            values=(${(f)"$(himalaya $account __complete mailboxes 2>/dev/null)"})
+            compadd -a values
+            return
+            ;;
+    esac
+
+    _himalaya "$@"
+}
+
+compdef _himalaya_dynamic himalaya
+"#;
+
+/// Fish hook completing accounts and mailboxes by calling back `himalaya __complete`.
+const FISH_HOOK: &str = r#"
+complete -c himalaya -s a -l account -x -a '(himalaya __complete accounts 2>/dev/null)'
+complete -c himalaya -s m -l mailbox -x -a '(himalaya (__fish_himalaya_account) __complete mailboxes 2>/dev/null)'
+
+function __fish_himalaya_account
+    set -l tokens (commandline -opc)
+    for i in (seq (count $tokens))
+        if contains -- $tokens[$i] -a --account
+            and test $i -lt (count $tokens)
+            echo --account $tokens[(math $i + 1)]
+        end
+    end
+end
+"#;
 
 /// Generate completion script from the given [`clap::App`] for the given shell slice.
 pub fn generate<'a>(mut app: App<'a, 'a>, shell: Option<&'a str>) -> Result<()> {
+    if shell == Some(NUSHELL) {
+        return gen_nushell_completions(&app, &mut io::stdout());
+    }
+
     let shell = Shell::from_str(shell.unwrap_or_default())
         .map_err(|err| anyhow!(err))
         .context("cannot parse shell")?;
     app.gen_completions_to("himalaya", shell, &mut io::stdout());
+
+    // Static completion scripts cannot complete values coming from the user config, so hooks
+    // calling back `himalaya` are appended for the shells supporting it.
+    let hook = match shell {
+        Shell::Bash => BASH_HOOK,
+        Shell::Zsh => ZSH_HOOK,
+        Shell::Fish => FISH_HOOK,
+        _ => "",
+    };
+    io::stdout()
+        .write_all(hook.as_bytes())
+        .context("cannot write completion hook")
+}
+
+/// Generate a Nushell completion script from the given [`clap::App`]. Clap does not provide any
+/// Nushell generator, so the script is built by declaring one `extern` per (sub)command, from the
+/// help of the (sub)command as rendered by clap.
+fn gen_nushell_completions(app: &App, buf: &mut dyn Write) -> Result<()> {
+    let app = app
+        .clone()
+        .global_setting(AppSettings::ColorNever)
+        .set_term_width(0);
+    writeln!(buf, "module himalaya_completions {{")?;
+    gen_nushell_extern(&app, &[], buf)?;
+    writeln!(buf, "}}")?;
+    writeln!(buf)?;
+    writeln!(buf, "use himalaya_completions *")?;
+    Ok(())
+}
+
+/// Represents the help of a (sub)command, split by section.
+#[derive(Debug, Default)]
+struct Help {
+    about: Option<String>,
+    usage: String,
+    flags: Vec<String>,
+    opts: Vec<String>,
+    args: Vec<String>,
+    subcmds: Vec<String>,
+}
+
+impl From<&str> for Help {
+    fn from(text: &str) -> Self {
+        let mut help = Self::default();
+        let mut section = "";
+        for line in text.lines() {
+            if !line.starts_with(' ') && line.ends_with(':') {
+                section = line;
+                continue;
+            }
+            let entry = line.trim();
+            // Entries are indented by 4 or 8 spaces, deeper lines continue their help.
+            if entry.is_empty() || line.len() - line.trim_start().len() > 8 {
+                continue;
+            }
+            match section {
+                "" if help.usage.is_empty() => help.about = Some(entry.to_owned()),
+                "USAGE:" => help.usage = entry.to_owned(),
+                "FLAGS:" => help.flags.push(entry.to_owned()),
+                "OPTIONS:" => help.opts.push(entry.to_owned()),
+                "ARGS:" => help.args.push(entry.to_owned()),
+                "SUBCOMMANDS:" => help.subcmds.push(entry.to_owned()),
+                _ => (),
+            }
+        }
+        help
+    }
+}
+
+/// Splits the given help entry into its specification (like `-p, --page <PAGE>`) and the first
+/// line of its help.
+fn split_entry(entry: &str) -> (&str, &str) {
+    match entry.find("  ") {
+        Some(i) => (&entry[..i], entry[i..].trim()),
+        None => (entry, ""),
+    }
+}
+
+/// Builds the Nushell switch of the given flag or option specification.
+fn nushell_switch(spec: &str) -> String {
+    let words = spec
+        .split(|c| c == ' ' || c == ',')
+        .filter(|w| !w.is_empty());
+    let (mut long, mut short) = (None, None);
+    for word in words {
+        if let Some(name) = word.strip_prefix("--") {
+            long = Some(name);
+        } else if let Some(name) = word.strip_prefix('-') {
+            short = Some(name);
+        }
+    }
+    match (long, short) {
+        (Some(long), Some(short)) => format!("--{}(-{})", long, short),
+        (Some(long), None) => format!("--{}", long),
+        (None, Some(short)) => format!("-{}", short),
+        (None, None) => String::new(),
+    }
+}
+
+/// Builds the Nushell parameter of the given positional argument specification, like `<SEQ>...`,
+/// the usage of the command telling whether it is optional.
+fn nushell_param(spec: &str, usage: &str) -> String {
+    let name = spec
+        .trim_end_matches("...")
+        .trim_matches(|c| c == '<' || c == '>');
+    let param = name
+        .to_lowercase()
+        .replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+    if spec.ends_with("...") {
+        format!("...{}", param)
+    } else if usage.contains(&format!("[{}]", name)) {
+        format!("{}?", param)
+    } else {
+        param
+    }
+}
+
+/// Generate the Nushell `extern` of the given (sub)command path, then recurse over its
+/// subcommands. The help of the (sub)command is obtained the way users do, with `--help`.
+fn gen_nushell_extern(app: &App, path: &[&str], buf: &mut dyn Write) -> Result<()> {
+    let args = ["himalaya"].iter().chain(path).chain(&["--help"]).copied();
+    let text = match app.clone().get_matches_from_safe(args) {
+        Err(err) if err.kind == clap::ErrorKind::HelpDisplayed => err.message,
+        Err(err) => return Err(anyhow!(err.message)),
+        Ok(_) => return Err(anyhow!("cannot get help of {:?}", path)),
+    };
+    let help = Help::from(text.as_str());
+    let comment = |text: &str| {
+        if text.is_empty() {
+            String::new()
+        } else {
+            format!("  # {}", text)
+        }
+    };
+
+    writeln!(buf)?;
+    if let Some(about) = help.about.as_ref() {
+        writeln!(buf, "  # {}", about)?;
+    }
+    let cmd = ["himalaya"].iter().chain(path).copied().collect::<Vec<_>>();
+    writeln!(buf, r#"  export extern "{}" ["#, cmd.join(" "))?;
+    for flag in help.flags.iter() {
+        let (spec, text) = split_entry(flag);
+        writeln!(buf, "    {}{}", nushell_switch(spec), comment(text))?;
+    }
+    for opt in help.opts.iter() {
+        let (spec, text) = split_entry(opt);
+        writeln!(buf, "    {}: string{}", nushell_switch(spec), comment(text))?;
+    }
+    for arg in help.args.iter() {
+        let (spec, text) = split_entry(arg);
+        let param = nushell_param(spec, &help.usage);
+        writeln!(buf, "    {}: string{}", param, comment(text))?;
+    }
+    writeln!(buf, "  ]")?;
+
+    for subcmd in help.subcmds.iter() {
+        let (name, _) = split_entry(subcmd);
+        if name != "help" {
+            let path = path.iter().copied().chain(Some(name)).collect::<Vec<_>>();
+            gen_nushell_extern(app, &path, buf)?;
+        }
+    }
+
     Ok(())
 }
+
+/// Print the values used by the dynamic completion hooks, one per line.
+pub fn values<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    kind: &str,
+    config: &Config,
+    printer: &mut Printer,
+    imap: &'a mut ImapService,
+) -> Result<()> {
+    let mut values: Vec<String> = match kind {
+        "accounts" => config.accounts.keys().cloned().collect(),
+        "mailboxes" => imap
+            .fetch_mboxes()?
+            .iter()
+            .map(|mbox| mbox.name.to_string())
+            .collect(),
+        kind => return Err(anyhow!(r#"cannot complete unknown values "{}""#, kind)),
+    };
+    values.sort();
+    printer.print(values.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{App, Arg, SubCommand};
+
+    use super::*;
+
+    #[test]
+    fn it_should_gen_nushell_externs_from_help() {
+        let app = App::new("himalaya")
+            .about("Command-line interface for email management")
+            .subcommand(
+                SubCommand::with_name("list")
+                    .about("Lists messages")
+                    .arg(
+                        Arg::with_name("max-width")
+                            .help("Defines a maximum width")
+                            .long("max-width")
+                            .short("w")
+                            .value_name("INT"),
+                    )
+                    .arg(
+                        Arg::with_name("seq")
+                            .help("Message sequence number")
+                            .multiple(true),
+                    ),
+            );
+        let mut buf = Vec::new();
+        gen_nushell_completions(&app, &mut buf).unwrap();
+        let script = String::from_utf8(buf).unwrap();
+
+        assert!(script.contains(r#"export extern "himalaya" ["#));
+        assert!(script.contains(r#"export extern "himalaya list" ["#));
+        assert!(script.contains("# Lists messages"));
+        assert!(script.contains("--max-width(-w): string  # Defines a maximum width"));
+        assert!(script.contains("...seq: string  # Message sequence number"));
+        assert!(!script.contains(r#""himalaya help""#));
+    }
+}
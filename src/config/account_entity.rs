@@ -1,22 +1,95 @@
 use anyhow::{anyhow, Context, Error, Result};
 use lettre::transport::smtp::authentication::Credentials as SmtpCredentials;
 use log::{debug, trace};
-use std::{convert::TryFrom, env, fs, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    env, fs,
+    path::PathBuf,
+    sync::Arc,
+};
 
 use crate::{
-    config::{Config, DEFAULT_PAGE_SIZE, DEFAULT_SIG_DELIM},
-    output::run_cmd,
+    config::{
+        Config, ConfigNotifyMboxEntry, DEFAULT_ATTACHMENT_KEYWORDS,
+        DEFAULT_ATTACHMENT_UPLOAD_THRESHOLD, DEFAULT_CONFIRM_THRESHOLD, DEFAULT_EXPORT_PDF_CMD,
+        DEFAULT_JUNK_FOLDER, DEFAULT_MAILBOX, DEFAULT_MSG_SIZE_LIMIT, DEFAULT_OUTBOX_FOLDER,
+        DEFAULT_PAGE_SIZE, DEFAULT_PICKER_CMD, DEFAULT_PRINT_CMD, DEFAULT_REPLY_ATTRIBUTION,
+        DEFAULT_REPLY_QUOTE_PREFIX, DEFAULT_SENT_FOLDER, DEFAULT_SIEVE_PORT, DEFAULT_SIG_DELIM,
+        DEFAULT_SNOOZED_FOLDER, DEFAULT_TRASH_FOLDER,
+    },
+    domain::{
+        msg::msg_utils, Blocklist, DateFmt, DkimConfig, EnvelopeDate, EnvelopeHighlight, Filter,
+        MdnPolicy, RateLimiter, RetentionPolicy,
+    },
+    output::{run_cmd, ExitCode},
 };
 
 /// Represent a user account.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Account {
     pub name: String,
     pub from: String,
     pub downloads_dir: PathBuf,
     pub sig: Option<String>,
+    pub sent_folder: String,
+    pub trash_folder: String,
+    pub junk_folder: String,
+    pub snoozed_folder: String,
+    pub outbox_folder: String,
+    pub identities: Vec<Identity>,
     pub default_page_size: usize,
+    pub default_mailbox: String,
+    /// The default arguments of commands, by command name.
+    pub default_args: HashMap<String, Vec<String>>,
     pub watch_cmds: Vec<String>,
+    /// The mailboxes watched together by the notify mode, by name.
+    pub notify_mboxes: BTreeMap<String, ConfigNotifyMboxEntry>,
+    pub filters: Vec<Filter>,
+    /// The Lua script defining the hooks.
+    pub hooks_script: Option<PathBuf>,
+    /// The command notifying new messages, used by the hooks as well.
+    pub notify_cmd: Option<String>,
+    /// The retention policies, by mailbox name.
+    pub retention: BTreeMap<String, RetentionPolicy>,
+    pub mdn_policy: MdnPolicy,
+    pub confirm_threshold: usize,
+    /// The size in MB above which outgoing messages are refused, 0 meaning no limit.
+    pub msg_size_limit: usize,
+    /// The command uploading attachments above the threshold, in MB, and printing a share URL.
+    pub attachment_upload_cmd: Option<String>,
+    pub attachment_upload_threshold: usize,
+    /// The command converting HTML exports to PDF, and the command printing messages.
+    pub export_pdf_cmd: String,
+    pub print_cmd: String,
+    /// The command picking envelopes.
+    pub picker_cmd: String,
+    /// Whether messages are always fetched with `BODY.PEEK`.
+    pub peek: bool,
+    /// Whether commands address messages by UID instead of sequence number.
+    pub uid: bool,
+    /// Whether the domains of recipients must have MX records.
+    pub verify_recipient_mx: bool,
+    /// The keywords announcing an attachment, all languages together.
+    pub attachment_keywords: Vec<String>,
+    pub format_flowed: bool,
+    /// The quoting style of replies.
+    pub reply_attribution: String,
+    pub reply_quote_prefix: String,
+    pub reply_quote_html: bool,
+    pub reply_strip_signature: bool,
+    /// How envelopes stand out in listings.
+    pub envelope_highlight: EnvelopeHighlight,
+    /// The date shown in listings and used to sort them.
+    pub list_date: EnvelopeDate,
+    /// The extra headers fetched along with envelopes.
+    pub list_headers: Vec<String>,
+    /// The format of the dates shown to the user.
+    pub date_fmt: DateFmt,
+    /// The local cache of the message notes, next to the config file.
+    pub notes_path: Option<PathBuf>,
+    /// The path of the certificate fingerprints trusted on first use.
+    pub known_hosts_path: Option<PathBuf>,
     pub default: bool,
     pub email: String,
 
@@ -24,6 +97,14 @@ pub struct Account {
     pub imap_port: u16,
     pub imap_starttls: bool,
     pub imap_insecure: bool,
+    pub imap_tofu: bool,
+    /// The maximum number of simultaneous IMAP sessions, 0 meaning no limit.
+    pub imap_max_connections: usize,
+    /// The maximum number of IMAP commands per minute, 0 meaning no limit.
+    pub imap_max_commands_per_minute: usize,
+    /// The rate limiter enforcing the limits above, shared by all the IMAP sessions of the
+    /// account (and its clones).
+    pub imap_rate_limiter: Arc<RateLimiter>,
     pub imap_login: String,
     pub imap_passwd_cmd: String,
 
@@ -33,9 +114,70 @@ pub struct Account {
     pub smtp_insecure: bool,
     pub smtp_login: String,
     pub smtp_passwd_cmd: String,
+    pub smtp_direct_mx: bool,
+    /// The DKIM signer of outgoing messages.
+    pub dkim: Option<DkimConfig>,
+
+    pub sieve_host: String,
+    pub sieve_port: u16,
+    pub sieve_starttls: bool,
+}
+
+/// Represent an additional identity of an account.
+#[derive(Debug, Default, Clone)]
+pub struct Identity {
+    pub from: Option<String>,
+    pub email: String,
+    pub sig: Option<String>,
+    pub sent_folder: Option<String>,
 }
 
 impl Account {
+    /// Build a copy of the account using the identity matching the given email. The account
+    /// email is accepted as well and gives an unchanged copy.
+    pub fn with_identity(&self, email: &str) -> Result<Account> {
+        if self.email.eq_ignore_ascii_case(email) {
+            return Ok(self.clone());
+        }
+
+        let identity = self
+            .identities
+            .iter()
+            .find(|identity| identity.email.eq_ignore_ascii_case(email))
+            .ok_or_else(|| anyhow!(r#"cannot find identity "{}""#, email))?;
+        debug!(r#"use identity "{}""#, identity.email);
+
+        let mut account = self.clone();
+        account.email = identity.email.to_owned();
+        if let Some(ref from) = identity.from {
+            account.from = from.to_owned();
+        }
+        if let Some(ref sig) = identity.sig {
+            account.sig = Some(sig.to_owned());
+        }
+        if let Some(ref sent_folder) = identity.sent_folder {
+            account.sent_folder = sent_folder.to_owned();
+        }
+        Ok(account)
+    }
+
+    /// Build a copy of the account using the first identity found in the given emails, for
+    /// example the recipients of a message being replied. Falls back to an unchanged copy.
+    pub fn with_matching_identity<'b, E: IntoIterator<Item = &'b str>>(
+        &self,
+        emails: E,
+    ) -> Account {
+        emails
+            .into_iter()
+            .find_map(|email| {
+                self.identities
+                    .iter()
+                    .find(|identity| identity.email.eq_ignore_ascii_case(email))
+            })
+            .and_then(|identity| self.with_identity(&identity.email).ok())
+            .unwrap_or_else(|| self.clone())
+    }
+
     pub fn address(&self) -> String {
         let name = &self.from;
         let has_special_chars = "()<>[]:;@.,".contains(|special_char| name.contains(special_char));
@@ -80,12 +222,14 @@ impl<'a> TryFrom<(&'a Config, Option<&str>)> for Account {
                 .iter()
                 .find(|(_, account)| account.default.unwrap_or(false))
                 .map(|(name, account)| (name.to_owned(), account))
-                .ok_or_else(|| anyhow!("cannot find default account")),
+                .ok_or_else(|| ExitCode::NotFound.err("cannot find default account")),
             Some(name) => config
                 .accounts
                 .get(name)
                 .map(|account| (name.to_owned(), account))
-                .ok_or_else(|| anyhow!(r#"cannot find account "{}""#, name)),
+                .ok_or_else(|| {
+                    ExitCode::NotFound.err(format!(r#"cannot find account "{}""#, name))
+                }),
         }?;
 
         let downloads_dir = account
@@ -117,35 +261,295 @@ impl<'a> TryFrom<(&'a Config, Option<&str>)> for Account {
             .as_ref()
             .or_else(|| config.signature_delimiter.as_ref())
             .unwrap_or(&default_sig_delim);
-        let sig = account
-            .signature
+        let build_sig = |sig: Option<&String>| {
+            sig.and_then(|sig| shellexpand::full(sig).ok())
+                .map(String::from)
+                .and_then(|sig| fs::read_to_string(sig).ok())
+                .or_else(|| sig.map(|sig| sig.to_owned()))
+                .map(|sig| format!("{}{}", sig_delim, sig.trim_end()))
+        };
+        let sig = build_sig(
+            account
+                .signature
+                .as_ref()
+                .or_else(|| config.signature.as_ref()),
+        );
+
+        let junk_folder = account
+            .junk_folder
+            .as_deref()
+            .unwrap_or(DEFAULT_JUNK_FOLDER)
+            .to_owned();
+
+        // Messages of blocked senders are moved to the junk folder before the other filters apply
+        let blocklist = match config.blocklist_path() {
+            Some(path) => Blocklist::load(path)?,
+            None => Blocklist::default(),
+        };
+        let filters = blocklist
+            .to_filter(&junk_folder)
+            .into_iter()
+            .chain(
+                account
+                    .filters
+                    .as_ref()
+                    .or_else(|| config.filters.as_ref())
+                    .cloned()
+                    .unwrap_or_default(),
+            )
+            .collect();
+
+        let hooks_script = account
+            .hooks_script
+            .as_deref()
+            .or_else(|| config.hooks_script.as_deref())
+            .map(|path| {
+                shellexpand::full(path)
+                    .map(|path| PathBuf::from(path.to_string()))
+                    .context(format!(r#"cannot expand hooks script path "{}""#, path))
+            })
+            .transpose()?;
+
+        let flagged_color = match account
+            .list_flagged_color
+            .as_deref()
+            .or_else(|| config.list_flagged_color.as_deref())
+        {
+            None => EnvelopeHighlight::default().flagged_color,
+            Some("none") => None,
+            Some(color) => Some(
+                color
+                    .parse()
+                    .context(format!(r#"cannot parse flagged color "{}""#, color))?,
+            ),
+        };
+        let week_start = match account
+            .week_start
+            .as_deref()
+            .or_else(|| config.week_start.as_deref())
+        {
+            None => None,
+            Some(day) => Some(
+                day.parse()
+                    .map_err(|_| anyhow!(r#"cannot parse week start "{}""#, day))?,
+            ),
+        };
+        let date_fmt = DateFmt::new(
+            account
+                .date_locale
+                .as_deref()
+                .or_else(|| config.date_locale.as_deref()),
+            account
+                .date_format
+                .as_deref()
+                .or_else(|| config.date_format.as_deref()),
+            account
+                .date_relative
+                .or(config.date_relative)
+                .unwrap_or_default(),
+            week_start,
+        );
+        let envelope_highlight = EnvelopeHighlight {
+            unseen_bold: account
+                .list_unseen_bold
+                .or(config.list_unseen_bold)
+                .unwrap_or(true),
+            flagged_color,
+        };
+        let list_headers = account
+            .list_headers
             .as_ref()
-            .or_else(|| config.signature.as_ref());
-        let sig = sig
-            .and_then(|sig| shellexpand::full(sig).ok())
-            .map(String::from)
-            .and_then(|sig| fs::read_to_string(sig).ok())
-            .or_else(|| sig.map(|sig| sig.to_owned()))
-            .map(|sig| format!("{}{}", sig_delim, sig.trim_end()));
+            .or_else(|| config.list_headers.as_ref())
+            .cloned()
+            .unwrap_or_default();
+        // Names are sent to the server in the fetch command, they must be valid header names.
+        if let Some(name) = list_headers
+            .iter()
+            .find(|name| !msg_utils::is_header_name(name))
+        {
+            return Err(ExitCode::Parse.err(format!(
+                r#"cannot use "{}" as list header: expected printable ASCII characters except ":""#,
+                name
+            )));
+        }
+
+        let identities = account
+            .identities
+            .as_ref()
+            .map(|identities| {
+                identities
+                    .iter()
+                    .map(|identity| Identity {
+                        from: identity.name.to_owned(),
+                        email: identity.email.to_owned(),
+                        sig: build_sig(identity.signature.as_ref()),
+                        sent_folder: identity.sent_folder.to_owned(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
 
         let account = Account {
             name,
             from: account.name.as_ref().unwrap_or(&config.name).to_owned(),
             downloads_dir,
             sig,
+            sent_folder: account
+                .sent_folder
+                .as_deref()
+                .unwrap_or(DEFAULT_SENT_FOLDER)
+                .to_owned(),
+            trash_folder: account
+                .trash_folder
+                .as_deref()
+                .unwrap_or(DEFAULT_TRASH_FOLDER)
+                .to_owned(),
+            junk_folder,
+            snoozed_folder: account
+                .snoozed_folder
+                .as_deref()
+                .unwrap_or(DEFAULT_SNOOZED_FOLDER)
+                .to_owned(),
+            outbox_folder: account
+                .outbox_folder
+                .as_deref()
+                .unwrap_or(DEFAULT_OUTBOX_FOLDER)
+                .to_owned(),
+            identities,
             default_page_size,
+            default_mailbox: account
+                .default_mailbox
+                .as_deref()
+                .or_else(|| config.default_mailbox.as_deref())
+                .unwrap_or(DEFAULT_MAILBOX)
+                .to_owned(),
+            default_args: config
+                .default_args
+                .iter()
+                .chain(account.default_args.iter())
+                .flatten()
+                .map(|(cmd, args)| {
+                    let args = args.split_whitespace().map(String::from).collect();
+                    (cmd.to_owned(), args)
+                })
+                .collect(),
             watch_cmds: account
                 .watch_cmds
                 .as_ref()
                 .or_else(|| config.watch_cmds.as_ref())
                 .unwrap_or(&vec![])
                 .to_owned(),
+            notify_mboxes: account.notify_mailboxes.clone().unwrap_or_default(),
+            filters,
+            hooks_script,
+            notify_cmd: config.notify_cmd.to_owned(),
+            retention: account
+                .retention
+                .as_ref()
+                .or_else(|| config.retention.as_ref())
+                .cloned()
+                .unwrap_or_default(),
+            mdn_policy: account.mdn_policy.or(config.mdn_policy).unwrap_or_default(),
+            confirm_threshold: account
+                .confirm_threshold
+                .or(config.confirm_threshold)
+                .unwrap_or(DEFAULT_CONFIRM_THRESHOLD),
+            msg_size_limit: account
+                .msg_size_limit
+                .or(config.msg_size_limit)
+                .unwrap_or(DEFAULT_MSG_SIZE_LIMIT),
+            attachment_upload_cmd: account
+                .attachment_upload_cmd
+                .as_ref()
+                .or_else(|| config.attachment_upload_cmd.as_ref())
+                .cloned(),
+            attachment_upload_threshold: account
+                .attachment_upload_threshold
+                .or(config.attachment_upload_threshold)
+                .unwrap_or(DEFAULT_ATTACHMENT_UPLOAD_THRESHOLD),
+            export_pdf_cmd: account
+                .export_pdf_cmd
+                .as_deref()
+                .or_else(|| config.export_pdf_cmd.as_deref())
+                .unwrap_or(DEFAULT_EXPORT_PDF_CMD)
+                .to_owned(),
+            print_cmd: account
+                .print_cmd
+                .as_deref()
+                .or_else(|| config.print_cmd.as_deref())
+                .unwrap_or(DEFAULT_PRINT_CMD)
+                .to_owned(),
+            picker_cmd: account
+                .picker_cmd
+                .as_deref()
+                .or_else(|| config.picker_cmd.as_deref())
+                .unwrap_or(DEFAULT_PICKER_CMD)
+                .to_owned(),
+            peek: account.peek.or(config.peek).unwrap_or_default(),
+            uid: account.uid.or(config.uid).unwrap_or_default(),
+            verify_recipient_mx: account
+                .verify_recipient_mx
+                .or(config.verify_recipient_mx)
+                .unwrap_or_default(),
+            attachment_keywords: {
+                let mut keywords: BTreeMap<String, Vec<String>> = DEFAULT_ATTACHMENT_KEYWORDS
+                    .iter()
+                    .map(|(lang, keywords)| {
+                        let keywords = keywords.iter().map(|kw| kw.to_string()).collect();
+                        (lang.to_string(), keywords)
+                    })
+                    .collect();
+                for overrides in [&config.attachment_keywords, &account.attachment_keywords] {
+                    keywords.extend(overrides.clone().unwrap_or_default());
+                }
+                keywords
+                    .into_iter()
+                    .flat_map(|(_, keywords)| keywords)
+                    .collect()
+            },
+            format_flowed: account
+                .format_flowed
+                .or(config.format_flowed)
+                .unwrap_or_default(),
+            reply_attribution: account
+                .reply_attribution
+                .as_deref()
+                .or_else(|| config.reply_attribution.as_deref())
+                .unwrap_or(DEFAULT_REPLY_ATTRIBUTION)
+                .to_owned(),
+            reply_quote_prefix: account
+                .reply_quote_prefix
+                .as_deref()
+                .or_else(|| config.reply_quote_prefix.as_deref())
+                .unwrap_or(DEFAULT_REPLY_QUOTE_PREFIX)
+                .to_owned(),
+            reply_quote_html: account
+                .reply_quote_html
+                .or(config.reply_quote_html)
+                .unwrap_or(true),
+            reply_strip_signature: account
+                .reply_strip_signature
+                .or(config.reply_strip_signature)
+                .unwrap_or(true),
+            envelope_highlight,
+            list_date: account.list_date.or(config.list_date).unwrap_or_default(),
+            list_headers,
+            date_fmt,
+            notes_path: config.notes_path(),
+            known_hosts_path: config.known_hosts_path(),
             default: account.default.unwrap_or(false),
             email: account.email.to_owned(),
             imap_host: account.imap_host.to_owned(),
             imap_port: account.imap_port,
             imap_starttls: account.imap_starttls.unwrap_or_default(),
             imap_insecure: account.imap_insecure.unwrap_or_default(),
+            imap_tofu: account.imap_tofu.unwrap_or_default(),
+            imap_max_connections: account.imap_max_connections.unwrap_or_default(),
+            imap_max_commands_per_minute: account.imap_max_commands_per_minute.unwrap_or_default(),
+            imap_rate_limiter: Arc::new(RateLimiter::new(
+                account.imap_max_connections.unwrap_or_default(),
+                account.imap_max_commands_per_minute.unwrap_or_default(),
+            )),
             imap_login: account.imap_login.to_owned(),
             imap_passwd_cmd: account.imap_passwd_cmd.to_owned(),
             smtp_host: account.smtp_host.to_owned(),
@@ -154,6 +558,31 @@ impl<'a> TryFrom<(&'a Config, Option<&str>)> for Account {
             smtp_insecure: account.smtp_insecure.unwrap_or_default(),
             smtp_login: account.smtp_login.to_owned(),
             smtp_passwd_cmd: account.smtp_passwd_cmd.to_owned(),
+            smtp_direct_mx: account.smtp_direct_mx.unwrap_or_default(),
+            dkim: match (&account.dkim_selector, &account.dkim_private_key) {
+                (Some(selector), Some(key)) => Some(DkimConfig {
+                    domain: account.dkim_domain.to_owned().unwrap_or_else(|| {
+                        account
+                            .email
+                            .rsplit('@')
+                            .next()
+                            .unwrap_or_default()
+                            .to_owned()
+                    }),
+                    selector: selector.to_owned(),
+                    private_key_path: shellexpand::full(key)
+                        .map(|key| PathBuf::from(key.to_string()))
+                        .context(format!("cannot expand DKIM private key path {:?}", key))?,
+                }),
+                _ => None,
+            },
+            sieve_host: account
+                .sieve_host
+                .as_ref()
+                .unwrap_or(&account.imap_host)
+                .to_owned(),
+            sieve_port: account.sieve_port.unwrap_or(DEFAULT_SIEVE_PORT),
+            sieve_starttls: account.sieve_starttls.unwrap_or(true),
         };
 
         trace!("{:#?}", account);
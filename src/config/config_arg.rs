@@ -2,7 +2,124 @@
 //!
 //! This module provides arguments related to config.
 
-use clap::Arg;
+use anyhow::Result;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use log::debug;
+use std::{collections::HashMap, convert::TryFrom};
+
+use crate::config::ImportFormat;
+
+/// Represents the config commands.
+pub enum Cmd<'a> {
+    /// Imports the accounts of the config at the given path, written in the given format.
+    Import(ImportFormat, &'a str),
+}
+
+/// Config command matcher.
+pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Cmd<'a>>> {
+    if let Some(m) = m
+        .subcommand_matches("config")
+        .and_then(|m| m.subcommand_matches("import"))
+    {
+        debug!("config import command matched");
+        let format = ImportFormat::try_from(m.value_of("from").unwrap())?;
+        debug!("format: {:?}", format);
+        let path = m.value_of("path").unwrap();
+        debug!("path: {}", path);
+        return Ok(Some(Cmd::Import(format, path)));
+    }
+
+    Ok(None)
+}
+
+/// Config subcommands.
+pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
+    vec![SubCommand::with_name("config")
+        .about("Manages the config")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Prints the accounts of a mbsync or OfflineIMAP config as himalaya accounts")
+                .args(&[
+                    Arg::with_name("from")
+                        .long("from")
+                        .help("Specifies the format of the config to import")
+                        .value_name("FORMAT")
+                        .possible_values(&["mbsyncrc", "offlineimaprc"])
+                        .required(true),
+                    Arg::with_name("path")
+                        .help("Specifies the path of the config to import")
+                        .value_name("PATH")
+                        .required(true),
+                ]),
+        )]
+}
+
+/// Replaces the alias of the raw arguments by the command it stands for. The arguments following
+/// the alias are kept after the command.
+pub fn expand_alias(args: &[String], alias: &str, cmd: &str) -> Vec<String> {
+    let pos = match args.iter().skip(1).position(|arg| arg == alias) {
+        Some(pos) => pos + 1,
+        None => return args.to_vec(),
+    };
+    args[..pos]
+        .iter()
+        .cloned()
+        .chain(cmd.split_whitespace().map(String::from))
+        .chain(args[pos + 1..].iter().cloned())
+        .collect()
+}
+
+/// Alias subcommands, from the config. They accept any argument, given to the aliased command.
+pub fn alias_subcmds<'a>(aliases: &'a HashMap<String, String>) -> Vec<App<'a, 'a>> {
+    let mut aliases: Vec<_> = aliases.iter().collect();
+    aliases.sort();
+    aliases
+        .into_iter()
+        .map(|(alias, cmd)| {
+            SubCommand::with_name(alias)
+                .about(cmd.as_str())
+                .setting(AppSettings::TrailingVarArg)
+                .arg(
+                    Arg::with_name("args")
+                        .help("Specifies the arguments given to the aliased command")
+                        .value_name("ARGS")
+                        .multiple(true)
+                        .allow_hyphen_values(true),
+                )
+        })
+        .collect()
+}
+
+/// Inserts the default arguments of a command, from the config, into the raw arguments. They are
+/// inserted before the trailing arguments (the ones after `--`), and the options already given on
+/// the command line (written the same way) are skipped so they keep their value.
+pub fn with_default_args(args: &[String], defaults: &[String]) -> Vec<String> {
+    let end = args
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(args.len());
+    let is_given = |opt: &str| {
+        args[..end]
+            .iter()
+            .any(|arg| arg == opt || arg.starts_with(&format!("{}=", opt)))
+    };
+
+    let mut skip = false;
+    let defaults = defaults.iter().filter(|arg| {
+        if arg.starts_with('-') {
+            skip = is_given(arg.split('=').next().unwrap_or_default());
+        }
+        !skip
+    });
+
+    args[..end]
+        .iter()
+        .chain(defaults)
+        .chain(args[end..].iter())
+        .cloned()
+        .collect()
+}
 
 /// Config arguments.
 pub fn args<'a>() -> Vec<Arg<'a, 'a>> {
@@ -19,3 +136,87 @@ pub fn args<'a>() -> Vec<Arg<'a, 'a>> {
             .value_name("NAME"),
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_expand_alias() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let cmd = "--mailbox INBOX search unseen";
+
+        assert_eq!(
+            args(&["himalaya", "--mailbox", "INBOX", "search", "unseen"]),
+            expand_alias(&args(&["himalaya", "inbox"]), "inbox", cmd)
+        );
+        assert_eq!(
+            args(&[
+                "himalaya",
+                "-o",
+                "json",
+                "--mailbox",
+                "INBOX",
+                "search",
+                "unseen",
+                "-w",
+                "80"
+            ]),
+            expand_alias(
+                &args(&["himalaya", "-o", "json", "inbox", "-w", "80"]),
+                "inbox",
+                cmd
+            )
+        );
+        assert_eq!(
+            args(&["himalaya", "list"]),
+            expand_alias(&args(&["himalaya", "list"]), "inbox", cmd)
+        );
+    }
+
+    #[test]
+    fn it_should_insert_default_args() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let defaults = args(&["--max-width", "120", "--size=20", "-p", "1"]);
+
+        assert_eq!(
+            args(&[
+                "himalaya",
+                "list",
+                "--max-width",
+                "120",
+                "--size=20",
+                "-p",
+                "1"
+            ]),
+            with_default_args(&args(&["himalaya", "list"]), &defaults)
+        );
+        assert_eq!(
+            args(&[
+                "himalaya",
+                "list",
+                "--size",
+                "5",
+                "--max-width",
+                "120",
+                "-p",
+                "1"
+            ]),
+            with_default_args(&args(&["himalaya", "list", "--size", "5"]), &defaults)
+        );
+        assert_eq!(
+            args(&[
+                "himalaya",
+                "send",
+                "--max-width",
+                "120",
+                "--size=20",
+                "-p",
+                "1",
+                "--",
+                "-p"
+            ]),
+            with_default_args(&args(&["himalaya", "send", "--", "-p"]), &defaults)
+        );
+    }
+}
@@ -1,13 +1,46 @@
 use anyhow::{Context, Error, Result};
 use log::{debug, trace};
 use serde::Deserialize;
-use std::{collections::HashMap, convert::TryFrom, env, fs, path::PathBuf, thread};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    env, fs,
+    path::PathBuf,
+    thread,
+};
 use toml;
 
-use crate::output::run_cmd;
+use crate::{
+    domain::{Blocklist, EnvelopeDate, Filter, KnownHosts, MdnPolicy, NotesCache, RetentionPolicy},
+    output::{run_cmd, run_cmd_with_args},
+};
 
 pub const DEFAULT_PAGE_SIZE: usize = 10;
+pub const DEFAULT_MAILBOX: &str = "INBOX";
 pub const DEFAULT_SIG_DELIM: &str = "-- \n";
+pub const DEFAULT_SIEVE_PORT: u16 = 4190;
+pub const DEFAULT_SENT_FOLDER: &str = "Sent";
+pub const DEFAULT_TRASH_FOLDER: &str = "Trash";
+pub const DEFAULT_JUNK_FOLDER: &str = "Junk";
+pub const DEFAULT_SNOOZED_FOLDER: &str = "Snoozed";
+pub const DEFAULT_OUTBOX_FOLDER: &str = "Outbox";
+pub const DEFAULT_CONFIRM_THRESHOLD: usize = 10;
+pub const DEFAULT_MSG_SIZE_LIMIT: usize = 25;
+pub const DEFAULT_ATTACHMENT_UPLOAD_THRESHOLD: usize = 10;
+pub const DEFAULT_EXPORT_PDF_CMD: &str = "wkhtmltopdf --quiet";
+pub const DEFAULT_PRINT_CMD: &str = "lp";
+pub const DEFAULT_PICKER_CMD: &str = "fzf --multi";
+pub const DEFAULT_REPLY_ATTRIBUTION: &str = "On {{date}}, {{from}} wrote:";
+pub const DEFAULT_REPLY_QUOTE_PREFIX: &str = "> ";
+pub const DEFAULT_ATTACHMENT_KEYWORDS: &[(&str, &[&str])] = &[
+    ("en", &["attached", "attachment", "attachments", "enclosed"]),
+    (
+        "fr",
+        &["ci-joint", "ci-jointe", "pièce jointe", "pièces jointes"],
+    ),
+    ("de", &["anhang", "angehängt", "beigefügt"]),
+    ("es", &["adjunto", "adjunta", "adjuntos", "archivo adjunto"]),
+];
 
 /// Represent the user config.
 #[derive(Debug, Default, Clone, Deserialize)]
@@ -21,10 +54,106 @@ pub struct Config {
     pub signature_delimiter: Option<String>,
     /// Define the signature.
     pub signature: Option<String>,
-    /// Define the default page size for listings.
+    /// Define the default page size for listings, also accepted as `list-page-size`.
+    #[serde(alias = "list-page-size")]
     pub default_page_size: Option<usize>,
+    /// Define the mailbox commands target when `--mailbox` is omitted, defaults to "INBOX".
+    pub default_mailbox: Option<String>,
+    /// Define the default arguments of commands, by command name, like
+    /// `list = "--max-width 120"`. They are inserted after the command line ones, which win
+    /// when the same option is given both ways.
+    pub default_args: Option<HashMap<String, String>>,
+    /// Define command aliases, like `inbox = "--mailbox INBOX search unseen"`. An alias is
+    /// replaced by its command, the arguments following the alias being kept after it.
+    pub aliases: Option<HashMap<String, String>>,
     pub notify_cmd: Option<String>,
     pub watch_cmds: Option<Vec<String>>,
+    /// Define the filter rules applied by `filters run` and by the watch mode.
+    pub filters: Option<Vec<Filter>>,
+    /// Define the path of the Lua script defining the hooks: the `on_new_mail`, `pre_send`,
+    /// `post_read` and `filter_message` functions.
+    pub hooks_script: Option<String>,
+    /// Define the bearer token required by the requests to the HTTP server (`serve` command).
+    /// The `HIMALAYA_SERVE_TOKEN` environment variable takes precedence. Without token, a random
+    /// one is generated at start.
+    pub serve_token: Option<String>,
+    /// Define the retention policies applied by `clean` and by the daemon, by mailbox name.
+    pub retention: Option<BTreeMap<String, RetentionPolicy>>,
+    /// Define the policy applied to read receipt requests: "ask" (default), "always" or "never".
+    pub mdn_policy: Option<MdnPolicy>,
+    /// Define the number of messages above which deletions need a confirmation, defaults to 10.
+    pub confirm_threshold: Option<usize>,
+    /// Define the size in MB above which outgoing messages are refused unless `--force` is given,
+    /// defaults to 25. A limit of 0 disables the check.
+    pub msg_size_limit: Option<usize>,
+    /// Define the command uploading attachments above the upload threshold. It receives the path
+    /// of the attachment as last argument and prints a share URL, inserted into the body instead
+    /// of attaching the file.
+    pub attachment_upload_cmd: Option<String>,
+    /// Define the size in MB above which attachments are uploaded, defaults to 10.
+    pub attachment_upload_threshold: Option<usize>,
+    /// Define the command converting messages exported with `read --export pdf`. It receives the
+    /// path of the HTML rendering and the path of the PDF as last arguments, defaults to
+    /// "wkhtmltopdf --quiet".
+    pub export_pdf_cmd: Option<String>,
+    /// Define the command messages are sent to by `read --print`, on its standard input,
+    /// defaults to "lp".
+    pub print_cmd: Option<String>,
+    /// Define the command picking envelopes with `--pick`. It receives one envelope per line on
+    /// its standard input and prints the picked ones, defaults to "fzf --multi" (fzf is not
+    /// shipped with himalaya and must be installed).
+    pub picker_cmd: Option<String>,
+    /// Define whether messages are always fetched with `BODY.PEEK`, so reading them never marks
+    /// them as seen, defaults to false.
+    pub peek: Option<bool>,
+    /// Define whether commands address messages by UID instead of sequence number, defaults to
+    /// false. The `--uid` and `--seq` flags override it.
+    pub uid: Option<bool>,
+    /// Define whether the domains of recipients must have MX records for messages to be sent,
+    /// defaults to false.
+    pub verify_recipient_mx: Option<bool>,
+    /// Define the keywords announcing an attachment, by language, like `en = ["attached"]`. A
+    /// warning is shown before sending a message mentioning one of them without attachment.
+    /// Languages not defined keep their default keywords (English, French, German and Spanish),
+    /// an empty list disables a language.
+    pub attachment_keywords: Option<BTreeMap<String, Vec<String>>>,
+    /// Define whether plain text bodies are sent as format=flowed, so they re-flow on the
+    /// recipient side, defaults to false.
+    pub format_flowed: Option<bool>,
+    /// Define the line introducing the quoted message of replies, where `{{date}}`, `{{from}}`
+    /// and `{{email}}` are replaced by the date and the sender of the message, defaults to
+    /// "On {{date}}, {{from}} wrote:".
+    pub reply_attribution: Option<String>,
+    /// Define the prefix of the quoted lines of replies, defaults to "> ".
+    pub reply_quote_prefix: Option<String>,
+    /// Define whether the text converted from HTML is quoted when the message has no plain text
+    /// part, defaults to true.
+    pub reply_quote_html: Option<bool>,
+    /// Define whether the signature of the quoted message is stripped, defaults to true.
+    pub reply_strip_signature: Option<bool>,
+    /// Define whether unseen messages are rendered in bold in listings, defaults to true.
+    pub list_unseen_bold: Option<bool>,
+    /// Define the color of the subject of flagged messages in listings: a color name, an ANSI
+    /// code (0-255) or "none", defaults to "magenta".
+    pub list_flagged_color: Option<String>,
+    /// Define the date shown in listings and used to sort them: "internal" (default) for the
+    /// date the server received the message, or "header" for its `Date` header.
+    pub list_date: Option<EnvelopeDate>,
+    /// Define the extra headers fetched along with envelopes (like `["List-Id"]`), without
+    /// downloading the messages. They are part of the JSON output of listings.
+    pub list_headers: Option<Vec<String>>,
+    /// Define the locale of dates (like "fr_FR"), defaults to the `LC_ALL`, `LC_TIME` or `LANG`
+    /// environment variable.
+    pub date_locale: Option<String>,
+    /// Define the format of dates (strftime), defaults to the date and time formats of the locale.
+    pub date_format: Option<String>,
+    /// Define whether listings shorten the dates of the current week, defaults to false.
+    pub date_relative: Option<bool>,
+    /// Define the first day of the week (like "sunday"), defaults to the one of the locale.
+    pub week_start: Option<String>,
+    /// The path of the config file, set once loaded.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
     #[serde(flatten)]
     pub accounts: ConfigAccountsMap,
 }
@@ -40,14 +169,69 @@ pub struct ConfigAccountEntry {
     pub downloads_dir: Option<PathBuf>,
     pub signature_delimiter: Option<String>,
     pub signature: Option<String>,
+    #[serde(alias = "list-page-size")]
     pub default_page_size: Option<usize>,
+    pub default_mailbox: Option<String>,
+    pub default_args: Option<HashMap<String, String>>,
     pub watch_cmds: Option<Vec<String>>,
+    /// Define the mailboxes watched together by the notify mode, each one with an optional
+    /// notify command overriding the global one.
+    pub notify_mailboxes: Option<BTreeMap<String, ConfigNotifyMboxEntry>>,
+    pub filters: Option<Vec<Filter>>,
+    pub hooks_script: Option<String>,
+    pub retention: Option<BTreeMap<String, RetentionPolicy>>,
+    pub mdn_policy: Option<MdnPolicy>,
+    pub confirm_threshold: Option<usize>,
+    pub msg_size_limit: Option<usize>,
+    pub attachment_upload_cmd: Option<String>,
+    pub attachment_upload_threshold: Option<usize>,
+    pub export_pdf_cmd: Option<String>,
+    pub print_cmd: Option<String>,
+    pub picker_cmd: Option<String>,
+    pub peek: Option<bool>,
+    pub uid: Option<bool>,
+    pub verify_recipient_mx: Option<bool>,
+    pub attachment_keywords: Option<BTreeMap<String, Vec<String>>>,
+    pub format_flowed: Option<bool>,
+    pub reply_attribution: Option<String>,
+    pub reply_quote_prefix: Option<String>,
+    pub reply_quote_html: Option<bool>,
+    pub reply_strip_signature: Option<bool>,
+    pub list_unseen_bold: Option<bool>,
+    pub list_flagged_color: Option<String>,
+    pub list_date: Option<EnvelopeDate>,
+    pub list_headers: Option<Vec<String>>,
+    pub date_locale: Option<String>,
+    pub date_format: Option<String>,
+    pub date_relative: Option<bool>,
+    pub week_start: Option<String>,
+    /// Define the mailbox sent messages are saved to, defaults to "Sent".
+    pub sent_folder: Option<String>,
+    /// Define the mailbox emptied by `empty-trash`, defaults to "Trash".
+    pub trash_folder: Option<String>,
+    /// Define the mailbox emptied by `empty-junk`, defaults to "Junk".
+    pub junk_folder: Option<String>,
+    /// Define the mailbox snoozed messages wait in, defaults to "Snoozed".
+    pub snoozed_folder: Option<String>,
+    /// Define the mailbox messages scheduled with `send --send-at` wait in, defaults to "Outbox".
+    pub outbox_folder: Option<String>,
+    /// Define additional identities the account can send messages with.
+    pub identities: Option<Vec<ConfigIdentityEntry>>,
     pub default: Option<bool>,
     pub email: String,
     pub imap_host: String,
     pub imap_port: u16,
     pub imap_starttls: Option<bool>,
     pub imap_insecure: Option<bool>,
+    /// Trust the certificate of the IMAP server on first use: its fingerprint is recorded on
+    /// first connection, then connections fail if the certificate changes.
+    pub imap_tofu: Option<bool>,
+    /// Define the maximum number of simultaneous IMAP sessions of the account, like when
+    /// watching several mailboxes, defaults to 0 (no limit).
+    pub imap_max_connections: Option<usize>,
+    /// Define the maximum number of IMAP commands sent per minute, commands above the limit
+    /// waiting for their turn, defaults to 0 (no limit).
+    pub imap_max_commands_per_minute: Option<usize>,
     pub imap_login: String,
     pub imap_passwd_cmd: String,
     pub smtp_host: String,
@@ -56,6 +240,59 @@ pub struct ConfigAccountEntry {
     pub smtp_insecure: Option<bool>,
     pub smtp_login: String,
     pub smtp_passwd_cmd: String,
+    /// Deliver messages directly to the mail exchangers of the recipient domains, found in their
+    /// MX records, instead of the SMTP server of the account.
+    pub smtp_direct_mx: Option<bool>,
+    /// Define the selector of the DKIM public key. Outgoing messages are DKIM signed when both
+    /// the selector and the private key are defined.
+    pub dkim_selector: Option<String>,
+    /// Define the path of the DKIM private key, in PEM format.
+    pub dkim_private_key: Option<String>,
+    /// Define the domain of the DKIM signature, defaults to the domain of the account email.
+    pub dkim_domain: Option<String>,
+    /// Define the ManageSieve host, defaults to the IMAP host.
+    pub sieve_host: Option<String>,
+    /// Define the ManageSieve port, defaults to 4190.
+    pub sieve_port: Option<u16>,
+    /// Enable STARTTLS for ManageSieve, defaults to true. When disabled, TLS is negociated as
+    /// soon as the connection is open.
+    pub sieve_starttls: Option<bool>,
+}
+
+/// Represent an identity in the identities section of an account.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ConfigIdentityEntry {
+    /// Override the full display name of the account.
+    pub name: Option<String>,
+    pub email: String,
+    /// Override the signature of the account.
+    pub signature: Option<String>,
+    /// Override the sent folder of the account.
+    pub sent_folder: Option<String>,
+}
+
+/// Represent a mailbox in the notify mailboxes section of an account.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ConfigNotifyMboxEntry {
+    /// Override the notify command for the messages arriving in the mailbox.
+    pub notify_cmd: Option<String>,
+}
+
+/// Runs the given notify command, or `notify-send` by default, with the subject and the sender
+/// of the new message.
+pub fn run_notify_cmd(cmd: Option<&str>, subject: &str, sender: &str) -> Result<()> {
+    // The values are passed as arguments, so they are never interpreted by the shell.
+    let title = format!("New message from {}", sender);
+    let (cmd, args) = match cmd {
+        Some(cmd) => (cmd, [subject, sender]),
+        None => ("notify-send", [title.as_str(), subject]),
+    };
+
+    run_cmd_with_args(cmd, &args).context("cannot run notify cmd")?;
+
+    Ok(())
 }
 
 impl Config {
@@ -68,6 +305,15 @@ impl Config {
         Ok(path)
     }
 
+    fn path_from_appdata() -> Result<PathBuf> {
+        let path = env::var("APPDATA").context("cannot find `APPDATA` env var")?;
+        let mut path = PathBuf::from(path);
+        path.push("himalaya");
+        path.push("config.toml");
+
+        Ok(path)
+    }
+
     fn path_from_xdg_alt() -> Result<PathBuf> {
         let home_var = if cfg!(target_family = "windows") {
             "USERPROFILE"
@@ -98,29 +344,55 @@ impl Config {
         Ok(path)
     }
 
+    /// Returns the path of the config file: the first existing one among `$XDG_CONFIG_HOME`,
+    /// `%APPDATA%` (Windows), `~/.config` and `~/.himalayarc`, or the first candidate when none
+    /// exists.
     pub fn path() -> Result<PathBuf> {
-        let path = Self::path_from_xdg()
-            .or_else(|_| Self::path_from_xdg_alt())
-            .or_else(|_| Self::path_from_home())
+        let paths: Vec<PathBuf> = [
+            Self::path_from_xdg,
+            Self::path_from_appdata,
+            Self::path_from_xdg_alt,
+            Self::path_from_home,
+        ]
+        .iter()
+        .filter_map(|path| path().ok())
+        .collect();
+        let path = paths
+            .iter()
+            .find(|path| path.exists())
+            .or_else(|| paths.first())
+            .cloned()
             .context("cannot find config path")?;
 
         Ok(path)
     }
 
-    pub fn run_notify_cmd<S: AsRef<str>>(&self, subject: S, sender: S) -> Result<()> {
-        let subject = subject.as_ref();
-        let sender = sender.as_ref();
+    /// Returns the path of the blocklist, next to the config file.
+    pub fn blocklist_path(&self) -> Option<PathBuf> {
+        self.path.as_deref().map(Blocklist::path_from_config)
+    }
 
-        let default_cmd = format!(r#"notify-send "New message from {}" "{}""#, sender, subject);
-        let cmd = self
-            .notify_cmd
-            .as_ref()
-            .map(|cmd| format!(r#"{} {:?} {:?}"#, cmd, subject, sender))
-            .unwrap_or(default_cmd);
+    /// Returns the path of the local cache of the message notes, next to the config file.
+    pub fn notes_path(&self) -> Option<PathBuf> {
+        self.path.as_deref().map(NotesCache::path_from_config)
+    }
 
-        run_cmd(&cmd).context("cannot run notify cmd")?;
+    /// Returns the path of the certificate fingerprints trusted on first use, next to the config
+    /// file.
+    pub fn known_hosts_path(&self) -> Option<PathBuf> {
+        self.path.as_deref().map(KnownHosts::path_from_config)
+    }
 
-        Ok(())
+    /// Runs the notify command, or the given one overriding it, with the subject and the sender
+    /// of the new message.
+    pub fn run_notify_cmd<S: AsRef<str>>(
+        &self,
+        cmd: Option<&str>,
+        subject: S,
+        sender: S,
+    ) -> Result<()> {
+        let cmd = cmd.or_else(|| self.notify_cmd.as_deref());
+        run_notify_cmd(cmd, subject.as_ref(), sender.as_ref())
     }
 
     pub fn _exec_watch_cmds(&self, account: &ConfigAccountEntry) -> Result<()> {
@@ -149,9 +421,10 @@ impl TryFrom<Option<&str>> for Config {
 
     fn try_from(path: Option<&str>) -> Result<Self, Self::Error> {
         debug!("init config from `{:?}`", path);
-        let path = path.map(|s| s.into()).unwrap_or(Config::path()?);
-        let content = fs::read_to_string(path).context("cannot read config file")?;
-        let config = toml::from_str(&content).context("cannot parse config file")?;
+        let path: PathBuf = path.map(|s| s.into()).unwrap_or(Config::path()?);
+        let content = fs::read_to_string(&path).context("cannot read config file")?;
+        let mut config: Config = toml::from_str(&content).context("cannot parse config file")?;
+        config.path = Some(path);
         trace!("{:#?}", config);
         Ok(config)
     }
@@ -0,0 +1,35 @@
+//! Module related to config handling.
+//!
+//! This module gathers all config commands.
+
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use std::fs;
+
+use crate::{
+    config::{parse_mbsyncrc, parse_offlineimaprc, ImportFormat},
+    output::PrinterService,
+};
+
+/// Imports the accounts of the given config of a mail synchronization tool. The accounts are
+/// printed like accounts of the config file, so they can be reviewed before being added to it.
+pub fn import<Printer: PrinterService>(
+    format: ImportFormat,
+    path: &str,
+    printer: &mut Printer,
+) -> Result<()> {
+    let content = fs::read_to_string(shellexpand::tilde(path).as_ref())
+        .context(format!(r#"cannot read config to import "{}""#, path))?;
+    let accounts = match format {
+        ImportFormat::Mbsync => parse_mbsyncrc(&content),
+        ImportFormat::Offlineimap => parse_offlineimaprc(&content),
+    }
+    .context(format!(r#"cannot import config "{}""#, path))?;
+    if accounts.is_empty() {
+        return Err(anyhow!(r#"cannot find accounts in config "{}""#, path));
+    }
+    debug!("imported accounts: {:?}", accounts.keys());
+
+    let config = toml::to_string(&accounts).context("cannot serialize imported accounts")?;
+    printer.print(config)
+}
@@ -0,0 +1,554 @@
+//! Import entity module.
+//!
+//! This module contains the translation of the configs of mail synchronization tools into
+//! accounts of the config file: the IMAP settings and the folder mappings of [mbsync] and
+//! [OfflineIMAP]. SMTP settings, unknown to these tools, are guessed from the IMAP ones.
+//!
+//! [mbsync]: https://isync.sourceforge.io/mbsync.html
+//! [OfflineIMAP]: https://www.offlineimap.org/doc/offlineimap.html
+
+use anyhow::{anyhow, Context, Error, Result};
+use log::warn;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    fs,
+};
+
+/// Represents the format of the configs accounts are imported from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    Mbsync,
+    Offlineimap,
+}
+
+impl TryFrom<&str> for ImportFormat {
+    type Error = Error;
+
+    fn try_from(format: &str) -> Result<Self, Self::Error> {
+        match format {
+            "mbsyncrc" => Ok(Self::Mbsync),
+            "offlineimaprc" => Ok(Self::Offlineimap),
+            format => Err(anyhow!(r#"cannot parse import format "{}""#, format)),
+        }
+    }
+}
+
+/// Represents an imported account, serialized like an account of the config file.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ImportedAccount {
+    pub email: String,
+    pub imap_host: String,
+    pub imap_port: u16,
+    pub imap_starttls: bool,
+    pub imap_login: String,
+    pub imap_passwd_cmd: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_starttls: bool,
+    pub smtp_login: String,
+    pub smtp_passwd_cmd: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sent_folder: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trash_folder: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub junk_folder: Option<String>,
+}
+
+/// Represents the imported accounts, by name.
+pub type ImportedAccounts = BTreeMap<String, ImportedAccount>;
+
+impl ImportedAccount {
+    /// Builds an account from its IMAP settings. The SMTP server is guessed: the host named like
+    /// the IMAP one (`imap.` being replaced by `smtp.`), on the submission port with STARTTLS.
+    fn new(
+        name: &str,
+        host: &str,
+        port: Option<u16>,
+        starttls: bool,
+        login: &str,
+        passwd_cmd: String,
+    ) -> Self {
+        if !login.contains('@') {
+            warn!(
+                r#"cannot find email of account "{}", using login "{}" instead"#,
+                name, login
+            );
+        }
+        warn!(
+            r#"SMTP settings of account "{}" are guessed from the IMAP ones, check them"#,
+            name
+        );
+        let smtp_host = match host.strip_prefix("imap.") {
+            Some(domain) => format!("smtp.{}", domain),
+            None => host.to_owned(),
+        };
+        Self {
+            email: login.to_owned(),
+            imap_host: host.to_owned(),
+            imap_port: port.unwrap_or(if starttls { 143 } else { 993 }),
+            imap_starttls: starttls,
+            imap_login: login.to_owned(),
+            imap_passwd_cmd: passwd_cmd.clone(),
+            smtp_host,
+            smtp_port: 587,
+            smtp_starttls: true,
+            smtp_login: login.to_owned(),
+            smtp_passwd_cmd: passwd_cmd,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the special folder matching the given local mailbox (like `Sent` or `trash`) to the
+    /// given remote mailbox.
+    fn map_folder(&mut self, local: &str, remote: &str) {
+        let local = local.rsplit('/').next().unwrap_or(local).to_lowercase();
+        let remote = Some(remote.to_owned());
+        match local.as_str() {
+            "sent" | "sent mail" | "sent items" | "sent messages" => self.sent_folder = remote,
+            "trash" | "bin" | "deleted" | "deleted items" | "deleted messages" => {
+                self.trash_folder = remote
+            }
+            "junk" | "spam" => self.junk_folder = remote,
+            _ => (),
+        }
+    }
+}
+
+/// Reads the password stored in the file at the given path, without the trailing line break.
+fn read_passwd_file(path: &str) -> Result<String> {
+    let path =
+        shellexpand::full(path).context(format!("cannot expand password file path {:?}", path))?;
+    let passwd = fs::read_to_string(path.as_ref())
+        .context(format!("cannot read password file {:?}", path))?;
+    Ok(passwd
+        .trim_end_matches(|c| c == '\r' || c == '\n')
+        .to_owned())
+}
+
+/// Turns a password written in plain text into a command printing it.
+fn passwd_echo_cmd(name: &str, passwd: &str) -> String {
+    warn!(
+        r#"password of account "{}" is written in plain text, consider a password manager"#,
+        name
+    );
+    format!("echo '{}'", passwd.replace('\'', r"'\''"))
+}
+
+/// Removes the double quotes around the given value, if any.
+fn unquote(val: &str) -> String {
+    match val.strip_prefix('"').and_then(|val| val.strip_suffix('"')) {
+        Some(val) => val.replace("\\\"", "\""),
+        None => val.to_owned(),
+    }
+}
+
+/// Represents a section of a mbsync config, like an `IMAPAccount` or a `Channel`.
+#[derive(Debug)]
+struct MbsyncSection {
+    /// The keyword starting the section, lowercased.
+    kind: String,
+    name: String,
+    /// The options of the section, by lowercased keyword.
+    opts: HashMap<String, String>,
+}
+
+/// Splits the sections of a mbsync config. Sections start with their keyword and end with an
+/// empty line.
+fn parse_mbsync_sections(content: &str) -> Vec<MbsyncSection> {
+    let mut sections = vec![];
+    let mut in_section = false;
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() {
+            in_section = false;
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.splitn(2, char::is_whitespace);
+        let key = parts.next().unwrap_or_default().to_lowercase();
+        let val = parts.next().unwrap_or_default().trim();
+        // A leading `+` only tells mbsync the command prompts on the terminal
+        let val = match key.as_str() {
+            "passcmd" => val.trim_start_matches('+'),
+            _ => val,
+        };
+        let val = unquote(val);
+        match sections.last_mut() {
+            Some(MbsyncSection { opts, .. }) if in_section => {
+                opts.insert(key, val);
+            }
+            _ => {
+                in_section = true;
+                sections.push(MbsyncSection {
+                    kind: key,
+                    name: val,
+                    opts: HashMap::new(),
+                });
+            }
+        }
+    }
+    sections
+}
+
+/// Splits a side of a mbsync channel, like `:work-remote:"[Gmail]/Sent Mail"`, into its store
+/// and its mailbox.
+fn parse_mbsync_channel_side(side: &str) -> Option<(&str, String)> {
+    let mut parts = side.strip_prefix(':')?.splitn(2, ':');
+    Some((parts.next()?, unquote(parts.next().unwrap_or_default())))
+}
+
+/// Builds an account from the options of a mbsync `IMAPAccount` (or of an `IMAPStore` holding
+/// them).
+fn mbsync_account(name: &str, opts: &HashMap<String, String>) -> Result<ImportedAccount> {
+    let host = opts
+        .get("host")
+        .ok_or_else(|| anyhow!(r#"cannot find host of mbsync account "{}""#, name))?;
+    let port = opts
+        .get("port")
+        .map(|port| port.parse())
+        .transpose()
+        .context(format!(r#"cannot parse port of mbsync account "{}""#, name))?;
+    let login = opts.get("user").map(String::as_str).unwrap_or_default();
+    // `TLSType` replaced `SSLType` in mbsync 1.4, both defaulting to STARTTLS
+    let tls = opts.get("tlstype").or_else(|| opts.get("ssltype"));
+    let starttls = match tls.map(|tls| tls.to_lowercase()).as_deref() {
+        Some("imaps") => false,
+        Some("none") => {
+            warn!(
+                r#"mbsync account "{}" does not use TLS, trying STARTTLS instead"#,
+                name
+            );
+            true
+        }
+        _ => true,
+    };
+    let passwd_cmd = match (opts.get("passcmd"), opts.get("pass")) {
+        (Some(cmd), _) => cmd.to_owned(),
+        (None, Some(passwd)) => passwd_echo_cmd(name, passwd),
+        (None, None) => {
+            warn!(
+                r#"cannot find password of account "{}", set its imap-passwd-cmd"#,
+                name
+            );
+            String::new()
+        }
+    };
+    Ok(ImportedAccount::new(
+        name, host, port, starttls, login, passwd_cmd,
+    ))
+}
+
+/// Parses the accounts of a mbsync config. Accounts are the `IMAPAccount` sections, or the
+/// `IMAPStore` ones holding their settings. The special folders are taken from the channels
+/// syncing a remote mailbox to a local one named like `Sent`, `Trash` or `Junk`.
+pub fn parse_mbsyncrc(content: &str) -> Result<ImportedAccounts> {
+    let sections = parse_mbsync_sections(content);
+    let mut accounts = ImportedAccounts::new();
+    let mut store_accounts: HashMap<&str, &str> = HashMap::new();
+
+    for section in sections.iter() {
+        match (section.kind.as_str(), section.opts.get("account")) {
+            ("imapaccount", _) => {
+                let account = mbsync_account(&section.name, &section.opts)?;
+                accounts.insert(section.name.clone(), account);
+            }
+            ("imapstore", Some(account)) => {
+                store_accounts.insert(&section.name, account);
+            }
+            ("imapstore", None) => {
+                let account = mbsync_account(&section.name, &section.opts)?;
+                accounts.insert(section.name.clone(), account);
+                store_accounts.insert(&section.name, &section.name);
+            }
+            _ => (),
+        }
+    }
+
+    for channel in sections.iter().filter(|section| section.kind == "channel") {
+        // `Far` and `Near` replaced `Master` and `Slave` in mbsync 1.4
+        let side = |new: &str, old: &str| {
+            channel
+                .opts
+                .get(new)
+                .or_else(|| channel.opts.get(old))
+                .and_then(|side| parse_mbsync_channel_side(side))
+        };
+        if let (Some((store, remote)), Some((_, local))) =
+            (side("far", "master"), side("near", "slave"))
+        {
+            let account = store_accounts
+                .get(store)
+                .and_then(|name| accounts.get_mut(*name));
+            if let Some(account) = account.filter(|_| !remote.is_empty()) {
+                account.map_folder(&local, &remote);
+            }
+        }
+    }
+
+    Ok(accounts)
+}
+
+/// Represents the sections of an INI file, by name. Options are indexed by lowercased name.
+type IniSections = BTreeMap<String, HashMap<String, String>>;
+
+/// Parses an INI file, like the OfflineIMAP config. Indented lines continue the value of the
+/// previous option.
+fn parse_ini(content: &str) -> IniSections {
+    let mut sections = IniSections::new();
+    let mut section = None;
+    let mut last_key = None;
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = trimmed
+            .strip_prefix('[')
+            .and_then(|name| name.strip_suffix(']'))
+        {
+            section = Some(name.trim().to_owned());
+            sections.entry(name.trim().to_owned()).or_default();
+            last_key = None;
+            continue;
+        }
+        let opts = match section.as_ref().and_then(|name| sections.get_mut(name)) {
+            Some(opts) => opts,
+            None => continue,
+        };
+        if line.starts_with(char::is_whitespace) {
+            if let Some(val) = last_key.as_ref().and_then(|key| opts.get_mut(key)) {
+                val.push(' ');
+                val.push_str(trimmed);
+            }
+            continue;
+        }
+        if let Some(pos) = trimmed.find(|c| c == '=' || c == ':') {
+            let key = trimmed[..pos].trim().to_lowercase();
+            opts.insert(key.clone(), trimmed[pos + 1..].trim().to_owned());
+            last_key = Some(key);
+        }
+    }
+    sections
+}
+
+/// Parses a boolean option of an INI file.
+fn parse_ini_bool(val: &str) -> bool {
+    matches!(val.to_lowercase().as_str(), "yes" | "true" | "on" | "1")
+}
+
+/// Parses the accounts of an OfflineIMAP config: the ones listed in the `general` section, with
+/// the settings of their remote repository. Gmail repositories get the Gmail special folders.
+pub fn parse_offlineimaprc(content: &str) -> Result<ImportedAccounts> {
+    let sections = parse_ini(content);
+    let names: Vec<String> = match sections
+        .get("general")
+        .and_then(|general| general.get("accounts"))
+    {
+        Some(names) => names
+            .split(',')
+            .map(|name| name.trim().to_owned())
+            .filter(|name| !name.is_empty())
+            .collect(),
+        None => sections
+            .keys()
+            .filter_map(|name| name.strip_prefix("Account "))
+            .map(|name| name.trim().to_owned())
+            .collect(),
+    };
+
+    let mut accounts = ImportedAccounts::new();
+    for name in names {
+        let repo = sections
+            .get(&format!("Account {}", name))
+            .and_then(|account| account.get("remoterepository"))
+            .ok_or_else(|| anyhow!(r#"cannot find remote repository of account "{}""#, name))?;
+        let repo = sections
+            .get(&format!("Repository {}", repo))
+            .ok_or_else(|| anyhow!(r#"cannot find repository "{}""#, repo))?;
+        let gmail = repo
+            .get("type")
+            .map_or(false, |kind| kind.eq_ignore_ascii_case("gmail"));
+        let host = match repo.get("remotehost") {
+            Some(host) => host.as_str(),
+            None if gmail => "imap.gmail.com",
+            None => return Err(anyhow!(r#"cannot find host of account "{}""#, name)),
+        };
+        let port = repo
+            .get("remoteport")
+            .map(|port| port.parse())
+            .transpose()
+            .context(format!(r#"cannot parse port of account "{}""#, name))?;
+        let login = repo
+            .get("remoteuser")
+            .map(String::as_str)
+            .unwrap_or_default();
+        let ssl = repo.get("ssl").map_or(true, |ssl| parse_ini_bool(ssl));
+        let starttls = repo
+            .get("starttls")
+            .map_or(false, |tls| parse_ini_bool(tls));
+        if !ssl && !starttls {
+            warn!(
+                r#"account "{}" does not use TLS, trying STARTTLS instead"#,
+                name
+            );
+        }
+        let passwd_cmd = if let Some(path) = repo.get("remotepassfile") {
+            let passwd = read_passwd_file(path)
+                .context(format!(r#"cannot import password of account "{}""#, name))?;
+            passwd_echo_cmd(&name, &passwd)
+        } else if let Some(passwd) = repo.get("remotepass") {
+            passwd_echo_cmd(&name, passwd)
+        } else {
+            // Like `remotepasseval`, which is Python code
+            warn!(
+                r#"cannot import password of account "{}", set its imap-passwd-cmd"#,
+                name
+            );
+            String::new()
+        };
+
+        let mut account =
+            ImportedAccount::new(&name, host, port, !ssl || starttls, login, passwd_cmd);
+        if gmail {
+            account.sent_folder = Some(String::from("[Gmail]/Sent Mail"));
+            account.trash_folder = Some(String::from("[Gmail]/Trash"));
+            account.junk_folder = Some(String::from("[Gmail]/Spam"));
+        }
+        accounts.insert(name, account);
+    }
+
+    Ok(accounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn it_should_parse_mbsyncrc() {
+        let content = concat!(
+            "IMAPAccount work\n",
+            "Host imap.example.com\n",
+            "User me@example.com\n",
+            "PassCmd +\"pass show work\"\n",
+            "TLSType IMAPS\n",
+            "\n",
+            "IMAPStore work-remote\n",
+            "Account work\n",
+            "\n",
+            "MaildirStore work-local\n",
+            "Path ~/Mail/work/\n",
+            "\n",
+            "Channel work-sent\n",
+            "Far :work-remote:\"Sent Items\"\n",
+            "Near :work-local:sent\n",
+            "\n",
+            "# An account without its own section\n",
+            "IMAPStore home\n",
+            "Host mail.example.org\n",
+            "User me\n",
+            "Pass s3cr'et\n",
+        );
+        let accounts = parse_mbsyncrc(content).unwrap();
+
+        assert_eq!(
+            ImportedAccount {
+                email: String::from("me@example.com"),
+                imap_host: String::from("imap.example.com"),
+                imap_port: 993,
+                imap_starttls: false,
+                imap_login: String::from("me@example.com"),
+                imap_passwd_cmd: String::from("pass show work"),
+                smtp_host: String::from("smtp.example.com"),
+                smtp_port: 587,
+                smtp_starttls: true,
+                smtp_login: String::from("me@example.com"),
+                smtp_passwd_cmd: String::from("pass show work"),
+                sent_folder: Some(String::from("Sent Items")),
+                ..ImportedAccount::default()
+            },
+            accounts["work"]
+        );
+        assert_eq!("mail.example.org", accounts["home"].smtp_host);
+        assert_eq!(143, accounts["home"].imap_port);
+        assert!(accounts["home"].imap_starttls);
+        assert_eq!(r"echo 's3cr'\''et'", accounts["home"].imap_passwd_cmd);
+    }
+
+    #[test]
+    fn it_should_parse_offlineimaprc() {
+        let mut passwd_file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(passwd_file, "s3cr'et").unwrap();
+        let content = format!(
+            concat!(
+                "[general]\n",
+                "accounts = gmail, work\n",
+                "\n",
+                "[Account gmail]\n",
+                "localrepository = gmail-local\n",
+                "remoterepository = gmail-remote\n",
+                "\n",
+                "[Repository gmail-remote]\n",
+                "type = Gmail\n",
+                "remoteuser = me@gmail.com\n",
+                "remotepassfile = {}\n",
+                "\n",
+                "[Account work]\n",
+                "remoterepository = work-remote\n",
+                "\n",
+                "[Repository work-remote]\n",
+                "type = IMAP\n",
+                "remotehost = imap.example.com\n",
+                "remoteport = 143\n",
+                "remoteuser = me@example.com\n",
+                "ssl = no\n",
+                "starttls = yes\n",
+                "remotepasseval = get_password(\n",
+                "    \"work\")\n",
+            ),
+            passwd_file.path().display()
+        );
+        let accounts = parse_offlineimaprc(&content).unwrap();
+
+        let gmail = &accounts["gmail"];
+        assert_eq!("imap.gmail.com", gmail.imap_host);
+        assert_eq!(993, gmail.imap_port);
+        assert_eq!("smtp.gmail.com", gmail.smtp_host);
+        assert_eq!(r"echo 's3cr'\''et'", gmail.imap_passwd_cmd);
+        assert_eq!(Some("[Gmail]/Sent Mail"), gmail.sent_folder.as_deref());
+
+        let work = &accounts["work"];
+        assert_eq!(143, work.imap_port);
+        assert!(work.imap_starttls);
+        assert_eq!("", work.imap_passwd_cmd);
+        assert_eq!(None, work.sent_folder);
+    }
+
+    #[test]
+    fn it_should_serialize_accounts_as_config() {
+        let mut accounts = ImportedAccounts::new();
+        accounts.insert(
+            String::from("work"),
+            ImportedAccount::new(
+                "work",
+                "imap.example.com",
+                None,
+                false,
+                "me@example.com",
+                String::from("pass work"),
+            ),
+        );
+        let config = toml::to_string(&accounts).unwrap();
+
+        assert!(config.starts_with("[work]\nemail = \"me@example.com\"\n"));
+        assert!(config.contains("smtp-host = \"smtp.example.com\"\n"));
+        assert!(!config.contains("sent-folder"));
+    }
+}
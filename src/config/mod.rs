@@ -1,9 +1,13 @@
 //! Module related to the user's configuration.
 
 pub mod config_arg;
+pub mod config_handler;
 
 pub mod account_entity;
 pub use account_entity::*;
 
 pub mod config_entity;
 pub use config_entity::*;
+
+pub mod import_entity;
+pub use import_entity::*;
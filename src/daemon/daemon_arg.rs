@@ -0,0 +1,139 @@
+//! Module related to daemon CLI.
+//!
+//! This module provides subcommands and a command matcher related to the daemon.
+
+use anyhow::Result;
+use clap::{self, App, ArgMatches, SubCommand};
+use log::debug;
+
+/// Subcommands that can be served by a running daemon. Interactive commands (the ones relying on
+/// the editor, on prompts or on stdin) are always executed by the CLI itself.
+const FORWARDABLE_CMDS: &[&str] = &[
+    "__complete",
+    "attachments",
+    "clean",
+    "copy",
+    "count",
+    "delete",
+    "filters",
+    "flag",
+    "list",
+    "mailboxes",
+    "move",
+    "note",
+    "notes",
+    "queue",
+    "read",
+    "search",
+    "snooze",
+    "stats",
+    "template",
+    "wake",
+];
+
+/// Arguments preventing the command from being forwarded: the daemon runs with its own config,
+/// and would resolve the paths from its own working directory.
+const LOCAL_ARGS: &[&str] = &["config", "log-file", "trace-imap-file"];
+
+/// Subcommand arguments preventing the command from being forwarded, since they take paths.
+const LOCAL_CMD_ARGS: &[(&str, &str)] = &[("read", "concat-output")];
+
+/// Daemon commands.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Cmd {
+    /// Start the daemon for the selected account.
+    Start,
+}
+
+/// Daemon command matcher.
+pub fn matches(m: &ArgMatches) -> Result<Option<Cmd>> {
+    if m.subcommand_matches("daemon").is_some() {
+        debug!("daemon command matched");
+        return Ok(Some(Cmd::Start));
+    }
+
+    Ok(None)
+}
+
+/// Checks if the matched command can be forwarded to a running daemon.
+pub fn is_forwardable(m: &ArgMatches) -> bool {
+    // Attachments are written as is to stdout, the daemon only forwards text
+    let is_cat = m
+        .subcommand_matches("attachments")
+        .and_then(|m| m.subcommand_name())
+        == Some("cat");
+    let is_local = LOCAL_ARGS.iter().any(|arg| m.is_present(arg))
+        || LOCAL_CMD_ARGS.iter().any(|(cmd, arg)| {
+            m.subcommand_matches(cmd)
+                .map(|m| m.is_present(arg))
+                .unwrap_or_default()
+        });
+    !is_cat
+        && !is_local
+        && m.subcommand_name()
+            .map(|name| FORWARDABLE_CMDS.contains(&name))
+            .unwrap_or(true)
+}
+
+/// Daemon subcommands.
+pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
+    vec![SubCommand::with_name("daemon")
+        .about("Starts a daemon serving commands over a Unix socket")
+        .long_about("Starts a daemon that keeps the IMAP session of the selected account open. While the daemon is running, non-interactive commands of this account are automatically forwarded to it, which saves the login and the TLS handshake.")]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_match_cmds() {
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .get_matches_from(&["himalaya", "daemon"]);
+        assert_eq!(Some(Cmd::Start), matches(&arg).unwrap());
+    }
+
+    #[test]
+    fn it_should_detect_forwardable_cmds() {
+        macro_rules! get_matches_from {
+            ($($arg:expr),*) => {
+                clap::App::new("himalaya")
+                    .arg(clap::Arg::with_name("config").long("config").takes_value(true))
+                    .subcommands(subcmds())
+                    .subcommand(SubCommand::with_name("list"))
+                    .subcommand(
+                        SubCommand::with_name("read").arg(
+                            clap::Arg::with_name("concat-output")
+                                .long("concat-output")
+                                .takes_value(true),
+                        ),
+                    )
+                    .subcommand(SubCommand::with_name("write"))
+                    .subcommand(
+                        SubCommand::with_name("attachments")
+                            .subcommand(SubCommand::with_name("cat")),
+                    )
+                    .get_matches_from(&["himalaya", $($arg,)*])
+            };
+        }
+
+        assert!(is_forwardable(&get_matches_from![]));
+        assert!(is_forwardable(&get_matches_from!["list"]));
+        assert!(!is_forwardable(&get_matches_from!["write"]));
+        assert!(!is_forwardable(&get_matches_from!["daemon"]));
+        assert!(is_forwardable(&get_matches_from!["attachments"]));
+        assert!(!is_forwardable(&get_matches_from!["attachments", "cat"]));
+        assert!(is_forwardable(&get_matches_from!["read"]));
+        assert!(!is_forwardable(&get_matches_from![
+            "read",
+            "--concat-output",
+            "out.txt"
+        ]));
+        assert!(!is_forwardable(&get_matches_from![
+            "--config",
+            "config.toml",
+            "list"
+        ]));
+    }
+}
@@ -0,0 +1,116 @@
+//! Daemon entity module.
+//!
+//! This module contains the definition of the messages exchanged between the CLI and the daemon.
+
+#[cfg(unix)]
+use anyhow::{anyhow, Context};
+use log::trace;
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, io, rc::Rc};
+#[cfg(unix)]
+use std::{
+    env, fs,
+    os::unix::fs::{DirBuilderExt, PermissionsExt},
+    path::PathBuf,
+};
+
+use crate::output::ExitCode;
+
+/// Represents a request sent by the CLI to the daemon.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DaemonRequest {
+    /// The raw arguments of the command, including the binary name.
+    pub args: Vec<String>,
+}
+
+/// Represents a response sent by the daemon to the CLI.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DaemonResponse {
+    /// The output printed by the command.
+    pub output: String,
+
+    /// The error returned by the command, if any.
+    pub error: Option<String>,
+
+    /// The exit code matching the error, if any.
+    pub code: Option<ExitCode>,
+}
+
+/// Represents an in-memory buffer used by the daemon to collect the output of a command.
+#[derive(Debug, Default, Clone)]
+pub struct DaemonBuf(Rc<RefCell<Vec<u8>>>);
+
+impl DaemonBuf {
+    /// Returns the content of the buffer as a string (invalid UTF-8 sequences are replaced).
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl io::Write for DaemonBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns the name of the daemon socket for the given account: the path of a Unix socket, placed
+/// in `$XDG_RUNTIME_DIR` when available, otherwise in a private `himalaya` directory of the user
+/// cache directory.
+#[cfg(unix)]
+pub fn socket_name(account_name: &str) -> anyhow::Result<String> {
+    let dir = match env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => private_socket_dir()?,
+    };
+    let path = dir.join(format!("himalaya-{}.sock", account_name));
+    trace!("daemon socket path: {:?}", path);
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// Returns the directory holding the daemon sockets when `$XDG_RUNTIME_DIR` is not set, creating it
+/// if needed. Sockets are never placed in a shared directory like `/tmp`, so the directory must be
+/// accessible only by its owner.
+#[cfg(unix)]
+fn private_socket_dir() -> anyhow::Result<PathBuf> {
+    let cache_dir = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .ok_or_else(|| {
+            anyhow!(
+                "cannot find a directory for the daemon socket: XDG_RUNTIME_DIR and HOME are unset"
+            )
+        })?;
+    let dir = cache_dir.join("himalaya");
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)
+        .context(format!("cannot create daemon socket directory {:?}", dir))?;
+    let mode = fs::metadata(&dir)
+        .context(format!("cannot read daemon socket directory {:?}", dir))?
+        .permissions()
+        .mode();
+    if mode & 0o077 != 0 {
+        return Err(anyhow!(
+            "daemon socket directory {:?} is accessible by other users (mode {:o}), expected 700",
+            dir,
+            mode & 0o777
+        ));
+    }
+    Ok(dir)
+}
+
+/// Returns the name of the daemon socket for the given account: a named pipe, the `@` prefix
+/// placing it in the namespace of pipes (`\\.\pipe\`).
+#[cfg(windows)]
+pub fn socket_name(account_name: &str) -> anyhow::Result<String> {
+    let name = format!("@himalaya-{}", account_name);
+    trace!("daemon named pipe: {}", name);
+    Ok(name)
+}
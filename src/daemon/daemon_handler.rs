@@ -0,0 +1,178 @@
+//! Module related to daemon handling.
+//!
+//! This module gathers the daemon server loop and the CLI side forwarding.
+
+use anyhow::{anyhow, Context, Result};
+use interprocess::local_socket::{LocalSocketListener, LocalSocketStream};
+use log::{debug, trace, warn};
+#[cfg(unix)]
+use std::{fs, path::Path};
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    thread,
+    time::Duration,
+};
+use termcolor::NoColor;
+
+use crate::{
+    config::Account,
+    daemon::{socket_name, DaemonBuf, DaemonRequest, DaemonResponse},
+    domain::SharedImapSession,
+    output::{ExitCode, WriteColor},
+};
+
+/// Represents the interval between two applications of the retention policies by the daemon.
+const CLEAN_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Represents the interval between two wake ups of the snoozed messages by the daemon.
+const WAKE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Represents the interval between two flushes of the due queued messages by the daemon.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Starts the daemon for the given account. Each request is executed by the `run` closure, which
+/// receives the raw arguments of the command, the writter collecting its output and the IMAP
+/// session shared between requests.
+pub fn start<F>(account: &Account, mut run: F) -> Result<()>
+where
+    F: FnMut(&[String], Box<dyn WriteColor>, SharedImapSession) -> Result<()>,
+{
+    let name = socket_name(&account.name)?;
+    if LocalSocketStream::connect(name.as_str()).is_ok() {
+        return Err(anyhow!(
+            r#"a daemon is already running for account "{}""#,
+            account.name
+        ));
+    }
+    // Unix sockets outlive their daemon as files, unlike named pipes
+    #[cfg(unix)]
+    {
+        if Path::new(&name).exists() {
+            debug!("remove stale daemon socket at {:?}", name);
+            fs::remove_file(&name).context(format!("cannot remove daemon socket at {:?}", name))?;
+        }
+    }
+
+    let listener = LocalSocketListener::bind(name.as_str())
+        .context(format!("cannot bind daemon socket at {:?}", name))?;
+    debug!("daemon listening at {:?}", name);
+    let sess = SharedImapSession::default();
+
+    if !account.retention.is_empty() {
+        spawn_job(name.clone(), &["clean"], CLEAN_INTERVAL);
+    }
+    spawn_job(name.clone(), &["wake"], WAKE_INTERVAL);
+    spawn_job(name, &["queue", "flush", "--due"], FLUSH_INTERVAL);
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(err) = serve(stream, &mut run, &sess) {
+                    warn!("cannot serve daemon request: {:?}", err);
+                }
+            }
+            Err(err) => warn!("cannot accept daemon connection: {}", err),
+        }
+    }
+
+    Ok(())
+}
+
+/// Spawns a thread running the given command at startup then periodically, like `clean` to apply
+/// the retention policies, `wake` to wake the snoozed messages up or `queue flush --due` to send
+/// the scheduled messages. The command is sent to the daemon itself, so it is executed with the
+/// shared IMAP session.
+fn spawn_job(name: String, cmd: &'static [&'static str], interval: Duration) {
+    let cmd_name = cmd.join(" ");
+    thread::spawn(move || loop {
+        debug!("run periodic command {}", cmd_name);
+        let args: Vec<String> = std::iter::once(env!("CARGO_PKG_NAME"))
+            .chain(cmd.iter().copied())
+            .map(String::from)
+            .collect();
+        let res = LocalSocketStream::connect(name.as_str())
+            .context(format!("cannot connect to daemon socket at {:?}", name))
+            .and_then(|stream| send(stream, &args));
+        match res {
+            Ok(DaemonResponse {
+                error: Some(err), ..
+            }) => warn!("cannot run periodic command {}: {}", cmd_name, err),
+            Ok(res) => debug!("{}", res.output.trim_end()),
+            Err(err) => warn!("cannot run periodic command {}: {:?}", cmd_name, err),
+        }
+        thread::sleep(interval);
+    });
+}
+
+/// Serves a single request.
+fn serve<F>(mut stream: LocalSocketStream, run: &mut F, sess: &SharedImapSession) -> Result<()>
+where
+    F: FnMut(&[String], Box<dyn WriteColor>, SharedImapSession) -> Result<()>,
+{
+    let mut req = String::new();
+    BufReader::new(&mut stream)
+        .read_line(&mut req)
+        .context("cannot read daemon request")?;
+    let req: DaemonRequest = serde_json::from_str(&req).context("cannot parse daemon request")?;
+    trace!("daemon request: {:?}", req);
+
+    let buf = DaemonBuf::default();
+    let res = run(&req.args, Box::new(NoColor::new(buf.clone())), sess.clone());
+    if res.is_err() {
+        // The session may be broken (timeout, network error…), so it is safer to open a new one
+        // for the next request.
+        debug!("reset IMAP session");
+        sess.replace(None);
+    }
+
+    let res = DaemonResponse {
+        output: buf.to_string_lossy(),
+        code: res.as_ref().err().map(ExitCode::from),
+        error: res.err().map(|err| format!("{:?}", err)),
+    };
+    trace!("daemon response: {:?}", res);
+    serde_json::to_writer(&mut stream, &res).context("cannot write daemon response")
+}
+
+/// Forwards the given raw arguments to the daemon of the given account. Returns `false` if no
+/// daemon is running, so the CLI can execute the command by itself.
+pub fn forward(account: &Account, args: &[String]) -> Result<bool> {
+    // Without a usable socket directory, no daemon can be running
+    let name = match socket_name(&account.name) {
+        Ok(name) => name,
+        Err(err) => {
+            debug!("cannot find daemon socket: {:?}", err);
+            return Ok(false);
+        }
+    };
+    let stream = match LocalSocketStream::connect(name.as_str()) {
+        Ok(stream) => stream,
+        Err(_) => return Ok(false),
+    };
+    debug!("forward command to daemon at {:?}", name);
+
+    let res = send(stream, args)?;
+    print!("{}", res.output);
+    io::stdout().flush().context("cannot flush stdout")?;
+
+    match (res.error, res.code) {
+        (Some(err), Some(code)) => Err(code.err(err)),
+        (Some(err), None) => Err(anyhow!(err)),
+        (None, _) => Ok(true),
+    }
+}
+
+/// Sends the given raw arguments to the daemon through the given stream, then waits for the
+/// response.
+fn send(mut stream: LocalSocketStream, args: &[String]) -> Result<DaemonResponse> {
+    let req = DaemonRequest {
+        args: args.to_vec(),
+    };
+    serde_json::to_writer(&mut stream, &req).context("cannot write daemon request")?;
+    writeln!(stream).context("cannot write daemon request")?;
+
+    let res: DaemonResponse =
+        serde_json::from_reader(&mut stream).context("cannot read daemon response")?;
+    trace!("daemon response: {:?}", res);
+    Ok(res)
+}
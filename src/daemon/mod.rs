@@ -0,0 +1,11 @@
+//! Module related to the daemon.
+//!
+//! The daemon keeps an authenticated IMAP session open and serves commands sent by the CLI over
+//! a local socket: a Unix socket, or a named pipe on Windows. This way, the login and the TLS
+//! handshake are paid only once.
+
+pub mod daemon_arg;
+pub mod daemon_handler;
+
+pub mod daemon_entity;
+pub use daemon_entity::*;
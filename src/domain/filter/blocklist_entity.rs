@@ -0,0 +1,139 @@
+//! Blocklist entity module.
+//!
+//! This module contains the definition of the list of blocked senders, stored next to the config
+//! file with the `.blocklist` extension, one address per line.
+
+use anyhow::{Context, Result};
+use std::{
+    collections::BTreeSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::domain::filter::Filter;
+
+/// Represents the list of blocked senders.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Blocklist {
+    /// The file the blocklist is stored in.
+    path: PathBuf,
+    /// The blocked addresses, lowercased.
+    senders: BTreeSet<String>,
+}
+
+/// Normalizes an address, so `<Spam@Example.com>` and `spam@example.com` are the same sender.
+fn normalize(addr: &str) -> String {
+    addr.trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_lowercase()
+}
+
+impl Blocklist {
+    /// Builds the path of the blocklist from the path of the config file.
+    pub fn path_from_config(config_path: &Path) -> PathBuf {
+        config_path.with_extension("blocklist")
+    }
+
+    /// Loads the blocklist from the given file. A missing file gives an empty blocklist.
+    pub fn load<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                return Err(err).context(format!("cannot read blocklist {:?}", path));
+            }
+        };
+        let senders = content
+            .lines()
+            .map(normalize)
+            .filter(|addr| !addr.is_empty() && !addr.starts_with('#'))
+            .collect();
+        Ok(Self { path, senders })
+    }
+
+    /// Saves the blocklist to its file.
+    pub fn save(&self) -> Result<()> {
+        let mut content = self.senders.iter().cloned().collect::<Vec<_>>().join("\n");
+        content.push('\n');
+        fs::write(&self.path, content).context(format!("cannot write blocklist {:?}", self.path))
+    }
+
+    /// Blocks the given sender. Returns false if it was already blocked.
+    pub fn block(&mut self, addr: &str) -> bool {
+        self.senders.insert(normalize(addr))
+    }
+
+    /// Unblocks the given sender. Returns false if it was not blocked.
+    pub fn unblock(&mut self, addr: &str) -> bool {
+        self.senders.remove(&normalize(addr))
+    }
+
+    /// Builds the filter moving the messages of the blocked senders to the given mailbox, or
+    /// `None` if no sender is blocked.
+    pub fn to_filter(&self, junk_folder: &str) -> Option<Filter> {
+        if self.senders.is_empty() {
+            return None;
+        }
+        let addrs: Vec<String> = self
+            .senders
+            .iter()
+            .map(|addr| regex::escape(addr))
+            .collect();
+        Some(Filter {
+            from: Some(format!(r"(^|[<\s,])({})($|[>\s,])", addrs.join("|"))),
+            move_to: Some(junk_folder.to_owned()),
+            ..Filter::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::filter::FilterHeaders;
+
+    #[test]
+    fn it_should_block_senders() {
+        let mut blocklist = Blocklist::default();
+        assert_eq!(None, blocklist.to_filter("Junk"));
+
+        assert!(blocklist.block("<Spam@Example.com>"));
+        assert!(!blocklist.block("spam@example.com"));
+        assert!(blocklist.block("promo+1@shop.example"));
+        assert_eq!(
+            vec!["promo+1@shop.example", "spam@example.com"],
+            blocklist.senders.iter().collect::<Vec<_>>()
+        );
+
+        let filter = blocklist.to_filter("Junk").unwrap();
+        assert_eq!(Some(String::from("Junk")), filter.move_to);
+        let matches = |from: &str| {
+            let headers = FilterHeaders {
+                from: from.into(),
+                ..FilterHeaders::default()
+            };
+            filter.matches(&headers).unwrap()
+        };
+        assert!(matches("Spammer <SPAM@example.com>"));
+        assert!(matches("promo+1@shop.example"));
+        assert!(!matches("not-spam@example.com"));
+        assert!(!matches("Spammer <spam@example.com.evil>"));
+
+        assert!(blocklist.unblock("SPAM@example.com"));
+        assert!(!blocklist.unblock("spam@example.com"));
+    }
+
+    #[test]
+    fn it_should_build_path_from_config() {
+        assert_eq!(
+            PathBuf::from("/home/me/.config/himalaya/config.blocklist"),
+            Blocklist::path_from_config(Path::new("/home/me/.config/himalaya/config.toml"))
+        );
+        assert_eq!(
+            PathBuf::from("/home/me/.himalayarc.blocklist"),
+            Blocklist::path_from_config(Path::new("/home/me/.himalayarc"))
+        );
+    }
+}
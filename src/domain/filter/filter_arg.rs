@@ -0,0 +1,110 @@
+//! Module related to filter CLI.
+//!
+//! This module provides subcommands and a command matcher related to filters.
+
+use anyhow::Result;
+use clap::{self, App, Arg, ArgMatches, SubCommand};
+use log::{debug, trace};
+
+type Mbox<'a> = Option<&'a str>;
+type Addr<'a> = &'a str;
+
+/// Filter commands.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Cmd<'a> {
+    /// Apply the filters to the given mailbox, or to the current one if omitted.
+    Run(Mbox<'a>),
+    /// Block the given sender.
+    Block(Addr<'a>),
+    /// Unblock the given sender.
+    Unblock(Addr<'a>),
+}
+
+/// Filter command matcher.
+pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Cmd<'a>>> {
+    if let Some(m) = m.subcommand_matches("filters") {
+        if let Some(m) = m.subcommand_matches("run") {
+            debug!("filters run command matched");
+            let mbox = m.value_of("mbox-target");
+            trace!(r#"target mailbox: "{:?}""#, mbox);
+            return Ok(Some(Cmd::Run(mbox)));
+        }
+    }
+
+    if let Some(m) = m.subcommand_matches("block") {
+        debug!("block command matched");
+        let addr = m.value_of("address").unwrap();
+        trace!("address: {}", addr);
+        return Ok(Some(Cmd::Block(addr)));
+    }
+
+    if let Some(m) = m.subcommand_matches("unblock") {
+        debug!("unblock command matched");
+        let addr = m.value_of("address").unwrap();
+        trace!("address: {}", addr);
+        return Ok(Some(Cmd::Unblock(addr)));
+    }
+
+    Ok(None)
+}
+
+/// Sender address argument.
+fn addr_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("address")
+        .help("Specifies the sender address")
+        .value_name("ADDRESS")
+        .required(true)
+}
+
+/// Filter subcommands.
+pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
+    vec![
+        SubCommand::with_name("filters")
+        .aliases(&["filter", "fil"])
+        .about("Handles local filter rules")
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Applies the filter rules of the account to a mailbox")
+                .arg(
+                    Arg::with_name("mbox-target")
+                        .help("Specifies the mailbox to filter (defaults to the current one)")
+                        .value_name("MAILBOX"),
+                ),
+        ),
+        SubCommand::with_name("block")
+            .about("Blocks a sender")
+            .long_about("Adds a sender to the blocklist. Messages from blocked senders are moved to the junk folder by the filters, see `filters run`.")
+            .arg(addr_arg()),
+        SubCommand::with_name("unblock")
+            .about("Unblocks a sender")
+            .arg(addr_arg()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_match_cmds() {
+        let app = || clap::App::new("himalaya").subcommands(subcmds());
+
+        let arg = app().get_matches_from(&["himalaya", "filters", "run"]);
+        assert_eq!(Some(Cmd::Run(None)), matches(&arg).unwrap());
+
+        let arg = app().get_matches_from(&["himalaya", "filter", "run", "Lists"]);
+        assert_eq!(Some(Cmd::Run(Some("Lists"))), matches(&arg).unwrap());
+
+        let arg = app().get_matches_from(&["himalaya", "filters"]);
+        assert_eq!(None, matches(&arg).unwrap());
+
+        let arg = app().get_matches_from(&["himalaya", "block", "spam@example.com"]);
+        assert_eq!(Some(Cmd::Block("spam@example.com")), matches(&arg).unwrap());
+
+        let arg = app().get_matches_from(&["himalaya", "unblock", "spam@example.com"]);
+        assert_eq!(
+            Some(Cmd::Unblock("spam@example.com")),
+            matches(&arg).unwrap()
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! Filter entity module.
+//!
+//! This module contains the definition of a filter rule and of the headers it matches on.
+
+use anyhow::{anyhow, Context, Error, Result};
+use mailparse::MailHeaderMap;
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use std::convert::TryFrom;
+
+use crate::domain::{msg::Flags, RawEnvelope};
+
+/// Represents a filter rule from the user config. Matchers are case-insensitive regular
+/// expressions, all the defined ones need to match. A filter without matcher never matches.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Filter {
+    /// Matches the `From` header.
+    pub from: Option<String>,
+    /// Matches the `Subject` header.
+    pub subject: Option<String>,
+    /// Matches the `List-Id` header.
+    pub list_id: Option<String>,
+
+    /// Adds the given flags (separated by spaces) to the matching messages.
+    pub flags: Option<String>,
+    /// Moves the matching messages to the given mailbox.
+    #[serde(rename = "move")]
+    pub move_to: Option<String>,
+    /// Deletes the matching messages.
+    pub delete: Option<bool>,
+    /// Runs the given command with the subject and the sender of the matching messages as
+    /// arguments.
+    pub cmd: Option<String>,
+}
+
+impl Filter {
+    /// Compiles the matchers of the filter, so they can be checked against many messages.
+    pub fn compile(&self) -> Result<FilterMatcher> {
+        let compile = |pattern: &Option<String>| {
+            pattern
+                .as_ref()
+                .map(|pattern| {
+                    RegexBuilder::new(pattern)
+                        .case_insensitive(true)
+                        .build()
+                        .context(format!(r#"cannot parse filter pattern "{}""#, pattern))
+                })
+                .transpose()
+        };
+        Ok(FilterMatcher {
+            filter: self,
+            from: compile(&self.from)?,
+            subject: compile(&self.subject)?,
+            list_id: compile(&self.list_id)?,
+        })
+    }
+
+    /// Checks if the filter matches the given headers.
+    pub fn matches(&self, headers: &FilterHeaders) -> Result<bool> {
+        Ok(self.compile()?.matches(headers))
+    }
+}
+
+/// Represents a filter with its matchers compiled.
+#[derive(Debug)]
+pub struct FilterMatcher<'a> {
+    pub filter: &'a Filter,
+    from: Option<Regex>,
+    subject: Option<Regex>,
+    list_id: Option<Regex>,
+}
+
+impl<'a> FilterMatcher<'a> {
+    /// Checks if the filter matches the given headers.
+    pub fn matches(&self, headers: &FilterHeaders) -> bool {
+        let matchers = [
+            (&self.from, &headers.from),
+            (&self.subject, &headers.subject),
+            (&self.list_id, &headers.list_id),
+        ];
+
+        let mut matched = false;
+        for (regex, value) in matchers.iter() {
+            if let Some(regex) = regex {
+                if !regex.is_match(value) {
+                    return false;
+                }
+                matched = true;
+            }
+        }
+
+        matched
+    }
+}
+
+/// Represents the headers of a message filters can match on.
+#[derive(Debug, Default)]
+pub struct FilterHeaders {
+    /// The sequence number of the message, or its UID in UID mode.
+    pub seq: u32,
+    /// The UID of the message, if fetched.
+    pub uid: Option<u32>,
+    /// The flags of the message, kept when the message is moved.
+    pub flags: Flags,
+    pub from: String,
+    pub subject: String,
+    pub list_id: String,
+}
+
+impl<'a> TryFrom<&'a RawEnvelope> for FilterHeaders {
+    type Error = Error;
+
+    fn try_from(fetch: &'a RawEnvelope) -> Result<Self> {
+        let raw_headers = fetch
+            .header()
+            .ok_or_else(|| anyhow!("cannot get headers of message {}", fetch.message))?;
+        let (headers, _) = mailparse::parse_headers(raw_headers)
+            .context(format!("cannot parse headers of message {}", fetch.message))?;
+
+        Ok(Self {
+            seq: fetch.message,
+            uid: fetch.uid,
+            flags: Flags::try_from(fetch.flags())?,
+            from: headers.get_first_value("From").unwrap_or_default(),
+            subject: headers.get_first_value("Subject").unwrap_or_default(),
+            list_id: headers.get_first_value("List-Id").unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_match_headers() {
+        let headers = FilterHeaders {
+            from: "Rust Weekly <newsletter@this-week-in-rust.org>".into(),
+            subject: "This Week in Rust 418".into(),
+            list_id: "<twir.this-week-in-rust.org>".into(),
+            ..FilterHeaders::default()
+        };
+
+        let filter = Filter::default();
+        assert!(!filter.matches(&headers).unwrap());
+
+        let filter = Filter {
+            from: Some("NEWSLETTER@".into()),
+            ..Filter::default()
+        };
+        assert!(filter.matches(&headers).unwrap());
+
+        let filter = Filter {
+            from: Some("newsletter@".into()),
+            subject: Some("^weekly".into()),
+            ..Filter::default()
+        };
+        assert!(!filter.matches(&headers).unwrap());
+
+        let filter = Filter {
+            list_id: Some(r"twir\.".into()),
+            ..Filter::default()
+        };
+        assert!(filter.matches(&headers).unwrap());
+
+        let filter = Filter {
+            subject: Some("(".into()),
+            ..Filter::default()
+        };
+        assert!(filter.matches(&headers).is_err());
+    }
+}
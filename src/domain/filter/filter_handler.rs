@@ -0,0 +1,209 @@
+//! Module related to filter handling.
+//!
+//! This module gathers all filter commands.
+
+use anyhow::{anyhow, Context, Result};
+use imap::types::Flag;
+use log::{debug, trace};
+use std::convert::TryFrom;
+
+use crate::{
+    config::{Account, Config},
+    domain::{
+        filter::{Blocklist, Filter, FilterHeaders, FilterMatcher},
+        hook::{hook_handler, Hook, HookMsg, Hooks},
+        imap::ImapServiceInterface,
+        msg::Flags,
+    },
+    output::{run_cmd_with_args, ExitCode, PrinterService},
+};
+
+/// Apply the filters of the account to the current mailbox. In dry run mode, the actions that
+/// would be taken are printed instead.
+pub fn run<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    dry_run: bool,
+    account: &Account,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+) -> Result<()> {
+    let hooks = Hooks::load(account)?;
+    let matched = apply(account, hooks.as_ref(), dry_run, &mut None, imap)?;
+    if dry_run && !matched.is_empty() {
+        printer.print(matched.join("\n"))
+    } else {
+        printer.print(format!("{} message(s) matched the filters", matched.len()))
+    }
+}
+
+/// Add the given sender to the blocklist.
+pub fn block<Printer: PrinterService>(
+    addr: &str,
+    config: &Config,
+    printer: &mut Printer,
+) -> Result<()> {
+    let path = config
+        .blocklist_path()
+        .ok_or_else(|| anyhow!("cannot find blocklist path"))?;
+    let mut blocklist = Blocklist::load(path)?;
+    if blocklist.block(addr) {
+        blocklist.save()?;
+        printer.print(format!(r#"Sender "{}" successfully blocked"#, addr))
+    } else {
+        printer.print(format!(r#"Sender "{}" already blocked"#, addr))
+    }
+}
+
+/// Remove the given sender from the blocklist.
+pub fn unblock<Printer: PrinterService>(
+    addr: &str,
+    config: &Config,
+    printer: &mut Printer,
+) -> Result<()> {
+    let path = config
+        .blocklist_path()
+        .ok_or_else(|| anyhow!("cannot find blocklist path"))?;
+    let mut blocklist = Blocklist::load(path)?;
+    if !blocklist.unblock(addr) {
+        return Err(anyhow!(r#"cannot find sender "{}" in the blocklist"#, addr));
+    }
+    blocklist.save()?;
+    printer.print(format!(r#"Sender "{}" successfully unblocked"#, addr))
+}
+
+/// Apply the filters of the account to the messages of the current mailbox, in the order they
+/// are defined, then its `filter_message` hook. Once a message is moved or deleted, the
+/// following filters are skipped. Returns the description of the actions taken for each message
+/// matching at least one filter or touched by the hook. In dry run mode, the actions are only
+/// described.
+///
+/// Only the messages having a UID greater than `last_uid` are filtered, all of them when it is
+/// `None`. It is then set to the greatest UID seen, so the next calls (like in watch mode) skip
+/// the messages already filtered. The hooks are loaded by the caller, so they can be loaded once
+/// for all the calls.
+pub fn apply<'a, ImapService: ImapServiceInterface<'a>>(
+    account: &Account,
+    hooks: Option<&Hooks>,
+    dry_run: bool,
+    last_uid: &mut Option<u32>,
+    imap: &mut ImapService,
+) -> Result<Vec<String>> {
+    if account.filters.is_empty() && hooks.is_none() {
+        debug!("no filter to apply");
+        return Ok(vec![]);
+    }
+    let filters = account
+        .filters
+        .iter()
+        .map(Filter::compile)
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut matched = vec![];
+    let mut expunge = false;
+
+    for headers in imap.fetch_filter_headers(*last_uid)? {
+        *last_uid = (*last_uid).max(headers.uid);
+        let res = apply_msg(&filters, hooks, &headers, dry_run, account, imap);
+        let (actions, deleted) = match res {
+            Ok(res) => res,
+            // Messages already filtered are not rolled back.
+            Err(err) if !matched.is_empty() => {
+                return Err(err.context(ExitCode::PartialFailure).context(format!(
+                    "cannot apply filters to message {} ({} message(s) already filtered)",
+                    headers.seq,
+                    matched.len()
+                )))
+            }
+            Err(err) => return Err(err),
+        };
+        expunge |= deleted;
+
+        if let Some(actions) = actions {
+            let actions = if actions.is_empty() {
+                String::from("no action")
+            } else {
+                actions.join(", ")
+            };
+            debug!("message {}: {}", headers.seq, actions);
+            matched.push(format!("Message {}: {}", headers.seq, actions));
+        }
+    }
+
+    if expunge && !dry_run {
+        imap.expunge()?;
+    }
+
+    Ok(matched)
+}
+
+/// Apply the given filters then the given hooks to a single message. Returns the actions taken,
+/// or `None` if the message does not match any filter nor is touched by the hooks, and whether
+/// the message was moved or deleted.
+fn apply_msg<'a, ImapService: ImapServiceInterface<'a>>(
+    filters: &[FilterMatcher],
+    hooks: Option<&Hooks>,
+    headers: &FilterHeaders,
+    dry_run: bool,
+    account: &Account,
+    imap: &mut ImapService,
+) -> Result<(Option<Vec<String>>, bool)> {
+    let seq = headers.seq.to_string();
+    let mut actions = vec![];
+    let mut is_matching = false;
+
+    for matcher in filters {
+        if !matcher.matches(headers) {
+            continue;
+        }
+        let filter = matcher.filter;
+        debug!("message {} matches filter", seq);
+        trace!("filter: {:?}", filter);
+        is_matching = true;
+
+        if let Some(ref cmd) = filter.cmd {
+            if !dry_run {
+                run_cmd_with_args(cmd, &[&headers.subject, &headers.from])
+                    .context(format!(r#"cannot run filter command "{}""#, cmd))?;
+            }
+            actions.push(format!(
+                "run command {:?} with {:?} {:?}",
+                cmd, headers.subject, headers.from
+            ));
+        }
+
+        if let Some(ref flags) = filter.flags {
+            let flags = Flags::from(flags.split_whitespace().collect::<Vec<_>>());
+            if !dry_run {
+                imap.add_flags(&seq, &flags)?;
+            }
+            actions.push(format!(r#"add flag(s) "{}""#, flags));
+        }
+
+        if let Some(ref mbox) = filter.move_to {
+            if !dry_run {
+                hook_handler::move_msg(&seq, mbox, imap)?;
+            }
+            actions.push(format!(r#"move to folder "{}""#, mbox));
+            return Ok((Some(actions), true));
+        }
+
+        if filter.delete.unwrap_or_default() {
+            if !dry_run {
+                imap.add_flags(&seq, &Flags::try_from(vec![Flag::Deleted])?)?;
+            }
+            actions.push(String::from("delete"));
+            return Ok((Some(actions), true));
+        }
+    }
+
+    if let Some(hooks) = hooks {
+        let msg = HookMsg::from(headers);
+        let (hook_actions, deleted) =
+            hook_handler::run(hooks, Hook::FilterMessage, &msg, dry_run, account, imap)?;
+        if !hook_actions.is_empty() {
+            actions.extend(hook_actions);
+            return Ok((Some(actions), deleted));
+        }
+    }
+
+    Ok((if is_matching { Some(actions) } else { None }, false))
+}
@@ -0,0 +1,13 @@
+//! Filter module.
+//!
+//! This module contains everything related to the local filter rules, a client-side alternative
+//! to Sieve.
+
+pub mod filter_arg;
+pub mod filter_handler;
+
+pub mod filter_entity;
+pub use filter_entity::*;
+
+pub mod blocklist_entity;
+pub use blocklist_entity::*;
@@ -0,0 +1,482 @@
+//! Hook entity module.
+//!
+//! This module contains the Lua runtime running the hooks of the account. A hook is a global
+//! function of the script, called with a table describing the message:
+//!
+//! ```lua
+//! function filter_message(msg)
+//!   if msg.list_id:find("rust-users") then
+//!     msg:move("Lists/Rust")
+//!   elseif msg.subject:find("URGENT") then
+//!     msg:flag("Flagged")
+//!     himalaya.notify(msg.subject, msg.from)
+//!   end
+//! end
+//! ```
+//!
+//! Hooks do not act on the mailbox directly: the actions they ask for are collected and then
+//! applied by the caller, so that they can be described in dry run mode.
+
+use anyhow::{anyhow, Context, Error, Result};
+use log::{debug, trace};
+use mailparse::MailHeaderMap;
+use mlua::{Function, HookTriggers, Lua, LuaOptions, StdLib, Table, Value, Variadic};
+use std::{
+    cell::{Cell, RefCell},
+    fmt, fs,
+    path::Path,
+    rc::Rc,
+};
+
+use crate::{
+    config::Account,
+    domain::{
+        filter::FilterHeaders,
+        msg::{Envelope, Flags, Msg},
+    },
+};
+
+/// Represents the name of the metatable of the messages, in the Lua registry.
+const MSG_METATABLE: &str = "himalaya_msg";
+
+/// Represents the base library functions able to load code from the file system or from strings,
+/// removed from the scripts.
+const UNSAFE_GLOBALS: [&str; 4] = ["dofile", "loadfile", "load", "require"];
+
+/// Represents the number of instructions between two checks of the instruction budget.
+const INSTRUCTIONS_STEP: u32 = 1_000;
+
+/// Represents the maximum number of instructions a hook run (or the loading of the script) can
+/// execute, as a number of [`INSTRUCTIONS_STEP`].
+const INSTRUCTIONS_BUDGET: u32 = 10_000;
+
+/// Represents the maximum memory the Lua state can use, in bytes.
+const MEMORY_LIMIT: usize = 16 * 1024 * 1024;
+
+/// Converts Lua errors, which keep the traceback of the script.
+fn lua_err(err: mlua::Error) -> Error {
+    anyhow!("{}", err)
+}
+
+/// Represents the hook points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hook {
+    /// Called for each message arriving in the mailbox watched by the notify mode.
+    OnNewMail,
+    /// Called before a message is sent. Returning `false` cancels the sending.
+    PreSend,
+    /// Called after a message is read.
+    PostRead,
+    /// Called for each message of the mailbox when the filters apply, after them.
+    FilterMessage,
+}
+
+impl Hook {
+    /// Returns the name of the Lua function implementing the hook.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::OnNewMail => "on_new_mail",
+            Self::PreSend => "pre_send",
+            Self::PostRead => "post_read",
+            Self::FilterMessage => "filter_message",
+        }
+    }
+}
+
+/// Represents the actions a hook can ask for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HookAction {
+    /// Moves the message to the given mailbox.
+    Move(String),
+    /// Adds the given flags to the message.
+    Flag(Vec<String>),
+    /// Deletes the message.
+    Delete,
+    /// Runs the notify command with the given subject and sender.
+    Notify(String, String),
+}
+
+impl fmt::Display for HookAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Move(mbox) => write!(f, r#"move to folder "{}""#, mbox),
+            Self::Flag(flags) => write!(f, r#"add flag(s) "{}""#, flags.join(" ")),
+            Self::Delete => write!(f, "delete"),
+            Self::Notify(subject, sender) => write!(f, "notify {:?} {:?}", subject, sender),
+        }
+    }
+}
+
+/// Represents the outcome of a hook.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct HookResult {
+    /// The actions asked by the hook, in order.
+    pub actions: Vec<HookAction>,
+    /// Whether the hook returned `false`.
+    pub cancelled: bool,
+}
+
+/// Represents the message given to hooks.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct HookMsg {
+    /// The sequence number of the message (or its UID in UID mode), `None` for messages not in a
+    /// mailbox yet.
+    pub seq: Option<u32>,
+    pub subject: String,
+    pub from: String,
+    pub to: Vec<String>,
+    pub date: Option<String>,
+    /// The flags of the message, without their leading backslash.
+    pub flags: Vec<String>,
+    pub list_id: String,
+}
+
+/// Lists the given flags without their leading backslash, like `Seen`.
+fn flag_names(flags: &Flags) -> Vec<String> {
+    let flags = flags.to_string();
+    let mut names: Vec<String> = flags
+        .split_whitespace()
+        .map(|flag| flag.trim_start_matches('\\').to_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+impl From<&FilterHeaders> for HookMsg {
+    fn from(headers: &FilterHeaders) -> Self {
+        Self {
+            seq: Some(headers.seq),
+            subject: headers.subject.to_owned(),
+            from: headers.from.to_owned(),
+            flags: flag_names(&headers.flags),
+            list_id: headers.list_id.to_owned(),
+            ..Self::default()
+        }
+    }
+}
+
+impl<'a> From<&Envelope<'a>> for HookMsg {
+    fn from(envelope: &Envelope<'a>) -> Self {
+        Self {
+            seq: Some(envelope.id),
+            subject: envelope.subject.to_string(),
+            from: envelope.sender.to_owned(),
+            date: envelope.date.to_owned(),
+            flags: flag_names(&envelope.flags),
+            ..Self::default()
+        }
+    }
+}
+
+impl From<&Msg> for HookMsg {
+    fn from(msg: &Msg) -> Self {
+        let addrs = |addrs: &Option<Vec<lettre::message::Mailbox>>| {
+            addrs
+                .iter()
+                .flatten()
+                .map(|addr| addr.to_string())
+                .collect::<Vec<_>>()
+        };
+        Self {
+            seq: Some(msg.id).filter(|id| *id > 0),
+            subject: msg.subject.to_owned(),
+            from: addrs(&msg.from).join(", "),
+            to: addrs(&msg.to),
+            date: msg.date.map(|date| date.to_rfc3339()),
+            flags: flag_names(&msg.flags),
+            ..Self::default()
+        }
+    }
+}
+
+impl HookMsg {
+    /// Builds the message from the headers of the given raw message, which is not in a mailbox.
+    pub fn from_raw(raw: &[u8]) -> Result<Self> {
+        let (headers, _) = mailparse::parse_headers(raw).context("cannot parse message headers")?;
+        let header = |key| headers.get_first_value(key).unwrap_or_default();
+        Ok(Self {
+            seq: None,
+            subject: header("Subject"),
+            from: header("From"),
+            to: headers
+                .get_all_values("To")
+                .iter()
+                .flat_map(|to| to.split(','))
+                .map(|to| to.trim().to_owned())
+                .filter(|to| !to.is_empty())
+                .collect(),
+            date: headers.get_first_value("Date"),
+            flags: vec![],
+            list_id: header("List-Id"),
+        })
+    }
+
+    /// Builds the Lua table of the message, its methods coming from the given metatable.
+    fn to_table<'lua>(&self, lua: &'lua Lua, metatable: Table<'lua>) -> mlua::Result<Table<'lua>> {
+        let table = lua.create_table()?;
+        table.set("seq", self.seq)?;
+        table.set("subject", self.subject.as_str())?;
+        table.set("from", self.from.as_str())?;
+        table.set("to", self.to.clone())?;
+        table.set("date", self.date.as_deref())?;
+        table.set("flags", self.flags.clone())?;
+        table.set("list_id", self.list_id.as_str())?;
+        table.set_metatable(Some(metatable));
+        Ok(table)
+    }
+}
+
+/// Represents the Lua script of the account, defining the hooks.
+pub struct Hooks {
+    lua: Lua,
+    /// The actions asked by the running hook.
+    actions: Rc<RefCell<Vec<HookAction>>>,
+    /// The instruction budget left to the running hook, as a number of [`INSTRUCTIONS_STEP`].
+    budget: Rc<Cell<u32>>,
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Hooks").finish()
+    }
+}
+
+impl Hooks {
+    /// Loads the hooks script of the given account, if any.
+    pub fn load(account: &Account) -> Result<Option<Self>> {
+        match account.hooks_script.as_deref() {
+            None => Ok(None),
+            Some(path) => Self::from_path(path).map(Some),
+        }
+    }
+
+    /// Loads the hooks script at the given path.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        debug!("loading hooks from {:?}…", path);
+        let script =
+            fs::read_to_string(path).context(format!("cannot read hooks script at {:?}", path))?;
+        Self::from_script(&script).context(format!("cannot load hooks script at {:?}", path))
+    }
+
+    /// Loads the given hooks script. Only the `string`, `table` and `math` standard libraries and
+    /// the base functions that cannot load code are available: hooks cannot access the file
+    /// system nor run commands. Each hook run (and the loading of the script) is limited in
+    /// instructions, and the whole state in memory.
+    pub fn from_script(script: &str) -> Result<Self> {
+        let libs = StdLib::STRING | StdLib::TABLE | StdLib::MATH;
+        let hooks = Self {
+            lua: Lua::new_with(libs, LuaOptions::default()).map_err(lua_err)?,
+            actions: Rc::new(RefCell::new(vec![])),
+            budget: Rc::new(Cell::new(INSTRUCTIONS_BUDGET)),
+        };
+        hooks.sandbox().map_err(lua_err)?;
+        hooks.register_api().map_err(lua_err)?;
+        hooks.lua.load(script).exec().map_err(lua_err)?;
+        Ok(hooks)
+    }
+
+    /// Removes the unsafe globals then limits the instructions and the memory of the scripts.
+    fn sandbox(&self) -> mlua::Result<()> {
+        let lua = &self.lua;
+        let globals = lua.globals();
+        for name in UNSAFE_GLOBALS.iter() {
+            globals.set(*name, Value::Nil)?;
+        }
+
+        lua.set_memory_limit(MEMORY_LIMIT)?;
+        let budget = self.budget.clone();
+        let triggers = HookTriggers {
+            every_nth_instruction: Some(INSTRUCTIONS_STEP),
+            ..HookTriggers::default()
+        };
+        lua.set_hook(triggers, move |_, _| match budget.get() {
+            0 => Err(mlua::Error::RuntimeError(String::from(
+                "hook exceeded its instruction budget",
+            ))),
+            left => {
+                budget.set(left - 1);
+                Ok(())
+            }
+        })
+    }
+
+    /// Registers the `himalaya` global table and the methods of the messages.
+    fn register_api(&self) -> mlua::Result<()> {
+        let lua = &self.lua;
+
+        let msg_methods = lua.create_table()?;
+        let actions = self.actions.clone();
+        msg_methods.set(
+            "move",
+            lua.create_function(move |_, (_, mbox): (Value, String)| {
+                actions.borrow_mut().push(HookAction::Move(mbox));
+                Ok(())
+            })?,
+        )?;
+        let actions = self.actions.clone();
+        msg_methods.set(
+            "flag",
+            lua.create_function(move |_, (_, flags): (Value, Variadic<String>)| {
+                let flags = flags.iter().flat_map(|flags| flags.split_whitespace());
+                let flags = flags.map(String::from).collect();
+                actions.borrow_mut().push(HookAction::Flag(flags));
+                Ok(())
+            })?,
+        )?;
+        let actions = self.actions.clone();
+        msg_methods.set(
+            "delete",
+            lua.create_function(move |_, _: Value| {
+                actions.borrow_mut().push(HookAction::Delete);
+                Ok(())
+            })?,
+        )?;
+        let metatable = lua.create_table()?;
+        metatable.set("__index", msg_methods)?;
+        lua.set_named_registry_value(MSG_METATABLE, metatable)?;
+
+        let himalaya = lua.create_table()?;
+        let actions = self.actions.clone();
+        himalaya.set(
+            "notify",
+            lua.create_function(move |_, (subject, sender): (String, Option<String>)| {
+                let sender = sender.unwrap_or_default();
+                actions
+                    .borrow_mut()
+                    .push(HookAction::Notify(subject, sender));
+                Ok(())
+            })?,
+        )?;
+        lua.globals().set("himalaya", himalaya)
+    }
+
+    /// Runs the given hook with the given message. Hooks missing from the script do nothing.
+    pub fn run(&self, hook: Hook, msg: &HookMsg) -> Result<HookResult> {
+        let name = hook.name();
+        let f: Option<Function> = self.lua.globals().get(name).map_err(lua_err)?;
+        let f = match f {
+            Some(f) => f,
+            None => return Ok(HookResult::default()),
+        };
+
+        debug!("running hook {} on message {:?}…", name, msg.seq);
+        trace!("message: {:?}", msg);
+        self.actions.borrow_mut().clear();
+        self.budget.set(INSTRUCTIONS_BUDGET);
+        let metatable = self
+            .lua
+            .named_registry_value(MSG_METATABLE)
+            .map_err(lua_err)?;
+        let table = msg.to_table(&self.lua, metatable).map_err(lua_err)?;
+        let ret: Value = f
+            .call(table)
+            .map_err(lua_err)
+            .context(format!("cannot run hook {}", name))?;
+
+        let res = HookResult {
+            actions: self.actions.borrow_mut().drain(..).collect(),
+            cancelled: matches!(ret, Value::Boolean(false)),
+        };
+        trace!("hook result: {:?}", res);
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_collect_actions() {
+        let hooks = Hooks::from_script(
+            r#"
+            function filter_message(msg)
+              if msg.list_id:find("rust-users") then
+                msg:move("Lists/Rust")
+              end
+              if msg.subject:find("URGENT") then
+                msg:flag("Flagged", "$Urgent")
+                himalaya.notify(msg.subject, msg.from)
+              end
+            end
+            "#,
+        )
+        .unwrap();
+
+        let msg = HookMsg {
+            seq: Some(1),
+            subject: String::from("URGENT: build broken"),
+            from: String::from("ci@example.org"),
+            list_id: String::from("<rust-users.lists.example.org>"),
+            ..HookMsg::default()
+        };
+        let res = hooks.run(Hook::FilterMessage, &msg).unwrap();
+        assert_eq!(
+            vec![
+                HookAction::Move(String::from("Lists/Rust")),
+                HookAction::Flag(vec![String::from("Flagged"), String::from("$Urgent")]),
+                HookAction::Notify(
+                    String::from("URGENT: build broken"),
+                    String::from("ci@example.org")
+                ),
+            ],
+            res.actions
+        );
+        assert!(!res.cancelled);
+
+        // Actions do not leak from one run to the next
+        let msg = HookMsg {
+            seq: Some(2),
+            ..HookMsg::default()
+        };
+        let res = hooks.run(Hook::FilterMessage, &msg).unwrap();
+        assert_eq!(HookResult::default(), res);
+    }
+
+    #[test]
+    fn it_should_cancel_sending() {
+        let hooks = Hooks::from_script(
+            r#"
+            function pre_send(msg)
+              return #msg.to > 0 and msg.subject ~= ""
+            end
+            "#,
+        )
+        .unwrap();
+
+        let msg =
+            HookMsg::from_raw(b"From: me@localhost\r\nTo: you@localhost\r\n\r\nHello").unwrap();
+        assert_eq!(vec![String::from("you@localhost")], msg.to);
+        assert!(hooks.run(Hook::PreSend, &msg).unwrap().cancelled);
+
+        let msg = HookMsg::from_raw(b"To: you@localhost\r\nSubject: Hi\r\n\r\nHello").unwrap();
+        assert!(!hooks.run(Hook::PreSend, &msg).unwrap().cancelled);
+
+        // Missing hooks do nothing
+        assert_eq!(
+            HookResult::default(),
+            hooks.run(Hook::PostRead, &msg).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_should_report_script_errors() {
+        assert!(Hooks::from_script("function on_new_mail(msg").is_err());
+
+        let hooks = Hooks::from_script("function post_read(msg) error('boom') end").unwrap();
+        let err = hooks.run(Hook::PostRead, &HookMsg::default()).unwrap_err();
+        assert!(format!("{:#}", err).contains("boom"));
+    }
+
+    #[test]
+    fn it_should_sandbox_scripts() {
+        assert!(Hooks::from_script("assert(string and table and math)").is_ok());
+        assert!(Hooks::from_script("os.execute('true')").is_err());
+        assert!(Hooks::from_script("io.open('/etc/passwd')").is_err());
+        assert!(Hooks::from_script("require('os')").is_err());
+        assert!(Hooks::from_script("dofile('/etc/passwd')").is_err());
+        assert!(Hooks::from_script("assert(not (dofile or loadfile or load or require))").is_ok());
+        assert!(Hooks::from_script("while true do end").is_err());
+        assert!(Hooks::from_script("local s = string.rep('x', 1 << 30)").is_err());
+    }
+}
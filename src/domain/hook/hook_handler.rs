@@ -0,0 +1,142 @@
+//! Module related to hook handling.
+//!
+//! This module gathers the functions running the hooks and applying the actions they ask for.
+
+use anyhow::{anyhow, Context, Result};
+use imap::types::Flag;
+use log::{debug, warn};
+use std::convert::TryFrom;
+
+use crate::{
+    config::{run_notify_cmd, Account},
+    domain::{
+        hook::{Hook, HookAction, HookMsg, Hooks},
+        imap::ImapServiceInterface,
+        mbox::Mbox,
+        msg::Flags,
+    },
+};
+
+/// Runs the given hook on the given message then applies the actions it asks for. Returns the
+/// description of the actions and whether the message was moved or deleted, in which case the
+/// caller expunges the mailbox. In dry run mode, the actions are only described.
+pub fn run<'a, ImapService: ImapServiceInterface<'a>>(
+    hooks: &Hooks,
+    hook: Hook,
+    msg: &HookMsg,
+    dry_run: bool,
+    account: &Account,
+    imap: &mut ImapService,
+) -> Result<(Vec<String>, bool)> {
+    let res = hooks.run(hook, msg)?;
+    let seq = match msg.seq {
+        Some(seq) => seq.to_string(),
+        None => return Ok((notify(&res.actions, dry_run, account)?, false)),
+    };
+    let mut actions = vec![];
+
+    for action in res.actions {
+        debug!("message {}: {}", seq, action);
+        match action {
+            HookAction::Move(ref mbox) => {
+                if !dry_run {
+                    move_msg(&seq, mbox, imap)?;
+                }
+                actions.push(action.to_string());
+                // The message is gone, the next actions cannot apply.
+                return Ok((actions, true));
+            }
+            HookAction::Delete => {
+                if !dry_run {
+                    imap.add_flags(&seq, &Flags::try_from(vec![Flag::Deleted])?)?;
+                }
+                actions.push(action.to_string());
+                return Ok((actions, true));
+            }
+            HookAction::Flag(ref flags) => {
+                if !dry_run {
+                    let flags = Flags::from(flags.iter().map(String::as_str).collect::<Vec<_>>());
+                    imap.add_flags(&seq, &flags)?;
+                }
+                actions.push(action.to_string());
+            }
+            HookAction::Notify(..) => {
+                actions.extend(notify(&[action], dry_run, account)?);
+            }
+        }
+    }
+
+    Ok((actions, false))
+}
+
+/// Runs the given hook on the given message, applying only the notifications it asks for: the
+/// message is left untouched, like when it is read with its flags preserved. Returns the
+/// description of the notifications.
+pub fn run_notify(
+    hooks: &Hooks,
+    hook: Hook,
+    msg: &HookMsg,
+    account: &Account,
+) -> Result<Vec<String>> {
+    let res = hooks.run(hook, msg)?;
+    let (notifications, skipped): (Vec<_>, Vec<_>) = res
+        .actions
+        .into_iter()
+        .partition(|action| matches!(action, HookAction::Notify(..)));
+    for action in skipped {
+        debug!(
+            "message {:?}: skipping {} to leave it untouched",
+            msg.seq, action
+        );
+    }
+    notify(&notifications, false, account)
+}
+
+/// Moves the message of the given sequence number to the given mailbox: the message is copied
+/// then flagged as deleted, the caller expunging the mailbox. Unlike a new `APPEND`, the `COPY`
+/// keeps the flags and the internal date of the message, and lets the server handle `\Recent`.
+pub fn move_msg<'a, ImapService: ImapServiceInterface<'a>>(
+    seq: &str,
+    mbox: &str,
+    imap: &mut ImapService,
+) -> Result<()> {
+    imap.copy_msgs(seq, &Mbox::new(mbox))?;
+    imap.add_flags(seq, &Flags::try_from(vec![Flag::Deleted])?)
+}
+
+/// Runs the `pre_send` hook on the given raw message. Fails when the hook cancels the sending.
+/// Only the notifications apply, the message not being in a mailbox yet.
+pub fn pre_send(account: &Account, raw_msg: &[u8]) -> Result<()> {
+    let hooks = match Hooks::load(account)? {
+        Some(hooks) => hooks,
+        None => return Ok(()),
+    };
+    let res = hooks.run(Hook::PreSend, &HookMsg::from_raw(raw_msg)?)?;
+    notify(&res.actions, false, account)?;
+    if res.cancelled {
+        return Err(anyhow!("message sending cancelled by the pre_send hook"));
+    }
+    Ok(())
+}
+
+/// Runs the notifications among the given actions, skipping the others. Returns their
+/// description.
+fn notify(actions: &[HookAction], dry_run: bool, account: &Account) -> Result<Vec<String>> {
+    let mut notified = vec![];
+    for action in actions {
+        match action {
+            HookAction::Notify(subject, sender) => {
+                if !dry_run {
+                    run_notify_cmd(account.notify_cmd.as_deref(), subject, sender)
+                        .context("cannot run hook notification")?;
+                }
+                notified.push(action.to_string());
+            }
+            action => warn!(
+                "cannot {} a message outside of a mailbox, skipping it",
+                action
+            ),
+        }
+    }
+    Ok(notified)
+}
@@ -0,0 +1,9 @@
+//! Hook module.
+//!
+//! This module contains everything related to the hooks, the Lua functions of the account run
+//! when messages arrive, are sent, are read or are filtered.
+
+pub mod hook_handler;
+
+pub mod hook_entity;
+pub use hook_entity::*;
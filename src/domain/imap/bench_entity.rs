@@ -0,0 +1,94 @@
+//! Benchmark entity module.
+//!
+//! This module contains the definition of the report of the `bench` command, measuring the IMAP
+//! session of an account.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::output::{Print, WriteColor};
+
+/// Represents the default envelope batch sizes measured by the benchmark.
+pub const DEFAULT_BENCH_BATCH_SIZES: &[usize] = &[10, 50, 200];
+
+/// Represents the fetch of a batch of envelopes.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct BenchFetch {
+    /// The number of envelopes asked for.
+    pub batch_size: usize,
+    /// The number of envelopes fetched, lower than the batch size for small mailboxes.
+    pub envelopes: usize,
+    /// The duration of the fetch, in milliseconds.
+    pub millis: u128,
+}
+
+impl BenchFetch {
+    pub fn new(batch_size: usize, envelopes: usize, duration: Duration) -> Self {
+        Self {
+            batch_size,
+            envelopes,
+            millis: duration.as_millis(),
+        }
+    }
+
+    /// Returns the number of envelopes fetched per second.
+    pub fn throughput(&self) -> f64 {
+        self.envelopes as f64 * 1_000.0 / self.millis.max(1) as f64
+    }
+}
+
+/// Represents the report of a benchmark.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct BenchReport {
+    /// The name of the account.
+    pub account: String,
+    /// The duration of the connection and the authentication, in milliseconds.
+    pub login_millis: u128,
+    /// The duration of the mailboxes listing, in milliseconds.
+    pub list_millis: u128,
+    /// The number of mailboxes listed.
+    pub mboxes: usize,
+    /// The envelope fetches, by batch size.
+    pub fetches: Vec<BenchFetch>,
+}
+
+/// Makes the benchmark report printable.
+impl Print for BenchReport {
+    fn print(&self, writter: &mut dyn WriteColor) -> Result<()> {
+        let mut report = vec![
+            format!("Account: {}", self.account),
+            format!("Login: {} ms", self.login_millis),
+            format!("List: {} ms ({} mailboxes)", self.list_millis, self.mboxes),
+        ];
+        if !self.fetches.is_empty() {
+            report.push(String::from("Envelope fetches:"));
+            for fetch in self.fetches.iter() {
+                report.push(format!(
+                    "  batch of {:>4}: {:>4} envelopes in {:>6} ms ({:.1}/s)",
+                    fetch.batch_size,
+                    fetch.envelopes,
+                    fetch.millis,
+                    fetch.throughput()
+                ));
+            }
+        }
+        writeln!(writter, "{}", report.join("\n")).context("cannot print benchmark report")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_compute_throughput() {
+        let fetch = BenchFetch::new(50, 50, Duration::from_millis(250));
+        assert_eq!(250, fetch.millis);
+        assert_eq!(200.0, fetch.throughput());
+        assert_eq!(
+            10_000.0,
+            BenchFetch::new(10, 10, Duration::default()).throughput()
+        );
+    }
+}
@@ -2,28 +2,68 @@
 //!
 //! This module provides subcommands and a command matcher related to IMAP.
 
-use anyhow::Result;
-use clap::{App, ArgMatches};
+use anyhow::{anyhow, Result};
+use clap::{App, Arg, ArgMatches};
 use log::debug;
+use std::path::PathBuf;
+
+use crate::domain::imap::{ImapTrace, DEFAULT_BENCH_BATCH_SIZES};
 
 type Keepalive = u64;
+type RawCmd<'a> = &'a str;
+type Select = bool;
+type BatchSizes = Vec<usize>;
+type AllAccounts = bool;
 
 /// IMAP commands.
-pub enum Command {
-    /// Start the IMAP notify mode with the give keepalive duration.
-    Notify(Keepalive),
+pub enum Command<'a> {
+    /// Measure the session with the given envelope batch sizes.
+    Bench(BatchSizes),
+
+    /// Run the given raw command in the authenticated session, after selecting the current
+    /// mailbox if asked to.
+    Exec(RawCmd<'a>, Select),
+
+    /// Start the IMAP notify mode with the give keepalive duration, in the mailboxes of all
+    /// the accounts if asked to.
+    Notify(Keepalive, AllAccounts),
 
     /// Start the IMAP watch mode with the give keepalive duration.
     Watch(Keepalive),
 }
 
 /// IMAP command matcher.
-pub fn matches(m: &ArgMatches) -> Result<Option<Command>> {
+pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Command<'a>>> {
+    if let Some(m) = m.subcommand_matches("bench") {
+        debug!("bench command matched");
+        let batch_sizes = match m.values_of("batch-sizes") {
+            Some(sizes) => sizes
+                .map(|size| {
+                    size.parse::<usize>()
+                        .map_err(|_| anyhow!(r#"cannot parse batch size "{}""#, size))
+                })
+                .collect::<Result<Vec<_>>>()?,
+            None => DEFAULT_BENCH_BATCH_SIZES.to_vec(),
+        };
+        debug!("batch sizes: {:?}", batch_sizes);
+        return Ok(Some(Command::Bench(batch_sizes)));
+    }
+    if let Some(m) = m.subcommand_matches("imap-exec") {
+        debug!("imap exec command matched");
+        let cmd = m.value_of("command").unwrap();
+        debug!("command: {}", cmd);
+        let select = m.is_present("select");
+        debug!("select: {}", select);
+        return Ok(Some(Command::Exec(cmd, select)));
+    }
+
     if let Some(m) = m.subcommand_matches("notify") {
         debug!("notify command matched");
         let keepalive = clap::value_t_or_exit!(m.value_of("keepalive"), u64);
         debug!("keepalive: {}", keepalive);
-        return Ok(Some(Command::Notify(keepalive)));
+        let all_accounts = m.is_present("all-accounts");
+        debug!("all accounts: {}", all_accounts);
+        return Ok(Some(Command::Notify(keepalive, all_accounts)));
     }
 
     if let Some(m) = m.subcommand_matches("watch") {
@@ -36,11 +76,78 @@ pub fn matches(m: &ArgMatches) -> Result<Option<Command>> {
     Ok(None)
 }
 
+/// IMAP arguments.
+pub fn args<'a>() -> Vec<Arg<'a, 'a>> {
+    vec![
+        Arg::with_name("trace-imap")
+            .long("trace-imap")
+            .help("Logs the raw IMAP commands and responses to stderr")
+            .long_help("Logs the raw IMAP commands (prefixed by C:) and responses (prefixed by S:) to stderr. Credentials sent by LOGIN and AUTHENTICATE are redacted."),
+        Arg::with_name("trace-imap-file")
+            .long("trace-imap-file")
+            .help("Logs the raw IMAP commands and responses to the given file")
+            .value_name("PATH"),
+        Arg::with_name("uid")
+            .long("uid")
+            .help("Addresses messages by UID")
+            .long_help("Addresses messages by UID instead of sequence number: commands take UIDs and listings show them in the UID column. Overrides the `uid` config option.")
+            .conflicts_with("seq"),
+        Arg::with_name("seq")
+            .long("seq")
+            .help("Addresses messages by sequence number")
+            .long_help("Addresses messages by sequence number: commands take sequence numbers and listings show them in the SEQ column. Overrides the `uid` config option."),
+    ]
+}
+
+/// IMAP addressing matcher. Returns whether messages are addressed by UID, or `None` to follow
+/// the account.
+pub fn uid(m: &ArgMatches) -> Option<bool> {
+    if m.is_present("uid") {
+        debug!("UID addressing");
+        return Some(true);
+    }
+
+    if m.is_present("seq") {
+        debug!("sequence number addressing");
+        return Some(false);
+    }
+
+    None
+}
+
+/// IMAP trace matcher.
+pub fn trace(m: &ArgMatches) -> Option<ImapTrace> {
+    if let Some(path) = m.value_of("trace-imap-file") {
+        debug!("IMAP trace file: {}", path);
+        return Some(ImapTrace::File(PathBuf::from(path)));
+    }
+
+    if m.is_present("trace-imap") {
+        debug!("IMAP trace to stderr");
+        return Some(ImapTrace::Stderr);
+    }
+
+    None
+}
+
 /// IMAP subcommands.
 pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
     vec![
+        clap::SubCommand::with_name("bench")
+            .about("Measures the IMAP session of the account")
+            .long_about("Measures the login time, the latency of the mailboxes listing and the envelope fetch throughput of the current mailbox for each batch size, to compare providers and tune the page size.")
+            .arg(
+                clap::Arg::with_name("batch-sizes")
+                    .help("Specifies the envelope batch sizes, defaults to 10,50,200")
+                    .long("batch-sizes")
+                    .short("b")
+                    .value_name("SIZES")
+                    .use_delimiter(true)
+                    .multiple(true),
+            ),
         clap::SubCommand::with_name("notify")
             .about("Notifies when new messages arrive in the given mailbox")
+            .long_about("Notifies when new messages arrive in the given mailbox. When the account defines `notify-mailboxes`, all of them are watched at once instead, each one with its own notify command if any.")
             .aliases(&["idle"])
             .arg(
                 clap::Arg::with_name("keepalive")
@@ -49,6 +156,28 @@ pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
                     .long("keepalive")
                     .value_name("SECS")
                     .default_value("500"),
+            )
+            .arg(
+                clap::Arg::with_name("all-accounts")
+                    .help("Watches the mailboxes of all the accounts")
+                    .long_help("Watches the mailboxes of all the accounts at once: the `notify-mailboxes` of each account, or its default mailbox when it defines none.")
+                    .long("all-accounts")
+                    .short("A"),
+            ),
+        clap::SubCommand::with_name("imap-exec")
+            .about("[advanced] Runs a raw IMAP command, use with caution")
+            .long_about("[advanced] Runs a raw IMAP command in the authenticated session and prints the untagged responses, for debugging server-specific extensions. The command is sent as is, without the tag: nothing prevents it from deleting messages or breaking the session, use with caution.")
+            .arg(
+                clap::Arg::with_name("command")
+                    .help("Specifies the raw command, without the tag")
+                    .value_name("COMMAND")
+                    .required(true),
+            )
+            .arg(
+                clap::Arg::with_name("select")
+                    .help("Selects the current mailbox before running the command")
+                    .long("select")
+                    .short("s"),
             ),
         clap::SubCommand::with_name("watch")
             .about("Watches IMAP server changes")
@@ -2,9 +2,68 @@
 //!
 //! This module gathers all IMAP handlers triggered by the CLI.
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
+use log::{debug, error};
+use std::{convert::TryFrom, thread, time::Instant};
 
-use crate::{config::Config, domain::imap::ImapServiceInterface};
+use crate::{
+    config::{Account, Config},
+    domain::{
+        filter::filter_handler,
+        hook::Hooks,
+        imap::{BenchFetch, BenchReport, ImapService, ImapServiceInterface},
+        mbox::Mbox,
+    },
+    output::PrinterService,
+};
+
+/// Raw command handler. The untagged responses are printed as sent by the server.
+pub fn exec<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    cmd: &str,
+    select: bool,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+) -> Result<()> {
+    let res = imap.run_raw_cmd(cmd, select)?;
+    printer.print(String::from_utf8_lossy(&res).trim_end())
+}
+
+/// Bench handler. The session is closed first, so the login is measured even when it is shared
+/// with the daemon. Envelopes are fetched from the current mailbox, once per batch size.
+pub fn bench<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    batch_sizes: Vec<usize>,
+    account: &Account,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+) -> Result<()> {
+    imap.logout()?;
+    let now = Instant::now();
+    imap.noop()?;
+    let login = now.elapsed();
+    debug!("login: {:?}", login);
+
+    let now = Instant::now();
+    let mboxes = imap.fetch_mbox_names()?.len();
+    let list = now.elapsed();
+    debug!("list: {:?}", list);
+
+    let mut fetches = Vec::with_capacity(batch_sizes.len());
+    for batch_size in batch_sizes {
+        let now = Instant::now();
+        let envelopes = imap.fetch_envelopes(&batch_size, &0)?.len();
+        let fetch = BenchFetch::new(batch_size, envelopes, now.elapsed());
+        debug!("fetch: {:?}", fetch);
+        fetches.push(fetch);
+    }
+
+    printer.print(BenchReport {
+        account: account.name.to_owned(),
+        login_millis: login.as_millis(),
+        list_millis: list.as_millis(),
+        mboxes,
+        fetches,
+    })
+}
 
 /// Notify handler.
 pub fn notify<'a, ImapService: ImapServiceInterface<'a>>(
@@ -12,13 +71,111 @@ pub fn notify<'a, ImapService: ImapServiceInterface<'a>>(
     config: &Config,
     imap: &mut ImapService,
 ) -> Result<()> {
-    imap.notify(&config, keepalive)
+    imap.notify(&config, None, keepalive)
+}
+
+/// Notify handler watching several mailboxes at once: the notify mailboxes of the given account,
+/// or of all the accounts. Accounts without notify mailboxes are watched on their default
+/// mailbox. Each mailbox waits for changes in its own thread, with its own IMAP session, since a
+/// session can only idle on one mailbox.
+pub fn notify_all(
+    keepalive: u64,
+    all_accounts: bool,
+    config: &Config,
+    account: &Account,
+) -> Result<()> {
+    let accounts = if all_accounts {
+        let mut names: Vec<&String> = config.accounts.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| Account::try_from((config, Some(name.as_str()))))
+            .collect::<Result<Vec<_>>>()?
+    } else {
+        vec![account.to_owned()]
+    };
+
+    let mut watches = vec![];
+    for account in accounts {
+        let mut mboxes: Vec<(String, Option<String>)> = account
+            .notify_mboxes
+            .iter()
+            .map(|(mbox, entry)| (mbox.to_owned(), entry.notify_cmd.to_owned()))
+            .collect();
+        if mboxes.is_empty() {
+            mboxes.push((account.default_mailbox.to_owned(), None));
+        }
+        // Watching sessions never close, so each mailbox needs its own connection
+        if account.imap_max_connections > 0 && mboxes.len() > account.imap_max_connections {
+            return Err(anyhow!(
+                r#"cannot watch {} mailboxes of account "{}" with imap-max-connections = {}"#,
+                mboxes.len(),
+                account.name,
+                account.imap_max_connections
+            ));
+        }
+        watches.push((account, mboxes));
+    }
+
+    let mut handles = vec![];
+    for (account, mboxes) in watches {
+        for (mbox, notify_cmd) in mboxes {
+            debug!(
+                r#"watching mailbox "{}" of account "{}""#,
+                mbox, account.name
+            );
+            let account = account.to_owned();
+            let config = config.to_owned();
+            let handle = thread::spawn(move || -> bool {
+                let mbox = Mbox::new(&mbox);
+                let mut imap = ImapService::from((&account, &mbox));
+                let res = imap
+                    .notify(&config, notify_cmd.as_deref(), keepalive)
+                    .context(format!(
+                        r#"cannot watch mailbox "{}" of account "{}""#,
+                        mbox.name, account.name
+                    ));
+                // Errors are logged right away, since other mailboxes keep being watched
+                if let Err(err) = res.as_ref() {
+                    error!("{:?}", err);
+                }
+                res.is_ok()
+            });
+            handles.push(handle);
+        }
+    }
+
+    let mut failures = 0;
+    for handle in handles {
+        if !handle
+            .join()
+            .map_err(|_| anyhow!("cannot join mailbox thread"))?
+        {
+            failures += 1;
+        }
+    }
+    if failures > 0 {
+        return Err(anyhow!("cannot watch {} mailbox(es)", failures));
+    }
+    Ok(())
 }
 
-/// Watch handler.
+/// Watch handler. The filters and the `filter_message` hook of the account are applied to the
+/// mailbox before each wait for changes, only to the messages arrived since the previous wait.
+/// The hooks script is loaded once, when the watch starts.
 pub fn watch<'a, ImapService: ImapServiceInterface<'a>>(
     keepalive: u64,
+    account: &Account,
     imap: &mut ImapService,
 ) -> Result<()> {
-    imap.watch(keepalive)
+    let hooks = Hooks::load(account)?;
+    let mut last_uid = None;
+    loop {
+        debug!("begin loop");
+        filter_handler::apply(account, hooks.as_ref(), false, &mut last_uid, imap)?;
+        imap.idle(keepalive)?;
+        // FIXME
+        // ctx.config.exec_watch_cmds(&ctx.account)?;
+        debug!("end loop");
+    }
 }
@@ -2,27 +2,69 @@
 //!
 //! This module exposes a service that can interact with IMAP servers.
 
-use anyhow::{anyhow, Context, Result};
-use log::{debug, trace};
-use native_tls::{TlsConnector, TlsStream};
+use anyhow::{anyhow, Context, Error, Result};
+use log::{debug, trace, warn};
 use std::{
-    collections::HashSet,
+    cell::{RefCell, RefMut},
+    collections::{HashMap, HashSet},
     convert::{TryFrom, TryInto},
     iter::FromIterator,
     net::TcpStream,
+    rc::Rc,
+    sync::Arc,
+    thread,
 };
 
 use crate::{
     config::{Account, Config},
-    domain::{Envelope, Envelopes, Flags, Mbox, Mboxes, Msg, RawEnvelopes, RawMboxes},
+    domain::{
+        hook::{hook_handler, Hook, HookMsg, Hooks},
+        imap::{
+            fingerprint, is_throttled, throttle_backoff, ImapTrace, KnownHosts, RateLimiter,
+            TraceStream, THROTTLE_RETRIES,
+        },
+        list_msgs,
+        msg::{
+            compress_seqs, is_seq_set, parse_gmail_ids, parse_note_annotation, quote_note,
+            GmailIds, ANNOTATE_CAPABILITY, GMAIL_CAPABILITY, NOTE_ANNOTATION_ENTRY,
+        },
+        Envelope, Envelopes, FilterHeaders, Flags, MailingLists, Mbox, MboxCount, MboxStats,
+        Mboxes, Msg, RawEnvelope, RawEnvelopes, RawMboxes,
+    },
+    output::ExitCode,
 };
 
-type ImapSession = imap::Session<TlsStream<TcpStream>>;
+#[cfg(not(any(feature = "default-tls", feature = "rustls-tls")))]
+compile_error!("either the `default-tls` or the `rustls-tls` feature must be enabled");
+
+#[cfg(feature = "default-tls")]
+pub(crate) type ImapTlsStream = native_tls::TlsStream<TcpStream>;
+#[cfg(all(feature = "rustls-tls", not(feature = "default-tls")))]
+pub(crate) type ImapTlsStream = rustls::StreamOwned<rustls::ClientSession, TcpStream>;
+
+/// Represents the number of messages fetched at once when computing mailbox statistics or
+/// grouping messages by mailing list.
+const STATS_BATCH_SIZE: u32 = 500;
+
+pub type ImapSession = imap::Session<TraceStream<ImapTlsStream>>;
+
+/// Represents an IMAP session that can be shared between services. It allows the session to
+/// outlive a service, for example to serve several commands in daemon mode.
+pub type SharedImapSession = Rc<RefCell<Option<ImapSession>>>;
 
 pub trait ImapServiceInterface<'a> {
-    fn notify(&mut self, config: &Config, keepalive: u64) -> Result<()>;
-    fn watch(&mut self, keepalive: u64) -> Result<()>;
+    /// Notify the messages arriving in the current mailbox with the notify command of the
+    /// config, or the given one.
+    fn notify(&mut self, config: &Config, notify_cmd: Option<&str>, keepalive: u64) -> Result<()>;
+    /// Examine the current mailbox then wait for changes using the `IDLE` extension.
+    fn idle(&mut self, keepalive: u64) -> Result<()>;
     fn fetch_mboxes(&'a mut self) -> Result<Mboxes>;
+    /// Fetch the names of the selectable mailboxes.
+    fn fetch_mbox_names(&mut self) -> Result<Vec<String>>;
+    /// Count unseen and total messages of the current mailbox using the `STATUS` command.
+    fn fetch_mbox_count(&mut self) -> Result<MboxCount>;
+    /// Compute the statistics of the given mailbox, fetching its messages by batches.
+    fn fetch_mbox_stats(&mut self, mbox: &str) -> Result<MboxStats>;
     fn fetch_envelopes(&mut self, page_size: &usize, page: &usize) -> Result<Envelopes>;
     fn fetch_envelopes_with(
         &'a mut self,
@@ -30,11 +72,48 @@ pub trait ImapServiceInterface<'a> {
         page_size: &usize,
         page: &usize,
     ) -> Result<Envelopes>;
+    /// Fetch the headers filters can match on for all messages of the current mailbox, or only
+    /// for the ones having a UID greater than the given one.
+    fn fetch_filter_headers(&mut self, since_uid: Option<u32>) -> Result<Vec<FilterHeaders>>;
+    /// Group the messages of the current mailbox by mailing list, fetching only their flags and
+    /// their `List-Id` header.
+    fn fetch_mailing_lists(&mut self) -> Result<MailingLists>;
+    /// Search the current mailbox, returning the sorted sequence numbers of the matching messages.
+    fn search_seqs(&mut self, query: &str) -> Result<Vec<u32>>;
     fn find_msg(&mut self, seq: &str) -> Result<Msg>;
     fn find_raw_msg(&mut self, seq: &str) -> Result<Vec<u8>>;
+    /// Fetches the raw messages of the given sequence range, without marking them as seen.
+    /// Returns the messages along with their sequence number, sorted by sequence number.
+    fn fetch_raw_msgs(&mut self, seq_range: &str) -> Result<Vec<(u32, Vec<u8>)>>;
+    /// Fetches the raw headers of the messages of the given sequence range, without marking them
+    /// as seen. Returns the headers along with their sequence number, sorted by sequence number.
+    fn fetch_raw_headers(&mut self, seq_range: &str) -> Result<Vec<(u32, Vec<u8>)>>;
+    /// Examine the given mailbox then fetch the raw messages matching the query, without marking
+    /// them as seen. Returns the messages along with their sequence number.
+    fn search_raw_msgs(&mut self, mbox: &str, query: &str) -> Result<Vec<(u32, Vec<u8>)>>;
+    /// Examine the given mailbox then fetch the envelopes of the messages matching the query.
+    fn search_envelopes_in(&mut self, mbox: &str, query: &str) -> Result<Vec<Envelope<'static>>>;
     fn append_msg(&mut self, mbox: &Mbox, msg: Msg) -> Result<()>;
     fn append_raw_msg_with_flags(&mut self, mbox: &Mbox, msg: &[u8], flags: Flags) -> Result<()>;
+    /// Copy all messages within the given sequence range to the given mailbox.
+    fn copy_msgs(&mut self, seq_range: &str, mbox: &Mbox) -> Result<()>;
     fn expunge(&mut self) -> Result<()>;
+    /// Select the current mailbox then permanently remove its messages flagged as deleted, using
+    /// `UID EXPUNGE` when the server supports `UIDPLUS`. Returns the number of removed messages.
+    fn expunge_deleted(&mut self) -> Result<usize>;
+    /// Send a `NOOP` command, connecting and authenticating first if needed.
+    fn noop(&mut self) -> Result<()>;
+    /// Return true if the server supports message annotations ([RFC5257]).
+    ///
+    /// [RFC5257]: https://datatracker.ietf.org/doc/html/rfc5257
+    fn has_annotate(&mut self) -> Result<bool>;
+    /// Fetch the note annotation of the given message.
+    fn fetch_note(&mut self, seq: &str) -> Result<Option<String>>;
+    /// Store the note annotation of the given message, or remove it.
+    fn store_note(&mut self, seq: &str, note: Option<&str>) -> Result<()>;
+    /// Run the given raw command, after selecting the current mailbox if asked to. Returns the
+    /// raw untagged responses.
+    fn run_raw_cmd(&mut self, cmd: &str, select: bool) -> Result<Vec<u8>>;
     fn logout(&mut self) -> Result<()>;
 
     /// Add flags to all messages within the given sequence range.
@@ -45,10 +124,144 @@ pub trait ImapServiceInterface<'a> {
     fn remove_flags(&mut self, seq_range: &str, flags: &Flags) -> Result<()>;
 }
 
+/// Checks the certificate presented by the IMAP server of the given account against the known
+/// hosts, when the account trusts certificates on first use.
+fn check_known_host(account: &Account, cert: Option<Vec<u8>>) -> Result<()> {
+    if !account.imap_tofu {
+        return Ok(());
+    }
+    let path = account
+        .known_hosts_path
+        .as_ref()
+        .ok_or_else(|| anyhow!("cannot trust certificate on first use: config file not found"))?;
+    let cert = cert.ok_or_else(|| anyhow!("cannot get certificate of IMAP server"))?;
+    let server = format!("{}:{}", account.imap_host, account.imap_port);
+    let fingerprint = fingerprint(&cert);
+    let mut known_hosts = KnownHosts::load(path)?;
+    if known_hosts.check(&server, &fingerprint)? {
+        warn!(
+            "trusting certificate of server {} on first use (fingerprint {})",
+            server, fingerprint
+        );
+        known_hosts.save()?;
+    }
+    Ok(())
+}
+
+/// Connects to the IMAP server of the given account using native-tls. When the account trusts
+/// certificates on first use, invalid certificates are accepted but checked against the known
+/// hosts before going further.
+#[cfg(feature = "default-tls")]
+fn connect(
+    account: &Account,
+    trace: Option<&ImapTrace>,
+) -> Result<imap::Client<TraceStream<ImapTlsStream>>> {
+    let insecure = account.imap_insecure || account.imap_tofu;
+    let tls = native_tls::TlsConnector::builder()
+        .danger_accept_invalid_certs(insecure)
+        .danger_accept_invalid_hostnames(insecure)
+        .build()
+        .context("cannot create TLS connector")?;
+
+    let mut client_builder = imap::ClientBuilder::new(&account.imap_host, account.imap_port);
+    if account.imap_starttls {
+        client_builder.starttls();
+    }
+    let sink = trace.map(ImapTrace::sink).transpose()?;
+    let mut cert = None;
+    let client = client_builder.connect(|domain, tcp| {
+        let stream = tls.connect(domain, tcp)?;
+        cert = stream
+            .peer_certificate()
+            .ok()
+            .flatten()
+            .and_then(|cert| cert.to_der().ok());
+        Ok(TraceStream::new(stream, sink))
+    })?;
+    check_known_host(account, cert)?;
+    Ok(client)
+}
+
+/// Connects to the IMAP server of the given account using rustls. Server certificates are
+/// verified against the Mozilla root certificates bundled by `webpki-roots`, or against the known
+/// hosts when the account trusts certificates on first use.
+#[cfg(all(feature = "rustls-tls", not(feature = "default-tls")))]
+fn connect(
+    account: &Account,
+    trace: Option<&ImapTrace>,
+) -> Result<imap::Client<TraceStream<ImapTlsStream>>> {
+    let mut tls = rustls::ClientConfig::new();
+    tls.root_store
+        .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+    if account.imap_insecure || account.imap_tofu {
+        tls.dangerous()
+            .set_certificate_verifier(Arc::new(InsecureCertVerifier));
+    }
+    let tls = Arc::new(tls);
+    let dns_name = webpki::DNSNameRef::try_from_ascii_str(&account.imap_host).map_err(|_| {
+        anyhow!(
+            r#"cannot use IMAP host "{}" as DNS name"#,
+            account.imap_host
+        )
+    })?;
+
+    let mut client_builder = imap::ClientBuilder::new(&account.imap_host, account.imap_port);
+    if account.imap_starttls {
+        client_builder.starttls();
+    }
+    let sink = trace.map(ImapTrace::sink).transpose()?;
+    let mut cert = None;
+    let client = client_builder.connect(|_, tcp| {
+        let sess = rustls::ClientSession::new(&tls, dns_name);
+        let mut stream = rustls::StreamOwned::new(sess, tcp);
+        if account.imap_tofu {
+            // The handshake is completed first so the certificate is known before going further
+            stream.sess.complete_io(&mut stream.sock)?;
+            cert = stream
+                .sess
+                .get_peer_certificates()
+                .and_then(|certs| certs.into_iter().next())
+                .map(|cert| cert.0);
+        }
+        Ok(TraceStream::new(stream, sink))
+    })?;
+    check_known_host(account, cert)?;
+    Ok(client)
+}
+
+/// Certificate verifier accepting any certificate, used by rustls when `imap-insecure` or
+/// `imap-tofu` is set.
+#[cfg(all(feature = "rustls-tls", not(feature = "default-tls")))]
+pub(crate) struct InsecureCertVerifier;
+
+#[cfg(all(feature = "rustls-tls", not(feature = "default-tls")))]
+impl rustls::ServerCertVerifier for InsecureCertVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &rustls::RootCertStore,
+        _certs: &[rustls::Certificate],
+        _dns_name: webpki::DNSNameRef,
+        _ocsp: &[u8],
+    ) -> Result<rustls::ServerCertVerified, rustls::TLSError> {
+        Ok(rustls::ServerCertVerified::assertion())
+    }
+}
+
 pub struct ImapService<'a> {
     account: &'a Account,
     mbox: &'a Mbox<'a>,
-    sess: Option<ImapSession>,
+    sess: SharedImapSession,
+    trace: Option<ImapTrace>,
+    /// Whether message bodies are fetched with `BODY.PEEK[]`, leaving their flags unchanged.
+    peek: bool,
+    /// Whether messages are addressed by UID instead of sequence number.
+    uid: bool,
+    /// Whether the Gmail IDs of envelopes are fetched, when the server supports them.
+    gmail_ids: bool,
+    /// The rate limiter of the account, shared by the services of the account.
+    rate_limiter: Arc<RateLimiter>,
+    /// Whether the session opened by the service is counted by the rate limiter.
+    conn_acquired: bool,
     /// Holds raw mailboxes fetched by the `imap` crate in order to extend mailboxes lifetime
     /// outside of handlers. Without that, it would be impossible for handlers to return a `Mbox`
     /// struct or a `Mboxes` struct due to the `ZeroCopy` constraint.
@@ -57,44 +270,224 @@ pub struct ImapService<'a> {
 }
 
 impl<'a> ImapService<'a> {
-    fn sess(&mut self) -> Result<&mut ImapSession> {
-        if let None = self.sess {
-            debug!("create TLS builder");
-            debug!("insecure: {}", self.account.imap_insecure);
-            let builder = TlsConnector::builder()
-                .danger_accept_invalid_certs(self.account.imap_insecure)
-                .danger_accept_invalid_hostnames(self.account.imap_insecure)
-                .build()
-                .context("cannot create TLS connector")?;
+    /// Fetches message bodies with `BODY.PEEK[]` if asked to, even when the account does not
+    /// always peek.
+    pub fn with_peek(mut self, peek: bool) -> Self {
+        self.peek = self.peek || peek;
+        self
+    }
+
+    /// Addresses messages by UID if asked to, by sequence number otherwise, whatever the account
+    /// addressing.
+    pub fn with_uid(mut self, uid: bool) -> Self {
+        self.uid = uid;
+        self
+    }
+
+    /// Returns the given data items, along with the UID in UID mode so the fetched messages can
+    /// be identified by it.
+    fn items(&self, items: &str) -> String {
+        if self.uid {
+            format!("(UID {})", items)
+        } else {
+            format!("({})", items)
+        }
+    }
+
+    /// Returns the identifier of the given fetched message: its UID in UID mode, its sequence
+    /// number otherwise.
+    fn fetch_id(&self, fetch: &RawEnvelope) -> u32 {
+        match (self.uid, fetch.uid) {
+            (true, Some(uid)) => uid,
+            _ => fetch.message,
+        }
+    }
+
+    /// Returns the UIDs of the given fetched messages by sequence number in UID mode, so
+    /// listings fetched by position can show them. Returns nothing otherwise.
+    fn uids(&self, fetches: &RawEnvelopes) -> HashMap<u32, u32> {
+        if !self.uid {
+            return HashMap::new();
+        }
+        fetches
+            .iter()
+            .filter_map(|fetch| fetch.uid.map(|uid| (fetch.message, uid)))
+            .collect()
+    }
+
+    /// Returns the prefix of the raw commands addressing messages: `UID ` in UID mode.
+    fn cmd_prefix(&self) -> &'static str {
+        if self.uid {
+            "UID "
+        } else {
+            ""
+        }
+    }
+
+    /// Fetches the given messages, by UID in UID mode ([RFC3501]).
+    ///
+    /// [RFC3501]: https://datatracker.ietf.org/doc/html/rfc3501#section-6.4.8
+    fn fetch_ids(&mut self, ids: &str, items: &str) -> Result<RawEnvelopes> {
+        let uid = self.uid;
+        let mut sess = self.sess()?;
+        let fetches = if uid {
+            sess.uid_fetch(ids, items)
+        } else {
+            sess.fetch(ids, items)
+        };
+        Ok(fetches?)
+    }
+
+    /// Searches the current mailbox, returning the UIDs of the matching messages in UID mode. A
+    /// bare sequence set then matches UIDs too.
+    fn search_ids(&mut self, query: &str) -> Result<HashSet<u32>> {
+        let uid = self.uid;
+        let mut sess = self.sess()?;
+        let ids = if !uid {
+            sess.search(query)
+        } else if is_seq_set(query) {
+            sess.uid_search(format!("UID {}", query))
+        } else {
+            sess.uid_search(query)
+        };
+        Ok(ids?)
+    }
+
+    /// Alters the flags of the given messages, by UID in UID mode.
+    fn store_ids(&mut self, ids: &str, query: &str) -> Result<()> {
+        let uid = self.uid;
+        let mut sess = self.sess()?;
+        if uid {
+            sess.uid_store(ids, query)?;
+        } else {
+            sess.store(ids, query)?;
+        }
+        Ok(())
+    }
+
+    /// Copies the given messages to the given mailbox, by UID in UID mode.
+    fn copy_ids(&mut self, ids: &str, mbox: &str) -> Result<()> {
+        let uid = self.uid;
+        let mut sess = self.sess()?;
+        if uid {
+            sess.uid_copy(ids, mbox)?;
+        } else {
+            sess.copy(ids, mbox)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the body section fetched by reads: `BODY.PEEK[]` when peeking, so reading messages
+    /// never marks them as seen, `BODY[]` otherwise.
+    fn body_section(&self) -> &'static str {
+        if self.peek {
+            "BODY.PEEK[]"
+        } else {
+            "BODY[]"
+        }
+    }
+
+    /// Returns the data items fetched to build envelopes. The extra headers of the account are
+    /// fetched with `BODY.PEEK[HEADER.FIELDS (…)]`, so listings never download whole messages.
+    /// Their names are quoted, since header names may contain characters atoms cannot.
+    fn envelope_items(&self) -> String {
+        let quote = |name: &String| {
+            let name = name.replace('\\', r"\\").replace('"', r#"\""#);
+            format!(r#""{}""#, name)
+        };
+        match self.account.list_headers.as_slice() {
+            [] => self.items("ENVELOPE FLAGS INTERNALDATE RFC822.SIZE"),
+            headers => self.items(&format!(
+                "ENVELOPE FLAGS INTERNALDATE RFC822.SIZE BODY.PEEK[HEADER.FIELDS ({})]",
+                headers.iter().map(quote).collect::<Vec<_>>().join(" ")
+            )),
+        }
+    }
 
+    /// Fetches the Gmail IDs of envelopes if asked to. It costs one more fetch per listing, so it
+    /// is only done for JSON output.
+    pub fn with_gmail_ids(mut self, gmail_ids: bool) -> Self {
+        self.gmail_ids = gmail_ids;
+        self
+    }
+
+    /// Fetches the Gmail IDs of the messages within the given sequence range, by sequence number.
+    /// Nothing is fetched when not asked to or when the server does not support them.
+    fn fetch_gmail_ids(&mut self, seq_range: &str) -> Result<HashMap<u32, GmailIds>> {
+        if !self.gmail_ids
+            || !self
+                .sess()?
+                .capabilities()
+                .context("cannot get IMAP capabilities")?
+                .has_str(GMAIL_CAPABILITY)
+        {
+            return Ok(HashMap::new());
+        }
+        // The `imap` crate does not know Gmail attributes, so the raw response is parsed
+        let res = self
+            .sess()?
+            .run_command_and_read_response(format!("FETCH {} (X-GM-MSGID X-GM-THRID)", seq_range))
+            .context(format!(
+                r#"cannot fetch Gmail IDs of messages "{}""#,
+                seq_range
+            ))?;
+        Ok(parse_gmail_ids(&res))
+    }
+
+    /// Traces the IMAP protocol of the session, once created, to the given trace.
+    pub fn with_trace(mut self, trace: Option<ImapTrace>) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    fn sess(&mut self) -> Result<RefMut<ImapSession>> {
+        if self.sess.borrow().is_none() {
             debug!("create client");
             debug!("host: {}", self.account.imap_host);
             debug!("port: {}", self.account.imap_port);
             debug!("starttls: {}", self.account.imap_starttls);
-            let mut client_builder =
-                imap::ClientBuilder::new(&self.account.imap_host, self.account.imap_port);
-            if self.account.imap_starttls {
-                client_builder.starttls();
+            debug!("insecure: {}", self.account.imap_insecure);
+            debug!("trust on first use: {}", self.account.imap_tofu);
+            if !self.conn_acquired {
+                self.rate_limiter.acquire_conn();
+                self.conn_acquired = true;
             }
-            let client = client_builder
-                .connect(|domain, tcp| Ok(TlsConnector::connect(&builder, domain, tcp)?))
-                .context("cannot connect to IMAP server")?;
-
-            debug!("create session");
-            debug!("login: {}", self.account.imap_login);
-            debug!("passwd cmd: {}", self.account.imap_passwd_cmd);
-            self.sess = Some(
-                client
-                    .login(&self.account.imap_login, &self.account.imap_passwd()?)
-                    .map_err(|res| res.0)
-                    .context("cannot login to IMAP server")?,
-            );
-        }
 
-        match self.sess {
-            Some(ref mut sess) => Ok(sess),
-            None => Err(anyhow!("cannot get IMAP session")),
+            let mut retry = 0;
+            let sess = loop {
+                let client = connect(self.account, self.trace.as_ref())
+                    .context(ExitCode::Network)
+                    .context("cannot connect to IMAP server")?;
+
+                debug!("create session");
+                debug!("login: {}", self.account.imap_login);
+                debug!("passwd cmd: {}", self.account.imap_passwd_cmd);
+                match client.login(&self.account.imap_login, &self.account.imap_passwd()?) {
+                    Ok(sess) => break sess,
+                    // Throttled servers are given some time before logging in again
+                    Err((err, _)) if retry < THROTTLE_RETRIES && is_throttled(&err.to_string()) => {
+                        let delay = throttle_backoff(retry);
+                        warn!("IMAP server throttled the login, retrying in {:?}…", delay);
+                        thread::sleep(delay);
+                        retry += 1;
+                    }
+                    Err((err, _)) => {
+                        let err = match err {
+                            imap::Error::No(_) | imap::Error::Bad(_) => {
+                                Error::new(err).context(ExitCode::Auth)
+                            }
+                            err => Error::new(err),
+                        };
+                        return Err(err.context("cannot login to IMAP server"));
+                    }
+                }
+            };
+            self.sess.replace(Some(sess));
         }
+
+        self.rate_limiter.acquire_cmd();
+        RefMut::filter_map(self.sess.borrow_mut(), |sess| sess.as_mut())
+            .map_err(|_| anyhow!("cannot get IMAP session"))
     }
 
     fn search_new_msgs(&mut self) -> Result<Vec<u32>> {
@@ -121,6 +514,87 @@ impl<'a> ImapServiceInterface<'a> for ImapService<'a> {
         Ok(Mboxes::from(self._raw_mboxes_cache.as_ref().unwrap()))
     }
 
+    fn fetch_mbox_names(&mut self) -> Result<Vec<String>> {
+        let names = self
+            .sess()?
+            .list(Some(""), Some("*"))
+            .context("cannot list mailboxes")?;
+        Ok(names
+            .iter()
+            .filter(|name| {
+                !name
+                    .attributes()
+                    .iter()
+                    .any(|attr| matches!(attr, imap::types::NameAttribute::NoSelect))
+            })
+            .map(|name| name.name().to_owned())
+            .collect())
+    }
+
+    fn fetch_mbox_stats(&mut self, mbox: &str) -> Result<MboxStats> {
+        let exists = self
+            .sess()?
+            .examine(mbox)
+            .context(format!(r#"cannot examine mailbox "{}""#, mbox))?
+            .exists;
+        let mut stats = MboxStats::new(mbox);
+
+        let mut begin = 1;
+        while begin <= exists {
+            let end = exists.min(begin + STATS_BATCH_SIZE - 1);
+            debug!("fetch statistics of messages {}:{}", begin, end);
+            let fetches = self
+                .sess()?
+                .fetch(
+                    format!("{}:{}", begin, end),
+                    "(FLAGS RFC822.SIZE INTERNALDATE ENVELOPE)",
+                )
+                .context(format!(
+                    r#"cannot fetch messages {}:{} of mailbox "{}""#,
+                    begin, end, mbox
+                ))?;
+            for fetch in fetches.iter() {
+                let seen = fetch
+                    .flags()
+                    .iter()
+                    .any(|flag| *flag == imap::types::Flag::Seen);
+                let sender = fetch
+                    .envelope()
+                    .and_then(|envelope| envelope.from.as_ref())
+                    .and_then(|addrs| addrs.get(0))
+                    .and_then(|addr| match (&addr.mailbox, &addr.host) {
+                        (Some(mbox), Some(host)) => Some(format!(
+                            "{}@{}",
+                            String::from_utf8_lossy(&mbox.to_vec()),
+                            String::from_utf8_lossy(&host.to_vec())
+                        )),
+                        _ => None,
+                    });
+                stats.add(
+                    fetch.size.unwrap_or_default() as usize,
+                    seen,
+                    fetch.internal_date(),
+                    sender,
+                );
+            }
+            begin = end + 1;
+        }
+
+        Ok(stats)
+    }
+
+    fn fetch_mbox_count(&mut self) -> Result<MboxCount> {
+        let mbox = self.mbox.to_owned();
+        let status = self
+            .sess()?
+            .status(&mbox.name, "(MESSAGES UNSEEN)")
+            .context(format!(
+                r#"cannot get status of mailbox "{}""#,
+                self.mbox.name
+            ))?;
+        Ok(MboxCount::from(&status))
+    }
+
     fn fetch_envelopes(&mut self, page_size: &usize, page: &usize) -> Result<Envelopes> {
         let mbox = self.mbox.to_owned();
         let last_seq = self
@@ -143,12 +617,17 @@ impl<'a> ImapServiceInterface<'a> for ImapService<'a> {
             String::from("1:*")
         };
 
+        let items = self.envelope_items();
         let fetches = self
             .sess()?
-            .fetch(range, "(ENVELOPE FLAGS INTERNALDATE)")
+            .fetch(&range, &items)
             .context(r#"cannot fetch messages within range "{}""#)?;
+        let gmail_ids = self.fetch_gmail_ids(&range)?;
+        let uids = self.uids(&fetches);
         self._raw_msgs_cache = Some(fetches);
-        Ok(Envelopes::try_from(self._raw_msgs_cache.as_ref().unwrap())?)
+        Ok(Envelopes::try_from(self._raw_msgs_cache.as_ref().unwrap())?
+            .with_gmail_ids(&gmail_ids)
+            .with_uids(&uids))
     }
 
     fn fetch_envelopes_with(
@@ -181,47 +660,266 @@ impl<'a> ImapServiceInterface<'a> for ImapService<'a> {
 
         // FIXME: panic if begin > end
         let range = seqs[begin..end.min(seqs.len())].join(",");
+        let items = self.envelope_items();
         let fetches = self
             .sess()?
-            .fetch(&range, "(ENVELOPE FLAGS INTERNALDATE)")
+            .fetch(&range, &items)
             .context(r#"cannot fetch messages within range "{}""#)?;
+        let gmail_ids = self.fetch_gmail_ids(&range)?;
+        let uids = self.uids(&fetches);
         self._raw_msgs_cache = Some(fetches);
-        Ok(Envelopes::try_from(self._raw_msgs_cache.as_ref().unwrap())?)
+        Ok(Envelopes::try_from(self._raw_msgs_cache.as_ref().unwrap())?
+            .with_gmail_ids(&gmail_ids)
+            .with_uids(&uids))
+    }
+
+    fn fetch_filter_headers(&mut self, since_uid: Option<u32>) -> Result<Vec<FilterHeaders>> {
+        let mbox = self.mbox.to_owned();
+        let exists = self
+            .sess()?
+            .select(&mbox.name)
+            .context(format!(r#"cannot select mailbox "{}""#, self.mbox.name))?
+            .exists;
+
+        if exists == 0 {
+            return Ok(vec![]);
+        }
+
+        let items = "(UID FLAGS RFC822.HEADER)";
+        let fetches = match since_uid {
+            None => self.sess()?.fetch("1:*", items),
+            Some(uid) => self
+                .sess()?
+                .uid_fetch(format!("{}:*", uid.saturating_add(1)), items),
+        }
+        .context(format!(
+            r#"cannot fetch headers of mailbox "{}""#,
+            self.mbox.name
+        ))?;
+        fetches
+            .iter()
+            // `n:*` always includes the last message, even when its UID is lower than `n`.
+            .filter(|fetch| match (since_uid, fetch.uid) {
+                (Some(since_uid), Some(uid)) => uid > since_uid,
+                _ => true,
+            })
+            .map(|fetch| {
+                let headers = FilterHeaders::try_from(fetch)?;
+                Ok(FilterHeaders {
+                    seq: self.fetch_id(fetch),
+                    ..headers
+                })
+            })
+            .collect()
+    }
+
+    fn fetch_mailing_lists(&mut self) -> Result<MailingLists> {
+        let mbox = self.mbox.to_owned();
+        let exists = self
+            .sess()?
+            .examine(&mbox.name)
+            .context(format!(r#"cannot examine mailbox "{}""#, self.mbox.name))?
+            .exists;
+
+        if exists == 0 {
+            return Ok(MailingLists::default());
+        }
+
+        let mut msgs = vec![];
+        let mut begin = 1;
+        while begin <= exists {
+            let end = exists.min(begin + STATS_BATCH_SIZE - 1);
+            debug!("fetch mailing lists of messages {}:{}", begin, end);
+            let fetches = self
+                .sess()?
+                .fetch(
+                    format!("{}:{}", begin, end),
+                    "(FLAGS BODY.PEEK[HEADER.FIELDS (LIST-ID)])",
+                )
+                .context(format!(
+                    r#"cannot fetch mailing lists of messages {}:{} of mailbox "{}""#,
+                    begin, end, self.mbox.name
+                ))?;
+            msgs.extend(list_msgs(&fetches));
+            begin = end + 1;
+        }
+        Ok(msgs
+            .iter()
+            .map(|(list_id, seen)| (list_id.as_str(), *seen))
+            .collect())
+    }
+
+    /// Search the sequence numbers (or the UIDs in UID mode) of the messages matching the given
+    /// query, sorted.
+    fn search_seqs(&mut self, query: &str) -> Result<Vec<u32>> {
+        let mbox = self.mbox.to_owned();
+        self.sess()?
+            .select(&mbox.name)
+            .context(format!(r#"cannot select mailbox "{}""#, self.mbox.name))?;
+        let mut seqs: Vec<u32> = self
+            .search_ids(query)
+            .context(format!(
+                r#"cannot search in "{}" with query: "{}""#,
+                self.mbox.name, query
+            ))?
+            .into_iter()
+            .collect();
+        seqs.sort_unstable();
+        Ok(seqs)
     }
 
-    /// Find a message by sequence number.
+    /// Find a message by sequence number, or by UID in UID mode.
     fn find_msg(&mut self, seq: &str) -> Result<Msg> {
         let mbox = self.mbox.to_owned();
+        let body_section = self.body_section();
         self.sess()?
             .select(&mbox.name)
             .context(format!(r#"cannot select mailbox "{}""#, self.mbox.name))?;
         let fetches = self
-            .sess()?
-            .fetch(seq, "(ENVELOPE FLAGS INTERNALDATE BODY[])")
+            .fetch_ids(
+                seq,
+                &format!("(ENVELOPE FLAGS INTERNALDATE {})", body_section),
+            )
             .context(r#"cannot fetch messages "{}""#)?;
         let fetch = fetches
             .first()
-            .ok_or(anyhow!(r#"cannot find message "{}"#, seq))?;
+            .ok_or_else(|| ExitCode::NotFound.err(format!(r#"cannot find message "{}""#, seq)))?;
 
         Ok(Msg::try_from(fetch)?)
     }
 
     fn find_raw_msg(&mut self, seq: &str) -> Result<Vec<u8>> {
         let mbox = self.mbox.to_owned();
+        let body_section = self.body_section();
         self.sess()?
             .select(&mbox.name)
             .context(format!(r#"cannot select mailbox "{}""#, self.mbox.name))?;
         let fetches = self
-            .sess()?
-            .fetch(seq, "BODY[]")
+            .fetch_ids(seq, body_section)
             .context(r#"cannot fetch raw messages "{}""#)?;
-        let fetch = fetches
-            .first()
-            .ok_or(anyhow!(r#"cannot find raw message "{}"#, seq))?;
+        let fetch = fetches.first().ok_or_else(|| {
+            ExitCode::NotFound.err(format!(r#"cannot find raw message "{}""#, seq))
+        })?;
 
         Ok(fetch.body().map(Vec::from).unwrap_or_default())
     }
 
+    fn fetch_raw_msgs(&mut self, seq_range: &str) -> Result<Vec<(u32, Vec<u8>)>> {
+        let mbox = self.mbox.to_owned();
+        self.sess()?
+            .select(&mbox.name)
+            .context(format!(r#"cannot select mailbox "{}""#, self.mbox.name))?;
+        let fetches = self
+            .fetch_ids(seq_range, "BODY.PEEK[]")
+            .context(format!(r#"cannot fetch raw messages "{}""#, seq_range))?;
+        let mut msgs: Vec<_> = fetches
+            .iter()
+            .map(|fetch| {
+                let body = fetch.body().map(Vec::from).unwrap_or_default();
+                (self.fetch_id(fetch), body)
+            })
+            .collect();
+        msgs.sort_by_key(|(id, _)| *id);
+        Ok(msgs)
+    }
+
+    fn fetch_raw_headers(&mut self, seq_range: &str) -> Result<Vec<(u32, Vec<u8>)>> {
+        let mbox = self.mbox.to_owned();
+        self.sess()?
+            .select(&mbox.name)
+            .context(format!(r#"cannot select mailbox "{}""#, self.mbox.name))?;
+        let fetches = self
+            .fetch_ids(seq_range, "BODY.PEEK[HEADER]")
+            .context(format!(
+                r#"cannot fetch headers of messages "{}""#,
+                seq_range
+            ))?;
+        let mut headers: Vec<_> = fetches
+            .iter()
+            .map(|fetch| {
+                let header = fetch.header().map(Vec::from).unwrap_or_default();
+                (self.fetch_id(fetch), header)
+            })
+            .collect();
+        headers.sort_by_key(|(id, _)| *id);
+        Ok(headers)
+    }
+
+    fn search_envelopes_in(&mut self, mbox: &str, query: &str) -> Result<Vec<Envelope<'static>>> {
+        self.sess()?
+            .examine(mbox)
+            .context(format!(r#"cannot examine mailbox "{}""#, mbox))?;
+        let seqs: Vec<String> = self
+            .sess()?
+            .search(query)
+            .context(format!(
+                r#"cannot search in "{}" with query: "{}""#,
+                mbox, query
+            ))?
+            .iter()
+            .map(|seq| seq.to_string())
+            .collect();
+        if seqs.is_empty() {
+            return Ok(vec![]);
+        }
+        let seq_range = seqs.join(",");
+        let items = self.envelope_items();
+        let fetches = self
+            .sess()?
+            .fetch(&seq_range, &items)
+            .context(format!(r#"cannot fetch envelopes "{}""#, seq_range))?;
+        let gmail_ids = self.fetch_gmail_ids(&seq_range)?;
+        fetches
+            .iter()
+            .map(|fetch| {
+                let mut envelope = Envelope::try_from(fetch)?.into_owned();
+                envelope.gmail = gmail_ids.get(&envelope.id).cloned();
+                envelope.id = self.fetch_id(fetch);
+                Ok(envelope)
+            })
+            .collect()
+    }
+
+    fn search_raw_msgs(&mut self, mbox: &str, query: &str) -> Result<Vec<(u32, Vec<u8>)>> {
+        self.sess()?
+            .examine(mbox)
+            .context(format!(r#"cannot examine mailbox "{}""#, mbox))?;
+        let mut seqs: Vec<u32> = self
+            .sess()?
+            .search(query)
+            .context(format!(
+                r#"cannot search in "{}" with query: "{}""#,
+                mbox, query
+            ))?
+            .into_iter()
+            .collect();
+        if seqs.is_empty() {
+            return Ok(vec![]);
+        }
+        seqs.sort_unstable();
+        let seq_range = seqs
+            .iter()
+            .map(|seq| seq.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let items = self.items("BODY.PEEK[]");
+        let fetches = self
+            .sess()?
+            .fetch(&seq_range, &items)
+            .context(format!(r#"cannot fetch raw messages "{}""#, seq_range))?;
+        let mut fetches: Vec<_> = fetches.iter().collect();
+        fetches.sort_by_key(|fetch| fetch.message);
+        Ok(fetches
+            .into_iter()
+            .map(|fetch| {
+                (
+                    self.fetch_id(fetch),
+                    fetch.body().map(Vec::from).unwrap_or_default(),
+                )
+            })
+            .collect())
+    }
+
     fn append_raw_msg_with_flags(&mut self, mbox: &Mbox, msg: &[u8], flags: Flags) -> Result<()> {
         self.sess()?
             .append(&mbox.name, &msg)
@@ -241,8 +939,10 @@ impl<'a> ImapServiceInterface<'a> for ImapService<'a> {
         Ok(())
     }
 
-    fn notify(&mut self, config: &Config, keepalive: u64) -> Result<()> {
+    fn notify(&mut self, config: &Config, notify_cmd: Option<&str>, keepalive: u64) -> Result<()> {
         let mbox = self.mbox.to_owned();
+        let account = self.account;
+        let hooks = Hooks::load(account)?;
 
         debug!("examine mailbox: {}", mbox.name);
         self.sess()?
@@ -287,6 +987,7 @@ impl<'a> ImapServiceInterface<'a> for ImapService<'a> {
                     .uid_fetch(uids, "(UID ENVELOPE)")
                     .context("cannot fetch new messages enveloppe")?;
 
+                let mut expunge = false;
                 for fetch in fetches.iter() {
                     let msg = Envelope::try_from(fetch)?;
                     let uid = fetch.uid.ok_or_else(|| {
@@ -294,22 +995,39 @@ impl<'a> ImapServiceInterface<'a> for ImapService<'a> {
                     })?;
 
                     let from = msg.sender.to_owned().into();
-                    config.run_notify_cmd(&msg.subject, &from)?;
+                    config.run_notify_cmd(notify_cmd, &msg.subject, &from)?;
 
                     debug!("notify message: {}", uid);
                     trace!("message: {:?}", msg);
 
+                    // A failing hook must not stop the notifications.
+                    if let Some(ref hooks) = hooks {
+                        let msg = HookMsg {
+                            seq: Some(self.fetch_id(fetch)),
+                            ..HookMsg::from(&msg)
+                        };
+                        match hook_handler::run(hooks, Hook::OnNewMail, &msg, false, account, self)
+                        {
+                            Ok((_, deleted)) => expunge |= deleted,
+                            Err(err) => warn!("cannot run hook on message {}: {:#}", uid, err),
+                        }
+                    }
+
                     debug!("insert message {} in hashset", uid);
                     msgs_set.insert(uid);
                     trace!("messages hashset: {:?}", msgs_set);
                 }
+
+                if expunge {
+                    self.expunge()?;
+                }
             }
 
             debug!("end loop");
         }
     }
 
-    fn watch(&mut self, keepalive: u64) -> Result<()> {
+    fn idle(&mut self, keepalive: u64) -> Result<()> {
         debug!("examine mailbox: {}", &self.mbox.name);
         let mbox = self.mbox.to_owned();
 
@@ -317,28 +1035,89 @@ impl<'a> ImapServiceInterface<'a> for ImapService<'a> {
             .examine(&mbox.name)
             .context(format!("cannot examine mailbox `{}`", &self.mbox.name))?;
 
-        loop {
-            debug!("begin loop");
-            self.sess()?
-                .idle()
-                .and_then(|mut idle| {
-                    idle.set_keepalive(std::time::Duration::new(keepalive, 0));
-                    idle.wait_keepalive_while(|res| {
-                        // TODO: handle response
-                        trace!("idle response: {:?}", res);
-                        false
-                    })
+        self.sess()?
+            .idle()
+            .and_then(|mut idle| {
+                idle.set_keepalive(std::time::Duration::new(keepalive, 0));
+                idle.wait_keepalive_while(|res| {
+                    // TODO: handle response
+                    trace!("idle response: {:?}", res);
+                    false
                 })
-                .context("cannot start the idle mode")?;
-            // FIXME
-            // ctx.config.exec_watch_cmds(&ctx.account)?;
-            debug!("end loop");
+            })
+            .context("cannot start the idle mode")?;
+        Ok(())
+    }
+
+    fn noop(&mut self) -> Result<()> {
+        self.sess()?
+            .noop()
+            .context("cannot send NOOP to IMAP server")?;
+        Ok(())
+    }
+
+    fn has_annotate(&mut self) -> Result<bool> {
+        Ok(self
+            .sess()?
+            .capabilities()
+            .context("cannot get IMAP capabilities")?
+            .has_str(ANNOTATE_CAPABILITY))
+    }
+
+    fn fetch_note(&mut self, seq: &str) -> Result<Option<String>> {
+        let mbox = self.mbox;
+        let prefix = self.cmd_prefix();
+        self.sess()?
+            .select(&mbox.name)
+            .context(format!(r#"cannot select mailbox "{}""#, self.mbox.name))?;
+        // The `imap` crate does not know annotations, so the raw response is parsed
+        let res = self
+            .sess()?
+            .run_command_and_read_response(format!(
+                "{}FETCH {} (ANNOTATION ({} value.priv))",
+                prefix, seq, NOTE_ANNOTATION_ENTRY
+            ))
+            .context(format!(r#"cannot fetch note of message "{}""#, seq))?;
+        Ok(parse_note_annotation(&res))
+    }
+
+    fn run_raw_cmd(&mut self, cmd: &str, select: bool) -> Result<Vec<u8>> {
+        if select {
+            let mbox = self.mbox;
+            self.sess()?
+                .select(&mbox.name)
+                .context(format!(r#"cannot select mailbox "{}""#, self.mbox.name))?;
         }
+        let res = self
+            .sess()?
+            .run_command_and_read_response(cmd)
+            .context(format!(r#"cannot run IMAP command "{}""#, cmd))?;
+        Ok(res)
+    }
+
+    fn store_note(&mut self, seq: &str, note: Option<&str>) -> Result<()> {
+        let mbox = self.mbox;
+        let prefix = self.cmd_prefix();
+        self.sess()?
+            .select(&mbox.name)
+            .context(format!(r#"cannot select mailbox "{}""#, self.mbox.name))?;
+        let value = note.map(quote_note).unwrap_or_else(|| String::from("NIL"));
+        self.sess()?
+            .run_command_and_read_response(format!(
+                "{}STORE {} ANNOTATION ({} (value.priv {}))",
+                prefix, seq, NOTE_ANNOTATION_ENTRY, value
+            ))
+            .context(format!(r#"cannot store note of message "{}""#, seq))?;
+        Ok(())
     }
 
     fn logout(&mut self) -> Result<()> {
-        if let Some(ref mut sess) = self.sess {
+        if let Some(mut sess) = self.sess.replace(None) {
             debug!("logout from IMAP server");
+            if self.conn_acquired {
+                self.rate_limiter.release_conn();
+                self.conn_acquired = false;
+            }
             sess.logout().context("cannot logout from IMAP server")?;
         }
         Ok(())
@@ -350,8 +1129,7 @@ impl<'a> ImapServiceInterface<'a> for ImapService<'a> {
         self.sess()?
             .select(&mbox.name)
             .context(format!(r#"cannot select mailbox "{}""#, self.mbox.name))?;
-        self.sess()?
-            .store(seq_range, format!("+FLAGS ({})", flags))
+        self.store_ids(seq_range, &format!("+FLAGS ({})", flags))
             .context(format!(r#"cannot add flags "{}""#, &flags))?;
         Ok(())
     }
@@ -361,8 +1139,7 @@ impl<'a> ImapServiceInterface<'a> for ImapService<'a> {
         self.sess()?
             .select(&mbox.name)
             .context(format!(r#"cannot select mailbox "{}""#, self.mbox.name))?;
-        self.sess()?
-            .store(seq_range, format!("FLAGS ({})", flags))
+        self.store_ids(seq_range, &format!("FLAGS ({})", flags))
             .context(format!(r#"cannot set flags "{}""#, &flags))?;
         Ok(())
     }
@@ -373,26 +1150,95 @@ impl<'a> ImapServiceInterface<'a> for ImapService<'a> {
         self.sess()?
             .select(&mbox.name)
             .context(format!(r#"cannot select mailbox "{}""#, self.mbox.name))?;
-        self.sess()?
-            .store(seq_range, format!("-FLAGS ({})", flags))
+        self.store_ids(seq_range, &format!("-FLAGS ({})", flags))
             .context(format!(r#"cannot remove flags "{}""#, &flags))?;
         Ok(())
     }
 
+    fn copy_msgs(&mut self, seq_range: &str, target: &Mbox) -> Result<()> {
+        let mbox = self.mbox.to_owned();
+        self.sess()?
+            .select(&mbox.name)
+            .context(format!(r#"cannot select mailbox "{}""#, self.mbox.name))?;
+        self.copy_ids(seq_range, &target.name).context(format!(
+            r#"cannot copy messages "{}" to mailbox "{}""#,
+            seq_range, target.name
+        ))?;
+        Ok(())
+    }
+
     fn expunge(&mut self) -> Result<()> {
         self.sess()?
             .expunge()
             .context(format!(r#"cannot expunge mailbox "{}""#, self.mbox.name))?;
         Ok(())
     }
+
+    fn expunge_deleted(&mut self) -> Result<usize> {
+        let mbox = self.mbox.to_owned();
+        self.sess()?
+            .select(&mbox.name)
+            .context(format!(r#"cannot select mailbox "{}""#, self.mbox.name))?;
+        let uids = self.sess()?.uid_search("DELETED").context(format!(
+            r#"cannot search deleted messages in "{}""#,
+            self.mbox.name
+        ))?;
+        debug!("found {} deleted messages", uids.len());
+        trace!("uids: {:?}", uids);
+        if uids.is_empty() {
+            return Ok(0);
+        }
+
+        let uidplus = self
+            .sess()?
+            .capabilities()
+            .context("cannot get IMAP capabilities")?
+            .has_str("UIDPLUS");
+        if uidplus {
+            // Only the messages found above are expunged, even if other clients flag messages
+            // as deleted in the meantime.
+            let mut uids: Vec<u32> = uids.iter().cloned().collect();
+            uids.sort_unstable();
+            self.sess()?
+                .uid_expunge(compress_seqs(&uids))
+                .context(format!(r#"cannot expunge mailbox "{}""#, self.mbox.name))?;
+        } else {
+            self.sess()?
+                .expunge()
+                .context(format!(r#"cannot expunge mailbox "{}""#, self.mbox.name))?;
+        }
+        Ok(uids.len())
+    }
+}
+
+/// Stops counting the session of the service, so other sessions of the account can be opened.
+impl<'a> Drop for ImapService<'a> {
+    fn drop(&mut self) {
+        if self.conn_acquired {
+            self.rate_limiter.release_conn();
+        }
+    }
 }
 
 impl<'a> From<(&'a Account, &'a Mbox<'a>)> for ImapService<'a> {
     fn from((account, mbox): (&'a Account, &'a Mbox)) -> Self {
+        Self::from((account, mbox, SharedImapSession::default()))
+    }
+}
+
+/// Builds a service on top of an existing shared session. The session is lazily created if empty.
+impl<'a> From<(&'a Account, &'a Mbox<'a>, SharedImapSession)> for ImapService<'a> {
+    fn from((account, mbox, sess): (&'a Account, &'a Mbox, SharedImapSession)) -> Self {
         Self {
             account,
             mbox,
-            sess: None,
+            sess,
+            trace: None,
+            peek: account.peek,
+            uid: account.uid,
+            gmail_ids: false,
+            rate_limiter: account.imap_rate_limiter.clone(),
+            conn_acquired: false,
             _raw_mboxes_cache: None,
             _raw_msgs_cache: None,
         }
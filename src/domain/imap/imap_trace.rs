@@ -0,0 +1,192 @@
+//! Module related to IMAP protocol tracing.
+//!
+//! This module exposes a stream wrapper logging the raw IMAP commands and responses, with
+//! credentials redacted.
+
+use anyhow::{Context, Result};
+use std::{
+    fmt::{self, Debug},
+    fs::OpenOptions,
+    io::{self, Read, Write},
+    path::PathBuf,
+};
+
+/// Represents where the IMAP protocol trace is written to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImapTrace {
+    Stderr,
+    File(PathBuf),
+}
+
+impl ImapTrace {
+    /// Opens the writer the trace is logged to.
+    pub fn sink(&self) -> Result<Box<dyn Write>> {
+        match self {
+            Self::Stderr => Ok(Box::new(io::stderr())),
+            Self::File(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .context(format!("cannot open IMAP trace file {:?}", path))?;
+                Ok(Box::new(file))
+            }
+        }
+    }
+}
+
+/// Wraps the stream of an IMAP connection. When a trace is given, every line written (commands)
+/// and read (responses) is logged, prefixed by `C:` and `S:`.
+pub struct TraceStream<S> {
+    inner: S,
+    sink: Option<Box<dyn Write>>,
+    redactor: Redactor,
+}
+
+impl<S> TraceStream<S> {
+    pub fn new(inner: S, sink: Option<Box<dyn Write>>) -> Self {
+        Self {
+            inner,
+            sink,
+            redactor: Redactor::default(),
+        }
+    }
+
+    fn log(&mut self, client: bool, buf: &[u8]) {
+        if let Some(sink) = self.sink.as_mut() {
+            for line in String::from_utf8_lossy(buf).lines() {
+                let (prefix, line) = if client {
+                    ("C:", self.redactor.client_line(line))
+                } else {
+                    ("S:", self.redactor.server_line(line))
+                };
+                // Tracing must never break the connection, errors are ignored.
+                let _ = writeln!(sink, "{} {}", prefix, line);
+            }
+        }
+    }
+}
+
+impl<S: Read> Read for TraceStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.log(false, &buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<S: Write> Write for TraceStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.log(true, &buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<S: Debug> Debug for TraceStream<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TraceStream")
+            .field("inner", &self.inner)
+            .field("trace", &self.sink.is_some())
+            .finish()
+    }
+}
+
+/// Redacts the credentials of the `LOGIN` and `AUTHENTICATE` commands, including the client
+/// responses to the challenges of an authentication ([RFC3501]), sent until the server ends it
+/// with a tagged response.
+///
+/// [RFC3501]: https://datatracker.ietf.org/doc/html/rfc3501#section-6.2.2
+#[derive(Debug, Default)]
+struct Redactor {
+    /// The tag of the authentication in progress, if any.
+    auth_tag: Option<String>,
+}
+
+impl Redactor {
+    fn client_line(&mut self, line: &str) -> String {
+        if self.auth_tag.is_some() {
+            return String::from("<redacted>");
+        }
+        let mut words = line.splitn(3, ' ');
+        match (words.next(), words.next()) {
+            (Some(tag), Some(cmd)) if cmd.eq_ignore_ascii_case("LOGIN") => {
+                format!("{} {} <redacted>", tag, cmd)
+            }
+            (Some(tag), Some(cmd)) if cmd.eq_ignore_ascii_case("AUTHENTICATE") => {
+                self.auth_tag = Some(tag.to_owned());
+                format!("{} {} <redacted>", tag, cmd)
+            }
+            _ => line.to_owned(),
+        }
+    }
+
+    fn server_line(&mut self, line: &str) -> String {
+        if self.auth_tag.as_deref() == line.split(' ').next() {
+            self.auth_tag = None;
+        }
+        line.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_redact_credentials() {
+        let mut redactor = Redactor::default();
+        assert_eq!(
+            "a1 LOGIN <redacted>",
+            redactor.client_line(r#"a1 LOGIN "user@example.com" "p4ssw0rd""#)
+        );
+        assert_eq!(
+            "a2 authenticate <redacted>",
+            redactor.client_line("a2 authenticate PLAIN dXNlcgB1c2VyAHBhc3M=")
+        );
+        assert_eq!("a2 OK done", redactor.server_line("a2 OK done"));
+        assert_eq!("a3 SELECT INBOX", redactor.client_line("a3 SELECT INBOX"));
+        assert_eq!("* OK ready", redactor.server_line("* OK ready"));
+    }
+
+    #[test]
+    fn it_should_redact_authentication_continuations() {
+        let mut redactor = Redactor::default();
+        assert_eq!(
+            "a1 AUTHENTICATE <redacted>",
+            redactor.client_line("a1 AUTHENTICATE XOAUTH2")
+        );
+        assert_eq!("+ ", redactor.server_line("+ "));
+        assert_eq!(
+            "<redacted>",
+            redactor.client_line("dXNlcj11c2VyAWF1dGg9QmVhcmVyIHRva2VuAQE=")
+        );
+        assert_eq!(
+            "* CAPABILITY IMAP4rev1",
+            redactor.server_line("* CAPABILITY IMAP4rev1")
+        );
+        assert_eq!("<redacted>", redactor.client_line(""));
+        assert_eq!("a1 OK done", redactor.server_line("a1 OK done"));
+        assert_eq!("a2 NOOP", redactor.client_line("a2 NOOP"));
+    }
+
+    #[test]
+    fn it_should_trace_lines() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let trace = ImapTrace::File(file.path().to_owned());
+
+        let mut stream = TraceStream::new(io::Cursor::new(Vec::new()), Some(trace.sink().unwrap()));
+        stream.write_all(b"a1 LOGIN user pass\r\n").unwrap();
+        stream.write_all(b"a2 NOOP\r\n").unwrap();
+        drop(stream);
+
+        assert_eq!(
+            "C: a1 LOGIN <redacted>\nC: a2 NOOP\n",
+            std::fs::read_to_string(file.path()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! Known hosts entity module.
+//!
+//! This module contains the trust store of the trust-on-first-use mode (`imap-tofu`): the
+//! fingerprint of the certificate of each IMAP server is recorded on first connection, next to
+//! the config file with the `.known_hosts` extension, then checked on every connection.
+
+use anyhow::{anyhow, Context, Result};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Computes the SHA-256 fingerprint of the given DER certificate, like `AB:CD:…`.
+pub fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Represents the fingerprints of the trusted certificates, by server.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct KnownHosts {
+    /// The file the store is saved in.
+    path: PathBuf,
+    /// The fingerprints, indexed by `host:port`.
+    hosts: BTreeMap<String, String>,
+}
+
+impl KnownHosts {
+    /// Builds the path of the store from the path of the config file.
+    pub fn path_from_config(config_path: &Path) -> PathBuf {
+        config_path.with_extension("known_hosts")
+    }
+
+    /// Loads the store from the given file. A missing file gives an empty store.
+    pub fn load<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                return Err(err).context(format!("cannot read known hosts {:?}", path));
+            }
+        };
+        let hosts =
+            toml::from_str(&content).context(format!("cannot parse known hosts {:?}", path))?;
+        Ok(Self { path, hosts })
+    }
+
+    /// Saves the store to its file.
+    pub fn save(&self) -> Result<()> {
+        let content = toml::to_string(&self.hosts).context("cannot serialize known hosts")?;
+        fs::write(&self.path, content).context(format!("cannot write known hosts {:?}", self.path))
+    }
+
+    /// Checks the fingerprint of the given server. Unknown servers are trusted and recorded, in
+    /// which case true is returned. Known servers presenting another certificate are rejected.
+    pub fn check(&mut self, server: &str, fingerprint: &str) -> Result<bool> {
+        match self.hosts.get(server) {
+            None => {
+                self.hosts
+                    .insert(server.to_owned(), fingerprint.to_owned());
+                Ok(true)
+            }
+            Some(known) if known == fingerprint => Ok(false),
+            Some(known) => Err(anyhow!(
+                "the certificate of server {} changed!\n\
+                 known fingerprint: {}\n\
+                 new fingerprint:   {}\n\
+                 it may be a man-in-the-middle attack: if the change is expected, remove the server from {:?}",
+                server,
+                known,
+                fingerprint,
+                self.path
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_check_fingerprints() {
+        let mut hosts = KnownHosts::default();
+        let cert = fingerprint(b"cert");
+        let other_cert = fingerprint(b"other cert");
+
+        assert_eq!(95, cert.len());
+        assert!(hosts.check("imap.example.com:993", &cert).unwrap());
+        assert!(!hosts.check("imap.example.com:993", &cert).unwrap());
+        assert!(hosts.check("imap.example.com:993", &other_cert).is_err());
+        assert!(hosts.check("imap.example.org:993", &other_cert).unwrap());
+    }
+}
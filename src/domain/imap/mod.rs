@@ -5,3 +5,15 @@ pub mod imap_handler;
 
 pub mod imap_service;
 pub use imap_service::*;
+
+pub mod imap_trace;
+pub use imap_trace::*;
+
+pub mod bench_entity;
+pub use bench_entity::*;
+
+pub mod known_hosts_entity;
+pub use known_hosts_entity::*;
+
+pub mod rate_limit_entity;
+pub use rate_limit_entity::*;
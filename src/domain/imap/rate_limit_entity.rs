@@ -0,0 +1,163 @@
+//! Rate limit entity module.
+//!
+//! This module contains the rate limiter of IMAP sessions, so aggressive commands (like searches
+//! across all mailboxes or notify mode on several mailboxes) do not trip the throttling of
+//! providers like Gmail or Office365.
+
+use log::debug;
+use std::{
+    collections::VecDeque,
+    sync::{Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Represents the window commands are counted in.
+const CMDS_WINDOW: Duration = Duration::from_secs(60);
+
+/// Represents the number of retries of a throttled login.
+pub const THROTTLE_RETRIES: u32 = 5;
+
+/// Represents the delay before the first retry of a throttled login, doubled at each retry.
+const THROTTLE_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Represents the markers of the responses of throttled servers: response codes ([RFC5530]) and
+/// messages of providers.
+///
+/// [RFC5530]: https://datatracker.ietf.org/doc/html/rfc5530
+const THROTTLE_MARKERS: &[&str] = &[
+    "[THROTTLED]",
+    "[LIMIT]",
+    "[UNAVAILABLE]",
+    "too many simultaneous connections",
+    "request is throttled",
+];
+
+/// Returns true if the given error comes from a throttled server.
+pub fn is_throttled(err: &str) -> bool {
+    let err = err.to_lowercase();
+    THROTTLE_MARKERS
+        .iter()
+        .any(|marker| err.contains(&marker.to_lowercase()))
+}
+
+/// Returns the delay before the given retry of a throttled login (starting at 0).
+pub fn throttle_backoff(retry: u32) -> Duration {
+    THROTTLE_BACKOFF * 2u32.pow(retry)
+}
+
+/// Returns how long to wait before sending a command, given the instants of the commands sent
+/// within the window. A limit of 0 means no limit.
+fn cmd_delay(cmds: &VecDeque<Instant>, max_cmds: usize, now: Instant) -> Option<Duration> {
+    if max_cmds == 0 || cmds.len() < max_cmds {
+        return None;
+    }
+    cmds.get(cmds.len() - max_cmds)
+        .map(|oldest| (*oldest + CMDS_WINDOW).saturating_duration_since(now))
+        .filter(|delay| !delay.is_zero())
+}
+
+#[derive(Debug, Default)]
+struct RateLimiterState {
+    conns: usize,
+    cmds: VecDeque<Instant>,
+}
+
+/// Represents the rate limiter of an account, shared by all its sessions.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    /// The maximum number of simultaneous sessions, 0 meaning no limit.
+    max_conns: usize,
+    /// The maximum number of commands per minute, 0 meaning no limit.
+    max_cmds: usize,
+    state: Mutex<RateLimiterState>,
+    conn_released: Condvar,
+}
+
+impl RateLimiter {
+    pub fn new(max_conns: usize, max_cmds: usize) -> Self {
+        Self {
+            max_conns,
+            max_cmds,
+            ..Self::default()
+        }
+    }
+
+    /// Waits until a session can be opened, then counts it.
+    pub fn acquire_conn(&self) {
+        let mut state = self.state.lock().unwrap();
+        while self.max_conns > 0 && state.conns >= self.max_conns {
+            debug!("waiting for one of the {} sessions to close…", state.conns);
+            state = self.conn_released.wait(state).unwrap();
+        }
+        state.conns += 1;
+    }
+
+    /// Stops counting a session, waking up a session waiting to be opened.
+    pub fn release_conn(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.conns = state.conns.saturating_sub(1);
+        self.conn_released.notify_one();
+    }
+
+    /// Waits until a command can be sent, then counts it.
+    pub fn acquire_cmd(&self) {
+        loop {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            while state
+                .cmds
+                .front()
+                .map_or(false, |cmd| now.duration_since(*cmd) >= CMDS_WINDOW)
+            {
+                state.cmds.pop_front();
+            }
+            match cmd_delay(&state.cmds, self.max_cmds, now) {
+                Some(delay) => {
+                    drop(state);
+                    debug!("command rate limit reached, waiting {:?}…", delay);
+                    thread::sleep(delay);
+                }
+                None => {
+                    if self.max_cmds > 0 {
+                        state.cmds.push_back(now);
+                    }
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_detect_throttled_servers() {
+        assert!(is_throttled(
+            "No Response: [THROTTLED] Request is throttled"
+        ));
+        assert!(is_throttled(
+            "No Response: [ALERT] Too many simultaneous connections. (Failure)"
+        ));
+        assert!(!is_throttled(
+            "No Response: [AUTHENTICATIONFAILED] Invalid credentials"
+        ));
+        assert_eq!(Duration::from_secs(8), throttle_backoff(2));
+    }
+
+    #[test]
+    fn it_should_delay_commands_above_the_limit() {
+        let now = Instant::now();
+        let cmds: VecDeque<Instant> = vec![now, now + Duration::from_secs(30)].into();
+
+        assert_eq!(None, cmd_delay(&cmds, 0, now));
+        assert_eq!(None, cmd_delay(&cmds, 3, now));
+        assert_eq!(Some(CMDS_WINDOW), cmd_delay(&cmds, 2, now));
+        assert_eq!(
+            Some(Duration::from_secs(30)),
+            cmd_delay(&cmds, 1, now + Duration::from_secs(60))
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! Dedupe entity module.
+//!
+//! This module contains the definitions used to find duplicate messages within a mailbox.
+
+use anyhow::{anyhow, Error, Result};
+use mailparse::MailHeaderMap;
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    convert::TryFrom,
+    hash::{Hash, Hasher},
+};
+
+/// Represents what makes two messages duplicates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupeKey {
+    /// Messages sharing the same `Message-ID` header are duplicates. Messages without one are
+    /// never considered as duplicates.
+    MessageId,
+    /// Messages with the exact same raw content are duplicates.
+    Content,
+}
+
+impl Default for DedupeKey {
+    fn default() -> Self {
+        Self::MessageId
+    }
+}
+
+impl TryFrom<Option<&str>> for DedupeKey {
+    type Error = Error;
+
+    fn try_from(key: Option<&str>) -> Result<Self, Self::Error> {
+        match key {
+            None | Some("message-id") => Ok(Self::MessageId),
+            Some("content") => Ok(Self::Content),
+            Some(key) => Err(anyhow!(r#"cannot parse dedupe key "{}""#, key)),
+        }
+    }
+}
+
+impl DedupeKey {
+    /// Computes the key of the given raw message (or raw headers, for the `MessageId` key).
+    pub fn of(&self, raw: &[u8]) -> Option<String> {
+        match self {
+            Self::MessageId => mailparse::parse_headers(raw)
+                .ok()?
+                .0
+                .get_first_value("Message-ID")
+                .map(|id| id.trim().to_owned())
+                .filter(|id| !id.is_empty()),
+            Self::Content => {
+                let mut hasher = DefaultHasher::new();
+                raw.hash(&mut hasher);
+                Some(format!("{:016x}", hasher.finish()))
+            }
+        }
+    }
+}
+
+/// Returns the sequence numbers of the duplicate messages, given the sequence numbers and the
+/// keys of the messages of a mailbox. The first message of each group of duplicates is kept.
+pub fn find_duplicates(msgs: &[(u32, Option<String>)]) -> Vec<u32> {
+    let mut keys = HashSet::new();
+    let mut seqs: Vec<u32> = msgs
+        .iter()
+        .filter_map(|(seq, key)| match key {
+            Some(key) if !keys.insert(key) => Some(*seq),
+            _ => None,
+        })
+        .collect();
+    seqs.sort_unstable();
+    seqs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_compute_keys() {
+        let raw = b"Message-ID: <abc@example.com>\r\nSubject: Hello\r\n\r\nHello";
+        assert_eq!(
+            Some(String::from("<abc@example.com>")),
+            DedupeKey::MessageId.of(raw)
+        );
+        assert_eq!(None, DedupeKey::MessageId.of(b"Subject: Hello\r\n\r\n"));
+        assert_eq!(DedupeKey::Content.of(raw), DedupeKey::Content.of(raw));
+        assert_ne!(
+            DedupeKey::Content.of(raw),
+            DedupeKey::Content.of(b"Subject: Hello\r\n\r\n")
+        );
+    }
+
+    #[test]
+    fn it_should_find_duplicates() {
+        let msgs = vec![
+            (1, Some(String::from("a"))),
+            (2, Some(String::from("b"))),
+            (3, None),
+            (4, Some(String::from("a"))),
+            (5, None),
+            (6, Some(String::from("b"))),
+            (7, Some(String::from("a"))),
+        ];
+        assert_eq!(vec![4, 6, 7], find_duplicates(&msgs));
+        assert!(find_duplicates(&[]).is_empty());
+    }
+}
@@ -6,20 +6,40 @@
 use anyhow::Result;
 use clap;
 use log::trace;
+use std::convert::TryFrom;
 
-use crate::ui::table_arg;
+use crate::{domain::mbox::DedupeKey, ui::table_arg};
 
 type MaxTableWidth = Option<usize>;
+type Mbox<'a> = Option<&'a str>;
+type TargetMbox<'a> = Option<&'a str>;
+type All = bool;
 
 /// Represents the mailbox commands.
 #[derive(Debug, PartialEq, Eq)]
-pub enum Cmd {
+pub enum Cmd<'a> {
     /// Represents the list mailboxes command.
     List(MaxTableWidth),
+    /// Represents the count messages command.
+    Count,
+    /// Represents the dedupe command. Duplicates of the given mailbox (or of the source one if
+    /// omitted) are moved to the target mailbox if any, otherwise deleted.
+    Dedupe(Mbox<'a>, DedupeKey, TargetMbox<'a>),
+    /// Represents the expunge command, targetting the given mailbox or the source one if omitted.
+    Expunge(Mbox<'a>),
+    /// Represents the empty trash command.
+    EmptyTrash,
+    /// Represents the empty junk command.
+    EmptyJunk,
+    /// Represents the statistics command, targetting the given mailbox (or the source one if
+    /// omitted) or all the mailboxes.
+    Stats(Mbox<'a>, All),
+    /// Represents the clean command, applying the retention policies of the account.
+    Clean,
 }
 
 /// Defines the mailbox command matcher.
-pub fn matches(m: &clap::ArgMatches) -> Result<Option<Cmd>> {
+pub fn matches<'a>(m: &'a clap::ArgMatches) -> Result<Option<Cmd<'a>>> {
     if let Some(m) = m.subcommand_matches("mailboxes") {
         trace!("mailboxes subcommand matched");
         let max_table_width = m
@@ -29,15 +49,119 @@ pub fn matches(m: &clap::ArgMatches) -> Result<Option<Cmd>> {
         return Ok(Some(Cmd::List(max_table_width)));
     }
 
+    if m.subcommand_matches("count").is_some() {
+        trace!("count subcommand matched");
+        return Ok(Some(Cmd::Count));
+    }
+
+    if let Some(m) = m.subcommand_matches("dedupe") {
+        trace!("dedupe subcommand matched");
+        let mbox = m.value_of("mbox");
+        trace!(r#"mailbox: "{:?}""#, mbox);
+        let key = DedupeKey::try_from(m.value_of("key"))?;
+        trace!("key: {:?}", key);
+        let target = m.value_of("move-to");
+        trace!(r#"target mailbox: "{:?}""#, target);
+        return Ok(Some(Cmd::Dedupe(mbox, key, target)));
+    }
+
+    if let Some(m) = m.subcommand_matches("expunge") {
+        trace!("expunge subcommand matched");
+        let mbox = m.value_of("mbox");
+        trace!(r#"mailbox: "{:?}""#, mbox);
+        return Ok(Some(Cmd::Expunge(mbox)));
+    }
+
+    if m.subcommand_matches("empty-trash").is_some() {
+        trace!("empty trash subcommand matched");
+        return Ok(Some(Cmd::EmptyTrash));
+    }
+
+    if m.subcommand_matches("empty-junk").is_some() {
+        trace!("empty junk subcommand matched");
+        return Ok(Some(Cmd::EmptyJunk));
+    }
+
+    if let Some(m) = m.subcommand_matches("stats") {
+        trace!("stats subcommand matched");
+        let mbox = m.value_of("mbox");
+        trace!(r#"mailbox: "{:?}""#, mbox);
+        let all = m.is_present("all");
+        trace!("all: {}", all);
+        return Ok(Some(Cmd::Stats(mbox, all)));
+    }
+
+    if m.subcommand_matches("clean").is_some() {
+        trace!("clean subcommand matched");
+        return Ok(Some(Cmd::Clean));
+    }
+
     Ok(None)
 }
 
 /// Contains mailbox subcommands.
 pub fn subcmds<'a>() -> Vec<clap::App<'a, 'a>> {
-    vec![clap::SubCommand::with_name("mailboxes")
-        .aliases(&["mailbox", "mboxes", "mbox", "mb", "m"])
-        .about("Lists mailboxes")
-        .arg(table_arg::max_width())]
+    vec![
+        clap::SubCommand::with_name("mailboxes")
+            .aliases(&["mailbox", "mboxes", "mbox", "mb", "m"])
+            .about("Lists mailboxes")
+            .arg(table_arg::max_width()),
+        clap::SubCommand::with_name("count")
+            .aliases(&["cnt"])
+            .about("Counts unseen and total messages of the source mailbox"),
+        clap::SubCommand::with_name("dedupe")
+            .aliases(&["dedup"])
+            .about("Deletes duplicate messages of a mailbox")
+            .long_about("Finds the messages of a mailbox sharing the same Message-ID (or the same content) and deletes the duplicates, keeping the first message of each group. Duplicates can be moved to another mailbox instead.")
+            .arg(
+                clap::Arg::with_name("mbox")
+                    .help("Specifies the mailbox to dedupe (defaults to the source one)")
+                    .value_name("MAILBOX"),
+            )
+            .arg(
+                clap::Arg::with_name("key")
+                    .long("by")
+                    .help("Defines what makes two messages duplicates")
+                    .value_name("KEY")
+                    .possible_values(&["message-id", "content"])
+                    .default_value("message-id"),
+            )
+            .arg(
+                clap::Arg::with_name("move-to")
+                    .long("move-to")
+                    .help("Moves the duplicates to the given mailbox instead of deleting them")
+                    .value_name("TARGET"),
+            ),
+        clap::SubCommand::with_name("expunge")
+            .about("Permanently removes the messages flagged as deleted")
+            .arg(
+                clap::Arg::with_name("mbox")
+                    .help("Specifies the mailbox to expunge (defaults to the source one)")
+                    .value_name("MAILBOX"),
+            ),
+        clap::SubCommand::with_name("empty-trash")
+            .about("Permanently removes all the messages of the trash folder"),
+        clap::SubCommand::with_name("empty-junk")
+            .about("Permanently removes all the messages of the junk folder"),
+        clap::SubCommand::with_name("stats")
+            .about("Shows statistics of a mailbox")
+            .long_about("Shows the number of messages, the total size, the oldest and newest dates and the top senders of a mailbox, or of all the mailboxes.")
+            .arg(
+                clap::Arg::with_name("mbox")
+                    .help("Specifies the mailbox (defaults to the source one)")
+                    .value_name("MAILBOX")
+                    .conflicts_with("all"),
+            )
+            .arg(
+                clap::Arg::with_name("all")
+                    .long("all")
+                    .short("a")
+                    .help("Shows statistics of all the mailboxes"),
+            ),
+        clap::SubCommand::with_name("clean")
+            .about("Applies the retention policies")
+            .long_about("Deletes the messages older than the retention policies of the account, or moves them to the archive mailbox of the policy."),
+    ]
 }
 
 /// Defines the source mailbox argument.
@@ -45,9 +169,8 @@ pub fn source_arg<'a>() -> clap::Arg<'a, 'a> {
     clap::Arg::with_name("mbox-source")
         .short("m")
         .long("mailbox")
-        .help("Specifies the source mailbox")
+        .help("Specifies the source mailbox, defaults to the `default-mailbox` config option")
         .value_name("SOURCE")
-        .default_value("INBOX")
 }
 
 /// Defines the target mailbox argument.
@@ -73,6 +196,72 @@ mod tests {
             .subcommands(subcmds())
             .get_matches_from(&["himalaya", "mailboxes", "--max-width", "20"]);
         assert_eq!(Some(Cmd::List(Some(20))), matches(&arg).unwrap());
+
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .get_matches_from(&["himalaya", "count"]);
+        assert_eq!(Some(Cmd::Count), matches(&arg).unwrap());
+
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .get_matches_from(&["himalaya", "dedupe"]);
+        assert_eq!(
+            Some(Cmd::Dedupe(None, DedupeKey::MessageId, None)),
+            matches(&arg).unwrap()
+        );
+
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .get_matches_from(&[
+                "himalaya",
+                "dedupe",
+                "Archives",
+                "--by",
+                "content",
+                "--move-to",
+                "Trash",
+            ]);
+        assert_eq!(
+            Some(Cmd::Dedupe(
+                Some("Archives"),
+                DedupeKey::Content,
+                Some("Trash")
+            )),
+            matches(&arg).unwrap()
+        );
+
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .get_matches_from(&["himalaya", "expunge", "Archives"]);
+        assert_eq!(Some(Cmd::Expunge(Some("Archives"))), matches(&arg).unwrap());
+
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .get_matches_from(&["himalaya", "empty-trash"]);
+        assert_eq!(Some(Cmd::EmptyTrash), matches(&arg).unwrap());
+
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .get_matches_from(&["himalaya", "empty-junk"]);
+        assert_eq!(Some(Cmd::EmptyJunk), matches(&arg).unwrap());
+
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .get_matches_from(&["himalaya", "stats", "Archives"]);
+        assert_eq!(
+            Some(Cmd::Stats(Some("Archives"), false)),
+            matches(&arg).unwrap()
+        );
+
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .get_matches_from(&["himalaya", "stats", "--all"]);
+        assert_eq!(Some(Cmd::Stats(None, true)), matches(&arg).unwrap());
+
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .get_matches_from(&["himalaya", "clean"]);
+        assert_eq!(Some(Cmd::Clean), matches(&arg).unwrap());
     }
 
     #[test]
@@ -104,7 +293,7 @@ mod tests {
         }
 
         let app = get_matches_from![];
-        assert_eq!(Some("INBOX"), app.value_of("mbox-source"));
+        assert_eq!(None, app.value_of("mbox-source"));
 
         let app = get_matches_from!["-m", "SOURCE"];
         assert_eq!(Some("SOURCE"), app.value_of("mbox-source"));
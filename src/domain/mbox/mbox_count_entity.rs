@@ -0,0 +1,62 @@
+//! Mailbox count entity module.
+//!
+//! This module contains the definition of the mailbox count and its traits implementations.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fmt::{self, Display};
+
+use crate::output::{Print, WriteColor};
+
+/// Represents the unseen and total messages counts of a mailbox, as returned by the IMAP `STATUS`
+/// command.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct MboxCount {
+    /// Represents the number of unseen messages.
+    pub unseen: u32,
+
+    /// Represents the total number of messages.
+    pub total: u32,
+}
+
+/// Makes the mailbox count displayable.
+impl Display for MboxCount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.unseen, self.total)
+    }
+}
+
+/// Makes the mailbox count printable.
+impl Print for MboxCount {
+    fn print(&self, writter: &mut dyn WriteColor) -> Result<()> {
+        write!(writter, "{}", self).context("cannot print mailbox count")
+    }
+}
+
+/// Converts an `imap::types::Mailbox` into a mailbox count.
+impl From<&imap::types::Mailbox> for MboxCount {
+    fn from(mbox: &imap::types::Mailbox) -> Self {
+        Self {
+            unseen: mbox.unseen.unwrap_or_default(),
+            total: mbox.exists,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_display_mbox_count() {
+        assert_eq!("0/0", MboxCount::default().to_string());
+        assert_eq!(
+            "3/120",
+            MboxCount {
+                unseen: 3,
+                total: 120
+            }
+            .to_string()
+        );
+    }
+}
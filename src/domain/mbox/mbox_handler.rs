@@ -3,13 +3,25 @@
 //! This module gathers all mailbox actions triggered by the CLI.
 
 use anyhow::Result;
-use log::trace;
+use chrono::Local;
+use imap::types::Flag;
+use log::{debug, trace};
+use std::convert::TryFrom;
 
 use crate::{
-    domain::ImapServiceInterface,
+    config::Account,
+    domain::{
+        mbox::{find_duplicates, DedupeKey, MboxStats, MboxesStats, RetentionPolicy},
+        msg::{compress_seqs, msg_handler},
+        Flags, ImapServiceInterface, Mbox,
+    },
     output::{PrintTableOpts, PrinterService},
+    ui::i18n::{tr, tr_args},
 };
 
+/// Represents the number of messages fetched at once when looking for duplicates.
+const DEDUPE_BATCH_SIZE: usize = 100;
+
 /// Lists all mailboxes.
 pub fn list<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
     max_width: Option<usize>,
@@ -21,6 +33,234 @@ pub fn list<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
     printer.print_table(mboxes, PrintTableOpts { max_width })
 }
 
+/// Counts unseen and total messages of the source mailbox.
+pub fn count<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    printer: &mut Printer,
+    imap: &mut ImapService,
+) -> Result<()> {
+    let count = imap.fetch_mbox_count()?;
+    trace!("mailbox count: {:?}", count);
+    printer.print(count)
+}
+
+/// Prints the statistics of the source mailbox, or of all the mailboxes.
+pub fn stats<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    mbox: &Mbox,
+    all: bool,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+) -> Result<()> {
+    if !all {
+        return printer.print(imap.fetch_mbox_stats(&mbox.name)?);
+    }
+
+    let stats = imap
+        .fetch_mbox_names()?
+        .iter()
+        .map(|mbox| imap.fetch_mbox_stats(mbox))
+        .collect::<Result<Vec<MboxStats>>>()?;
+    printer.print(MboxesStats(stats))
+}
+
+/// Applies the given retention policy to the source mailbox: messages older than the policy are
+/// deleted, or moved to the archive mailbox of the policy. No confirmation is asked since the
+/// policies come from the config, and are also applied by the daemon. Returns the report of the
+/// operation, so the reports of all the policies can be printed at once.
+pub fn clean<'a, ImapService: ImapServiceInterface<'a>>(
+    mbox: &Mbox,
+    policy: &RetentionPolicy,
+    dry_run: bool,
+    imap: &mut ImapService,
+) -> Result<String> {
+    let seqs = imap.search_seqs(&policy.to_query(Local::now()))?;
+    debug!(
+        r#"found {} messages older than {} in "{}""#,
+        seqs.len(),
+        policy,
+        mbox
+    );
+    if seqs.is_empty() {
+        return Ok(format!(
+            r#"No message older than {} in folder "{}""#,
+            policy, mbox
+        ));
+    }
+
+    let seq_range = compress_seqs(&seqs);
+    let archive = policy.archive.as_deref().map(Mbox::new);
+    if dry_run {
+        return Ok(match archive {
+            Some(archive) => format!(
+                r#"{} message(s) older than {} would be moved from folder "{}" to folder "{}""#,
+                seqs.len(),
+                policy,
+                mbox,
+                archive
+            ),
+            None => format!(
+                r#"{} message(s) older than {} would be deleted from folder "{}""#,
+                seqs.len(),
+                policy,
+                mbox
+            ),
+        });
+    }
+
+    if let Some(ref archive) = archive {
+        imap.copy_msgs(&seq_range, archive)?;
+    }
+    imap.add_flags(&seq_range, &Flags::try_from(vec![Flag::Deleted])?)?;
+    let count = imap.expunge_deleted()?;
+
+    Ok(match archive {
+        Some(archive) => format!(
+            r#"{} message(s) older than {} successfully moved from folder "{}" to folder "{}""#,
+            count, policy, mbox, archive
+        ),
+        None => format!(
+            r#"{} message(s) older than {} successfully deleted from folder "{}""#,
+            count, policy, mbox
+        ),
+    })
+}
+
+/// Deletes the duplicate messages of the source mailbox, or moves them to the given target
+/// mailbox. The first message of each group of duplicates is kept.
+pub fn dedupe<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    key: DedupeKey,
+    target: Option<&str>,
+    dry_run: bool,
+    yes: bool,
+    account: &Account,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+) -> Result<()> {
+    let seqs = imap.search_seqs("ALL")?;
+    if seqs.is_empty() {
+        return printer.print(tr("no-duplicate"));
+    }
+
+    let mut msgs = vec![];
+    for seqs in seqs.chunks(DEDUPE_BATCH_SIZE) {
+        let seq_range = compress_seqs(seqs);
+        let raws = match key {
+            // Headers are enough to compare Message-IDs, which avoids fetching whole messages.
+            DedupeKey::MessageId => imap.fetch_raw_headers(&seq_range)?,
+            DedupeKey::Content => imap.fetch_raw_msgs(&seq_range)?,
+        };
+        msgs.extend(raws.into_iter().map(|(seq, raw)| (seq, key.of(&raw))));
+    }
+    let duplicates = find_duplicates(&msgs);
+    debug!("found {} duplicate messages", duplicates.len());
+    trace!("duplicates: {:?}", duplicates);
+    if duplicates.is_empty() {
+        return printer.print(tr("no-duplicate"));
+    }
+
+    let seq_range = compress_seqs(&duplicates);
+    let target = target.map(Mbox::new);
+    if dry_run {
+        return printer.print(match target {
+            Some(target) => tr_args(
+                "duplicates-would-move",
+                &[("seqs", &seq_range), ("mbox", &target.to_string())],
+            ),
+            None => tr_args("duplicates-would-delete", &[("seqs", &seq_range)]),
+        });
+    }
+    if !yes && !msg_handler::confirm_deletion(&seq_range, account, printer, imap)? {
+        return printer.print(tr("dedupe-aborted"));
+    }
+
+    if let Some(ref target) = target {
+        imap.copy_msgs(&seq_range, target)?;
+    }
+    let flags = Flags::try_from(vec![Flag::Seen, Flag::Deleted])?;
+    imap.add_flags(&seq_range, &flags)?;
+    imap.expunge()?;
+
+    printer.print(match target {
+        Some(target) => tr_args(
+            "duplicates-moved",
+            &[("seqs", &seq_range), ("mbox", &target.to_string())],
+        ),
+        None => tr_args("duplicates-deleted", &[("seqs", &seq_range)]),
+    })
+}
+
+/// Permanently removes the messages of the source mailbox flagged as deleted. In dry run mode,
+/// the messages that would be removed are printed instead.
+pub fn expunge<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    mbox: &Mbox,
+    dry_run: bool,
+    yes: bool,
+    account: &Account,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+) -> Result<()> {
+    let seqs = imap.search_seqs("DELETED")?;
+    if seqs.is_empty() {
+        return printer.print(tr_args("no-msg-to-expunge", &[("mbox", &mbox.to_string())]));
+    }
+
+    let seq_range = compress_seqs(&seqs);
+    if dry_run {
+        return printer.print(tr_args(
+            "msgs-would-expunge",
+            &[("seqs", &seq_range), ("mbox", &mbox.to_string())],
+        ));
+    }
+    if !yes && !msg_handler::confirm_deletion(&seq_range, account, printer, imap)? {
+        return printer.print(tr("expunge-aborted"));
+    }
+
+    let count = imap.expunge_deleted()?;
+    printer.print(tr_args(
+        "msgs-expunged",
+        &[("count", &count.to_string()), ("mbox", &mbox.to_string())],
+    ))
+}
+
+/// Permanently removes all the messages of the source mailbox, used to empty the trash and the
+/// junk folders.
+pub fn empty<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    mbox: &Mbox,
+    dry_run: bool,
+    yes: bool,
+    account: &Account,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+) -> Result<()> {
+    let seqs = imap.search_seqs("ALL")?;
+    if seqs.is_empty() {
+        return printer.print(tr_args(
+            "mbox-already-empty",
+            &[("mbox", &mbox.to_string())],
+        ));
+    }
+
+    let seq_range = compress_seqs(&seqs);
+    if dry_run {
+        return printer.print(tr_args(
+            "mbox-would-empty",
+            &[
+                ("count", &seqs.len().to_string()),
+                ("mbox", &mbox.to_string()),
+            ],
+        ));
+    }
+    if !yes && !msg_handler::confirm_deletion(&seq_range, account, printer, imap)? {
+        return printer.print(tr("deletion-aborted"));
+    }
+
+    imap.add_flags(&seq_range, &Flags::try_from(vec![Flag::Deleted])?)?;
+    let count = imap.expunge_deleted()?;
+    printer.print(tr_args(
+        "mbox-emptied",
+        &[("count", &count.to_string()), ("mbox", &mbox.to_string())],
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use serde::Serialize;
@@ -29,7 +269,10 @@ mod tests {
 
     use crate::{
         config::Config,
-        domain::{AttrRemote, Attrs, Envelopes, Flags, Mbox, Mboxes, Msg},
+        domain::{
+            AttrRemote, Attrs, Envelope, Envelopes, FilterHeaders, Flags, MailingLists, Mbox,
+            MboxCount, MboxStats, Mboxes, Msg,
+        },
         output::{Print, PrintTable, WriteColor},
     };
 
@@ -88,7 +331,10 @@ mod tests {
             fn print<T: Serialize + Print>(&mut self, _data: T) -> Result<()> {
                 unimplemented!()
             }
-            fn is_json(&self) -> bool {
+            fn is_structured(&self) -> bool {
+                unimplemented!()
+            }
+            fn is_interactive(&self) -> bool {
                 unimplemented!()
             }
         }
@@ -114,10 +360,19 @@ mod tests {
                 ]))
             }
 
-            fn notify(&mut self, _: &Config, _: u64) -> Result<()> {
+            fn fetch_mbox_names(&mut self) -> Result<Vec<String>> {
+                unimplemented!()
+            }
+            fn fetch_mbox_count(&mut self) -> Result<MboxCount> {
+                unimplemented!()
+            }
+            fn fetch_mbox_stats(&mut self, _: &str) -> Result<MboxStats> {
                 unimplemented!()
             }
-            fn watch(&mut self, _: u64) -> Result<()> {
+            fn notify(&mut self, _: &Config, _: Option<&str>, _: u64) -> Result<()> {
+                unimplemented!()
+            }
+            fn idle(&mut self, _: u64) -> Result<()> {
                 unimplemented!()
             }
             fn fetch_envelopes(&mut self, _: &usize, _: &usize) -> Result<Envelopes> {
@@ -126,24 +381,66 @@ mod tests {
             fn fetch_envelopes_with(&mut self, _: &str, _: &usize, _: &usize) -> Result<Envelopes> {
                 unimplemented!()
             }
+            fn fetch_filter_headers(&mut self, _: Option<u32>) -> Result<Vec<FilterHeaders>> {
+                unimplemented!()
+            }
+            fn fetch_mailing_lists(&mut self) -> Result<MailingLists> {
+                unimplemented!()
+            }
+            fn search_seqs(&mut self, _: &str) -> Result<Vec<u32>> {
+                unimplemented!()
+            }
             fn find_msg(&mut self, _: &str) -> Result<Msg> {
                 unimplemented!()
             }
             fn find_raw_msg(&mut self, _: &str) -> Result<Vec<u8>> {
                 unimplemented!()
             }
+            fn fetch_raw_msgs(&mut self, _: &str) -> Result<Vec<(u32, Vec<u8>)>> {
+                unimplemented!()
+            }
+            fn fetch_raw_headers(&mut self, _: &str) -> Result<Vec<(u32, Vec<u8>)>> {
+                unimplemented!()
+            }
             fn append_msg(&mut self, _: &Mbox, _: Msg) -> Result<()> {
                 unimplemented!()
             }
             fn append_raw_msg_with_flags(&mut self, _: &Mbox, _: &[u8], _: Flags) -> Result<()> {
                 unimplemented!()
             }
+            fn copy_msgs(&mut self, _: &str, _: &Mbox) -> Result<()> {
+                unimplemented!()
+            }
             fn expunge(&mut self) -> Result<()> {
                 unimplemented!()
             }
+            fn expunge_deleted(&mut self) -> Result<usize> {
+                unimplemented!()
+            }
+            fn noop(&mut self) -> Result<()> {
+                unimplemented!()
+            }
             fn logout(&mut self) -> Result<()> {
                 unimplemented!()
             }
+            fn has_annotate(&mut self) -> Result<bool> {
+                unimplemented!()
+            }
+            fn fetch_note(&mut self, _: &str) -> Result<Option<String>> {
+                unimplemented!()
+            }
+            fn store_note(&mut self, _: &str, _: Option<&str>) -> Result<()> {
+                unimplemented!()
+            }
+            fn run_raw_cmd(&mut self, _: &str, _: bool) -> Result<Vec<u8>> {
+                unimplemented!()
+            }
+            fn search_raw_msgs(&mut self, _: &str, _: &str) -> Result<Vec<(u32, Vec<u8>)>> {
+                unimplemented!()
+            }
+            fn search_envelopes_in(&mut self, _: &str, _: &str) -> Result<Vec<Envelope<'static>>> {
+                unimplemented!()
+            }
             fn add_flags(&mut self, _: &str, _: &Flags) -> Result<()> {
                 unimplemented!()
             }
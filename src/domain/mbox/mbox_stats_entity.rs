@@ -0,0 +1,206 @@
+//! Mailbox statistics entity module.
+//!
+//! This module contains the definition of the mailbox statistics and its traits implementations.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset};
+use serde::{Serialize, Serializer};
+use std::collections::HashMap;
+
+use crate::output::{format_size, Print, WriteColor};
+
+/// Represents the number of senders kept by the statistics.
+pub const TOP_SENDERS: usize = 5;
+
+fn serialize_date<S: Serializer>(
+    date: &Option<DateTime<FixedOffset>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match date {
+        Some(date) => serializer.serialize_some(&date.to_rfc3339()),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn serialize_top_senders<S: Serializer>(
+    senders: &HashMap<String, usize>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(top_senders(senders))
+}
+
+fn top_senders(senders: &HashMap<String, usize>) -> Vec<SenderCount> {
+    let mut top_senders: Vec<_> = senders
+        .iter()
+        .map(|(sender, count)| SenderCount {
+            sender: sender.to_owned(),
+            count: *count,
+        })
+        .collect();
+    top_senders.sort_by(|a, b| b.count.cmp(&a.count).then(a.sender.cmp(&b.sender)));
+    top_senders.truncate(TOP_SENDERS);
+    top_senders
+}
+
+/// Represents the number of messages of a sender.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SenderCount {
+    pub sender: String,
+    pub count: usize,
+}
+
+/// Represents the statistics of a mailbox, used for housekeeping.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct MboxStats {
+    /// Represents the name of the mailbox.
+    pub mbox: String,
+
+    /// Represents the total number of messages.
+    pub total: usize,
+
+    /// Represents the number of unseen messages.
+    pub unseen: usize,
+
+    /// Represents the total size of the messages in bytes, as given by `RFC822.SIZE`.
+    pub size: usize,
+
+    /// Represents the internal date of the oldest message.
+    #[serde(serialize_with = "serialize_date")]
+    pub oldest: Option<DateTime<FixedOffset>>,
+
+    /// Represents the internal date of the newest message.
+    #[serde(serialize_with = "serialize_date")]
+    pub newest: Option<DateTime<FixedOffset>>,
+
+    /// Represents the number of messages per sender, serialized as the top senders.
+    #[serde(rename = "top_senders", serialize_with = "serialize_top_senders")]
+    senders: HashMap<String, usize>,
+}
+
+impl MboxStats {
+    /// Creates empty statistics for the given mailbox.
+    pub fn new<S: ToString>(mbox: S) -> Self {
+        Self {
+            mbox: mbox.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Adds a message to the statistics.
+    pub fn add(
+        &mut self,
+        size: usize,
+        seen: bool,
+        date: Option<DateTime<FixedOffset>>,
+        sender: Option<String>,
+    ) {
+        self.total += 1;
+        if !seen {
+            self.unseen += 1;
+        }
+        self.size += size;
+        if let Some(date) = date {
+            self.oldest = Some(self.oldest.map_or(date, |oldest| oldest.min(date)));
+            self.newest = Some(self.newest.map_or(date, |newest| newest.max(date)));
+        }
+        if let Some(sender) = sender {
+            *self.senders.entry(sender.to_lowercase()).or_default() += 1;
+        }
+    }
+
+    /// Returns the senders with the most messages.
+    pub fn top_senders(&self) -> Vec<SenderCount> {
+        top_senders(&self.senders)
+    }
+}
+
+/// Makes the mailbox statistics printable.
+impl Print for MboxStats {
+    fn print(&self, writter: &mut dyn WriteColor) -> Result<()> {
+        let date = |date: &Option<DateTime<FixedOffset>>| {
+            date.map(|date| date.naive_local().to_string())
+                .unwrap_or_else(|| String::from("-"))
+        };
+        let mut stats = vec![
+            format!("Mailbox: {}", self.mbox),
+            format!("Messages: {} ({} unseen)", self.total, self.unseen),
+            format!("Size: {}", format_size(self.size)),
+            format!("Oldest: {}", date(&self.oldest)),
+            format!("Newest: {}", date(&self.newest)),
+        ];
+        let top_senders = self.top_senders();
+        if !top_senders.is_empty() {
+            stats.push(String::from("Top senders:"));
+            for sender in top_senders {
+                stats.push(format!("  {:>6}  {}", sender.count, sender.sender));
+            }
+        }
+        writeln!(writter, "{}", stats.join("\n")).context("cannot print mailbox statistics")
+    }
+}
+
+/// Represents the statistics of several mailboxes.
+#[derive(Debug, Default, Serialize)]
+pub struct MboxesStats(pub Vec<MboxStats>);
+
+/// Makes the statistics of several mailboxes printable, separated by blank lines.
+impl Print for MboxesStats {
+    fn print(&self, writter: &mut dyn WriteColor) -> Result<()> {
+        for (i, stats) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(writter).context("cannot print mailbox statistics")?;
+            }
+            stats.print(writter)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_add_msgs_to_stats() {
+        let date = |date| DateTime::parse_from_rfc3339(date).ok();
+        let mut stats = MboxStats::new("INBOX");
+        stats.add(
+            1_000,
+            true,
+            date("2021-03-01T10:00:00+01:00"),
+            Some("bob@example.com".into()),
+        );
+        stats.add(
+            500,
+            false,
+            date("2020-01-01T10:00:00+01:00"),
+            Some("Alice@example.com".into()),
+        );
+        stats.add(
+            250,
+            false,
+            date("2021-06-01T10:00:00+01:00"),
+            Some("alice@example.com".into()),
+        );
+        stats.add(250, true, None, None);
+
+        assert_eq!(4, stats.total);
+        assert_eq!(2, stats.unseen);
+        assert_eq!(2_000, stats.size);
+        assert_eq!(date("2020-01-01T10:00:00+01:00"), stats.oldest);
+        assert_eq!(date("2021-06-01T10:00:00+01:00"), stats.newest);
+        assert_eq!(
+            vec![
+                SenderCount {
+                    sender: "alice@example.com".into(),
+                    count: 2,
+                },
+                SenderCount {
+                    sender: "bob@example.com".into(),
+                    count: 1,
+                },
+            ],
+            stats.top_senders()
+        );
+    }
+}
@@ -11,8 +11,20 @@ pub use attr_entity::*;
 pub mod attrs_entity;
 pub use attrs_entity::*;
 
+pub mod dedupe_entity;
+pub use dedupe_entity::*;
+
 pub mod mbox_entity;
 pub use mbox_entity::*;
 
+pub mod mbox_count_entity;
+pub use mbox_count_entity::*;
+
+pub mod mbox_stats_entity;
+pub use mbox_stats_entity::*;
+
 pub mod mboxes_entity;
 pub use mboxes_entity::*;
+
+pub mod retention_entity;
+pub use retention_entity::*;
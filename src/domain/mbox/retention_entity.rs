@@ -0,0 +1,141 @@
+//! Retention entity module.
+//!
+//! This module contains the definition of the retention policies applied to mailboxes by the
+//! `clean` command.
+
+use anyhow::{anyhow, Error, Result};
+use chrono::{DateTime, Duration, TimeZone};
+use serde::Deserialize;
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display},
+};
+
+/// Represents a retention policy from the user config. It is either the maximum age of the
+/// messages, like `"30d"`, or a table like `{ max-age = "1y", archive = "Archives" }` to move the
+/// old messages to another mailbox instead of deleting them.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawRetentionPolicy")]
+pub struct RetentionPolicy {
+    /// The maximum age of the messages, as written in the config.
+    pub max_age: String,
+    /// The maximum age of the messages, in days.
+    pub days: i64,
+    /// The mailbox the old messages are moved to. Old messages are deleted if omitted.
+    pub archive: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawRetentionPolicy {
+    MaxAge(String),
+    Full {
+        #[serde(rename = "max-age")]
+        max_age: String,
+        archive: Option<String>,
+    },
+}
+
+impl TryFrom<RawRetentionPolicy> for RetentionPolicy {
+    type Error = Error;
+
+    fn try_from(raw: RawRetentionPolicy) -> Result<Self, Self::Error> {
+        let (max_age, archive) = match raw {
+            RawRetentionPolicy::MaxAge(max_age) => (max_age, None),
+            RawRetentionPolicy::Full { max_age, archive } => (max_age, archive),
+        };
+        Ok(Self {
+            days: parse_days(&max_age)?,
+            max_age,
+            archive,
+        })
+    }
+}
+
+/// Parses an age made of a number followed by a unit: `d` (days), `w` (weeks), `m` (months of 30
+/// days) or `y` (years of 365 days).
+fn parse_days(age: &str) -> Result<i64> {
+    let age = age.trim();
+    let err = || anyhow!(r#"cannot parse retention age "{}""#, age);
+    let unit = age.chars().last().ok_or_else(err)?;
+    let count: u32 = age[..age.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| err())?;
+    let days = match unit {
+        'd' => 1,
+        'w' => 7,
+        'm' => 30,
+        'y' => 365,
+        _ => return Err(err()),
+    };
+    Ok(count as i64 * days)
+}
+
+impl RetentionPolicy {
+    /// Builds the IMAP query matching the messages older than the policy.
+    pub fn to_query<Tz: TimeZone>(&self, now: DateTime<Tz>) -> String
+    where
+        Tz::Offset: Display,
+    {
+        let date = now - Duration::days(self.days);
+        format!("BEFORE {}", date.format("%d-%b-%Y"))
+    }
+}
+
+impl Display for RetentionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.max_age)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn it_should_parse_policies() {
+        let policies: HashMap<String, RetentionPolicy> = toml::from_str(
+            r#"
+            "Lists/cron" = "30d"
+            "INBOX" = { max-age = "1y", archive = "Archives" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            RetentionPolicy {
+                max_age: "30d".into(),
+                days: 30,
+                archive: None,
+            },
+            policies["Lists/cron"]
+        );
+        assert_eq!(
+            RetentionPolicy {
+                max_age: "1y".into(),
+                days: 365,
+                archive: Some("Archives".into()),
+            },
+            policies["INBOX"]
+        );
+
+        assert_eq!(14, parse_days("2w").unwrap());
+        assert_eq!(180, parse_days("6m").unwrap());
+        assert!(parse_days("30").is_err());
+        assert!(parse_days("d").is_err());
+        assert!(toml::from_str::<HashMap<String, RetentionPolicy>>(r#"INBOX = "soon""#).is_err());
+    }
+
+    #[test]
+    fn it_should_build_query() {
+        let policy = RetentionPolicy {
+            max_age: "30d".into(),
+            days: 30,
+            archive: None,
+        };
+        let now = Utc.ymd(2021, 10, 16).and_hms(10, 0, 0);
+        assert_eq!("BEFORE 16-Sep-2021", policy.to_query(now));
+    }
+}
@@ -0,0 +1,112 @@
+//! Module related to mailing list CLI.
+//!
+//! This module provides subcommands and a command matcher related to mailing lists.
+
+use anyhow::Result;
+use clap::{self, App, Arg, ArgMatches, SubCommand};
+use log::{debug, trace};
+
+use crate::{domain::msg::msg_arg, ui::table_arg};
+
+type ListId<'a> = &'a str;
+type MaxTableWidth = Option<usize>;
+type PageSize = Option<usize>;
+type Page = usize;
+
+/// Mailing list commands.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Cmd<'a> {
+    /// List the mailing lists of the current mailbox along with their message counts.
+    List(MaxTableWidth),
+    /// List the messages of the given mailing list.
+    Read(ListId<'a>, MaxTableWidth, PageSize, Page),
+}
+
+/// Mailing list command matcher.
+pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Cmd<'a>>> {
+    let m = match m.subcommand_matches("lists") {
+        Some(m) => m,
+        None => return Ok(None),
+    };
+
+    if let Some(m) = m.subcommand_matches("read") {
+        debug!("lists read command matched");
+        let list_id = m.value_of("list-id").unwrap();
+        trace!(r#"list id: "{}""#, list_id);
+        let max_table_width = max_table_width(m);
+        let page_size = m.value_of("page-size").and_then(|s| s.parse().ok());
+        trace!(r#"page size: "{:?}""#, page_size);
+        let page = m
+            .value_of("page")
+            .unwrap_or("1")
+            .parse()
+            .ok()
+            .map(|page| 1.max(page) - 1)
+            .unwrap_or_default();
+        trace!(r#"page: "{:?}""#, page);
+        return Ok(Some(Cmd::Read(list_id, max_table_width, page_size, page)));
+    }
+
+    debug!("lists command matched");
+    Ok(Some(Cmd::List(max_table_width(m))))
+}
+
+/// Matches the max table width argument.
+fn max_table_width(m: &ArgMatches) -> MaxTableWidth {
+    let max_table_width = m
+        .value_of("max-table-width")
+        .and_then(|width| width.parse::<usize>().ok());
+    trace!(r#"max table width: "{:?}""#, max_table_width);
+    max_table_width
+}
+
+/// Mailing list subcommands.
+pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
+    vec![SubCommand::with_name("lists")
+        .about("Lists the mailing lists of the mailbox")
+        .long_about("Groups the messages of the mailbox by mailing list, recognized by their List-Id header, and shows the number of unseen and total messages of each list. Only the flags and the List-Id header of the messages are fetched.")
+        .arg(table_arg::max_width())
+        .subcommand(
+            SubCommand::with_name("read")
+                .about("Lists the messages of a mailing list")
+                .long_about("Lists the messages of the mailing list having the given ID, as shown by the lists command (like `rust-users.example.org`).")
+                .arg(
+                    Arg::with_name("list-id")
+                        .help("ID of the mailing list")
+                        .value_name("LIST-ID")
+                        .required(true),
+                )
+                .arg(msg_arg::page_size_arg())
+                .arg(msg_arg::page_arg())
+                .arg(table_arg::max_width()),
+        )]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_match_cmds() {
+        let app = || App::new("himalaya").subcommands(subcmds());
+
+        let m = app().get_matches_from(&["himalaya", "lists", "-w", "80"]);
+        assert_eq!(Some(Cmd::List(Some(80))), matches(&m).unwrap());
+
+        let m = app().get_matches_from(&[
+            "himalaya",
+            "lists",
+            "read",
+            "rust-users.example.org",
+            "--page",
+            "2",
+        ]);
+        assert_eq!(
+            Some(Cmd::Read("rust-users.example.org", None, None, 1)),
+            matches(&m).unwrap()
+        );
+
+        let m = app().get_matches_from(&["himalaya"]);
+        assert_eq!(None, matches(&m).unwrap());
+    }
+}
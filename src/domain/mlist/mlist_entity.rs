@@ -0,0 +1,202 @@
+//! Mailing list entity module.
+//!
+//! This module contains the definition of the mailing lists found in a mailbox and their traits
+//! implementations.
+
+use anyhow::Result;
+use imap::types::Flag;
+use mailparse::MailHeaderMap;
+use serde::Serialize;
+use std::{collections::BTreeMap, iter::FromIterator, ops::Deref};
+
+use crate::{
+    domain::{msg::decode_header_value, RawEnvelopes},
+    output::{PrintTable, PrintTableOpts, WriteColor},
+    ui::{Cell, Row, Table},
+};
+
+/// Parses the value of a `List-Id` header, for example `Rust Users <rust-users.example.org>`, into
+/// the identifier of the list and its optional description. Values without angle brackets are
+/// taken as a whole as the identifier.
+pub fn parse_list_id(value: &str) -> Option<(String, Option<String>)> {
+    let value = value.trim();
+    let (name, id) = match (value.rfind('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => (&value[..start], &value[start + 1..end]),
+        _ => ("", value),
+    };
+    let id = id.trim().to_lowercase();
+    if id.is_empty() {
+        return None;
+    }
+    let name = name.trim().trim_matches('"').trim();
+    let name = if name.is_empty() {
+        None
+    } else {
+        Some(name.to_owned())
+    };
+    Some((id, name))
+}
+
+/// Represents a mailing list and the counts of its messages in a mailbox.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct MailingList {
+    /// The identifier of the list, like `rust-users.example.org`.
+    pub id: String,
+
+    /// The description of the list, like `Rust Users`.
+    pub name: Option<String>,
+
+    /// The number of unseen messages of the list.
+    pub unseen: usize,
+
+    /// The total number of messages of the list.
+    pub total: usize,
+}
+
+impl Table for MailingList {
+    fn head() -> Row {
+        Row::new()
+            .cell(Cell::new("ID").bold().underline().white())
+            .cell(Cell::new("NAME").shrinkable().bold().underline().white())
+            .cell(Cell::new("UNSEEN").bold().underline().white())
+            .cell(Cell::new("TOTAL").bold().underline().white())
+    }
+
+    fn row(&self) -> Row {
+        let unseen = self.unseen > 0;
+        Row::new()
+            .cell(Cell::new(&self.id).bold_if(unseen).blue())
+            .cell(
+                Cell::new(self.name.as_deref().unwrap_or_default())
+                    .shrinkable()
+                    .bold_if(unseen)
+                    .green(),
+            )
+            .cell(Cell::new(self.unseen.to_string()).bold_if(unseen).red())
+            .cell(Cell::new(self.total.to_string()).bold_if(unseen).white())
+    }
+}
+
+/// Represents the mailing lists of a mailbox, sorted by identifier.
+#[derive(Debug, Default, Serialize)]
+pub struct MailingLists(pub Vec<MailingList>);
+
+impl Deref for MailingLists {
+    type Target = Vec<MailingList>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Groups messages by mailing list, from their `List-Id` header value and whether they are seen.
+/// Messages with an invalid header are skipped. The description of a list is the first one found.
+impl<'a> FromIterator<(&'a str, bool)> for MailingLists {
+    fn from_iter<I: IntoIterator<Item = (&'a str, bool)>>(msgs: I) -> Self {
+        let mut lists: BTreeMap<String, MailingList> = BTreeMap::new();
+        for (list_id, seen) in msgs {
+            let (id, name) = match parse_list_id(list_id) {
+                Some(list_id) => list_id,
+                None => continue,
+            };
+            let list = lists.entry(id.clone()).or_insert_with(|| MailingList {
+                id,
+                ..MailingList::default()
+            });
+            if list.name.is_none() {
+                list.name = name;
+            }
+            list.total += 1;
+            if !seen {
+                list.unseen += 1;
+            }
+        }
+        Self(lists.into_values().collect())
+    }
+}
+
+/// Extracts the `List-Id` header value and whether they are seen from the fetched messages, so
+/// they can be grouped by mailing list once all batches are fetched. Messages are expected to be
+/// fetched with their flags and their `List-Id` header, the ones without it being skipped.
+pub fn list_msgs(fetches: &RawEnvelopes) -> Vec<(String, bool)> {
+    fetches
+        .iter()
+        .filter_map(|fetch| {
+            let (headers, _) = mailparse::parse_headers(fetch.header()?).ok()?;
+            let list_id = headers.get_first_header("List-Id")?;
+            let list_id = decode_header_value(list_id.get_value_raw());
+            let seen = fetch.flags().iter().any(|flag| *flag == Flag::Seen);
+            Some((list_id, seen))
+        })
+        .collect()
+}
+
+/// Makes the mailing lists printable.
+impl PrintTable for MailingLists {
+    fn print_table(&self, writter: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        writeln!(writter)?;
+        Table::print(writter, &self, opts)?;
+        writeln!(writter)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_parse_list_id() {
+        assert_eq!(
+            Some((
+                String::from("rust-users.example.org"),
+                Some(String::from("Rust Users"))
+            )),
+            parse_list_id("Rust Users <Rust-Users.example.org>")
+        );
+        assert_eq!(
+            Some((
+                String::from("rust-users.example.org"),
+                Some(String::from("Rust <Users>"))
+            )),
+            parse_list_id(r#""Rust <Users>" <rust-users.example.org>"#)
+        );
+        assert_eq!(
+            Some((String::from("rust-users.example.org"), None)),
+            parse_list_id(" <rust-users.example.org>")
+        );
+        assert_eq!(
+            Some((String::from("rust-users"), None)),
+            parse_list_id("rust-users")
+        );
+        assert_eq!(None, parse_list_id("Rust Users <>"));
+    }
+
+    #[test]
+    fn it_should_group_msgs_by_list() {
+        let lists = MailingLists::from_iter(vec![
+            ("<twir.example.org>", true),
+            ("Rust Users <rust-users.example.org>", false),
+            ("This Week in Rust <twir.example.org>", false),
+            ("", false),
+            ("<rust-users.example.org>", true),
+        ]);
+        assert_eq!(
+            vec![
+                MailingList {
+                    id: String::from("rust-users.example.org"),
+                    name: Some(String::from("Rust Users")),
+                    unseen: 1,
+                    total: 2,
+                },
+                MailingList {
+                    id: String::from("twir.example.org"),
+                    name: Some(String::from("This Week in Rust")),
+                    unseen: 1,
+                    total: 2,
+                },
+            ],
+            lists.0
+        );
+    }
+}
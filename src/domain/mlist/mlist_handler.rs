@@ -0,0 +1,40 @@
+//! Module related to mailing list handling.
+//!
+//! This module gathers all mailing list commands.
+
+use anyhow::Result;
+use log::trace;
+
+use crate::{
+    config::Account,
+    domain::{imap::ImapServiceInterface, msg::msg_handler},
+    output::{PrintTableOpts, PrinterService},
+};
+
+/// List the mailing lists of the current mailbox along with their unseen and total messages.
+pub fn list<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    max_width: Option<usize>,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+) -> Result<()> {
+    let lists = imap.fetch_mailing_lists()?;
+    trace!("mailing lists: {:?}", lists);
+    printer.print_table(lists, PrintTableOpts { max_width })
+}
+
+/// List the messages of the given mailing list, searched by the server.
+pub fn read<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    list_id: &str,
+    max_width: Option<usize>,
+    page_size: Option<usize>,
+    page: usize,
+    account: &Account,
+    printer: &mut Printer,
+    imap: &'a mut ImapService,
+) -> Result<()> {
+    // Angle brackets match the whole ID, so `rust` does not match `rust-users`.
+    let list_id = list_id.trim().trim_start_matches('<').trim_end_matches('>');
+    let query = format!(r#"HEADER List-Id "<{}>""#, list_id);
+    trace!(r#"query: "{}""#, query);
+    msg_handler::search(query, max_width, page_size, page, account, printer, imap)
+}
@@ -0,0 +1,12 @@
+//! Module related to mailing lists.
+//!
+//! This module contains everything related to the overview of the mailing lists of a mailbox,
+//! recognized by their `List-Id` header ([RFC2919]).
+//!
+//! [RFC2919]: https://datatracker.ietf.org/doc/html/rfc2919
+
+pub mod mlist_arg;
+pub mod mlist_handler;
+
+pub mod mlist_entity;
+pub use mlist_entity::*;
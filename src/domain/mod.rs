@@ -1,13 +1,28 @@
 //! Domain-specific modules.
 
+pub mod filter;
+pub use filter::*;
+
+pub mod hook;
+pub use hook::*;
+
 pub mod imap;
 pub use self::imap::*;
 
 pub mod mbox;
 pub use mbox::*;
 
+pub mod mlist;
+pub use mlist::*;
+
 pub mod msg;
 pub use msg::*;
 
+pub mod ping;
+pub use ping::*;
+
+pub mod sieve;
+pub use sieve::*;
+
 pub mod smtp;
 pub use smtp::*;
@@ -0,0 +1,233 @@
+//! Date format entity module.
+//!
+//! This module contains the format of the dates shown to the user, following their locale: the
+//! names of months and days, the default date and time formats and the first day of the week.
+
+use chrono::{Datelike, Duration, Locale, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use log::warn;
+use std::{convert::TryFrom, env};
+
+/// Represents the format of dates without locale.
+const DEFAULT_DATE_FMT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Represents the format of dates with a locale: its own date and time formats.
+const LOCALE_DATE_FMT: &str = "%x %X";
+
+/// Represents the format of the dates of today, when shortened.
+const TODAY_DATE_FMT: &str = "%X";
+
+/// Represents the format of the dates of the current week, when shortened.
+const WEEK_DATE_FMT: &str = "%a %X";
+
+/// Represents the regions whose weeks start on Sunday ([CLDR]). Others start on Monday, except
+/// the ones starting on Saturday.
+///
+/// [CLDR]: https://github.com/unicode-org/cldr/blob/main/common/supplemental/supplementalData.xml
+const SUNDAY_REGIONS: &[&str] = &[
+    "AG", "AS", "BR", "BS", "BT", "BW", "BZ", "CA", "CN", "CO", "DM", "DO", "ET", "GT", "GU", "HK",
+    "HN", "ID", "IL", "IN", "JM", "JP", "KE", "KH", "KR", "LA", "MH", "MM", "MO", "MT", "MX", "MZ",
+    "NI", "NP", "PA", "PE", "PH", "PK", "PR", "PT", "PY", "SA", "SG", "SV", "TH", "TT", "TW", "UM",
+    "US", "VE", "VI", "WS", "YE", "ZA", "ZW",
+];
+
+/// Represents the regions whose weeks start on Saturday ([CLDR]).
+///
+/// [CLDR]: https://github.com/unicode-org/cldr/blob/main/common/supplemental/supplementalData.xml
+const SATURDAY_REGIONS: &[&str] = &[
+    "AE", "AF", "BH", "DJ", "DZ", "EG", "IQ", "IR", "JO", "KW", "LY", "OM", "QA", "SD", "SY",
+];
+
+/// Finds the locale of dates from the environment.
+fn locale_from_env() -> Option<String> {
+    ["LC_ALL", "LC_TIME", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|locale| !locale.is_empty())
+}
+
+/// Strips the encoding and the modifier of the given locale, like `fr_FR` for `fr_FR.UTF-8`.
+fn strip_locale(locale: &str) -> &str {
+    locale
+        .split(|c| c == '.' || c == '@')
+        .next()
+        .unwrap_or_default()
+}
+
+/// Finds the first day of the week of the given locale, from its region. Defaults to Monday
+/// ([ISO 8601]).
+///
+/// [ISO 8601]: https://en.wikipedia.org/wiki/ISO_week_date
+fn locale_week_start(locale: &str) -> Weekday {
+    match locale.split('_').nth(1) {
+        Some(region) if SUNDAY_REGIONS.contains(&region) => Weekday::Sun,
+        Some(region) if SATURDAY_REGIONS.contains(&region) => Weekday::Sat,
+        _ => Weekday::Mon,
+    }
+}
+
+/// Parses the dates of envelopes, like `2021-10-16 07:30:00`.
+pub fn parse_envelope_date(date: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S%.f").ok()
+}
+
+/// Represents the format of the dates shown to the user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DateFmt {
+    /// The locale of the names and the formats, `None` for the POSIX one.
+    locale: Option<Locale>,
+    /// The format of dates ([strftime]).
+    ///
+    /// [strftime]: https://docs.rs/chrono/0.4.19/chrono/format/strftime/index.html
+    fmt: String,
+    /// Whether the dates of the current week are shortened: the time for today, the weekday and
+    /// the time for the other days.
+    relative: bool,
+    /// The first day of the week.
+    week_start: Weekday,
+}
+
+impl Default for DateFmt {
+    fn default() -> Self {
+        Self {
+            locale: None,
+            fmt: DEFAULT_DATE_FMT.to_owned(),
+            relative: false,
+            week_start: Weekday::Mon,
+        }
+    }
+}
+
+impl DateFmt {
+    /// Builds the format of dates, the locale defaulting to the one of the environment and the
+    /// first day of the week to the one of the locale.
+    pub fn new(
+        locale: Option<&str>,
+        fmt: Option<&str>,
+        relative: bool,
+        week_start: Option<Weekday>,
+    ) -> Self {
+        let env_locale = locale_from_env();
+        let name = locale
+            .or_else(|| env_locale.as_deref())
+            .map(strip_locale)
+            .filter(|name| !matches!(*name, "" | "C" | "POSIX"));
+        let locale = name.and_then(|name| {
+            Locale::try_from(name)
+                .map_err(|_| warn!(r#"unknown date locale "{}", skipping it"#, name))
+                .ok()
+        });
+        let default_fmt = match locale {
+            Some(_) => LOCALE_DATE_FMT,
+            None => DEFAULT_DATE_FMT,
+        };
+        Self {
+            locale,
+            fmt: fmt.unwrap_or(default_fmt).to_owned(),
+            relative,
+            week_start: week_start
+                .or_else(|| name.map(locale_week_start))
+                .unwrap_or(Weekday::Mon),
+        }
+    }
+
+    /// Formats the given date with the given format, in the locale.
+    pub fn format_with(&self, date: &NaiveDateTime, fmt: &str) -> String {
+        let date = Utc.from_utc_datetime(date);
+        match self.locale {
+            Some(locale) => date.format_localized(fmt, locale).to_string(),
+            None => date.format(fmt).to_string(),
+        }
+    }
+
+    /// Formats the given date in full.
+    pub fn format_full(&self, date: &NaiveDateTime) -> String {
+        self.format_with(date, &self.fmt)
+    }
+
+    /// Formats the given date, shortened relatively to the given time when enabled.
+    pub fn format(&self, date: &NaiveDateTime, now: &NaiveDateTime) -> String {
+        let (day, today) = (date.date(), now.date());
+        let fmt = if !self.relative || day > today {
+            &self.fmt
+        } else if day == today {
+            TODAY_DATE_FMT
+        } else if day >= self.week_first_day(today) {
+            WEEK_DATE_FMT
+        } else {
+            &self.fmt
+        };
+        self.format_with(date, fmt)
+    }
+
+    /// Returns the first day of the week of the given day.
+    fn week_first_day(&self, day: NaiveDate) -> NaiveDate {
+        let days =
+            (7 + day.weekday().num_days_from_monday() - self.week_start.num_days_from_monday()) % 7;
+        day - Duration::days(days as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_find_the_first_day_of_the_week() {
+        assert_eq!(Weekday::Sun, locale_week_start("en_US"));
+        assert_eq!(Weekday::Mon, locale_week_start("fr_FR"));
+        assert_eq!(Weekday::Sat, locale_week_start("ar_EG"));
+        assert_eq!(Weekday::Mon, locale_week_start("fr"));
+        assert_eq!("de_DE", strip_locale("de_DE.UTF-8@euro"));
+
+        // Saturday, October 16th
+        let day = NaiveDate::from_ymd(2021, 10, 16);
+        let fmt = DateFmt::new(Some("C"), None, true, Some(Weekday::Sun));
+        assert_eq!(NaiveDate::from_ymd(2021, 10, 10), fmt.week_first_day(day));
+        let fmt = DateFmt::new(Some("C"), None, true, Some(Weekday::Mon));
+        assert_eq!(NaiveDate::from_ymd(2021, 10, 11), fmt.week_first_day(day));
+        let fmt = DateFmt::new(Some("C"), None, true, Some(Weekday::Sat));
+        assert_eq!(day, fmt.week_first_day(day));
+    }
+
+    #[test]
+    fn it_should_shorten_recent_dates() {
+        let date = |date: &str| parse_envelope_date(date).unwrap();
+        let now = date("2021-10-16 12:00:00");
+        let fmt = DateFmt::new(Some("C"), None, true, Some(Weekday::Mon));
+
+        assert_eq!("07:30:00", fmt.format(&date("2021-10-16 07:30:00"), &now));
+        assert_eq!(
+            "Mon 07:30:00",
+            fmt.format(&date("2021-10-11 07:30:00"), &now)
+        );
+        assert_eq!(
+            "2021-10-10 07:30:00",
+            fmt.format(&date("2021-10-10 07:30:00"), &now)
+        );
+
+        let fmt = DateFmt::new(Some("C"), None, true, Some(Weekday::Sun));
+        assert_eq!(
+            "Sun 07:30:00",
+            fmt.format(&date("2021-10-10 07:30:00"), &now)
+        );
+
+        let fmt = DateFmt::new(Some("C"), None, false, None);
+        assert_eq!(
+            "2021-10-16 07:30:00",
+            fmt.format(&date("2021-10-16 07:30:00"), &now)
+        );
+    }
+
+    #[test]
+    fn it_should_format_dates_in_the_locale() {
+        let date = parse_envelope_date("2021-10-16 07:30:00").unwrap();
+        let fmt = DateFmt::new(Some("fr_FR.UTF-8"), Some("%A %d %B %Y"), false, None);
+
+        assert_eq!("samedi 16 octobre 2021", fmt.format_full(&date));
+        assert_eq!(Weekday::Mon, fmt.week_start);
+        assert_eq!(
+            Weekday::Sun,
+            DateFmt::new(Some("en_US"), None, false, None).week_start
+        );
+    }
+}
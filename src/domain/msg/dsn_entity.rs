@@ -0,0 +1,237 @@
+//! DSN entity module.
+//!
+//! This module contains the definitions of Delivery Status Notifications (bounces), as described
+//! in the [RFC3464], and of the delivery status of sent messages.
+//!
+//! [RFC3464]: https://datatracker.ietf.org/doc/html/rfc3464
+
+use anyhow::{Context, Result};
+use mailparse::MailHeaderMap;
+use serde::Serialize;
+use std::{
+    fmt::{self, Display},
+    ops::Deref,
+};
+
+use crate::{
+    output::{PrintTable, PrintTableOpts, WriteColor},
+    ui::{Cell, Row, Table},
+};
+
+/// Represents the IMAP query matching the delivery status notifications.
+pub const DSN_QUERY: &str = r#"HEADER Content-Type "delivery-status""#;
+
+/// Represents the delivery status of a message. When several notifications concern the same
+/// message, the greatest status wins: a bounce beats a delivery, which beats a delay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DsnStatus {
+    Unknown,
+    Delayed,
+    Delivered,
+    Bounced,
+}
+
+impl Default for DsnStatus {
+    fn default() -> Self {
+        Self::Unknown
+    }
+}
+
+impl Display for DsnStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match self {
+            Self::Unknown => "unknown",
+            Self::Delayed => "delayed",
+            Self::Delivered => "delivered",
+            Self::Bounced => "bounced",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+impl DsnStatus {
+    /// Parses the `Action` fields of a `message/delivery-status` part.
+    fn from_delivery_status(body: &str) -> Self {
+        body.lines()
+            .filter_map(|line| line.split_once(':'))
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case("Action"))
+            .map(|(_, action)| match action.trim().to_lowercase().as_str() {
+                "failed" => Self::Bounced,
+                "delayed" => Self::Delayed,
+                "delivered" | "relayed" | "expanded" => Self::Delivered,
+                _ => Self::Unknown,
+            })
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+/// Represents a delivery status notification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dsn {
+    /// The Message-ID of the message the notification is about.
+    pub message_id: String,
+    pub status: DsnStatus,
+}
+
+impl Dsn {
+    /// Parses the given raw message. Returns `None` if it is not a delivery status notification
+    /// or if the original message cannot be identified.
+    pub fn from_raw(raw: &[u8]) -> Option<Self> {
+        let parsed = mailparse::parse_mail(raw).ok()?;
+        if !parsed
+            .ctype
+            .mimetype
+            .eq_ignore_ascii_case("multipart/report")
+        {
+            return None;
+        }
+
+        let mut status = DsnStatus::Unknown;
+        let mut message_id = None;
+        for part in &parsed.subparts {
+            match part.ctype.mimetype.to_lowercase().as_str() {
+                "message/delivery-status" => {
+                    status = DsnStatus::from_delivery_status(&part.get_body().ok()?);
+                }
+                "message/rfc822" | "text/rfc822-headers" => {
+                    let raw_headers = part.get_body_raw().ok()?;
+                    let (headers, _) = mailparse::parse_headers(&raw_headers).ok()?;
+                    message_id = headers.get_first_value("Message-ID");
+                }
+                _ => (),
+            }
+        }
+
+        Some(Self {
+            message_id: message_id?.trim().to_owned(),
+            status,
+        })
+    }
+}
+
+/// Represents the delivery status of a sent message.
+#[derive(Debug, Default, Serialize)]
+pub struct SentStatus {
+    pub message_id: String,
+    pub date: String,
+    pub to: String,
+    pub subject: String,
+    pub status: DsnStatus,
+}
+
+impl SentStatus {
+    /// Builds the status from the raw headers of a sent message, with an unknown status.
+    pub fn from_raw_headers(raw_headers: &[u8]) -> Result<Self> {
+        let (headers, _) = mailparse::parse_headers(raw_headers)
+            .context("cannot parse headers of sent message")?;
+        Ok(Self {
+            message_id: headers
+                .get_first_value("Message-ID")
+                .unwrap_or_default()
+                .trim()
+                .to_owned(),
+            date: headers.get_first_value("Date").unwrap_or_default(),
+            to: headers.get_first_value("To").unwrap_or_default(),
+            subject: headers.get_first_value("Subject").unwrap_or_default(),
+            status: DsnStatus::Unknown,
+        })
+    }
+}
+
+/// Makes the status printable in a table.
+impl Table for SentStatus {
+    fn head() -> Row {
+        Row::new()
+            .cell(Cell::new("DATE").bold().underline().white())
+            .cell(Cell::new("TO").bold().underline().white())
+            .cell(Cell::new("SUBJECT").shrinkable().bold().underline().white())
+            .cell(Cell::new("STATUS").bold().underline().white())
+    }
+
+    fn row(&self) -> Row {
+        let status = Cell::new(self.status.to_string());
+        let status = match self.status {
+            DsnStatus::Bounced => status.bold().red(),
+            DsnStatus::Delayed => status.yellow(),
+            DsnStatus::Delivered => status.green(),
+            DsnStatus::Unknown => status.white(),
+        };
+        Row::new()
+            .cell(Cell::new(&self.date).yellow())
+            .cell(Cell::new(&self.to).blue())
+            .cell(Cell::new(&self.subject).shrinkable().green())
+            .cell(status)
+    }
+}
+
+/// Represents the delivery statuses of the sent messages.
+#[derive(Debug, Default, Serialize)]
+pub struct SentStatuses(pub Vec<SentStatus>);
+
+impl Deref for SentStatuses {
+    type Target = Vec<SentStatus>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Makes the statuses printable.
+impl PrintTable for SentStatuses {
+    fn print_table(&self, writter: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        writeln!(writter)?;
+        Table::print(writter, &self, opts)?;
+        writeln!(writter)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_parse_dsn() {
+        let raw = concat![
+            "From: MAILER-DAEMON@example.com\r\n",
+            "Content-Type: multipart/report; report-type=delivery-status; boundary=b\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "Delivery failed.\r\n",
+            "--b\r\n",
+            "Content-Type: message/delivery-status\r\n",
+            "\r\n",
+            "Reporting-MTA: dns; mx.example.com\r\n",
+            "\r\n",
+            "Final-Recipient: rfc822; bob@example.com\r\n",
+            "Action: failed\r\n",
+            "Status: 5.1.1\r\n",
+            "\r\n",
+            "Final-Recipient: rfc822; carol@example.com\r\n",
+            "Action: delivered\r\n",
+            "Status: 2.0.0\r\n",
+            "--b\r\n",
+            "Content-Type: text/rfc822-headers\r\n",
+            "\r\n",
+            "Message-ID: <abc@example.com>\r\n",
+            "Subject: Hello\r\n",
+            "--b--\r\n",
+        ];
+
+        assert_eq!(
+            Some(Dsn {
+                message_id: String::from("<abc@example.com>"),
+                status: DsnStatus::Bounced,
+            }),
+            Dsn::from_raw(raw.as_bytes())
+        );
+        assert_eq!(
+            None,
+            Dsn::from_raw(b"Content-Type: text/plain\r\n\r\nHello\r\n")
+        );
+    }
+}
@@ -1,19 +1,115 @@
-use anyhow::{anyhow, Context, Error, Result};
-use serde::Serialize;
-use std::{borrow::Cow, convert::TryFrom};
+use anyhow::{Error, Result};
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use imap_proto::Address;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    convert::TryFrom,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use termcolor::Color;
 
 use crate::{
-    domain::msg::{Flag, Flags},
+    domain::msg::{Flag, Flags, GmailIds},
+    output::format_size,
     ui::{Cell, Row, Table},
 };
 
 pub type RawEnvelope = imap::types::Fetch;
 
+/// Whether the listings show UIDs instead of sequence numbers, following the addressing of the
+/// command.
+static SHOW_UIDS: AtomicBool = AtomicBool::new(false);
+
+/// Labels the identifiers of the listings `UID` if asked to, `SEQ` otherwise.
+pub fn show_uids(uid: bool) {
+    SHOW_UIDS.store(uid, Ordering::Relaxed);
+}
+
+/// Represents the date shown in listings, and used to sort them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EnvelopeDate {
+    /// The internal date, set by the server when the message was received or appended.
+    Internal,
+    /// The `Date` header, set by the sender. The internal date is used when the header is missing
+    /// or invalid.
+    Header,
+}
+
+impl Default for EnvelopeDate {
+    fn default() -> Self {
+        Self::Internal
+    }
+}
+
+/// Parses the `Date` header of a message. Dates chrono cannot parse (like the ones followed by a
+/// comment) are given to mailparse, which is more lenient but loses the offset.
+pub fn parse_date_header(date: &[u8]) -> Option<DateTime<FixedOffset>> {
+    let date = String::from_utf8_lossy(date);
+    let date = date.split('(').next().unwrap_or_default().trim();
+    DateTime::parse_from_rfc2822(date).ok().or_else(|| {
+        mailparse::dateparse(date)
+            .ok()
+            .map(|timestamp| Utc.timestamp(timestamp, 0).into())
+    })
+}
+
+/// Decodes a header value, falling back to a lossy UTF-8 conversion when the value cannot be
+/// decoded (like raw 8-bit headers).
+pub fn decode_header_value(value: &[u8]) -> String {
+    rfc2047_decoder::decode(value).unwrap_or_else(|_| String::from_utf8_lossy(value).into_owned())
+}
+
+/// Formats the first address of the given list: its name, or its email when it has no name. The
+/// group syntax markers ([RFC3501]) are skipped, the group name being used only when the group
+/// is empty (like `undisclosed-recipients:;`).
+///
+/// [RFC3501]: https://datatracker.ietf.org/doc/html/rfc3501#section-7.4.2
+fn format_first_addr(addrs: &[Address]) -> Option<String> {
+    let addr = addrs
+        .iter()
+        .find(|addr| addr.host.is_some())
+        .or_else(|| addrs.first())?;
+    let name = addr
+        .name
+        .as_ref()
+        .map(|name| decode_header_value(name))
+        .filter(|name| !name.trim().is_empty());
+    let mbox = addr.mailbox.as_ref().map(|mbox| decode_header_value(mbox));
+    let host = addr.host.as_ref().map(|host| decode_header_value(host));
+    match (name, mbox, host) {
+        (Some(name), _, _) => Some(name),
+        (None, Some(mbox), Some(host)) => Some(format!("{}@{}", mbox, host)),
+        (None, mbox, None) => mbox,
+        (None, None, Some(_)) => None,
+    }
+}
+
+/// Represents how envelopes stand out in listings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnvelopeHighlight {
+    /// Renders unseen envelopes in bold.
+    pub unseen_bold: bool,
+    /// Renders the subject of flagged envelopes with this color.
+    pub flagged_color: Option<Color>,
+}
+
+impl Default for EnvelopeHighlight {
+    fn default() -> Self {
+        Self {
+            unseen_bold: true,
+            flagged_color: Some(Color::Magenta),
+        }
+    }
+}
+
 /// Representation of an envelope. An envelope gathers basic information related to a message. It
 /// is mostly used for listings.
 #[derive(Debug, Default, Serialize)]
 pub struct Envelope<'a> {
-    /// The sequence number of the message.
+    /// The sequence number of the message ([RFC3501]), or its UID in UID mode.
     ///
     /// [RFC3501]: https://datatracker.ietf.org/doc/html/rfc3501#section-2.3.1.2
     pub id: u32,
@@ -27,19 +123,55 @@ pub struct Envelope<'a> {
     /// The sender of the message.
     pub sender: String,
 
-    /// The internal date of the message.
+    /// The internal date of the message ([RFC3501]), or its `Date` header when the listings are
+    /// set to.
     ///
     /// [RFC3501]: https://datatracker.ietf.org/doc/html/rfc3501#section-2.3.3
     pub date: Option<String>,
+
+    /// The size of the message in bytes, as given by the server ([RFC3501]).
+    ///
+    /// [RFC3501]: https://datatracker.ietf.org/doc/html/rfc3501#section-2.3.4
+    pub size: usize,
+
+    /// The extra headers fetched along with the envelope (`list-headers` config option), by
+    /// name.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+
+    /// The date of the message as a Unix timestamp, matching `date` and used to sort listings.
+    #[serde(skip)]
+    pub timestamp: Option<i64>,
+
+    /// The `Date` header of the message, if present and valid.
+    #[serde(skip)]
+    pub header_date: Option<DateTime<FixedOffset>>,
+
+    /// The date as shown in listings, formatted for the user.
+    #[serde(skip)]
+    pub formatted_date: Option<String>,
+
+    /// The `Message-ID` header of the message, used to recognize the same message across
+    /// mailboxes.
+    #[serde(skip)]
+    pub message_id: Option<String>,
+
+    /// The Gmail IDs of the message, fetched for JSON output when the server supports them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gmail: Option<GmailIds>,
+
+    /// How the envelope stands out in listings.
+    #[serde(skip)]
+    pub highlight: EnvelopeHighlight,
 }
 
 impl<'a> TryFrom<&'a RawEnvelope> for Envelope<'a> {
     type Error = Error;
 
     fn try_from(fetch: &'a RawEnvelope) -> Result<Envelope> {
-        let envelope = fetch
-            .envelope()
-            .ok_or(anyhow!("cannot get envelope of message {}", fetch.message))?;
+        // Messages without envelope are still listed, so one broken message does not break
+        // the whole listing
+        let envelope = fetch.envelope();
 
         // Get the sequence number
         let id = fetch.message;
@@ -49,63 +181,52 @@ impl<'a> TryFrom<&'a RawEnvelope> for Envelope<'a> {
 
         // Get the subject
         let subject: Cow<str> = envelope
-            .subject
-            .as_ref()
-            .map(|subj| {
-                rfc2047_decoder::decode(subj).context(format!(
-                    "cannot decode subject of message {}",
-                    fetch.message
-                ))
-            })
-            .unwrap_or(Ok(String::default()))?
+            .and_then(|envelope| envelope.subject.as_ref())
+            .map(|subj| decode_header_value(subj))
+            .unwrap_or_default()
             .into();
 
         // Get the sender
         let sender = envelope
-            .sender
-            .as_ref()
-            .and_then(|addrs| addrs.get(0))
-            .or_else(|| envelope.from.as_ref().and_then(|addrs| addrs.get(0)))
-            .ok_or(anyhow!("cannot get sender of message {}", fetch.message))?;
-        let sender = if let Some(ref name) = sender.name {
-            rfc2047_decoder::decode(&name.to_vec()).context(format!(
-                "cannot decode sender's name of message {}",
-                fetch.message,
-            ))?
-        } else {
-            let mbox = sender
-                .mailbox
-                .as_ref()
-                .ok_or(anyhow!(
-                    "cannot get sender's mailbox of message {}",
-                    fetch.message
-                ))
-                .and_then(|mbox| {
-                    rfc2047_decoder::decode(&mbox.to_vec()).context(format!(
-                        "cannot decode sender's mailbox of message {}",
-                        fetch.message,
-                    ))
-                })?;
-            let host = sender
-                .host
-                .as_ref()
-                .ok_or(anyhow!(
-                    "cannot get sender's host of message {}",
-                    fetch.message
-                ))
-                .and_then(|host| {
-                    rfc2047_decoder::decode(&host.to_vec()).context(format!(
-                        "cannot decode sender's host of message {}",
-                        fetch.message,
-                    ))
-                })?;
-            format!("{}@{}", mbox, host)
-        };
+            .and_then(|envelope| {
+                envelope
+                    .sender
+                    .as_deref()
+                    .and_then(format_first_addr)
+                    .or_else(|| envelope.from.as_deref().and_then(format_first_addr))
+            })
+            .unwrap_or_default();
 
         // Get the internal date
-        let date = fetch
-            .internal_date()
-            .map(|date| date.naive_local().to_string());
+        let internal_date = fetch.internal_date();
+        let date = internal_date.map(|date| date.naive_local().to_string());
+        let timestamp = internal_date.map(|date| date.timestamp());
+
+        // Get the size
+        let size = fetch.size.unwrap_or_default() as usize;
+
+        // Get the extra headers
+        let headers = fetch
+            .header()
+            .and_then(|header| mailparse::parse_headers(header).ok())
+            .map(|(headers, _)| {
+                headers
+                    .iter()
+                    .map(|header| (header.get_key(), header.get_value()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // Get the date header
+        let header_date = envelope
+            .and_then(|envelope| envelope.date.as_ref())
+            .and_then(|date| parse_date_header(date));
+
+        // Get the message id
+        let message_id = envelope
+            .and_then(|envelope| envelope.message_id.as_ref())
+            .map(|id| String::from_utf8_lossy(id).trim().to_owned())
+            .filter(|id| !id.is_empty());
 
         Ok(Self {
             id,
@@ -113,36 +234,205 @@ impl<'a> TryFrom<&'a RawEnvelope> for Envelope<'a> {
             subject,
             sender,
             date,
+            size,
+            headers,
+            timestamp,
+            header_date,
+            formatted_date: None,
+            message_id,
+            gmail: None,
+            highlight: EnvelopeHighlight::default(),
         })
     }
 }
 
+impl<'a> Envelope<'a> {
+    /// Converts the envelope into one owning its subject, so it outlives the raw envelope.
+    pub fn into_owned(self) -> Envelope<'static> {
+        Envelope {
+            id: self.id,
+            flags: self.flags,
+            subject: Cow::Owned(self.subject.into_owned()),
+            sender: self.sender,
+            date: self.date,
+            size: self.size,
+            headers: self.headers,
+            timestamp: self.timestamp,
+            header_date: self.header_date,
+            formatted_date: self.formatted_date,
+            message_id: self.message_id,
+            gmail: self.gmail,
+            highlight: self.highlight,
+        }
+    }
+}
+
 impl<'a> Table for Envelope<'a> {
     fn head() -> Row {
+        let id_label = if SHOW_UIDS.load(Ordering::Relaxed) {
+            "UID"
+        } else {
+            "SEQ"
+        };
         Row::new()
-            .cell(Cell::new("ID").bold().underline().white())
+            .cell(Cell::new(id_label).bold().underline().white())
             .cell(Cell::new("FLAGS").bold().underline().white())
             .cell(Cell::new("SUBJECT").shrinkable().bold().underline().white())
             .cell(Cell::new("SENDER").bold().underline().white())
+            .cell(Cell::new("SIZE").bold().underline().white())
             .cell(Cell::new("DATE").bold().underline().white())
     }
 
     fn row(&self) -> Row {
         let id = self.id.to_string();
-        let flags = self.flags.to_symbols_string();
-        let unseen = !self.flags.contains(&Flag::Seen);
+        let keywords = self.flags.to_keywords_string();
+        let flags = match keywords.as_str() {
+            "" => self.flags.to_symbols_string(),
+            keywords => format!("{} {}", self.flags.to_symbols_string(), keywords),
+        };
+        let unseen = self.highlight.unseen_bold && !self.flags.contains(&Flag::Seen);
+        let flagged = self.flags.contains(&Flag::Flagged);
         let subject = &self.subject;
         let sender = &self.sender;
         let date = self
-            .date
+            .formatted_date
             .as_ref()
+            .or_else(|| self.date.as_ref())
             .map(|date| date.as_str())
             .unwrap_or_default();
         Row::new()
             .cell(Cell::new(id).bold_if(unseen).red())
             .cell(Cell::new(flags).bold_if(unseen).white())
-            .cell(Cell::new(subject).shrinkable().bold_if(unseen).green())
+            .cell(
+                Cell::new(subject)
+                    .shrinkable()
+                    .bold_if(unseen)
+                    .green()
+                    .color_if(self.highlight.flagged_color, flagged),
+            )
             .cell(Cell::new(sender).bold_if(unseen).blue())
+            .cell(Cell::new(format_size(self.size)).bold_if(unseen).white())
             .cell(Cell::new(date).bold_if(unseen).yellow())
     }
 }
+
+/// Representation of an envelope belonging to a given account. It is used by listings merging the
+/// envelopes of several accounts.
+#[derive(Debug, Serialize)]
+pub struct AccountEnvelope {
+    /// The name of the account the envelope belongs to.
+    pub account: String,
+
+    /// The envelope itself, owned so it can be sent across threads.
+    #[serde(flatten)]
+    pub envelope: Envelope<'static>,
+}
+
+impl<'a> From<(&str, Envelope<'a>)> for AccountEnvelope {
+    fn from((account, envelope): (&str, Envelope<'a>)) -> Self {
+        Self {
+            account: account.to_owned(),
+            envelope: envelope.into_owned(),
+        }
+    }
+}
+
+impl Table for AccountEnvelope {
+    fn head() -> Row {
+        let mut head = Row::new().cell(Cell::new("ACCOUNT").bold().underline().white());
+        head.0.extend(Envelope::head().0);
+        head
+    }
+
+    fn row(&self) -> Row {
+        let unseen =
+            self.envelope.highlight.unseen_bold && !self.envelope.flags.contains(&Flag::Seen);
+        let mut row = Row::new().cell(Cell::new(&self.account).bold_if(unseen).white());
+        row.0.extend(self.envelope.row().0);
+        row
+    }
+}
+
+/// Representation of an envelope found in one or several mailboxes. It is used by searches
+/// across mailboxes, where the same message can live in several of them (like Gmail labels).
+#[derive(Debug, Serialize)]
+pub struct MboxEnvelope {
+    /// The names of the mailboxes the message lives in.
+    pub mboxes: Vec<String>,
+
+    /// The envelope found in the first mailbox, its sequence number being relative to it.
+    #[serde(flatten)]
+    pub envelope: Envelope<'static>,
+}
+
+impl Table for MboxEnvelope {
+    fn head() -> Row {
+        let mut head = Row::new().cell(Cell::new("MAILBOXES").bold().underline().white());
+        head.0.extend(Envelope::head().0);
+        head
+    }
+
+    fn row(&self) -> Row {
+        let unseen =
+            self.envelope.highlight.unseen_bold && !self.envelope.flags.contains(&Flag::Seen);
+        let mut row = Row::new().cell(Cell::new(self.mboxes.join(", ")).bold_if(unseen).white());
+        row.0.extend(self.envelope.row().0);
+        row
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_parse_date_header() {
+        let date = |date: &str| parse_date_header(date.as_bytes()).map(|date| date.to_rfc3339());
+        assert_eq!(
+            Some(String::from("2021-10-16T10:00:00+02:00")),
+            date("Sat, 16 Oct 2021 10:00:00 +0200")
+        );
+        assert_eq!(
+            Some(String::from("2021-10-16T10:00:00+00:00")),
+            date("Sat, 16 Oct 2021 10:00:00 +0000 (UTC)")
+        );
+    }
+
+    #[test]
+    fn it_should_decode_invalid_header_values() {
+        assert_eq!("Hello", decode_header_value(b"=?utf-8?q?Hello?="));
+        assert_eq!("Caf\u{fffd}", decode_header_value(b"Caf\xe9"));
+    }
+
+    #[test]
+    fn it_should_format_first_addr() {
+        let addr =
+            |name: Option<&'static str>, mbox: Option<&'static str>, host: Option<&'static str>| {
+                Address {
+                    name: name.map(|name| Cow::Borrowed(name.as_bytes())),
+                    adl: None,
+                    mailbox: mbox.map(|mbox| Cow::Borrowed(mbox.as_bytes())),
+                    host: host.map(|host| Cow::Borrowed(host.as_bytes())),
+                }
+            };
+        let alice = addr(None, Some("alice"), Some("example.com"));
+        let bob = addr(Some("Bob"), Some("bob"), Some("example.com"));
+        let group_start = addr(None, Some("friends"), None);
+        let group_end = addr(None, None, None);
+
+        assert_eq!(
+            Some(String::from("alice@example.com")),
+            format_first_addr(&[alice])
+        );
+        assert_eq!(Some(String::from("Bob")), format_first_addr(&[bob.clone()]));
+        assert_eq!(
+            Some(String::from("Bob")),
+            format_first_addr(&[group_start.clone(), bob, group_end.clone()])
+        );
+        assert_eq!(
+            Some(String::from("friends")),
+            format_first_addr(&[group_start, group_end])
+        );
+        assert_eq!(None, format_first_addr(&[]));
+    }
+}
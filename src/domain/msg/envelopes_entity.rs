@@ -1,9 +1,16 @@
 use anyhow::{Error, Result};
+use chrono::Local;
 use serde::Serialize;
-use std::{convert::TryFrom, ops::Deref};
+use std::{collections::HashMap, convert::TryFrom, ops::Deref};
 
 use crate::{
-    domain::{msg::Envelope, RawEnvelope},
+    domain::{
+        msg::{
+            parse_envelope_date, AccountEnvelope, DateFmt, Envelope, EnvelopeDate,
+            EnvelopeHighlight, GmailIds, MboxEnvelope,
+        },
+        RawEnvelope,
+    },
     output::{PrintTable, PrintTableOpts, WriteColor},
     ui::Table,
 };
@@ -22,6 +29,64 @@ impl<'a> Deref for Envelopes<'a> {
     }
 }
 
+impl<'a> Envelopes<'a> {
+    /// Applies the given highlight to all the envelopes.
+    pub fn with_highlight(mut self, highlight: EnvelopeHighlight) -> Self {
+        for envelope in self.0.iter_mut() {
+            envelope.highlight = highlight;
+        }
+        self
+    }
+
+    /// Attaches the given Gmail IDs, by sequence number, to the envelopes.
+    pub fn with_gmail_ids(mut self, ids: &HashMap<u32, GmailIds>) -> Self {
+        for envelope in self.0.iter_mut() {
+            envelope.gmail = ids.get(&envelope.id).cloned();
+        }
+        self
+    }
+
+    /// Identifies the envelopes by the given UIDs, by sequence number, in UID mode. Envelopes
+    /// missing from the given UIDs keep their sequence number.
+    pub fn with_uids(mut self, uids: &HashMap<u32, u32>) -> Self {
+        for envelope in self.0.iter_mut() {
+            if let Some(uid) = uids.get(&envelope.id) {
+                envelope.id = *uid;
+            }
+        }
+        self
+    }
+
+    /// Shows the envelopes with the given date, then sorts them by date, newest first.
+    /// Envelopes without a valid `Date` header keep their internal date.
+    pub fn with_date(mut self, date: EnvelopeDate) -> Self {
+        if date == EnvelopeDate::Header {
+            for envelope in self.0.iter_mut() {
+                if let Some(date) = envelope.header_date {
+                    envelope.date = Some(date.naive_local().to_string());
+                    envelope.timestamp = Some(date.timestamp());
+                }
+            }
+            // Header dates come with various time zones, so they are compared as instants
+            self.0.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        }
+        self
+    }
+
+    /// Formats the dates of the envelopes for listings.
+    pub fn with_date_fmt(mut self, fmt: &DateFmt) -> Self {
+        let now = Local::now().naive_local();
+        for envelope in self.0.iter_mut() {
+            envelope.formatted_date = envelope
+                .date
+                .as_deref()
+                .and_then(parse_envelope_date)
+                .map(|date| fmt.format(&date, &now));
+        }
+        self
+    }
+}
+
 impl<'a> TryFrom<&'a RawEnvelopes> for Envelopes<'a> {
     type Error = Error;
 
@@ -44,3 +109,149 @@ impl<'a> PrintTable for Envelopes<'a> {
         Ok(())
     }
 }
+
+/// Representation of a list of envelopes coming from several accounts.
+#[derive(Debug, Default, Serialize)]
+pub struct AccountEnvelopes(pub Vec<AccountEnvelope>);
+
+impl Deref for AccountEnvelopes {
+    type Target = Vec<AccountEnvelope>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl PrintTable for AccountEnvelopes {
+    fn print_table(&self, writter: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        writeln!(writter)?;
+        Table::print(writter, &self, opts)?;
+        writeln!(writter)?;
+        Ok(())
+    }
+}
+
+/// Representation of a list of envelopes coming from several mailboxes. Envelopes are cached by
+/// Message-ID, so a message living in several mailboxes is listed once, along with all of them.
+#[derive(Debug, Default, Serialize)]
+#[serde(transparent)]
+pub struct MboxEnvelopes {
+    envelopes: Vec<MboxEnvelope>,
+    /// The position of the envelopes, by Message-ID.
+    #[serde(skip)]
+    index: HashMap<String, usize>,
+}
+
+impl Deref for MboxEnvelopes {
+    type Target = Vec<MboxEnvelope>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.envelopes
+    }
+}
+
+impl MboxEnvelopes {
+    /// Adds the given envelope found in the given mailbox. If an envelope with the same
+    /// Message-ID is already known, the mailbox is added to it instead. Envelopes without
+    /// Message-ID cannot be recognized, so they are always added.
+    pub fn insert(&mut self, mbox: &str, envelope: Envelope<'static>) {
+        if let Some(pos) = envelope
+            .message_id
+            .as_ref()
+            .and_then(|id| self.index.get(id))
+        {
+            let mboxes = &mut self.envelopes[*pos].mboxes;
+            if !mboxes.iter().any(|name| name == mbox) {
+                mboxes.push(mbox.to_owned());
+            }
+            return;
+        }
+        if let Some(id) = envelope.message_id.as_ref() {
+            self.index.insert(id.to_owned(), self.envelopes.len());
+        }
+        self.envelopes.push(MboxEnvelope {
+            mboxes: vec![mbox.to_owned()],
+            envelope,
+        });
+    }
+
+    /// Sorts the envelopes by date, newest first, then keeps the given page only.
+    pub fn paginate(mut self, page_size: usize, page: usize) -> Self {
+        self.envelopes
+            .sort_by(|a, b| b.envelope.date.cmp(&a.envelope.date));
+        self.index.clear();
+        if page_size > 0 {
+            self.envelopes = self
+                .envelopes
+                .into_iter()
+                .skip(page * page_size)
+                .take(page_size)
+                .collect();
+        }
+        self
+    }
+}
+
+impl PrintTable for MboxEnvelopes {
+    fn print_table(&self, writter: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        writeln!(writter)?;
+        Table::print(writter, &self.envelopes, opts)?;
+        writeln!(writter)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+
+    #[test]
+    fn it_should_sort_envelopes_by_header_date_instant() {
+        let envelope = |id: u32, date: &str| Envelope {
+            id,
+            header_date: DateTime::parse_from_rfc2822(date).ok(),
+            ..Envelope::default()
+        };
+
+        // The second one is the most recent, even if its local time is the earliest
+        let envelopes = Envelopes(vec![
+            envelope(1, "Sat, 16 Oct 2021 10:00:00 +0000"),
+            envelope(2, "Sat, 16 Oct 2021 09:00:00 -0500"),
+            envelope(3, "Sat, 16 Oct 2021 11:00:00 +0200"),
+        ])
+        .with_date(EnvelopeDate::Header);
+
+        assert_eq!(
+            vec![2, 1, 3],
+            envelopes.iter().map(|e| e.id).collect::<Vec<_>>()
+        );
+        assert_eq!(Some("2021-10-16 09:00:00"), envelopes[0].date.as_deref());
+    }
+
+    #[test]
+    fn it_should_dedupe_envelopes_by_message_id() {
+        let envelope = |id: u32, message_id: Option<&str>| Envelope {
+            id,
+            message_id: message_id.map(String::from),
+            ..Envelope::default()
+        };
+
+        let mut envelopes = MboxEnvelopes::default();
+        envelopes.insert("INBOX", envelope(1, Some("<a@example.com>")));
+        envelopes.insert("INBOX", envelope(2, None));
+        envelopes.insert("[Gmail]/All Mail", envelope(5, Some("<a@example.com>")));
+        envelopes.insert("[Gmail]/All Mail", envelope(6, None));
+        envelopes.insert("Work", envelope(3, Some("<a@example.com>")));
+
+        assert_eq!(3, envelopes.len());
+        assert_eq!(1, envelopes[0].envelope.id);
+        assert_eq!(
+            vec!["INBOX", "[Gmail]/All Mail", "Work"],
+            envelopes[0].mboxes
+        );
+        assert_eq!(vec!["INBOX"], envelopes[1].mboxes);
+        assert_eq!(vec!["[Gmail]/All Mail"], envelopes[2].mboxes);
+    }
+}
@@ -0,0 +1,152 @@
+//! Export entity module.
+//!
+//! This module contains the renderings of messages used by the `read --export` and `read --print`
+//! commands: a printable plain text and a standalone HTML document with basic styling.
+
+use anyhow::{anyhow, Error, Result};
+use std::convert::TryFrom;
+
+use crate::domain::msg::Msg;
+
+/// Represents the format messages are exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Html,
+    /// PDF documents are converted from the HTML rendering by the export PDF command.
+    Pdf,
+}
+
+impl ExportFormat {
+    /// Returns the extension of the exported files.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Pdf => "pdf",
+        }
+    }
+}
+
+impl TryFrom<&str> for ExportFormat {
+    type Error = Error;
+
+    fn try_from(format: &str) -> Result<Self, Self::Error> {
+        match format {
+            "html" => Ok(Self::Html),
+            "pdf" => Ok(Self::Pdf),
+            format => Err(anyhow!(r#"cannot parse export format "{}""#, format)),
+        }
+    }
+}
+
+/// Escapes the characters HTML gives a meaning to.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Msg {
+    /// Returns the headers shown in exports, in order. Missing headers are omitted.
+    fn export_headers(&self) -> Vec<(&'static str, String)> {
+        let addrs = |addrs: &Option<Vec<lettre::message::Mailbox>>| {
+            addrs.as_ref().map(|addrs| {
+                addrs
+                    .iter()
+                    .map(|addr| addr.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+        };
+        vec![
+            ("From", addrs(&self.from)),
+            ("To", addrs(&self.to)),
+            ("Cc", addrs(&self.cc)),
+            ("Date", self.date.map(|date| date.to_rfc2822())),
+            ("Subject", Some(self.subject.to_owned())),
+        ]
+        .into_iter()
+        .filter_map(|(key, val)| Some((key, val?)).filter(|(_, val)| !val.is_empty()))
+        .collect()
+    }
+
+    /// Renders the message as plain text: the headers, a blank line then the text bodies.
+    pub fn to_printable_text(&self) -> String {
+        let headers: Vec<String> = self
+            .export_headers()
+            .into_iter()
+            .map(|(key, val)| format!("{}: {}", key, val))
+            .collect();
+        format!(
+            "{}\n\n{}",
+            headers.join("\n"),
+            self.fold_text_parts("plain")
+        )
+    }
+
+    /// Renders the message as a standalone HTML document. The body is the plain text rendering,
+    /// so the HTML of the message (and its remote content) never reaches the export.
+    pub fn to_html_document(&self) -> String {
+        let headers: String = self
+            .export_headers()
+            .into_iter()
+            .map(|(key, val)| format!("<tr><th>{}</th><td>{}</td></tr>\n", key, escape_html(&val)))
+            .collect();
+        let body = escape_html(&self.fold_text_parts("plain"));
+        format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{}</title>
+<style>
+body {{ font-family: sans-serif; max-width: 50em; margin: 2em auto; }}
+table {{ border-bottom: 1px solid #ccc; padding-bottom: 1em; margin-bottom: 1em; }}
+th {{ text-align: right; padding-right: 1em; color: #555; vertical-align: top; }}
+pre {{ white-space: pre-wrap; font-family: monospace; }}
+</style>
+</head>
+<body>
+<table>
+{}</table>
+<pre>{}</pre>
+</body>
+</html>
+"#,
+            escape_html(&self.subject),
+            headers,
+            body
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::msg::{Part, Parts, TextPlainPart};
+
+    #[test]
+    fn it_should_render_exports() {
+        let msg = Msg {
+            subject: "Q&A <draft>".into(),
+            from: Some(vec!["Alice <alice@example.com>".parse().unwrap()]),
+            parts: Parts(vec![Part::TextPlain(TextPlainPart {
+                content: "See <https://example.com>".into(),
+                flowed: false,
+            })]),
+            ..Msg::default()
+        };
+
+        assert_eq!(
+            "From: Alice <alice@example.com>\nSubject: Q&A <draft>\n\nSee <https://example.com>",
+            msg.to_printable_text()
+        );
+        let html = msg.to_html_document();
+        assert!(html.contains("<title>Q&amp;A &lt;draft&gt;</title>"));
+        assert!(html.contains("<pre>See &lt;https://example.com&gt;</pre>"));
+        assert!(html.contains("<tr><th>From</th><td>Alice &lt;alice@example.com&gt;</td></tr>"));
+
+        assert_eq!(ExportFormat::Pdf, ExportFormat::try_from("pdf").unwrap());
+        assert!(ExportFormat::try_from("doc").is_err());
+    }
+}
@@ -7,48 +7,47 @@ use anyhow::Result;
 use clap::{self, App, AppSettings, Arg, ArgMatches, SubCommand};
 use log::{debug, trace};
 
-use crate::domain::msg::msg_arg;
+use crate::domain::msg::{msg_arg, Selection};
 
-type SeqRange<'a> = &'a str;
 type Flags<'a> = Vec<&'a str>;
 
 /// Represents the flag commands.
 pub enum Command<'a> {
     /// Represents the add flags command.
-    Add(SeqRange<'a>, Flags<'a>),
+    Add(Selection<'a>, Flags<'a>),
     /// Represents the set flags command.
-    Set(SeqRange<'a>, Flags<'a>),
+    Set(Selection<'a>, Flags<'a>),
     /// Represents the remove flags command.
-    Remove(SeqRange<'a>, Flags<'a>),
+    Remove(Selection<'a>, Flags<'a>),
 }
 
 /// Defines the flag command matcher.
 pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Command<'a>>> {
     if let Some(m) = m.subcommand_matches("add") {
         debug!("add subcommand matched");
-        let seq_range = m.value_of("seq-range").unwrap();
-        trace!(r#"seq range: "{}""#, seq_range);
+        let selection = msg_arg::selection(m);
+        trace!("selection: {:?}", selection);
         let flags: Vec<&str> = m.values_of("flags").unwrap_or_default().collect();
         trace!(r#"flags: "{:?}""#, flags);
-        return Ok(Some(Command::Add(seq_range, flags)));
+        return Ok(Some(Command::Add(selection, flags)));
     }
 
     if let Some(m) = m.subcommand_matches("set") {
         debug!("set subcommand matched");
-        let seq_range = m.value_of("seq-range").unwrap();
-        trace!(r#"seq range: "{}""#, seq_range);
+        let selection = msg_arg::selection(m);
+        trace!("selection: {:?}", selection);
         let flags: Vec<&str> = m.values_of("flags").unwrap_or_default().collect();
         trace!(r#"flags: "{:?}""#, flags);
-        return Ok(Some(Command::Set(seq_range, flags)));
+        return Ok(Some(Command::Set(selection, flags)));
     }
 
     if let Some(m) = m.subcommand_matches("remove") {
         trace!("remove subcommand matched");
-        let seq_range = m.value_of("seq-range").unwrap();
-        trace!(r#"seq range: "{}""#, seq_range);
+        let selection = msg_arg::selection(m);
+        trace!("selection: {:?}", selection);
         let flags: Vec<&str> = m.values_of("flags").unwrap_or_default().collect();
         trace!(r#"flags: "{:?}""#, flags);
-        return Ok(Some(Command::Remove(seq_range, flags)));
+        return Ok(Some(Command::Remove(selection, flags)));
     }
 
     Ok(None)
@@ -58,7 +57,7 @@ pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Command<'a>>> {
 fn flags_arg<'a>() -> Arg<'a, 'a> {
     Arg::with_name("flags")
         .help("IMAP flags")
-        .long_help("IMAP flags. Flags are case-insensitive, and they do not need to be prefixed with `\\`.")
+        .long_help("IMAP flags. System flags are case-insensitive, and they do not need to be prefixed with `\\`. Other flags are custom keywords, like `$label1` or `Important`.")
         .value_name("FLAGS…")
         .multiple(true)
         .required(true)
@@ -75,6 +74,7 @@ pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
                 .aliases(&["a"])
                 .about("Adds flags to a message")
                 .arg(msg_arg::seq_range_arg())
+                .arg(msg_arg::query_arg())
                 .arg(flags_arg()),
         )
         .subcommand(
@@ -82,6 +82,7 @@ pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
                 .aliases(&["s", "change", "c"])
                 .about("Replaces all message flags")
                 .arg(msg_arg::seq_range_arg())
+                .arg(msg_arg::query_arg())
                 .arg(flags_arg()),
         )
         .subcommand(
@@ -89,6 +90,7 @@ pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
                 .aliases(&["rem", "rm", "r", "delete", "del", "d"])
                 .about("Removes flags from a message")
                 .arg(msg_arg::seq_range_arg())
+                .arg(msg_arg::query_arg())
                 .arg(flags_arg()),
         )]
 }
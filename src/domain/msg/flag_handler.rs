@@ -5,54 +5,58 @@
 use anyhow::Result;
 
 use crate::{
-    domain::{Flags, ImapServiceInterface},
+    domain::{Flags, ImapServiceInterface, Selection},
     output::PrinterService,
+    ui::i18n::tr_args,
 };
 
-/// Adds flags to all messages matching the given sequence range.
+/// Adds flags to all messages matching the given selection.
 /// Flags are case-insensitive, and they do not need to be prefixed with `\`.
 pub fn add<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
-    seq_range: &'a str,
+    selection: Selection<'a>,
     flags: Vec<&'a str>,
     printer: &'a mut Printer,
     imap: &'a mut ImapService,
 ) -> Result<()> {
+    let seq_range = selection.to_seq_range(imap)?;
     let flags = Flags::from(flags);
-    imap.add_flags(seq_range, &flags)?;
-    printer.print(format!(
-        r#"Flag(s) "{}" successfully added to message(s) "{}""#,
-        flags, seq_range
+    imap.add_flags(&seq_range, &flags)?;
+    printer.print(tr_args(
+        "flags-added",
+        &[("flags", &flags.to_string()), ("seqs", &seq_range)],
     ))
 }
 
-/// Removes flags from all messages matching the given sequence range.
+/// Removes flags from all messages matching the given selection.
 /// Flags are case-insensitive, and they do not need to be prefixed with `\`.
 pub fn remove<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
-    seq_range: &'a str,
+    selection: Selection<'a>,
     flags: Vec<&'a str>,
     printer: &'a mut Printer,
     imap: &'a mut ImapService,
 ) -> Result<()> {
+    let seq_range = selection.to_seq_range(imap)?;
     let flags = Flags::from(flags);
-    imap.remove_flags(seq_range, &flags)?;
-    printer.print(format!(
-        r#"Flag(s) "{}" successfully removed from message(s) "{}""#,
-        flags, seq_range
+    imap.remove_flags(&seq_range, &flags)?;
+    printer.print(tr_args(
+        "flags-removed",
+        &[("flags", &flags.to_string()), ("seqs", &seq_range)],
     ))
 }
 
-/// Replaces flags of all messages matching the given sequence range.
+/// Replaces flags of all messages matching the given selection.
 /// Flags are case-insensitive, and they do not need to be prefixed with `\`.
 pub fn set<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
-    seq_range: &'a str,
+    selection: Selection<'a>,
     flags: Vec<&'a str>,
     printer: &'a mut Printer,
     imap: &'a mut ImapService,
 ) -> Result<()> {
+    let seq_range = selection.to_seq_range(imap)?;
     let flags = Flags::from(flags);
-    imap.set_flags(seq_range, &flags)?;
-    printer.print(format!(
-        r#"Flag(s) "{}" successfully set for message(s) "{}""#,
-        flags, seq_range
+    imap.set_flags(&seq_range, &flags)?;
+    printer.print(tr_args(
+        "flags-set",
+        &[("flags", &flags.to_string()), ("seqs", &seq_range)],
     ))
 }
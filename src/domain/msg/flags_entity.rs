@@ -36,6 +36,20 @@ impl Flags {
         });
         flags
     }
+
+    /// Builds a string of the custom keywords (non-system flags like `$label1` or `Important`),
+    /// sorted and separated by spaces.
+    pub fn to_keywords_string(&self) -> String {
+        let mut keywords: Vec<&str> = self
+            .iter()
+            .filter_map(|flag| match flag {
+                Flag::Custom(keyword) => Some(keyword.as_ref()),
+                _ => None,
+            })
+            .collect();
+        keywords.sort_unstable();
+        keywords.join(" ")
+    }
 }
 
 impl Display for Flags {
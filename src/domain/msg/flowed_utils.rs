@@ -0,0 +1,202 @@
+//! Module related to format=flowed text.
+//!
+//! This module exposes helpers to encode and decode text bodies following the [RFC3676].
+//!
+//! [RFC3676]: https://datatracker.ietf.org/doc/html/rfc3676
+
+/// The width of the lines of generated flowed text, as recommended by the RFC.
+pub const FLOWED_WIDTH: usize = 78;
+
+const SIG_DELIM: &str = "-- ";
+
+/// Splits the quote marks of the given line. Spaces between quote marks are accepted, so both
+/// `>> text` and `> > text` have a depth of 2. Returns the depth and the remaining content,
+/// without the space following the quote marks.
+fn split_quote(line: &str) -> (usize, &str) {
+    let mut depth = 0;
+    let mut content = line;
+    while let Some(rest) = content.strip_prefix('>') {
+        depth += 1;
+        content = rest.strip_prefix(' ').unwrap_or(rest);
+    }
+    (depth, content)
+}
+
+fn quote_prefix(depth: usize) -> String {
+    if depth == 0 {
+        String::new()
+    } else {
+        format!("{} ", ">".repeat(depth))
+    }
+}
+
+/// Splits the given content into lines of at most the given width, breaking at spaces. Each
+/// line but the last keeps its trailing space. Words longer than the width are not broken.
+fn split_words(content: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in content.split_inclusive(' ') {
+        if !line.is_empty() && line.chars().count() + word.trim_end().chars().count() > width {
+            lines.push(line);
+            line = String::new();
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    lines
+}
+
+/// Encodes the given text as format=flowed. Lines longer than the width are wrapped with soft
+/// line breaks (a trailing space), trailing spaces of other lines are removed so they stay hard
+/// line breaks, and lines starting with a space or with `From ` are space-stuffed.
+pub fn flow(text: &str, width: usize) -> String {
+    let mut flowed = vec![];
+    for line in text.lines() {
+        if line == SIG_DELIM {
+            flowed.push(line.to_owned());
+            continue;
+        }
+
+        let (depth, content) = split_quote(line.trim_end());
+        let prefix = quote_prefix(depth);
+        if content.is_empty() {
+            flowed.push(prefix.trim_end().to_owned());
+            continue;
+        }
+
+        let width = width.saturating_sub(prefix.len()).max(1);
+        for line in split_words(content, width) {
+            let stuffing = if depth == 0 && (line.starts_with(' ') || line.starts_with("From ")) {
+                " "
+            } else {
+                ""
+            };
+            flowed.push(format!("{}{}{}", prefix, stuffing, line));
+        }
+    }
+    flowed.join("\r\n")
+}
+
+/// Decodes the given format=flowed text. Lines joined by soft line breaks become a single line
+/// prefixed by its quote marks. When `delsp` is set, the space of soft line breaks is removed.
+pub fn unflow(text: &str, delsp: bool) -> String {
+    let mut lines: Vec<String> = vec![];
+    let mut prev: Option<usize> = None;
+
+    for line in text.lines() {
+        let (depth, content) = split_quote(line.trim_end_matches('\r'));
+        // Quoted lines are unstuffed by the quote split.
+        let content = match depth {
+            0 => content.strip_prefix(' ').unwrap_or(content),
+            _ => content,
+        };
+        let is_flowed = content.ends_with(' ') && content != SIG_DELIM;
+        let content = match (is_flowed, delsp) {
+            (true, true) => &content[..content.len() - 1],
+            _ => content,
+        };
+
+        match (prev, lines.last_mut()) {
+            // A quote depth change ends the paragraph, even after a soft line break.
+            (Some(prev), Some(last)) if prev == depth => last.push_str(content),
+            _ => lines.push(format!("{}{}", quote_prefix(depth), content)),
+        }
+        prev = if is_flowed { Some(depth) } else { None };
+    }
+
+    lines
+        .iter()
+        .map(|line| match line.as_str() {
+            SIG_DELIM => line.to_owned(),
+            line => line.trim_end().to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Wraps the lines of the given text longer than the width at spaces, repeating their quote
+/// marks.
+pub fn wrap(text: &str, width: usize) -> String {
+    let mut wrapped = vec![];
+    for line in text.lines() {
+        if line.chars().count() <= width {
+            wrapped.push(line.to_owned());
+            continue;
+        }
+
+        let (depth, content) = split_quote(line);
+        let prefix = quote_prefix(depth);
+        let width = width.saturating_sub(prefix.len()).max(1);
+        for line in split_words(content, width) {
+            wrapped.push(format!("{}{}", prefix, line.trim_end()));
+        }
+    }
+    wrapped.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_flow_text() {
+        let text = "Hello world, this is a long line. \n> Quoted line\n\nFrom here\n-- \nSig";
+        assert_eq!(
+            concat![
+                "Hello world, \r\n",
+                "this is a long \r\n",
+                "line.\r\n",
+                "> Quoted line\r\n",
+                "\r\n",
+                " From here\r\n",
+                "-- \r\n",
+                "Sig",
+            ],
+            flow(text, 16)
+        );
+    }
+
+    #[test]
+    fn it_should_unflow_text() {
+        let text = concat![
+            "Hello world, \r\n",
+            "this is a long \r\n",
+            "line.\r\n",
+            ">> Quoted \r\n",
+            ">> paragraph\r\n",
+            "> Depth \r\n",
+            ">> change\r\n",
+            " From here\r\n",
+            "-- \r\n",
+            "Sig",
+        ];
+        assert_eq!(
+            concat![
+                "Hello world, this is a long line.\n",
+                ">> Quoted paragraph\n",
+                "> Depth\n",
+                ">> change\n",
+                "From here\n",
+                "-- \n",
+                "Sig",
+            ],
+            unflow(text, false)
+        );
+        assert_eq!("Helloworld", unflow("Hello \r\nworld", true));
+    }
+
+    #[test]
+    fn it_should_roundtrip_flowed_text() {
+        let text = "A paragraph long enough to be wrapped.\n> And a quoted one, wrapped too.";
+        assert_eq!(text, unflow(&flow(text, 20), false));
+    }
+
+    #[test]
+    fn it_should_wrap_text() {
+        let text = "Short line\n> A quoted line longer than the width";
+        assert_eq!(
+            "Short line\n> A quoted line\n> longer than\n> the width",
+            wrap(text, 16)
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Gmail entity module.
+//!
+//! This module contains the Gmail message and thread IDs, fetched with the `X-GM-MSGID` and
+//! `X-GM-THRID` attributes of the [Gmail IMAP extensions].
+//!
+//! [Gmail IMAP extensions]: https://developers.google.com/gmail/imap/imap-extensions
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Represents the capability announcing the Gmail IMAP extensions.
+pub const GMAIL_CAPABILITY: &str = "X-GM-EXT-1";
+
+/// Represents the Gmail IDs of a message. IDs are 64-bit integers, serialized as strings so they
+/// do not lose precision in JSON parsers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GmailIds {
+    /// The unique ID of the message, shared by all the mailboxes (labels) it lives in.
+    pub msg_id: String,
+    /// The ID of the thread the message belongs to.
+    pub thread_id: String,
+    /// The URL of the message in the Gmail web interface.
+    pub url: String,
+}
+
+impl GmailIds {
+    pub fn new(msg_id: u64, thread_id: u64) -> Self {
+        Self {
+            msg_id: msg_id.to_string(),
+            thread_id: thread_id.to_string(),
+            url: format!("https://mail.google.com/mail/#all/{:x}", msg_id),
+        }
+    }
+}
+
+/// Parses the Gmail IDs out of the raw response of a fetch, like
+/// `* 1 FETCH (X-GM-MSGID 1278455344230334865 X-GM-THRID 1266894439832287888)`. Returns them by
+/// sequence number.
+pub fn parse_gmail_ids(res: &[u8]) -> HashMap<u32, GmailIds> {
+    let res = String::from_utf8_lossy(res);
+    let mut ids = HashMap::new();
+    for line in res.lines() {
+        let tokens: Vec<String> = line
+            .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+            .filter(|token| !token.is_empty())
+            .map(|token| token.to_uppercase())
+            .collect();
+        let seq = match tokens.as_slice() {
+            [star, seq, fetch, ..] if star == "*" && fetch == "FETCH" => seq.parse::<u32>().ok(),
+            _ => None,
+        };
+        let attr = |name: &str| {
+            tokens
+                .iter()
+                .position(|token| token == name)
+                .and_then(|i| tokens.get(i + 1))
+                .and_then(|val| val.parse::<u64>().ok())
+        };
+        if let (Some(seq), Some(msg_id), Some(thread_id)) =
+            (seq, attr("X-GM-MSGID"), attr("X-GM-THRID"))
+        {
+            ids.insert(seq, GmailIds::new(msg_id, thread_id));
+        }
+    }
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_parse_gmail_ids() {
+        let res = concat!(
+            "* 1 FETCH (X-GM-MSGID 1278455344230334865 X-GM-THRID 1266894439832287888)\r\n",
+            "* 2 FETCH (X-GM-THRID 1266894439832287888 X-GM-MSGID 1278455344230334866)\r\n",
+            "* 3 FETCH (FLAGS (\\Seen))\r\n",
+        );
+        let ids = parse_gmail_ids(res.as_bytes());
+
+        assert_eq!(2, ids.len());
+        assert_eq!(
+            GmailIds {
+                msg_id: String::from("1278455344230334865"),
+                thread_id: String::from("1266894439832287888"),
+                url: String::from("https://mail.google.com/mail/#all/11bdfc5cae0c8191"),
+            },
+            ids[&1]
+        );
+        assert_eq!("1278455344230334866", ids[&2].msg_id);
+    }
+}
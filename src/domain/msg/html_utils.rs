@@ -0,0 +1,65 @@
+//! Module related to HTML bodies.
+//!
+//! This module exposes the sanitizer applied to HTML parts before they are shown: scripts are
+//! stripped and remote resources (like tracking pixels) are blocked unless asked otherwise.
+
+use std::{
+    borrow::Cow,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// Returns true if the given URL points to a remote resource, loaded when the HTML is displayed.
+fn is_remote_url(url: &str) -> bool {
+    let url = url.trim_start().to_lowercase();
+    url.starts_with("http://") || url.starts_with("https://") || url.starts_with("//")
+}
+
+/// Sanitizes the given HTML: scripts, styles and event handlers are stripped, and the sources
+/// pointing to remote resources are removed unless `load_remote` is set. Returns the sanitized
+/// HTML along with the number of blocked resources.
+pub fn sanitize_html(html: &str, load_remote: bool) -> (String, usize) {
+    let blocked = Arc::new(AtomicUsize::new(0));
+    let counter = blocked.clone();
+    let html = ammonia::Builder::new()
+        .attribute_filter(move |_, attr, val| {
+            if !load_remote && attr == "src" && is_remote_url(val) {
+                counter.fetch_add(1, Ordering::SeqCst);
+                None
+            } else {
+                Some(Cow::Borrowed(val))
+            }
+        })
+        .clean(html)
+        .to_string();
+    let blocked = blocked.load(Ordering::SeqCst);
+    (html, blocked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_sanitize_html() {
+        let html = concat!(
+            r#"<p onclick="steal()">Hi</p><script>steal()</script>"#,
+            r#"<img src="https://tracker.example.com/pixel.gif" alt="">"#,
+            r#"<img src="//cdn.example.com/logo.png" alt="logo">"#,
+        );
+
+        let (sanitized, blocked) = sanitize_html(html, false);
+        assert_eq!(2, blocked);
+        assert!(!sanitized.contains("script"));
+        assert!(!sanitized.contains("onclick"));
+        assert!(!sanitized.contains("example.com"));
+        assert!(sanitized.contains("<p>Hi</p>"));
+
+        let (sanitized, blocked) = sanitize_html(html, true);
+        assert_eq!(0, blocked);
+        assert!(!sanitized.contains("script"));
+        assert!(sanitized.contains("https://tracker.example.com/pixel.gif"));
+    }
+}
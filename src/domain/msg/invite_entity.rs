@@ -0,0 +1,425 @@
+//! Invite entity module.
+//!
+//! This module contains the definition of calendar invites ([RFC5545] events sent by email) and
+//! the generation of [iTIP] replies.
+//!
+//! [RFC5545]: https://datatracker.ietf.org/doc/html/rfc5545
+//! [iTIP]: https://datatracker.ietf.org/doc/html/rfc5546
+
+use chrono::Utc;
+use std::fmt::{self, Display};
+
+/// Represents the participation status sent back to the organizer of an invite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InviteReply {
+    Accept,
+    Decline,
+    Tentative,
+}
+
+impl InviteReply {
+    /// Returns the iCalendar `PARTSTAT` value of the reply.
+    pub fn partstat(&self) -> &'static str {
+        match self {
+            Self::Accept => "ACCEPTED",
+            Self::Decline => "DECLINED",
+            Self::Tentative => "TENTATIVE",
+        }
+    }
+
+    /// Returns the subject prefix commonly used by calendar clients.
+    pub fn subject_prefix(&self) -> &'static str {
+        match self {
+            Self::Accept => "Accepted",
+            Self::Decline => "Declined",
+            Self::Tentative => "Tentative",
+        }
+    }
+}
+
+/// Represents a property of an iCalendar component, for example
+/// `DTSTART;TZID=Europe/Paris:20211105T140000`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Property {
+    name: String,
+    params: String,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params.split(';').find_map(|param| {
+            let mut kv = param.splitn(2, '=');
+            match (kv.next(), kv.next()) {
+                (Some(key), Some(val)) if key.eq_ignore_ascii_case(name) => {
+                    Some(val.trim_matches('"'))
+                }
+                _ => None,
+            }
+        })
+    }
+}
+
+impl Display for Property {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.params.is_empty() {
+            write!(f, "{}:{}", self.name, self.value)
+        } else {
+            write!(f, "{};{}:{}", self.name, self.params, self.value)
+        }
+    }
+}
+
+/// Represents the event of a calendar invite.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Invite {
+    /// The `METHOD` of the calendar, `REQUEST` for invites.
+    pub method: Option<String>,
+    /// The properties of the first `VEVENT` component.
+    props: Vec<Property>,
+}
+
+impl Invite {
+    /// Parses the first event of the given iCalendar content. Returns `None` if the content does
+    /// not contain any event.
+    pub fn from_ics(ics: &str) -> Option<Self> {
+        let mut invite = Self::default();
+        let mut in_event = false;
+        let mut has_event = false;
+
+        for line in unfold_lines(ics) {
+            let prop = match parse_property(&line) {
+                Some(prop) => prop,
+                None => continue,
+            };
+            match (prop.name.as_str(), prop.value.to_ascii_uppercase().as_str()) {
+                ("BEGIN", "VEVENT") if !has_event => in_event = true,
+                ("END", "VEVENT") if in_event => {
+                    in_event = false;
+                    has_event = true;
+                }
+                ("METHOD", _) if !in_event => invite.method = Some(prop.value),
+                _ if in_event => invite.props.push(prop),
+                _ => (),
+            }
+        }
+
+        if has_event {
+            Some(invite)
+        } else {
+            None
+        }
+    }
+
+    fn prop(&self, name: &str) -> Option<&Property> {
+        self.props.iter().find(|prop| prop.name == name)
+    }
+
+    fn text(&self, name: &str) -> Option<String> {
+        self.prop(name).map(|prop| unescape(&prop.value))
+    }
+
+    pub fn uid(&self) -> Option<&str> {
+        self.prop("UID").map(|prop| prop.value.as_str())
+    }
+
+    pub fn summary(&self) -> Option<String> {
+        self.text("SUMMARY")
+    }
+
+    /// Returns the email of the organizer.
+    pub fn organizer(&self) -> Option<&str> {
+        self.prop("ORGANIZER").map(|prop| strip_mailto(&prop.value))
+    }
+
+    /// Returns the emails of the attendees.
+    pub fn attendees(&self) -> Vec<&str> {
+        self.props
+            .iter()
+            .filter(|prop| prop.name == "ATTENDEE")
+            .map(|prop| strip_mailto(&prop.value))
+            .collect()
+    }
+
+    /// Builds the iTIP reply of the given attendee, as an iCalendar content.
+    pub fn to_reply(&self, reply: InviteReply, attendee_name: &str, attendee: &str) -> String {
+        let mut lines = vec![
+            String::from("BEGIN:VCALENDAR"),
+            String::from("PRODID:-//himalaya//EN"),
+            String::from("VERSION:2.0"),
+            String::from("METHOD:REPLY"),
+            String::from("BEGIN:VEVENT"),
+        ];
+        for name in &[
+            "UID",
+            "SEQUENCE",
+            "RECURRENCE-ID",
+            "DTSTART",
+            "DTEND",
+            "SUMMARY",
+        ] {
+            if let Some(prop) = self.prop(name) {
+                lines.push(prop.to_string());
+            }
+        }
+        if let Some(prop) = self.prop("ORGANIZER") {
+            lines.push(prop.to_string());
+        }
+        lines.push(format!(
+            "DTSTAMP:{}",
+            Utc::now().format("%Y%m%dT%H%M%SZ").to_string()
+        ));
+        let attendee_params = if attendee_name.is_empty() {
+            format!("PARTSTAT={}", reply.partstat())
+        } else {
+            format!(
+                "PARTSTAT={};CN={}",
+                reply.partstat(),
+                quote_param(attendee_name)
+            )
+        };
+        lines.push(format!("ATTENDEE;{}:mailto:{}", attendee_params, attendee));
+        lines.push(String::from("END:VEVENT"));
+        lines.push(String::from("END:VCALENDAR"));
+
+        lines.iter().map(|line| fold_line(line)).collect()
+    }
+}
+
+/// Renders the summary of the event (what, when, where and who).
+impl Display for Invite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Invitation: {}",
+            self.summary().unwrap_or_else(|| "(no title)".into())
+        )?;
+        if let Some(start) = self.prop("DTSTART") {
+            write!(f, "When: {}", format_date(start))?;
+            if let Some(end) = self.prop("DTEND") {
+                write!(f, " → {}", format_date(end))?;
+            }
+            writeln!(f)?;
+        }
+        if let Some(location) = self.text("LOCATION") {
+            writeln!(f, "Where: {}", location)?;
+        }
+        if let Some(organizer) = self.organizer() {
+            writeln!(f, "Organizer: {}", organizer)?;
+        }
+        let attendees = self.attendees();
+        if !attendees.is_empty() {
+            writeln!(f, "Attendees: {}", attendees.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Unfolds the content lines, continuation lines start with a space or a tab.
+fn unfold_lines(ics: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in ics.lines() {
+        let line = line.trim_end_matches('\r');
+        match lines.last_mut() {
+            Some(last) if line.starts_with(|c: char| c == ' ' || c == '\t') => {
+                last.push_str(&line[1..])
+            }
+            _ => lines.push(line.to_owned()),
+        }
+    }
+    lines
+}
+
+/// Parses a content line into a property. The value starts at the first colon outside of a
+/// quoted parameter value.
+fn parse_property(line: &str) -> Option<Property> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut head = head.splitn(2, ';');
+    Some(Property {
+        name: head.next()?.to_ascii_uppercase(),
+        params: head.next().unwrap_or_default().to_owned(),
+        value: value.to_owned(),
+    })
+}
+
+/// Folds the given content line so that no line is longer than 75 octets, continuation lines
+/// starting with a space. Lines are never split in the middle of a UTF-8 character.
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            len = 1;
+        }
+        folded.push(c);
+        len += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// Quotes the given parameter value. Double quotes cannot appear in a parameter value, so they
+/// are encoded following [RFC6868], as well as the carets and the line breaks. Other control
+/// characters are not allowed and are dropped.
+///
+/// [RFC6868]: https://datatracker.ietf.org/doc/html/rfc6868
+fn quote_param(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '^' => quoted.push_str("^^"),
+            '"' => quoted.push_str("^'"),
+            '\n' => quoted.push_str("^n"),
+            c if c.is_control() => (),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("\\n", "\n")
+        .replace("\\N", "\n")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+fn strip_mailto(value: &str) -> &str {
+    if value.len() > 7 && value[..7].eq_ignore_ascii_case("mailto:") {
+        &value[7..]
+    } else {
+        value
+    }
+}
+
+/// Formats a date or date-time property, for example `20211105T140000Z` becomes
+/// `2021-11-05 14:00 UTC`.
+fn format_date(prop: &Property) -> String {
+    let value = prop.value.as_str();
+    if value.len() < 8 || !value.is_ascii() {
+        return value.to_owned();
+    }
+    let date = format!("{}-{}-{}", &value[..4], &value[4..6], &value[6..8]);
+    if value.len() < 13 || &value[8..9] != "T" {
+        return date;
+    }
+    let time = format!("{}:{}", &value[9..11], &value[11..13]);
+    let tz = if value.ends_with('Z') {
+        Some("UTC")
+    } else {
+        prop.param("TZID")
+    };
+    match tz {
+        Some(tz) => format!("{} {} {}", date, time, tz),
+        None => format!("{} {}", date, time),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ICS: &str = concat![
+        "BEGIN:VCALENDAR\r\n",
+        "METHOD:REQUEST\r\n",
+        "BEGIN:VTIMEZONE\r\n",
+        "TZID:Europe/Paris\r\n",
+        "END:VTIMEZONE\r\n",
+        "BEGIN:VEVENT\r\n",
+        "UID:42@example.com\r\n",
+        "SEQUENCE:1\r\n",
+        "SUMMARY:Weekly sync\\, with the\r\n",
+        "  team\r\n",
+        "DTSTART;TZID=Europe/Paris:20211105T140000\r\n",
+        "DTEND:20211105T140000Z\r\n",
+        "LOCATION:Room 42\r\n",
+        "ORGANIZER;CN=\"Bob: the boss\":mailto:bob@example.com\r\n",
+        "ATTENDEE;PARTSTAT=NEEDS-ACTION:mailto:alice@example.com\r\n",
+        "ATTENDEE;PARTSTAT=ACCEPTED:MAILTO:bob@example.com\r\n",
+        "END:VEVENT\r\n",
+        "END:VCALENDAR\r\n",
+    ];
+
+    #[test]
+    fn it_should_parse_invite() {
+        let invite = Invite::from_ics(ICS).unwrap();
+        assert_eq!(Some("REQUEST"), invite.method.as_deref());
+        assert_eq!(Some("42@example.com"), invite.uid());
+        assert_eq!(
+            Some("Weekly sync, with the team"),
+            invite.summary().as_deref()
+        );
+        assert_eq!(Some("bob@example.com"), invite.organizer());
+        assert_eq!(
+            vec!["alice@example.com", "bob@example.com"],
+            invite.attendees()
+        );
+        assert_eq!(
+            concat![
+                "Invitation: Weekly sync, with the team\n",
+                "When: 2021-11-05 14:00 Europe/Paris → 2021-11-05 14:00 UTC\n",
+                "Where: Room 42\n",
+                "Organizer: bob@example.com\n",
+                "Attendees: alice@example.com, bob@example.com\n",
+            ],
+            invite.to_string()
+        );
+
+        assert_eq!(
+            None,
+            Invite::from_ics("BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n")
+        );
+    }
+
+    #[test]
+    fn it_should_build_reply() {
+        let invite = Invite::from_ics(ICS).unwrap();
+        let reply = invite.to_reply(InviteReply::Tentative, "Alice", "alice@example.com");
+        assert!(reply.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(reply.contains("\r\nMETHOD:REPLY\r\n"));
+        assert!(reply.contains("\r\nUID:42@example.com\r\n"));
+        assert!(reply.contains("\r\nSEQUENCE:1\r\n"));
+        assert!(reply.contains("\r\nDTSTART;TZID=Europe/Paris:20211105T140000\r\n"));
+        assert!(reply.contains("\r\nORGANIZER;CN=\"Bob: the boss\":mailto:bob@example.com\r\n"));
+        assert!(reply
+            .contains("\r\nATTENDEE;PARTSTAT=TENTATIVE;CN=\"Alice\":mailto:alice@example.com\r\n"));
+        assert!(!reply.contains("NEEDS-ACTION"));
+        assert!(reply.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+
+        let reply = invite.to_reply(InviteReply::Accept, "Alice \"Al\" ^", "alice@example.com");
+        assert!(
+            reply.contains("\r\nATTENDEE;PARTSTAT=ACCEPTED;CN=\"Alice ^'Al^' ^^\":mailto:alice")
+        );
+    }
+
+    #[test]
+    fn it_should_fold_lines() {
+        assert_eq!("SEQUENCE:1\r\n", fold_line("SEQUENCE:1"));
+
+        let line = format!("SUMMARY:{}", "a".repeat(100));
+        let folded = fold_line(&line);
+        let lines: Vec<&str> = folded.trim_end().split("\r\n").collect();
+        assert_eq!(2, lines.len());
+        assert_eq!(75, lines[0].len());
+        assert_eq!(format!(" {}", "a".repeat(33)), lines[1]);
+
+        // Multi-byte characters are not split.
+        let line = format!("SUMMARY:{}", "é".repeat(40));
+        let folded = fold_line(&line);
+        assert!(folded.split("\r\n").all(|line| line.len() <= 75));
+        assert_eq!(line, unfold_lines(&folded).join(""));
+    }
+}
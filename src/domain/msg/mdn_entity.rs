@@ -0,0 +1,201 @@
+//! MDN entity module.
+//!
+//! This module contains the definitions of Message Disposition Notifications (read receipts), as
+//! described in the [RFC8098].
+//!
+//! [RFC8098]: https://datatracker.ietf.org/doc/html/rfc8098
+
+use anyhow::Result;
+use chrono::{Local, Utc};
+use lettre::message::header::{Header, HeaderName, HeaderValue};
+use serde::Deserialize;
+use std::error::Error as StdError;
+
+use crate::domain::msg::msg_utils;
+
+/// Represents the name of the header requesting a read receipt.
+pub const DISPOSITION_NOTIFICATION_TO: &str = "Disposition-Notification-To";
+
+/// Represents the custom flag set once a read receipt has been sent or denied, as described in
+/// the [RFC3503](https://datatracker.ietf.org/doc/html/rfc3503#section-3.1).
+pub const MDN_SENT_FLAG: &str = "$MDNSent";
+
+/// Represents the policy applied to incoming read receipt requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MdnPolicy {
+    /// Asks the user before sending the read receipt.
+    Ask,
+    /// Always sends the read receipt.
+    Always,
+    /// Never sends the read receipt.
+    Never,
+}
+
+impl Default for MdnPolicy {
+    fn default() -> Self {
+        Self::Ask
+    }
+}
+
+impl MdnPolicy {
+    /// Decides whether the read receipt is sent, calling `ask` when the policy asks and the user
+    /// can be asked. Returns `None` when nobody can be asked (like in the daemon): the request is
+    /// then left pending, so that the next interactive read asks for it.
+    pub fn decide<F: FnOnce() -> Result<bool>>(
+        self,
+        can_ask: bool,
+        ask: F,
+    ) -> Result<Option<bool>> {
+        match self {
+            Self::Never => Ok(Some(false)),
+            Self::Always => Ok(Some(true)),
+            Self::Ask if can_ask => ask().map(Some),
+            Self::Ask => Ok(None),
+        }
+    }
+}
+
+/// Represents the `Disposition-Notification-To` header of outgoing messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DispositionNotificationTo(pub String);
+
+impl Header for DispositionNotificationTo {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str(DISPOSITION_NOTIFICATION_TO)
+    }
+
+    fn parse(s: &str) -> Result<Self, Box<dyn StdError + Send + Sync>> {
+        Ok(Self(s.to_owned()))
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), self.0.to_owned())
+    }
+}
+
+/// Represents the read receipt of a displayed message.
+#[derive(Debug)]
+pub struct Mdn<'a> {
+    /// The address of the account the receipt is sent from.
+    pub from: &'a str,
+    /// The email of the account the original message was displayed by.
+    pub final_recipient: &'a str,
+    /// The address asking for the receipt.
+    pub to: &'a str,
+    /// The subject of the original message.
+    pub subject: &'a str,
+    /// The message identifier of the original message.
+    pub original_message_id: Option<&'a str>,
+    /// Whether the receipt is sent without asking the user.
+    pub automatic: bool,
+}
+
+impl<'a> Mdn<'a> {
+    /// Builds the raw `multipart/report` message of the receipt.
+    pub fn to_raw(&self) -> String {
+        let boundary = format!("mdn-{}", Utc::now().timestamp_nanos());
+        let disposition = if self.automatic {
+            "automatic-action/MDN-sent-automatically; displayed"
+        } else {
+            "manual-action/MDN-sent-manually; displayed"
+        };
+
+        let mut lines = vec![
+            format!("Date: {}", Local::now().to_rfc2822()),
+            format!("From: {}", self.from),
+            format!("To: {}", self.to),
+            format!(
+                "Subject: Read: {}",
+                msg_utils::encode_header_value(self.subject)
+            ),
+            format!(
+                "Message-ID: {}",
+                msg_utils::gen_message_id(self.final_recipient)
+            ),
+            String::from("MIME-Version: 1.0"),
+            format!(
+                "Content-Type: multipart/report; report-type=disposition-notification; boundary=\"{}\"",
+                boundary
+            ),
+            String::new(),
+            format!("--{}", boundary),
+            String::from("Content-Type: text/plain; charset=utf-8"),
+            String::new(),
+            format!(
+                "The message \"{}\" sent to {} has been displayed. This is no guarantee that the message has been read or understood.",
+                self.subject, self.final_recipient
+            ),
+            String::new(),
+            format!("--{}", boundary),
+            String::from("Content-Type: message/disposition-notification"),
+            String::new(),
+            format!("Reporting-UA: himalaya; himalaya {}", env!("CARGO_PKG_VERSION")),
+            format!("Final-Recipient: rfc822; {}", self.final_recipient),
+        ];
+        if let Some(id) = self.original_message_id {
+            lines.push(format!("Original-Message-ID: {}", id));
+        }
+        lines.push(format!("Disposition: {}", disposition));
+        lines.push(String::new());
+        lines.push(format!("--{}--", boundary));
+
+        let mut raw = lines.join("\r\n");
+        raw.push_str("\r\n");
+        raw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_decide_mdn() {
+        let ask = || Ok(true);
+        assert_eq!(Some(false), MdnPolicy::Never.decide(true, ask).unwrap());
+        assert_eq!(Some(true), MdnPolicy::Always.decide(false, ask).unwrap());
+        assert_eq!(Some(true), MdnPolicy::Ask.decide(true, ask).unwrap());
+        let ask = || -> Result<bool> { panic!("nobody can be asked") };
+        assert_eq!(None, MdnPolicy::Ask.decide(false, ask).unwrap());
+    }
+
+    #[test]
+    fn it_should_build_raw_mdn() {
+        let mdn = Mdn {
+            from: "Alice <alice@example.com>",
+            final_recipient: "alice@example.com",
+            to: "bob@example.com",
+            subject: "Report",
+            original_message_id: Some("<42@example.com>"),
+            automatic: false,
+        };
+        let raw = mdn.to_raw();
+
+        assert!(raw.contains("\r\nFrom: Alice <alice@example.com>\r\n"));
+        assert!(raw.contains("\r\nTo: bob@example.com\r\n"));
+        assert!(raw.contains("\r\nSubject: Read: Report\r\n"));
+        let raw = Mdn {
+            subject: "Café",
+            ..mdn
+        }
+        .to_raw();
+        assert!(raw.contains("\r\nSubject: Read: =?utf-8?b?Q2Fmw6k=?=\r\n"));
+        assert!(raw.contains("@example.com>\r\nMIME-Version: 1.0\r\n"));
+        assert!(raw.contains(
+            "Content-Type: multipart/report; report-type=disposition-notification; boundary="
+        ));
+        assert!(raw.contains("\r\nFinal-Recipient: rfc822; alice@example.com\r\n"));
+        assert!(raw.contains("\r\nOriginal-Message-ID: <42@example.com>\r\n"));
+        assert!(raw.contains("\r\nDisposition: manual-action/MDN-sent-manually; displayed\r\n"));
+
+        let mdn = Mdn {
+            original_message_id: None,
+            automatic: true,
+            ..mdn
+        };
+        let raw = mdn.to_raw();
+        assert!(!raw.contains("Original-Message-ID"));
+        assert!(raw.contains("automatic-action/MDN-sent-automatically; displayed"));
+    }
+}
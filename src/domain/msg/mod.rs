@@ -14,6 +14,7 @@
 /// - `copy`
 /// - `move`
 /// - `delete`
+/// - `invite`
 /// - `template`
 ///
 /// Execute `himalaya help <cmd>` where `<cmd>` is one entry of this list above
@@ -23,6 +24,9 @@ pub mod msg_arg;
 pub mod msg_handler;
 pub mod msg_utils;
 
+pub mod flowed_utils;
+pub mod html_utils;
+
 pub mod flag_arg;
 pub mod flag_handler;
 
@@ -48,3 +52,33 @@ pub use msg_entity::*;
 
 pub mod parts_entity;
 pub use parts_entity::*;
+
+pub mod invite_entity;
+pub use invite_entity::*;
+
+pub mod mdn_entity;
+pub use mdn_entity::*;
+
+pub mod selection_entity;
+pub use selection_entity::*;
+
+pub mod dsn_entity;
+pub use dsn_entity::*;
+
+pub mod schedule_entity;
+pub use schedule_entity::*;
+
+pub mod note_entity;
+pub use note_entity::*;
+
+pub mod export_entity;
+pub use export_entity::*;
+
+pub mod thread_entity;
+pub use thread_entity::*;
+
+pub mod gmail_entity;
+pub use gmail_entity::*;
+
+pub mod date_fmt_entity;
+pub use date_fmt_entity::*;
@@ -2,15 +2,17 @@
 //!
 //! This module provides subcommands, arguments and a command matcher related to message.
 
-use anyhow::Result;
-use clap::{self, App, Arg, ArgMatches, SubCommand};
+use anyhow::{anyhow, Result};
+use clap::{self, App, AppSettings, Arg, ArgMatches, SubCommand};
 use log::{debug, trace};
+use std::convert::TryFrom;
 
 use crate::{
     domain::{
         mbox::mbox_arg,
-        msg::{flag_arg, msg_arg, tpl_arg},
+        msg::{flag_arg, msg_arg, msg_utils, tpl_arg, ExportFormat, InviteReply, Selection},
     },
+    output::{parse_size, ExitCode},
     ui::table_arg,
 };
 
@@ -25,21 +27,80 @@ type RawMsg<'a> = &'a str;
 type Query = String;
 type AttachmentsPaths<'a> = Vec<&'a str>;
 type MaxTableWidth = Option<usize>;
+type Identity<'a> = Option<&'a str>;
+type AskReceipt = bool;
+type Recipients<'a> = Vec<&'a str>;
+type MimePart<'a> = Option<&'a str>;
+type Cmd<'a> = Vec<&'a str>;
+type MaxSentMsgs = usize;
+type Seqs<'a> = Vec<&'a str>;
+type MimeGlob<'a> = Option<&'a str>;
+type NameGlob<'a> = Option<&'a str>;
+type AttachmentName<'a> = &'a str;
+type ConcatOutput<'a> = Option<&'a str>;
+type Export = Option<ExportFormat>;
+type PrintMsgs = bool;
+type LoadRemote = bool;
+type EnvelopeOnly = bool;
+type PreserveFlags = bool;
+type Until<'a> = &'a str;
+type SendAt<'a> = Option<&'a str>;
+type Due = bool;
+type NoteText = Option<String>;
+type DeleteNote = bool;
+type NotesQuery<'a> = Option<&'a str>;
+type AllMboxes = bool;
+type CollapseQuotes = bool;
+
+/// Represents the action applied to the messages picked with `--pick`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickAction<'a> {
+    /// Prints the sequence numbers of the picked messages.
+    Print,
+    Read,
+    Delete,
+    Move(Mbox<'a>),
+}
 
 /// Message commands.
 pub enum Command<'a> {
-    Attachments(Seq<'a>),
-    Copy(Seq<'a>, Mbox<'a>),
-    Delete(Seq<'a>),
-    Forward(Seq<'a>, AttachmentsPaths<'a>),
+    Attachments(Seq<'a>, MimeGlob<'a>, NameGlob<'a>),
+    AttachmentCat(Seq<'a>, AttachmentName<'a>),
+    Bounce(Seq<'a>, Recipients<'a>),
+    Copy(Selection<'a>, Mbox<'a>),
+    Delete(Selection<'a>),
+    Edit(Seq<'a>),
+    Forward(Seq<'a>, AttachmentsPaths<'a>, Identity<'a>, AskReceipt),
+    Invite(Seq<'a>, InviteReply),
     List(MaxTableWidth, Option<PageSize>, Page),
-    Move(Seq<'a>, Mbox<'a>),
-    Read(Seq<'a>, TextMime<'a>, Raw),
-    Reply(Seq<'a>, All, AttachmentsPaths<'a>),
+    ListAllAccounts(MaxTableWidth, Option<PageSize>, Page),
+    Move(Selection<'a>, Mbox<'a>),
+    Note(Seq<'a>, NoteText, DeleteNote),
+    Notes(NotesQuery<'a>, MaxTableWidth),
+    Pick(Option<Query>, Option<PageSize>, Page, PickAction<'a>),
+    Pipe(Seq<'a>, MimePart<'a>, Cmd<'a>),
+    Read(
+        Seqs<'a>,
+        TextMime<'a>,
+        Raw,
+        ConcatOutput<'a>,
+        Export,
+        PrintMsgs,
+        LoadRemote,
+        EnvelopeOnly,
+        PreserveFlags,
+    ),
+    Reply(Seq<'a>, All, AttachmentsPaths<'a>, Identity<'a>, AskReceipt),
     Save(RawMsg<'a>),
-    Search(Query, MaxTableWidth, Option<PageSize>, Page),
-    Send(RawMsg<'a>),
-    Write(AttachmentsPaths<'a>),
+    Search(Query, MaxTableWidth, Option<PageSize>, Page, AllMboxes),
+    QueueFlush(Due),
+    Send(RawMsg<'a>, SendAt<'a>),
+    SentStatus(MaxSentMsgs),
+    Snooze(Seq<'a>, Until<'a>),
+    Thread(Seq<'a>, AllMboxes, CollapseQuotes),
+    Unsubscribe(Seq<'a>),
+    Wake,
+    Write(AttachmentsPaths<'a>, Identity<'a>, AskReceipt),
 
     Flag(Option<flag_arg::Command<'a>>),
     Tpl(Option<tpl_arg::Command<'a>>),
@@ -48,26 +109,55 @@ pub enum Command<'a> {
 /// Message command matcher.
 pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Command<'a>>> {
     if let Some(m) = m.subcommand_matches("attachments") {
+        if let Some(m) = m.subcommand_matches("cat") {
+            debug!("attachments cat command matched");
+            let seq = m.value_of("seq").unwrap();
+            trace!("seq: {}", seq);
+            let name = m.value_of("name").unwrap();
+            trace!("name: {}", name);
+            return Ok(Some(Command::AttachmentCat(seq, name)));
+        }
+
         debug!("attachments command matched");
         let seq = m.value_of("seq").unwrap();
         trace!("seq: {}", seq);
-        return Ok(Some(Command::Attachments(seq)));
+        let mime = m.value_of("mime");
+        trace!("mime glob: {:?}", mime);
+        let name = m.value_of("name");
+        trace!("name glob: {:?}", name);
+        return Ok(Some(Command::Attachments(seq, mime, name)));
     }
 
-    if let Some(m) = m.subcommand_matches("copy") {
-        debug!("copy command matched");
+    if let Some(m) = m.subcommand_matches("bounce") {
+        debug!("bounce command matched");
         let seq = m.value_of("seq").unwrap();
         trace!("seq: {}", seq);
+        let to: Vec<&str> = m.values_of("to").unwrap_or_default().collect();
+        trace!("recipients: {:?}", to);
+        return Ok(Some(Command::Bounce(seq, to)));
+    }
+
+    if let Some(m) = m.subcommand_matches("copy") {
+        debug!("copy command matched");
+        let selection = selection(m);
+        trace!("selection: {:?}", selection);
         let mbox = m.value_of("mbox-target").unwrap();
         trace!(r#"target mailbox: "{:?}""#, mbox);
-        return Ok(Some(Command::Copy(seq, mbox)));
+        return Ok(Some(Command::Copy(selection, mbox)));
     }
 
     if let Some(m) = m.subcommand_matches("delete") {
-        debug!("copy command matched");
+        debug!("delete command matched");
+        let selection = selection(m);
+        trace!("selection: {:?}", selection);
+        return Ok(Some(Command::Delete(selection)));
+    }
+
+    if let Some(m) = m.subcommand_matches("edit") {
+        debug!("edit command matched");
         let seq = m.value_of("seq").unwrap();
         trace!("seq: {}", seq);
-        return Ok(Some(Command::Delete(seq)));
+        return Ok(Some(Command::Edit(seq)));
     }
 
     if let Some(m) = m.subcommand_matches("forward") {
@@ -76,7 +166,11 @@ pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Command<'a>>> {
         trace!("seq: {}", seq);
         let paths: Vec<&str> = m.values_of("attachments").unwrap_or_default().collect();
         trace!("attachments paths: {:?}", paths);
-        return Ok(Some(Command::Forward(seq, paths)));
+        let identity = m.value_of("identity");
+        trace!(r#"identity: "{:?}""#, identity);
+        let ask_receipt = m.is_present("ask-receipt");
+        trace!("ask receipt: {}", ask_receipt);
+        return Ok(Some(Command::Forward(seq, paths, identity, ask_receipt)));
     }
 
     if let Some(m) = m.subcommand_matches("list") {
@@ -95,27 +189,87 @@ pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Command<'a>>> {
             .map(|page| 1.max(page) - 1)
             .unwrap_or_default();
         trace!(r#"page: "{:?}""#, page);
+        let filter_query = filter_query(m)?;
+        trace!(r#"filter query: "{:?}""#, filter_query);
+        if m.is_present("pick") {
+            let action = pick_action(m)?;
+            return Ok(Some(Command::Pick(filter_query, page_size, page, action)));
+        }
+        if m.is_present("all-accounts") {
+            debug!("all accounts flag matched");
+            return Ok(Some(Command::ListAllAccounts(
+                max_table_width,
+                page_size,
+                page,
+            )));
+        }
+        // Filters are search keys, so the listing turns into a search.
+        if let Some(query) = filter_query {
+            return Ok(Some(Command::Search(
+                query,
+                max_table_width,
+                page_size,
+                page,
+                false,
+            )));
+        }
         return Ok(Some(Command::List(max_table_width, page_size, page)));
     }
 
     if let Some(m) = m.subcommand_matches("move") {
         debug!("move command matched");
-        let seq = m.value_of("seq").unwrap();
-        trace!("seq: {}", seq);
+        let selection = selection(m);
+        trace!("selection: {:?}", selection);
         let mbox = m.value_of("mbox-target").unwrap();
         trace!(r#"target mailbox: "{:?}""#, mbox);
-        return Ok(Some(Command::Move(seq, mbox)));
+        return Ok(Some(Command::Move(selection, mbox)));
     }
 
     if let Some(m) = m.subcommand_matches("read") {
         debug!("read command matched");
-        let seq = m.value_of("seq").unwrap();
-        trace!("seq: {}", seq);
+        let seqs: Vec<&str> = m.values_of("seq").unwrap_or_default().collect();
+        trace!("seqs: {:?}", seqs);
         let mime = m.value_of("mime-type").unwrap();
         trace!("text mime: {}", mime);
         let raw = m.is_present("raw");
         trace!("raw: {}", raw);
-        return Ok(Some(Command::Read(seq, mime, raw)));
+        let concat_output = m.value_of("concat-output");
+        trace!("concat output: {:?}", concat_output);
+        let export = m
+            .value_of("export")
+            .map(ExportFormat::try_from)
+            .transpose()?;
+        trace!("export: {:?}", export);
+        let print = m.is_present("print");
+        trace!("print: {}", print);
+        let load_remote = m.is_present("load-remote");
+        trace!("load remote: {}", load_remote);
+        let envelope_only = m.is_present("envelope-only");
+        trace!("envelope only: {}", envelope_only);
+        let preserve_flags = m.is_present("preserve-flags");
+        trace!("preserve flags: {}", preserve_flags);
+        return Ok(Some(Command::Read(
+            seqs,
+            mime,
+            raw,
+            concat_output,
+            export,
+            print,
+            load_remote,
+            envelope_only,
+            preserve_flags,
+        )));
+    }
+
+    if let Some(m) = m.subcommand_matches("pipe") {
+        debug!("pipe command matched");
+        let seq = m.value_of("seq").unwrap();
+        trace!("seq: {}", seq);
+        let part = m.value_of("part");
+        trace!("part: {:?}", part);
+        let cmd: Vec<&str> = m.values_of("command").unwrap_or_default().collect();
+        trace!("command: {:?}", cmd);
+        return Ok(Some(Command::Pipe(seq, part, cmd)));
     }
 
     if let Some(m) = m.subcommand_matches("reply") {
@@ -126,7 +280,11 @@ pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Command<'a>>> {
         trace!("reply all: {}", all);
         let paths: Vec<&str> = m.values_of("attachments").unwrap_or_default().collect();
         trace!("attachments paths: {:#?}", paths);
-        return Ok(Some(Command::Reply(seq, all, paths)));
+        let identity = m.value_of("identity");
+        trace!(r#"identity: "{:?}""#, identity);
+        let ask_receipt = m.is_present("ask-receipt");
+        trace!("ask receipt: {}", ask_receipt);
+        return Ok(Some(Command::Reply(seq, all, paths, identity, ask_receipt)));
     }
 
     if let Some(m) = m.subcommand_matches("save") {
@@ -174,14 +332,29 @@ pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Command<'a>>> {
                     }
                 }
             })
-            .1
+            .1;
+        // Keywords are custom flags, matched with the KEYWORD search key.
+        let query = m
+            .values_of("keyword")
+            .unwrap_or_default()
+            .map(|keyword| format!("KEYWORD {}", keyword))
+            .chain(query)
+            .chain(filter_query(m)?)
+            .collect::<Vec<_>>()
             .join(" ");
         trace!(r#"query: "{:?}""#, query);
+        if m.is_present("pick") {
+            let action = pick_action(m)?;
+            return Ok(Some(Command::Pick(Some(query), page_size, page, action)));
+        }
+        let all_mboxes = m.is_present("all-mailboxes");
+        trace!("all mailboxes: {}", all_mboxes);
         return Ok(Some(Command::Search(
             query,
             max_table_width,
             page_size,
             page,
+            all_mboxes,
         )));
     }
 
@@ -189,14 +362,112 @@ pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Command<'a>>> {
         debug!("send command matched");
         let msg = m.value_of("message").unwrap_or_default();
         trace!("message: {}", msg);
-        return Ok(Some(Command::Send(msg)));
+        let send_at = m.value_of("send-at");
+        trace!("send at: {:?}", send_at);
+        return Ok(Some(Command::Send(msg, send_at)));
+    }
+
+    if let Some(m) = m.subcommand_matches("queue") {
+        if let Some(m) = m.subcommand_matches("flush") {
+            debug!("queue flush command matched");
+            let due = m.is_present("due");
+            trace!("due: {}", due);
+            return Ok(Some(Command::QueueFlush(due)));
+        }
+    }
+
+    if let Some(m) = m.subcommand_matches("invite") {
+        debug!("invite command matched");
+        let (reply, m) = match m.subcommand() {
+            ("accept", Some(m)) => (InviteReply::Accept, m),
+            ("decline", Some(m)) => (InviteReply::Decline, m),
+            ("tentative", Some(m)) => (InviteReply::Tentative, m),
+            _ => return Ok(None),
+        };
+        trace!("reply: {:?}", reply);
+        let seq = m.value_of("seq").unwrap();
+        trace!("seq: {}", seq);
+        return Ok(Some(Command::Invite(seq, reply)));
+    }
+
+    if let Some(m) = m.subcommand_matches("sent") {
+        if let Some(m) = m.subcommand_matches("status") {
+            debug!("sent status command matched");
+            let max = clap::value_t_or_exit!(m.value_of("max"), usize);
+            trace!("max: {}", max);
+            return Ok(Some(Command::SentStatus(max)));
+        }
+    }
+
+    if let Some(m) = m.subcommand_matches("snooze") {
+        debug!("snooze command matched");
+        let seq = m.value_of("seq").unwrap();
+        trace!("seq: {}", seq);
+        let until = m.value_of("until").unwrap();
+        trace!("until: {}", until);
+        return Ok(Some(Command::Snooze(seq, until)));
+    }
+
+    if let Some(m) = m.subcommand_matches("note") {
+        debug!("note command matched");
+        let seq = m.value_of("seq").unwrap();
+        trace!("seq: {}", seq);
+        let note = m
+            .values_of("note")
+            .map(|words| words.collect::<Vec<_>>().join(" "));
+        trace!("note: {:?}", note);
+        let delete = m.is_present("delete");
+        trace!("delete: {}", delete);
+        return Ok(Some(Command::Note(seq, note, delete)));
+    }
+
+    if let Some(m) = m.subcommand_matches("notes") {
+        debug!("notes command matched");
+        let query = m.value_of("query");
+        trace!("query: {:?}", query);
+        let max_table_width = m
+            .value_of("max-table-width")
+            .and_then(|width| width.parse::<usize>().ok());
+        trace!("max table width: {:?}", max_table_width);
+        return Ok(Some(Command::Notes(query, max_table_width)));
+    }
+
+    if let Some(m) = m.subcommand_matches("thread") {
+        debug!("thread command matched");
+        let seq = m.value_of("seq").unwrap();
+        trace!("seq: {}", seq);
+        let all_mboxes = m.is_present("all-mailboxes");
+        trace!("all mailboxes: {}", all_mboxes);
+        let collapse_quotes = m.is_present("collapse-quotes");
+        trace!("collapse quotes: {}", collapse_quotes);
+        return Ok(Some(Command::Thread(seq, all_mboxes, collapse_quotes)));
+    }
+
+    if m.subcommand_matches("wake").is_some() {
+        debug!("wake command matched");
+        return Ok(Some(Command::Wake));
+    }
+
+    if let Some(m) = m.subcommand_matches("unsubscribe") {
+        debug!("unsubscribe command matched");
+        let seq = m.value_of("seq").unwrap();
+        trace!("seq: {}", seq);
+        return Ok(Some(Command::Unsubscribe(seq)));
     }
 
     if let Some(m) = m.subcommand_matches("write") {
         debug!("write command matched");
         let attachment_paths: Vec<&str> = m.values_of("attachments").unwrap_or_default().collect();
         trace!("attachments paths: {:?}", attachment_paths);
-        return Ok(Some(Command::Write(attachment_paths)));
+        let identity = m.value_of("identity");
+        trace!(r#"identity: "{:?}""#, identity);
+        let ask_receipt = m.is_present("ask-receipt");
+        trace!("ask receipt: {}", ask_receipt);
+        return Ok(Some(Command::Write(
+            attachment_paths,
+            identity,
+            ask_receipt,
+        )));
     }
 
     if let Some(m) = m.subcommand_matches("template") {
@@ -215,6 +486,7 @@ pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Command<'a>>> {
 pub fn seq_arg<'a>() -> Arg<'a, 'a> {
     Arg::with_name("seq")
         .help("Specifies the targetted message")
+        .long_help("Specifies the targetted message by its sequence number, shown in the SEQ column of listings, or by its UID with `--uid`, shown in the UID column then. Sequence numbers are relative to the mailbox and shift when messages are expunged, so list again after deletions or moves. UIDs do not shift, unless the UIDVALIDITY of the mailbox changes.")
         .value_name("SEQ")
         .required(true)
 }
@@ -228,6 +500,25 @@ pub fn seq_range_arg<'a>() -> Arg<'a, 'a> {
         .required(true)
 }
 
+/// Message query argument, turning the sequence range argument into an IMAP query.
+pub fn query_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("query")
+        .help("Targets messages matching the IMAP query given instead of SEQ")
+        .long_help("Targets messages matching the IMAP query given instead of SEQ, for example `himalaya delete --query \"FROM spam@example.com\"`. The query format follows the [RFC3501](https://tools.ietf.org/html/rfc3501#section-6.4.4).")
+        .short("q")
+        .long("query")
+}
+
+/// Message selection matcher, from the sequence range and query arguments.
+pub fn selection<'a>(m: &'a ArgMatches) -> Selection<'a> {
+    let seq_range = m.value_of("seq-range").unwrap();
+    if m.is_present("query") {
+        Selection::Query(seq_range)
+    } else {
+        Selection::SeqRange(seq_range)
+    }
+}
+
 /// Message reply all argument.
 pub fn reply_all_arg<'a>() -> Arg<'a, 'a> {
     Arg::with_name("reply-all")
@@ -237,7 +528,7 @@ pub fn reply_all_arg<'a>() -> Arg<'a, 'a> {
 }
 
 /// Message page size argument.
-fn page_size_arg<'a>() -> Arg<'a, 'a> {
+pub fn page_size_arg<'a>() -> Arg<'a, 'a> {
     Arg::with_name("page-size")
         .help("Page size")
         .short("s")
@@ -246,7 +537,7 @@ fn page_size_arg<'a>() -> Arg<'a, 'a> {
 }
 
 /// Message page argument.
-fn page_arg<'a>() -> Arg<'a, 'a> {
+pub fn page_arg<'a>() -> Arg<'a, 'a> {
     Arg::with_name("page")
         .help("Page number")
         .short("p")
@@ -255,6 +546,134 @@ fn page_arg<'a>() -> Arg<'a, 'a> {
         .default_value("0")
 }
 
+/// Matches the action applied to the picked messages.
+fn pick_action<'a>(m: &'a ArgMatches) -> Result<PickAction<'a>> {
+    let action = match m.values_of("then").map(|vals| vals.collect::<Vec<_>>()) {
+        None => PickAction::Print,
+        Some(vals) => match vals.as_slice() {
+            ["read"] => PickAction::Read,
+            ["delete"] => PickAction::Delete,
+            ["move", mbox] => PickAction::Move(mbox),
+            vals => {
+                return Err(anyhow!(
+                    r#"cannot parse action "{}": expected "read", "delete" or "move MBOX""#,
+                    vals.join(" ")
+                ))
+            }
+        },
+    };
+    trace!("pick action: {:?}", action);
+    Ok(action)
+}
+
+/// Message pick arguments.
+fn pick_args<'a>() -> Vec<Arg<'a, 'a>> {
+    vec![
+        Arg::with_name("pick")
+            .help("Picks messages with the picker command")
+            .long_help("Pipes the envelopes through the picker command (`picker-cmd` config option, `fzf --multi` by default, which must be installed) and applies the action given by `--then` to the picked messages, or prints their sequence numbers.")
+            .long("pick"),
+        Arg::with_name("then")
+            .help("Applies the action to the picked messages: read, delete or move MBOX")
+            .long_help("Applies the action to the picked messages: read, delete or move MBOX. It takes up to two values, so it goes after the search query.")
+            .long("then")
+            .value_names(&["ACTION", "MBOX"])
+            .min_values(1)
+            .max_values(2)
+            .requires("pick"),
+    ]
+}
+
+/// Message filter arguments.
+fn filter_args<'a>() -> Vec<Arg<'a, 'a>> {
+    vec![
+        Arg::with_name("min-size")
+            .help("Lists messages of at least the given size")
+            .long_help("Lists messages of at least the given size, in bytes or followed by a unit like `500K`, `10M` or `1G`.")
+            .long("min-size")
+            .value_name("SIZE"),
+        Arg::with_name("max-size")
+            .help("Lists messages of at most the given size")
+            .long_help("Lists messages of at most the given size, in bytes or followed by a unit like `500K`, `10M` or `1G`.")
+            .long("max-size")
+            .value_name("SIZE"),
+        Arg::with_name("filter-header")
+            .help("Lists messages having a header containing the given value")
+            .long_help("Lists messages having a header containing the given value, like `List-Id=rust-users`. The match is done by the server and is case-insensitive. Can be repeated.")
+            .long("filter-header")
+            .value_name("NAME=VALUE")
+            .multiple(true)
+            .number_of_values(1),
+    ]
+}
+
+/// Matches the filter arguments into IMAP search keys. The `LARGER` and `SMALLER` keys being
+/// exclusive ([RFC3501]), the size bounds are shifted by one byte.
+///
+/// [RFC3501]: https://datatracker.ietf.org/doc/html/rfc3501#section-6.4.4
+fn filter_query(m: &ArgMatches) -> Result<Option<String>> {
+    let min = m.value_of("min-size").map(parse_size).transpose()?;
+    let max = m.value_of("max-size").map(parse_size).transpose()?;
+    let headers = m
+        .values_of("filter-header")
+        .unwrap_or_default()
+        .map(header_key)
+        .collect::<Result<Vec<_>>>()?;
+    let keys = min
+        .filter(|min| *min > 0)
+        .map(|min| format!("LARGER {}", min - 1))
+        .into_iter()
+        .chain(max.map(|max| format!("SMALLER {}", max.saturating_add(1))))
+        .chain(headers)
+        .collect::<Vec<_>>();
+    Ok(if keys.is_empty() {
+        None
+    } else {
+        Some(keys.join(" "))
+    })
+}
+
+/// Builds the `HEADER` search key of the given header filter, like `List-Id=rust-users`.
+fn header_key(filter: &str) -> Result<String> {
+    let (name, val) = match filter.find('=') {
+        Some(i) => (filter[..i].trim(), &filter[i + 1..]),
+        None => ("", ""),
+    };
+    if !msg_utils::is_header_name(name) {
+        return Err(ExitCode::Usage.err(format!(
+            r#"cannot parse header filter "{}": expected NAME=VALUE"#,
+            filter
+        )));
+    }
+    let val = val.replace('\\', "\\\\").replace('"', "\\\"");
+    Ok(format!(r#"HEADER {} "{}""#, name, val))
+}
+
+/// Message all accounts argument.
+fn all_accounts_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("all-accounts")
+        .help("Lists messages of all accounts")
+        .long_help("Lists messages of all configured accounts concurrently, merged by date.")
+        .long("all-accounts")
+}
+
+/// Message identity argument.
+fn identity_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("identity")
+        .help("Sends the message with the given identity")
+        .long_help("Sends the message with the account identity matching the given email. When replying, the identity found in the recipients of the original message is used by default.")
+        .long("from")
+        .value_name("EMAIL")
+}
+
+/// Message ask receipt argument.
+fn ask_receipt_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("ask-receipt")
+        .help("Requests a read receipt")
+        .long_help("Requests a read receipt by adding the Disposition-Notification-To header to the message.")
+        .long("ask-receipt")
+}
+
 /// Message attachment argument.
 fn attachment_arg<'a>() -> Arg<'a, 'a> {
     Arg::with_name("attachments")
@@ -274,80 +693,367 @@ pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
             SubCommand::with_name("attachments")
                 .aliases(&["attachment", "att", "a"])
                 .about("Downloads all message attachments")
-                .arg(msg_arg::seq_arg()),
+                .setting(AppSettings::SubcommandsNegateReqs)
+                .arg(msg_arg::seq_arg())
+                .arg(
+                    Arg::with_name("mime")
+                        .help("Downloads only attachments matching the MIME type glob")
+                        .long_help("Downloads only attachments whose MIME type matches the given glob, for example `image/*`.")
+                        .long("mime")
+                        .value_name("GLOB"),
+                )
+                .arg(
+                    Arg::with_name("name")
+                        .help("Downloads only attachments matching the file name glob")
+                        .long_help("Downloads only attachments whose file name matches the given glob, for example `*.pdf`.")
+                        .long("name")
+                        .value_name("GLOB"),
+                )
+                .subcommand(
+                    SubCommand::with_name("cat")
+                        .about("Writes an attachment to stdout")
+                        .long_about("Writes the decoded content of the attachment having the given file name to stdout, as is, so it can be piped to a viewer: `attachments cat 42 report.pdf | zathura -`.")
+                        .arg(msg_arg::seq_arg())
+                        .arg(
+                            Arg::with_name("name")
+                                .help("File name of the attachment")
+                                .value_name("NAME")
+                                .required(true),
+                        ),
+                ),
             SubCommand::with_name("list")
                 .aliases(&["lst", "l"])
                 .about("Lists all messages")
                 .arg(page_size_arg())
                 .arg(page_arg())
-                .arg(table_arg::max_width()),
+                .arg(table_arg::max_width())
+                .arg(
+                    all_accounts_arg()
+                        .conflicts_with_all(&["min-size", "max-size", "filter-header"]),
+                )
+                .args(&filter_args())
+                .args(&pick_args()),
             SubCommand::with_name("search")
+                .args(&pick_args())
                 .aliases(&["s", "query", "q"])
                 .about("Lists messages matching the given IMAP query")
                 .arg(page_size_arg())
                 .arg(page_arg())
                 .arg(table_arg::max_width())
+                .args(&filter_args())
                 .arg(
                     Arg::with_name("query")
                         .help("IMAP query")
                         .long_help("The IMAP query format follows the [RFC3501](https://tools.ietf.org/html/rfc3501#section-6.4.4). The query is case-insensitive.")
                         .value_name("QUERY")
                         .multiple(true)
-                        .required(true),
+                        .required_unless_one(&["keyword", "min-size", "max-size", "filter-header"]),
+                )
+                .arg(
+                    Arg::with_name("keyword")
+                        .help("Lists messages having the given keyword")
+                        .long_help("Lists messages having the given keyword, a custom flag like `$label1` or `Important`. Can be repeated, and combined with a query.")
+                        .short("k")
+                        .long("keyword")
+                        .value_name("KEYWORD")
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("all-mailboxes")
+                        .help("Searches in all the mailboxes")
+                        .long_help("Searches in all the mailboxes. A message living in several mailboxes (like Gmail labels) is listed once, along with the mailboxes it lives in, and its ID is relative to the first of them.")
+                        .long("all-mailboxes")
+                        .short("A")
+                        .conflicts_with("pick"),
                 ),
             SubCommand::with_name("write")
                 .about("Writes a new message")
-                .arg(attachment_arg()),
+                .arg(attachment_arg())
+                .arg(identity_arg())
+                .arg(ask_receipt_arg()),
             SubCommand::with_name("send")
                 .about("Sends a raw message")
+                .arg(
+                    Arg::with_name("send-at")
+                        .help("Schedules the sending at the given time")
+                        .long_help("Queues the message in the outbox folder instead of sending it, until the given time: a duration (`+2h`), a date with an optional time (`2021-12-01 08:00`), a day with an optional time (`tomorrow 9am`) or a time (`14:30`). Queued messages are sent by `queue flush --due`, run every minute by the daemon.")
+                        .long("send-at")
+                        .value_name("TIME"),
+                )
                 .arg(Arg::with_name("message").raw(true).last(true)),
+            SubCommand::with_name("queue")
+                .about("Handles the messages queued in the outbox folder")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("flush")
+                        .about("Sends the queued messages")
+                        .arg(
+                            Arg::with_name("due")
+                                .help("Sends only the messages whose scheduled time has come")
+                                .long("due"),
+                        ),
+                ),
             SubCommand::with_name("save")
                 .about("Saves a raw message")
                 .arg(Arg::with_name("message").raw(true)),
             SubCommand::with_name("read")
-                .about("Reads text bodies of a message")
-                .arg(seq_arg())
+                .about("Reads text bodies of messages")
+                .arg(
+                    seq_arg()
+                        .long_help("Specifies the targetted messages, either sequence numbers or ranges like `42:45`. Several messages are printed one after the other, with a separator.")
+                        .multiple(true),
+                )
                 .arg(
                     Arg::with_name("mime-type")
                         .help("MIME type to use")
                         .short("t")
                         .long("mime-type")
                         .value_name("MIME")
-                        .possible_values(&["plain", "html"])
-                        .default_value("plain"),
+                        .long_help("MIME type to use. With `auto`, the best displayable parts are picked from the structure of the message: plain text alternatives are preferred, HTML ones are converted to plain text otherwise.")
+                        .possible_values(&["auto", "plain", "html"])
+                        .default_value("auto"),
                 )
                 .arg(
                     Arg::with_name("raw")
                         .help("Reads raw message")
                         .long("raw")
                         .short("r"),
+                )
+                .arg(
+                    Arg::with_name("envelope-only")
+                        .help("Reads the envelope of messages only")
+                        .long_help("Reads the envelope headers of messages only. The body is not downloaded and the messages are not marked as seen.")
+                        .long("envelope-only")
+                        .short("e")
+                        .conflicts_with_all(&["raw", "export", "print"]),
+                )
+                .arg(
+                    Arg::with_name("preserve-flags")
+                        .help("Reads messages without marking them as seen")
+                        .long_help("Reads messages without marking them as seen, using BODY.PEEK[] fetches. Set the `peek` config option to make it the default.")
+                        .long("preserve-flags")
+                        .short("P"),
+                )
+                .arg(
+                    Arg::with_name("load-remote")
+                        .help("Loads the remote content of HTML parts")
+                        .long_help("Loads the remote content (like images) of HTML parts. By default HTML parts are sanitized and their remote content is blocked, so tracking pixels cannot tell the message was read.")
+                        .long("load-remote"),
+                )
+                .arg(
+                    Arg::with_name("concat-output")
+                        .help("Exports the messages to the given file")
+                        .long_help("Exports the messages to the given file instead of printing them, one after the other with a separator.")
+                        .long("concat-output")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::with_name("export")
+                        .help("Exports the messages to the downloads directory")
+                        .long_help("Exports each message with its headers to the downloads directory, as a styled HTML document or as a PDF document converted by the `export-pdf-cmd` command.")
+                        .long("export")
+                        .value_name("FORMAT")
+                        .possible_values(&["html", "pdf"])
+                        .conflicts_with_all(&["raw", "concat-output"]),
+                )
+                .arg(
+                    Arg::with_name("print")
+                        .help("Sends the messages to the printer")
+                        .long_help("Sends each message with its headers to the `print-cmd` command, the system print spooler by default.")
+                        .long("print")
+                        .conflicts_with_all(&["raw", "concat-output"]),
+                ),
+            SubCommand::with_name("pipe")
+                .about("Pipes a message to a command")
+                .long_about("Streams the raw message, or only one of its MIME parts, to the stdin of the given command.")
+                .arg(seq_arg())
+                .arg(
+                    Arg::with_name("part")
+                        .help("Pipes only the given MIME part")
+                        .long_help("Pipes only the given MIME part, either its position among the message parts (starting from 1) or its MIME type (eg. text/plain).")
+                        .long("part")
+                        .value_name("PART"),
+                )
+                .arg(
+                    Arg::with_name("command")
+                        .help("Command to pipe the message to")
+                        .value_name("COMMAND")
+                        .multiple(true)
+                        .required(true)
+                        .last(true),
                 ),
             SubCommand::with_name("reply")
                 .aliases(&["rep", "r"])
                 .about("Answers to a message")
                 .arg(seq_arg())
                 .arg(reply_all_arg())
-                .arg(attachment_arg()),
+                .arg(attachment_arg())
+                .arg(identity_arg())
+                .arg(ask_receipt_arg()),
+            SubCommand::with_name("edit")
+                .aliases(&["ed"])
+                .about("Edits a message as a new one")
+                .long_about("Loads an existing message, for example a bounced one or a template kept in a mailbox, into the editor as a brand-new message.")
+                .arg(seq_arg()),
             SubCommand::with_name("forward")
                 .aliases(&["fwd", "f"])
                 .about("Forwards a message")
                 .arg(seq_arg())
-                .arg(attachment_arg()),
+                .arg(attachment_arg())
+                .arg(identity_arg())
+                .arg(ask_receipt_arg()),
+            SubCommand::with_name("bounce")
+                .aliases(&["redirect", "resend"])
+                .about("Bounces a message")
+                .long_about("Resends a message unchanged to new recipients, using the Resent-* headers.")
+                .arg(seq_arg())
+                .arg(
+                    Arg::with_name("to")
+                        .help("Recipients of the bounced message")
+                        .short("t")
+                        .long("to")
+                        .value_name("ADDR")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .required(true),
+                ),
             SubCommand::with_name("copy")
                 .aliases(&["cp", "c"])
-                .about("Copies a message to the targetted mailbox")
-                .arg(seq_arg())
+                .about("Copies messages to the targetted mailbox")
+                .arg(seq_range_arg())
+                .arg(query_arg())
                 .arg(mbox_arg::target_arg()),
             SubCommand::with_name("move")
                 .aliases(&["mv"])
-                .about("Moves a message to the targetted mailbox")
-                .arg(seq_arg())
+                .about("Moves messages to the targetted mailbox")
+                .arg(seq_range_arg())
+                .arg(query_arg())
                 .arg(mbox_arg::target_arg()),
             SubCommand::with_name("delete")
                 .aliases(&["del", "d", "remove", "rm"])
-                .about("Deletes a message")
+                .about("Deletes messages")
+                .arg(seq_range_arg())
+                .arg(query_arg()),
+            SubCommand::with_name("snooze")
+                .about("Snoozes a message until a later time")
+                .long_about("Moves a message to the snoozed folder until the given time. Once the time has come, the `wake` command (run periodically by the daemon) moves it back to the inbox, marked as unseen.")
+                .arg(seq_arg())
+                .arg(
+                    Arg::with_name("until")
+                        .help("Specifies the time the message wakes up at")
+                        .long_help("Specifies the time the message wakes up at: a duration (`+2h`, `3d`), a date with an optional time (`2021-12-01 08:00`), a day with an optional time (`tomorrow 9am`, `monday`) or a time (`14:30`). Days without time mean 8am.")
+                        .long("until")
+                        .short("u")
+                        .value_name("TIME")
+                        .required(true),
+                ),
+            SubCommand::with_name("note")
+                .about("Shows, attaches or deletes the private note of a message")
+                .long_about("Shows the private note of a message, or attaches the given one. Notes are cached locally next to the config file, and stored as annotations on the server when it supports them (RFC5257).")
+                .arg(seq_arg())
+                .arg(
+                    Arg::with_name("note")
+                        .help("Specifies the note to attach")
+                        .value_name("NOTE")
+                        .multiple(true)
+                        .conflicts_with("delete"),
+                )
+                .arg(
+                    Arg::with_name("delete")
+                        .help("Deletes the note of the message")
+                        .long("delete")
+                        .short("d"),
+                ),
+            SubCommand::with_name("notes")
+                .about("Lists the cached notes, or searches them")
+                .arg(
+                    Arg::with_name("query")
+                        .help("Specifies the text to search in the notes and subjects")
+                        .value_name("QUERY"),
+                )
+                .arg(table_arg::max_width()),
+            SubCommand::with_name("thread")
+                .about("Shows the conversation of a message")
+                .long_about("Shows the conversation of a message: the messages linked to it by their Message-ID, In-Reply-To and References headers, sorted chronologically.")
+                .arg(seq_arg())
+                .arg(
+                    Arg::with_name("all-mailboxes")
+                        .help("Searches the conversation in all the mailboxes")
+                        .long("all-mailboxes")
+                        .short("A"),
+                )
+                .arg(
+                    Arg::with_name("collapse-quotes")
+                        .help("Collapses the quoted lines of the messages")
+                        .long("collapse-quotes")
+                        .short("q"),
+                ),
+            SubCommand::with_name("wake")
+                .about("Moves back the snoozed messages whose time has come to the inbox"),
+            SubCommand::with_name("unsubscribe")
+                .aliases(&["unsub"])
+                .about("Unsubscribes from the mailing list of a message")
+                .long_about("Unsubscribes from the mailing list of a message using its List-Unsubscribe header: the unsubscribe page is opened in the browser, or the unsubscribe message is sent.")
                 .arg(seq_arg()),
+            SubCommand::with_name("invite")
+                .aliases(&["inv", "rsvp"])
+                .about("Replies to the calendar invite of a message")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("accept")
+                        .about("Accepts the invite")
+                        .arg(seq_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("decline")
+                        .about("Declines the invite")
+                        .arg(seq_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("tentative")
+                        .aliases(&["maybe"])
+                        .about("Tentatively accepts the invite")
+                        .arg(seq_arg()),
+                ),
+            SubCommand::with_name("sent")
+                .about("Manages sent messages")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("status")
+                        .about("Shows the delivery status of the last sent messages")
+                        .long_about("Shows the delivery status (delivered, delayed, bounced or unknown) of the last messages of the sent folder, found in the delivery status notifications of the current mailbox.")
+                        .arg(
+                            Arg::with_name("max")
+                                .help("Defines the number of sent messages to check")
+                                .short("n")
+                                .long("max")
+                                .value_name("INT")
+                                .default_value("20"),
+                        ),
+                ),
         ],
     ]
     .concat()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_build_header_keys() {
+        assert_eq!(
+            r#"HEADER List-Id "rust-users""#,
+            header_key("List-Id=rust-users").unwrap()
+        );
+        assert_eq!(
+            r#"HEADER X-Tag "a=\"b\"""#,
+            header_key(r#"X-Tag=a="b""#).unwrap()
+        );
+        assert_eq!(r#"HEADER X-Spam """#, header_key("X-Spam=").unwrap());
+        assert!(header_key("List-Id").is_err());
+        assert!(header_key("=rust-users").is_err());
+        assert!(header_key("List Id=rust-users").is_err());
+    }
+}
@@ -3,8 +3,9 @@ use anyhow::{anyhow, Context, Error, Result};
 use chrono::{DateTime, FixedOffset};
 use html_escape;
 use imap::types::Flag;
-use lettre::message::{Attachment, MultiPart, SinglePart};
-use log::trace;
+use lettre::message::{header::ContentType, Attachment, MultiPart, SinglePart};
+use log::{debug, trace};
+use mailparse::MailHeaderMap;
 use regex::Regex;
 use rfc2047_decoder;
 use std::{
@@ -19,13 +20,18 @@ use crate::{
     domain::{
         imap::ImapServiceInterface,
         mbox::Mbox,
-        msg::{msg_utils, BinaryPart, Flags, Part, Parts, TextPlainPart, TplOverride},
+        msg::{
+            decode_header_value, flowed_utils, msg_utils, BinaryPart, DispositionNotificationTo,
+            Flags, Invite, Part, Parts, TextCalendarPart, TextPlainPart, TplOverride,
+            DISPOSITION_NOTIFICATION_TO,
+        },
         smtp::SmtpServiceInterface,
     },
-    output::PrinterService,
+    output::{format_size, run_cmd_with_args, PrinterService},
     ui::{
         choice::{self, PostEditChoice, PreEditChoice},
         editor,
+        i18n::tr,
     },
 };
 
@@ -53,11 +59,25 @@ pub struct Msg {
     pub in_reply_to: Option<String>,
     pub message_id: Option<String>,
 
+    /// The addresses asking for a read receipt.
+    ///
+    /// [RFC8098]: https://datatracker.ietf.org/doc/html/rfc8098#section-2.1
+    pub disposition_notification_to: Option<Vec<Addr>>,
+
     /// The internal date of the message.
     ///
     /// [RFC3501]: https://datatracker.ietf.org/doc/html/rfc3501#section-2.3.3
     pub date: Option<DateTime<FixedOffset>>,
     pub parts: Parts,
+
+    /// The displayable parts of the body, picked from the structure of the message. Empty when
+    /// the message was not fetched.
+    pub body_parts: Parts,
+
+    /// Whether the plain text part is sent as format=flowed.
+    ///
+    /// [RFC3676]: https://datatracker.ietf.org/doc/html/rfc3676
+    pub format_flowed: bool,
 }
 
 impl Msg {
@@ -71,53 +91,78 @@ impl Msg {
             .collect()
     }
 
+    /// Find the calendar invite of the message, from its first `text/calendar` part.
+    pub fn invite(&self) -> Option<Invite> {
+        self.parts.iter().find_map(|part| match part {
+            Part::TextCalendar(part) => Invite::from_ics(&part.content),
+            _ => None,
+        })
+    }
+
     /// Fold string body from all plain text parts into a single string body. If no plain text
     /// parts are found, HTML parts are used instead. The result is sanitized (all HTML markup is
     /// removed).
     pub fn fold_text_plain_parts(&self) -> String {
-        let (plain, html) = self.parts.iter().fold(
-            (String::default(), String::default()),
-            |(mut plain, mut html), part| {
+        self.fold_text_plain_parts_with(false)
+    }
+
+    /// Fold string body from all plain text parts, like `fold_text_plain_parts`. Inline images
+    /// are rendered as placeholders like `[image: photo.jpg]` if asked to.
+    fn fold_text_plain_parts_with(&self, placeholders: bool) -> String {
+        let push = |text: &mut String, content: &str| {
+            let glue = if text.is_empty() { "" } else { "\n\n" };
+            text.push_str(glue);
+            text.push_str(content);
+        };
+        let (plain, html, has_plain) = self.parts.iter().fold(
+            (String::default(), String::default(), false),
+            |(mut plain, mut html, mut has_plain), part| {
                 match part {
                     Part::TextPlain(part) => {
-                        let glue = if plain.is_empty() { "" } else { "\n\n" };
-                        plain.push_str(glue);
-                        plain.push_str(&part.content);
+                        push(&mut plain, &part.content);
+                        has_plain = has_plain || !part.content.is_empty();
                     }
-                    Part::TextHtml(part) => {
-                        let glue = if html.is_empty() { "" } else { "\n\n" };
-                        html.push_str(glue);
-                        html.push_str(&part.content);
+                    Part::TextHtml(part) => push(&mut html, &part.content),
+                    Part::Binary(part)
+                        if placeholders && part.inline && part.mime.starts_with("image/") =>
+                    {
+                        let placeholder = format!("[image: {}]", part.filename);
+                        push(&mut plain, &placeholder);
+                        push(&mut html, &placeholder);
                     }
                     _ => (),
                 };
-                (plain, html)
+                (plain, html, has_plain)
             },
         );
-        if plain.is_empty() {
-            // Remove HTML markup
-            let sanitized_html = ammonia::Builder::new()
-                .tags(HashSet::default())
-                .clean(&html)
-                .to_string();
-            // Replace `&nbsp;` by regular space
-            let sanitized_html = Regex::new(r"&nbsp;")
-                .unwrap()
-                .replace_all(&sanitized_html, " ")
-                .to_string();
-            // Merge new line chars
-            let sanitized_html = Regex::new(r"(\r?\n ?){2,}")
-                .unwrap()
-                .replace_all(&sanitized_html, "\n\n")
-                .to_string();
-            // Decode HTML entities
-            let sanitized_html = html_escape::decode_html_entities(&sanitized_html).to_string();
-            sanitized_html
+        if !has_plain {
+            html_to_text(&html)
         } else {
             plain
         }
     }
 
+    /// Fold string body from the displayable parts picked from the structure of the message:
+    /// plain text parts are kept as is, HTML parts are converted to plain text and inline images
+    /// are rendered as placeholders. Falls back to `fold_text_plain_parts` when the structure of
+    /// the message is unknown.
+    fn fold_best_text_parts(&self) -> String {
+        if self.body_parts.is_empty() {
+            return self.fold_text_plain_parts_with(true);
+        }
+        self.body_parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::TextPlain(part) => Some(part.content.to_owned()),
+                Part::TextHtml(part) => Some(html_to_text(&part.content)),
+                Part::Binary(part) => Some(format!("[image: {}]", part.filename)),
+                Part::TextCalendar(_) => None,
+            })
+            .filter(|text| !text.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
     /// Fold string body from all HTML parts into a single string body.
     fn fold_text_html_parts(&self) -> String {
         let text_parts = self
@@ -137,21 +182,32 @@ impl Msg {
     }
 
     /// Fold string body from all text parts into a single string body. The mime allows users to
-    /// choose between plain text parts and html text parts.
+    /// choose between plain text parts and html text parts, or to let the best parts be picked
+    /// with `auto`.
     pub fn fold_text_parts(&self, text_mime: &str) -> String {
-        if text_mime == "html" {
-            self.fold_text_html_parts()
-        } else {
-            self.fold_text_plain_parts()
+        match text_mime {
+            "html" => self.fold_text_html_parts(),
+            "auto" => self.fold_best_text_parts(),
+            _ => self.fold_text_plain_parts_with(true),
         }
     }
 
+    /// Wraps the plain text parts sent as format=flowed to the given width, like the width of the
+    /// terminal. The other parts keep the line breaks of the sender.
+    pub fn wrap_flowed_parts(&mut self, width: usize) {
+        self.parts.wrap_flowed(width);
+        self.body_parts.wrap_flowed(width);
+    }
+
     pub fn into_reply(mut self, all: bool, account: &Account) -> Result<Self> {
         let account_addr: Addr = account.address().parse()?;
 
         // Message-Id
         self.message_id = None;
 
+        // Disposition-Notification-To
+        self.disposition_notification_to = None;
+
         // In-Reply-To
         self.in_reply_to = self.message_id.to_owned();
 
@@ -193,26 +249,56 @@ impl Msg {
             let date = self
                 .date
                 .as_ref()
-                .map(|date| date.format("%d %b %Y, at %H:%M").to_string())
+                .map(|date| {
+                    account
+                        .date_fmt
+                        .format_with(&date.naive_local(), "%d %b %Y, at %H:%M")
+                })
                 .unwrap_or("unknown date".into());
-            let sender = self
-                .reply_to
-                .as_ref()
-                .or(self.from.as_ref())
+            let sender = self.reply_to.as_ref().or(self.from.as_ref());
+            let name = sender
                 .and_then(|addrs| addrs.first())
                 .map(|addr| addr.name.to_owned().unwrap_or(addr.email.to_string()))
                 .unwrap_or("unknown sender".into());
-            let mut content = format!("\n\nOn {}, {} wrote:\n", date, sender);
+            let email = sender
+                .and_then(|addrs| addrs.first())
+                .map(|addr| addr.email.to_string())
+                .unwrap_or_default();
+            let attribution = account
+                .reply_attribution
+                .replace("{{date}}", &date)
+                .replace("{{from}}", &name)
+                .replace("{{email}}", &email);
+            let mut content = format!("\n\n{}\n", attribution);
+
+            let has_plain_parts = self.parts.iter().any(|part| match part {
+                Part::TextPlain(_) => true,
+                _ => false,
+            });
+            let quoted = if has_plain_parts || account.reply_quote_html {
+                self.fold_text_plain_parts()
+            } else {
+                String::new()
+            };
+            let prefix = account.reply_quote_prefix.as_str();
+            let nested_prefix = prefix.trim_end();
 
             let mut glue = "";
-            for line in self.fold_text_parts("plain").trim().lines() {
-                if line == DEFAULT_SIG_DELIM {
+            for line in quoted.trim().lines() {
+                if account.reply_strip_signature && line == DEFAULT_SIG_DELIM.trim_end_matches('\n')
+                {
                     break;
                 }
                 content.push_str(glue);
-                content.push_str(">");
-                content.push_str(if line.starts_with(">") { "" } else { " " });
-                content.push_str(line);
+                if line.is_empty() {
+                    content.push_str(nested_prefix);
+                } else if !nested_prefix.is_empty() && line.starts_with(nested_prefix) {
+                    content.push_str(nested_prefix);
+                    content.push_str(line);
+                } else {
+                    content.push_str(prefix);
+                    content.push_str(line);
+                }
                 glue = "\n";
             }
 
@@ -235,6 +321,9 @@ impl Msg {
         // Message-Id
         self.message_id = None;
 
+        // Disposition-Notification-To
+        self.disposition_notification_to = None;
+
         // In-Reply-To
         self.in_reply_to = None;
 
@@ -284,8 +373,53 @@ impl Msg {
         }
         content.push_str("\n");
         content.push_str(&self.fold_text_parts("plain"));
-        self.parts
-            .replace_text_plain_parts_with(TextPlainPart { content });
+        self.parts.replace_text_plain_parts_with(TextPlainPart {
+            content,
+            flowed: false,
+        });
+
+        Ok(self)
+    }
+
+    /// Turn an existing message (a bounced one, a template kept in a mailbox…) into a brand-new
+    /// outgoing message. Recipients, subject, body and attachments are kept.
+    pub fn into_edit(mut self, account: &Account) -> Result<Self> {
+        let account_addr: Addr = account.address().parse()?;
+
+        // In-Reply-To, only kept when editing a reply draft: the new message does not answer the
+        // message the original one was replying to
+        if !self.flags.contains(&Flag::Draft) {
+            self.in_reply_to = None;
+        }
+
+        // Id, flags and date
+        self.id = 0;
+        self.flags = Flags::default();
+        self.date = None;
+
+        // Message-Id
+        self.message_id = None;
+
+        // Disposition-Notification-To
+        self.disposition_notification_to = None;
+
+        // From
+        self.from = Some(vec![account_addr]);
+
+        // Body, without the signature added back by the template
+        let content = self.fold_text_plain_parts();
+        let content = match account.sig.as_ref() {
+            Some(sig) => content
+                .trim_end()
+                .strip_suffix(sig.trim_end())
+                .map(|content| content.trim_end().to_owned())
+                .unwrap_or(content),
+            None => content,
+        };
+        self.parts.replace_text_plain_parts_with(TextPlainPart {
+            content,
+            flowed: false,
+        });
 
         Ok(self)
     }
@@ -296,6 +430,8 @@ impl Msg {
         Self::from_tpl(&tpl)
     }
 
+    /// Edits the message with the editor then asks what to do with it. Messages above the size
+    /// limit of the account cannot be sent unless forced.
     pub fn edit_with_editor<
         'a,
         Printer: PrinterService,
@@ -303,6 +439,8 @@ impl Msg {
         SmtpService: SmtpServiceInterface,
     >(
         mut self,
+        force: bool,
+        yes: bool,
         account: &Account,
         printer: &mut Printer,
         imap: &mut ImapService,
@@ -337,12 +475,43 @@ impl Msg {
         loop {
             match choice::post_edit() {
                 Ok(PostEditChoice::Send) => {
-                    let mbox = Mbox::new("Sent");
+                    self.format_flowed = account.format_flowed;
+                    let size = self.size()?;
+                    let checks =
+                        msg_utils::check_recipients(&self, force, account).and_then(|warnings| {
+                            let info = msg_utils::check_msg_size(size, force, account)?;
+                            let warnings = warnings
+                                .into_iter()
+                                .chain(msg_utils::check_attachments(&self, account))
+                                .collect::<Vec<_>>();
+                            Ok((warnings, info))
+                        });
+                    // A failed check (like the size limit) is reported, then the user can edit the
+                    // message again.
+                    let (mut lines, info) = match checks {
+                        Ok(checks) => checks,
+                        Err(err) => {
+                            eprintln!("{}: {:#}", tr("error"), err);
+                            continue;
+                        }
+                    };
+                    // Nobody can confirm without a terminal, like in the REPL over a pipe.
+                    if !yes && printer.is_interactive() {
+                        lines.insert(0, msg_utils::format_send_summary(&self));
+                        printer.print(lines.join("\n"))?;
+                        if !choice::confirm(&tr("confirm-send")).unwrap_or_default() {
+                            continue;
+                        }
+                        lines.clear();
+                    }
+                    lines.push(info);
+                    printer.print(lines.join("\n"))?;
+                    let mbox = Mbox::new(&account.sent_folder);
                     let sent_msg = smtp.send_msg(&self)?;
                     let flags = Flags::try_from(vec![Flag::Seen])?;
                     imap.append_raw_msg_with_flags(&mbox, &sent_msg.formatted(), flags)?;
                     msg_utils::remove_local_draft()?;
-                    printer.print("Message successfully sent")?;
+                    printer.print(tr("msg-sent"))?;
                     break;
                 }
                 Ok(PostEditChoice::Edit) => {
@@ -350,7 +519,7 @@ impl Msg {
                     continue;
                 }
                 Ok(PostEditChoice::LocalDraft) => {
-                    printer.print("Message successfully saved locally")?;
+                    printer.print(tr("msg-saved-locally"))?;
                     break;
                 }
                 Ok(PostEditChoice::RemoteDraft) => {
@@ -359,7 +528,7 @@ impl Msg {
                     let tpl = self.to_tpl(TplOverride::default(), account);
                     imap.append_raw_msg_with_flags(&mbox, tpl.as_bytes(), flags)?;
                     msg_utils::remove_local_draft()?;
-                    printer.print("Message successfully saved to Drafts")?;
+                    printer.print(tr("msg-saved-drafts"))?;
                     break;
                 }
                 Ok(PostEditChoice::Discard) => {
@@ -376,7 +545,29 @@ impl Msg {
         Ok(())
     }
 
-    pub fn add_attachments(mut self, attachments_paths: Vec<&str>) -> Result<Self> {
+    /// Returns the size in bytes of the message, once encoded.
+    pub fn size(&self) -> Result<usize> {
+        let msg: lettre::Message = self.try_into()?;
+        Ok(msg.formatted().len())
+    }
+
+    /// Request a read receipt to the account address.
+    pub fn ask_receipt(mut self, ask_receipt: bool, account: &Account) -> Result<Self> {
+        if ask_receipt {
+            self.disposition_notification_to = Some(vec![account.address().parse()?]);
+        }
+        Ok(self)
+    }
+
+    /// Attaches the given files. When the account defines an upload command, files above the
+    /// upload threshold are uploaded instead and their share URL is appended to the body.
+    pub fn add_attachments(
+        mut self,
+        attachments_paths: Vec<&str>,
+        account: &Account,
+    ) -> Result<Self> {
+        let mut links = vec![];
+
         for path in attachments_paths {
             let path = shellexpand::full(path)
                 .context(format!(r#"cannot expand attachment path "{}""#, path))?;
@@ -387,15 +578,53 @@ impl Msg {
                 .to_string_lossy()
                 .into();
             let content = fs::read(&path).context(format!("cannot read attachment {:?}", path))?;
+
+            if let Some(cmd) = account.attachment_upload_cmd.as_ref() {
+                if content.len() > account.attachment_upload_threshold * 1_000_000 {
+                    debug!("upload attachment {:?}", path);
+                    let url = run_cmd_with_args(cmd, &[path.to_string_lossy().as_ref()])
+                        .context(format!(r#"cannot run attachment upload command "{}""#, cmd))?;
+                    let url = url.trim();
+                    if url.is_empty() {
+                        return Err(anyhow!(
+                            r#"attachment upload command "{}" did not print any URL"#,
+                            cmd
+                        ));
+                    }
+                    links.push(format!(
+                        "{} ({}): {}",
+                        filename,
+                        format_size(content.len()),
+                        url
+                    ));
+                    continue;
+                }
+            }
+
             let mime = tree_magic::from_u8(&content);
 
             self.parts.push(Part::Binary(BinaryPart {
                 filename,
                 mime,
                 content,
+                inline: false,
             }))
         }
 
+        if !links.is_empty() {
+            let body = self.fold_text_plain_parts();
+            let links = links.join("\n");
+            let content = if body.trim().is_empty() {
+                links
+            } else {
+                format!("{}\n\n{}", body.trim_end(), links)
+            };
+            self.parts.replace_text_plain_parts_with(TextPlainPart {
+                content,
+                flowed: false,
+            });
+        }
+
         Ok(self)
     }
 
@@ -416,13 +645,17 @@ impl Msg {
             self.bcc = msg.bcc;
         }
 
+        if msg.disposition_notification_to.is_some() {
+            self.disposition_notification_to = msg.disposition_notification_to;
+        }
+
         if !msg.subject.is_empty() {
             self.subject = msg.subject;
         }
 
         for part in msg.parts.0.into_iter() {
             match part {
-                Part::Binary(_) => self.parts.push(part),
+                Part::Binary(_) | Part::TextCalendar(_) => self.parts.push(part),
                 Part::TextPlain(_) => {
                     self.parts.retain(|p| match p {
                         Part::TextPlain(_) => false,
@@ -497,6 +730,19 @@ impl Msg {
             tpl.push_str(&format!("Bcc: {}\n", addrs));
         }
 
+        // Disposition-Notification-To
+        if let Some(addrs) = self.disposition_notification_to.as_ref() {
+            tpl.push_str(&format!(
+                "{}: {}\n",
+                DISPOSITION_NOTIFICATION_TO,
+                addrs
+                    .iter()
+                    .map(|addr| addr.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
         // Subject
         tpl.push_str(&format!(
             "Subject: {}\n",
@@ -584,6 +830,13 @@ impl Msg {
                 "Subject" | _ if key.eq_ignore_ascii_case("subject") => {
                     msg.subject = val;
                 }
+                _ if key.eq_ignore_ascii_case(DISPOSITION_NOTIFICATION_TO) => {
+                    msg.disposition_notification_to = Some(
+                        val.split(',')
+                            .filter_map(|addr| addr.parse().ok())
+                            .collect::<Vec<_>>(),
+                    );
+                }
                 _ => (),
             }
         }
@@ -592,7 +845,10 @@ impl Msg {
             .get_body_raw()
             .context("cannot get body from parsed message")?;
         let content = String::from_utf8(content).context("cannot decode body from utf-8")?;
-        msg.parts.push(Part::TextPlain(TextPlainPart { content }));
+        msg.parts.push(Part::TextPlain(TextPlainPart {
+            content,
+            flowed: false,
+        }));
 
         Ok(msg)
     }
@@ -659,8 +915,59 @@ impl TryInto<lettre::Message> for &Msg {
                 .fold(msg_builder, |builder, addr| builder.bcc(addr.to_owned()))
         };
 
-        let mut multipart =
-            MultiPart::mixed().singlepart(SinglePart::plain(self.fold_text_plain_parts()));
+        if let Some(addrs) = self.disposition_notification_to.as_ref() {
+            msg_builder = msg_builder.header(DispositionNotificationTo(
+                addrs
+                    .iter()
+                    .map(|addr| addr.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ));
+        };
+
+        let calendar_parts: Vec<&TextCalendarPart> = self
+            .parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::TextCalendar(part) => Some(part),
+                _ => None,
+            })
+            .collect();
+        let plain_part = if self.format_flowed {
+            SinglePart::builder()
+                .header::<ContentType>(
+                    "text/plain; charset=utf-8; format=flowed"
+                        .parse()
+                        .context("cannot parse content type of plain part")?,
+                )
+                .body(flowed_utils::flow(
+                    &self.fold_text_plain_parts(),
+                    flowed_utils::FLOWED_WIDTH,
+                ))
+        } else {
+            SinglePart::plain(self.fold_text_plain_parts())
+        };
+        let mut multipart = if calendar_parts.is_empty() {
+            MultiPart::mixed().singlepart(plain_part)
+        } else {
+            // Calendar parts are alternatives of the plain text part, as described in the
+            // [iMIP](https://datatracker.ietf.org/doc/html/rfc6047#section-2.4).
+            let mut alternative = MultiPart::alternative().singlepart(plain_part);
+            for part in calendar_parts {
+                let method = Invite::from_ics(&part.content)
+                    .and_then(|invite| invite.method)
+                    .unwrap_or_else(|| String::from("PUBLISH"));
+                let ctype = format!("text/calendar; charset=utf-8; method={}", method)
+                    .parse()
+                    .context("cannot parse content type of calendar part")?;
+                alternative = alternative.singlepart(
+                    SinglePart::builder()
+                        .header::<ContentType>(ctype)
+                        .body(part.content.to_owned()),
+                );
+            }
+            MultiPart::mixed().multipart(alternative)
+        };
 
         for part in self.attachments() {
             let filename = part.filename;
@@ -705,13 +1012,8 @@ impl<'a> TryFrom<&'a imap::types::Fetch> for Msg {
         let subject = envelope
             .subject
             .as_ref()
-            .map(|subj| {
-                rfc2047_decoder::decode(subj).context(format!(
-                    "cannot decode subject of message {}",
-                    fetch.message
-                ))
-            })
-            .unwrap_or(Ok(String::default()))?;
+            .map(|subj| decode_header_value(subj))
+            .unwrap_or_default();
 
         // Get the sender(s) address(es)
         let from = match envelope
@@ -765,15 +1067,26 @@ impl<'a> TryFrom<&'a imap::types::Fetch> for Msg {
         // Get the internal date
         let date = fetch.internal_date();
 
+        let parsed_mail = mailparse::parse_mail(
+            fetch
+                .body()
+                .ok_or(anyhow!("cannot get body of message {}", id))?,
+        )
+        .context(format!("cannot parse body of message {}", id))?;
+
+        // Get the address(es) asking for a read receipt
+        let disposition_notification_to = parsed_mail
+            .headers
+            .get_first_value(DISPOSITION_NOTIFICATION_TO)
+            .map(|val| {
+                val.split(',')
+                    .filter_map(|addr| addr.trim().parse().ok())
+                    .collect::<Vec<_>>()
+            });
+
         // Get all parts
-        let parts = Parts::from(
-            &mailparse::parse_mail(
-                fetch
-                    .body()
-                    .ok_or(anyhow!("cannot get body of message {}", id))?,
-            )
-            .context(format!("cannot parse body of message {}", id))?,
-        );
+        let parts = Parts::from(&parsed_mail);
+        let body_parts = Parts::best_of(&parsed_mail);
 
         Ok(Self {
             id,
@@ -783,15 +1096,40 @@ impl<'a> TryFrom<&'a imap::types::Fetch> for Msg {
             from,
             reply_to,
             in_reply_to,
+            disposition_notification_to,
             to,
             cc,
             bcc,
             date,
             parts,
+            body_parts,
+            format_flowed: false,
         })
     }
 }
 
+/// Converts the given HTML to plain text: the markup is removed, blank lines are merged and HTML
+/// entities are decoded.
+fn html_to_text(html: &str) -> String {
+    // Remove HTML markup
+    let text = ammonia::Builder::new()
+        .tags(HashSet::default())
+        .clean(html)
+        .to_string();
+    // Replace `&nbsp;` by regular space
+    let text = Regex::new(r"&nbsp;")
+        .unwrap()
+        .replace_all(&text, " ")
+        .to_string();
+    // Merge new line chars
+    let text = Regex::new(r"(\r?\n ?){2,}")
+        .unwrap()
+        .replace_all(&text, "\n\n")
+        .to_string();
+    // Decode HTML entities
+    html_escape::decode_html_entities(&text).to_string()
+}
+
 pub fn parse_addr(addr: &imap_proto::Address) -> Result<Addr> {
     let name = addr
         .name
@@ -820,9 +1158,13 @@ pub fn parse_addr(addr: &imap_proto::Address) -> Result<Addr> {
     Ok(Addr::new(name, lettre::Address::new(mbox, host)?))
 }
 
+/// Parses a list of addresses. The group syntax markers ([RFC3501]), which have no host, are
+/// skipped so only the addresses of the groups are kept.
+///
+/// [RFC3501]: https://datatracker.ietf.org/doc/html/rfc3501#section-7.4.2
 pub fn parse_addrs(addrs: &Vec<imap_proto::Address>) -> Result<Vec<Addr>> {
     let mut parsed_addrs = vec![];
-    for addr in addrs {
+    for addr in addrs.iter().filter(|addr| addr.host.is_some()) {
         parsed_addrs
             .push(parse_addr(addr).context(format!(r#"cannot parse address "{:?}""#, addr))?);
     }
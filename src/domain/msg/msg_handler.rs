@@ -2,37 +2,68 @@
 //!
 //! This module gathers all message commands.  
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use atty::Stream;
+use chrono::{Local, Utc};
 use imap::types::Flag;
-use log::{debug, trace};
+use log::{debug, trace, warn};
 use std::{
     borrow::Cow,
+    collections::HashMap,
     convert::{TryFrom, TryInto},
     fs,
-    io::{self, BufRead},
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    thread,
 };
 use url::Url;
 
 use crate::{
-    config::Account,
+    config::{Account, Config},
     domain::{
-        imap::ImapServiceInterface,
+        hook::{hook_handler, Hook, HookMsg, Hooks},
+        imap::{ImapService, ImapServiceInterface},
         mbox::Mbox,
-        msg::{Flags, Msg, Part, TextPlainPart},
+        msg::{
+            collapse_quotes, compress_seqs, html_utils, msg_utils, parse_schedule_flag,
+            parse_schedule_time, schedule_flag, thread_ids, thread_query, AccountEnvelope,
+            AccountEnvelopes, Dsn, DsnStatus, Envelopes, ExportFormat, Flags, InviteReply,
+            MboxEnvelopes, Mdn, MdnPolicy, Msg, Note, NotesCache, Part, Selection, SentStatus,
+            SentStatuses, TextCalendarPart, TextPlainPart, Thread, ThreadMsg, DSN_QUERY,
+            MDN_SENT_FLAG, SEND_AT_FLAG_PREFIX, SNOOZED_FLAG_PREFIX, THREAD_SEARCH_PASSES,
+        },
         smtp::SmtpServiceInterface,
     },
-    output::{PrintTableOpts, PrinterService},
+    output::{
+        open_url, pick_lines, pipe_cmd, run_cmd_with_args, ExitCode, PrintTableOpts, PrinterService,
+    },
+    ui::{
+        choice,
+        i18n::{tr, tr_args},
+    },
 };
 
-/// Download all message attachments to the user account downloads directory.
+/// Download the message attachments to the user account downloads directory. Only attachments
+/// matching the given MIME type and file name globs are downloaded.
 pub fn attachments<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
     seq: &str,
+    mime: Option<&str>,
+    name: Option<&str>,
     account: &Account,
     printer: &mut Printer,
     imap: &mut ImapService,
 ) -> Result<()> {
-    let attachments = imap.find_msg(&seq)?.attachments();
+    let attachments: Vec<_> = imap
+        .find_msg(&seq)?
+        .attachments()
+        .into_iter()
+        .filter(|attachment| {
+            mime.map_or(true, |mime| msg_utils::glob_match(mime, &attachment.mime))
+                && name.map_or(true, |name| {
+                    msg_utils::glob_match(name, &attachment.filename)
+                })
+        })
+        .collect();
     let attachments_len = attachments.len();
     debug!(
         r#"{} attachment(s) found for message "{}""#,
@@ -46,39 +77,180 @@ pub fn attachments<'a, Printer: PrinterService, ImapService: ImapServiceInterfac
             .context(format!("cannot download attachment {:?}", filepath))?;
     }
 
-    printer.print(format!(
-        "{} attachment(s) successfully downloaded to {:?}",
-        attachments_len, account.downloads_dir
+    printer.print(tr_args(
+        "attachments-downloaded",
+        &[
+            ("count", &attachments_len.to_string()),
+            ("dir", &account.downloads_dir.to_string_lossy()),
+        ],
     ))
 }
 
-/// Copy a message from a mailbox to another.
-pub fn copy<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+/// Write the decoded content of the attachment of the given message having the given file name
+/// to stdout, as is. A reader closing the pipe early is not an error.
+pub fn attachment_cat<'a, ImapService: ImapServiceInterface<'a>>(
+    seq: &str,
+    name: &str,
+    imap: &mut ImapService,
+) -> Result<()> {
+    let attachments = imap.find_msg(&seq)?.attachments();
+    let attachment = attachments
+        .iter()
+        .find(|attachment| attachment.filename == name)
+        .ok_or_else(|| {
+            let names: Vec<_> = attachments
+                .iter()
+                .map(|attachment| attachment.filename.as_str())
+                .collect();
+            ExitCode::NotFound.err(format!(
+                r#"cannot find attachment "{}" in message {} (found: {})"#,
+                name,
+                seq,
+                names.join(", ")
+            ))
+        })?;
+    debug!("writing {} ({} bytes)…", name, attachment.content.len());
+
+    let mut stdout = io::stdout();
+    match stdout
+        .write_all(&attachment.content)
+        .and_then(|()| stdout.flush())
+    {
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        res => res.context(format!(r#"cannot write attachment "{}" to stdout"#, name)),
+    }
+}
+
+/// Bounce a message unchanged to the given recipients.
+pub fn bounce<
+    'a,
+    Printer: PrinterService,
+    ImapService: ImapServiceInterface<'a>,
+    SmtpService: SmtpServiceInterface,
+>(
     seq: &str,
+    to: Vec<&str>,
+    account: &Account,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+    smtp: &mut SmtpService,
+) -> Result<()> {
+    let recipients = to
+        .iter()
+        .map(|addr| {
+            addr.parse::<lettre::message::Mailbox>()
+                .map(|addr| addr.email)
+                .context(format!(r#"cannot parse recipient "{}""#, addr))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let envelope =
+        lettre::address::Envelope::new(Some(account.email.parse::<lettre::Address>()?), recipients)
+            .context("cannot create bounce envelope")?;
+
+    let raw_msg = imap.find_raw_msg(seq)?;
+    let resent_msg =
+        msg_utils::prepend_resent_headers(&raw_msg, &account.address(), &account.email, &to);
+    smtp.send_raw_msg(&envelope, &resent_msg)?;
+    debug!("message bounced!");
+
+    printer.print(tr_args(
+        "msg-bounced",
+        &[("seq", seq), ("to", &to.join(", "))],
+    ))
+}
+
+/// Copy messages matching the given selection from a mailbox to another.
+pub fn copy<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    selection: Selection,
     mbox: &str,
     printer: &mut Printer,
     imap: &mut ImapService,
 ) -> Result<()> {
     let mbox = Mbox::new(mbox);
-    let msg = imap.find_raw_msg(&seq)?;
-    let flags = Flags::try_from(vec![Flag::Seen])?;
-    imap.append_raw_msg_with_flags(&mbox, &msg, flags)?;
-    printer.print(format!(
-        r#"Message {} successfully copied to folder "{}""#,
-        seq, mbox
+    let seq_range = selection.to_seq_range(imap)?;
+    // Copies are marked as seen, like the moved messages
+    imap.add_flags(&seq_range, &Flags::try_from(vec![Flag::Seen])?)?;
+    imap.copy_msgs(&seq_range, &mbox)?;
+    printer.print(tr_args(
+        "msgs-copied",
+        &[("seqs", &seq_range), ("mbox", &mbox.to_string())],
     ))
 }
 
-/// Delete messages matching the given sequence range.
+/// Delete messages matching the given selection.
 pub fn delete<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
-    seq: &str,
+    selection: Selection,
+    dry_run: bool,
+    yes: bool,
+    account: &Account,
     printer: &mut Printer,
     imap: &mut ImapService,
 ) -> Result<()> {
+    let seq_range = selection.to_seq_range(imap)?;
+    if dry_run {
+        return printer.print(tr_args("msgs-would-delete", &[("seqs", &seq_range)]));
+    }
+    if !yes && !confirm_deletion(&seq_range, account, printer, imap)? {
+        return printer.print(tr("deletion-aborted"));
+    }
     let flags = Flags::try_from(vec![Flag::Seen, Flag::Deleted])?;
-    imap.add_flags(seq, &flags)?;
+    imap.add_flags(&seq_range, &flags)?;
     imap.expunge()?;
-    printer.print(format!(r#"Message(s) {} successfully deleted"#, seq))
+    printer.print(tr_args("msgs-deleted", &[("seqs", &seq_range)]))
+}
+
+/// Ask for a confirmation when more messages than the account threshold would be expunged from
+/// the current mailbox. Without a terminal to ask, the deletion is refused.
+pub fn confirm_deletion<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    seq_range: &str,
+    account: &Account,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+) -> Result<bool> {
+    let count = imap.search_seqs(seq_range)?.len();
+    debug!("{} message(s) to expunge", count);
+    if count <= account.confirm_threshold {
+        return Ok(true);
+    }
+
+    if !printer.is_interactive() || printer.is_structured() {
+        return Err(anyhow!(tr_args(
+            "confirmation-required",
+            &[("count", &count.to_string())]
+        )));
+    }
+
+    choice::confirm(&tr_args(
+        "confirm-expunge",
+        &[("count", &count.to_string())],
+    ))
+}
+
+/// Edit the given message as a new one, sent with the identity it was sent from.
+pub fn edit<
+    'a,
+    Printer: PrinterService,
+    ImapService: ImapServiceInterface<'a>,
+    SmtpService: SmtpServiceInterface,
+>(
+    seq: &str,
+    force: bool,
+    yes: bool,
+    account: &Account,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+    smtp: &mut SmtpService,
+) -> Result<()> {
+    let msg = imap.find_msg(seq)?;
+    let senders: Vec<String> = msg
+        .from
+        .iter()
+        .flatten()
+        .map(|addr| addr.email.to_string())
+        .collect();
+    let account = account.with_matching_identity(senders.iter().map(String::as_str));
+    msg.into_edit(&account)?
+        .edit_with_editor(force, yes, &account, printer, imap, smtp)
 }
 
 /// Forward the given message UID from the selected mailbox.
@@ -90,15 +262,76 @@ pub fn forward<
 >(
     seq: &str,
     attachments_paths: Vec<&str>,
+    identity: Option<&str>,
+    ask_receipt: bool,
+    force: bool,
+    yes: bool,
     account: &Account,
     printer: &mut Printer,
     imap: &mut ImapService,
     smtp: &mut SmtpService,
 ) -> Result<()> {
+    let account = match identity {
+        Some(email) => account.with_identity(email)?,
+        None => account.clone(),
+    };
     imap.find_msg(seq)?
-        .into_forward(account)?
-        .add_attachments(attachments_paths)?
-        .edit_with_editor(account, printer, imap, smtp)
+        .into_forward(&account)?
+        .ask_receipt(ask_receipt, &account)?
+        .add_attachments(attachments_paths, &account)?
+        .edit_with_editor(force, yes, &account, printer, imap, smtp)
+}
+
+/// Reply to the calendar invite of the given message, by sending an [iTIP] reply to the organizer.
+///
+/// [iTIP]: https://datatracker.ietf.org/doc/html/rfc5546#section-3.2.3
+pub fn invite<
+    'a,
+    Printer: PrinterService,
+    ImapService: ImapServiceInterface<'a>,
+    SmtpService: SmtpServiceInterface,
+>(
+    seq: &str,
+    reply: InviteReply,
+    account: &Account,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+    smtp: &mut SmtpService,
+) -> Result<()> {
+    let invite = imap.find_msg(seq)?.invite().ok_or_else(|| {
+        ExitCode::NotFound.err(format!(
+            r#"cannot find calendar invite in message "{}""#,
+            seq
+        ))
+    })?;
+    let organizer = invite
+        .organizer()
+        .ok_or_else(|| anyhow!(r#"cannot find organizer of invite "{}""#, seq))?;
+    let summary = invite.summary().unwrap_or_default();
+
+    // Reply with the identity invited, if any
+    let account = account.with_matching_identity(invite.attendees());
+
+    let mut msg = Msg::default();
+    msg.from = Some(vec![account.address().parse()?]);
+    msg.to = Some(vec![organizer
+        .parse()
+        .context(format!(r#"cannot parse organizer "{}""#, organizer))?]);
+    msg.subject = format!("{}: {}", reply.subject_prefix(), summary);
+    msg.parts.push(Part::TextPlain(TextPlainPart {
+        content: msg.subject.to_owned(),
+        flowed: false,
+    }));
+    msg.parts.push(Part::TextCalendar(TextCalendarPart {
+        content: invite.to_reply(reply, &account.from, &account.email),
+    }));
+    let sent_msg = smtp.send_msg(&msg)?;
+    debug!("invite reply sent!");
+
+    let mbox = Mbox::new(&account.sent_folder);
+    let flags = Flags::try_from(vec![Flag::Seen])?;
+    imap.append_raw_msg_with_flags(&mbox, &sent_msg.formatted(), flags)?;
+    printer.print(format!("Invite reply sent to {}", organizer))
 }
 
 /// List paginated messages from the selected mailbox.
@@ -113,11 +346,118 @@ pub fn list<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
     let page_size = page_size.unwrap_or(account.default_page_size);
     trace!("page size: {}", page_size);
 
-    let msgs = imap.fetch_envelopes(&page_size, &page)?;
+    let msgs = imap
+        .fetch_envelopes(&page_size, &page)?
+        .with_highlight(account.envelope_highlight)
+        .with_date(account.list_date)
+        .with_date_fmt(&account.date_fmt);
     trace!("messages: {:#?}", msgs);
     printer.print_table(msgs, PrintTableOpts { max_width })
 }
 
+/// Pick messages of the current page, or of the page of messages matching the query, with the
+/// picker command of the account. Returns the sequence range of the picked messages, if any.
+pub fn pick<'a, ImapService: ImapServiceInterface<'a>>(
+    query: Option<String>,
+    page_size: Option<usize>,
+    page: usize,
+    account: &Account,
+    imap: &'a mut ImapService,
+) -> Result<Option<String>> {
+    let page_size = page_size.unwrap_or(account.default_page_size);
+    trace!("page size: {}", page_size);
+
+    let msgs = match query {
+        Some(query) => imap.fetch_envelopes_with(&query, &page_size, &page)?,
+        None => imap.fetch_envelopes(&page_size, &page)?,
+    }
+    .with_date(account.list_date)
+    .with_date_fmt(&account.date_fmt);
+    let lines: Vec<String> = msgs
+        .iter()
+        .map(|msg| {
+            format!(
+                "{}\t{}\t{}\t{}\t{}",
+                msg.id,
+                msg.flags.to_symbols_string(),
+                msg.subject,
+                msg.sender,
+                msg.formatted_date
+                    .as_deref()
+                    .or_else(|| msg.date.as_deref())
+                    .unwrap_or_default()
+            )
+        })
+        .collect();
+
+    let cmd: Vec<&str> = account.picker_cmd.split_whitespace().collect();
+    let mut seqs: Vec<u32> = pick_lines(&cmd, &lines)?
+        .iter()
+        .filter_map(|line| line.split('\t').next()?.trim().parse().ok())
+        .collect();
+    trace!("picked seqs: {:?}", seqs);
+    if seqs.is_empty() {
+        return Ok(None);
+    }
+    seqs.sort_unstable();
+    Ok(Some(compress_seqs(&seqs)))
+}
+
+/// List paginated messages of all configured accounts. Each account gets its own thread and its
+/// own IMAP session, then envelopes are merged and sorted by date.
+pub fn list_all_accounts<Printer: PrinterService>(
+    max_width: Option<usize>,
+    page_size: Option<usize>,
+    page: usize,
+    mbox: &Mbox,
+    config: &Config,
+    printer: &mut Printer,
+) -> Result<()> {
+    let mut names: Vec<&String> = config.accounts.keys().collect();
+    names.sort();
+
+    let gmail_ids = printer.is_structured();
+    let mut handles = Vec::with_capacity(names.len());
+    for name in names {
+        let account = Account::try_from((config, Some(name.as_str())))?;
+        let mbox = mbox.name.to_string();
+        let page_size = page_size.unwrap_or(account.default_page_size);
+        trace!(r#"page size of account "{}": {}"#, account.name, page_size);
+        handles.push(thread::spawn(move || -> Result<Vec<AccountEnvelope>> {
+            let mbox = Mbox::new(&mbox);
+            let mut imap = ImapService::from((&account, &mbox)).with_gmail_ids(gmail_ids);
+            let envelopes = imap
+                .fetch_envelopes(&page_size, &page)
+                .context(format!(
+                    r#"cannot list messages of account "{}""#,
+                    account.name
+                ))?
+                .with_highlight(account.envelope_highlight)
+                .with_date(account.list_date)
+                .with_date_fmt(&account.date_fmt)
+                .0
+                .into_iter()
+                .map(|envelope| AccountEnvelope::from((account.name.as_str(), envelope)))
+                .collect();
+            imap.logout()?;
+            Ok(envelopes)
+        }));
+    }
+
+    let mut envelopes = vec![];
+    for handle in handles {
+        let account_envelopes = handle
+            .join()
+            .map_err(|_| anyhow!("cannot join account thread"))??;
+        envelopes.extend(account_envelopes);
+    }
+    // Accounts may live in different time zones, so dates are compared as instants
+    envelopes.sort_by(|a, b| b.envelope.timestamp.cmp(&a.envelope.timestamp));
+    trace!("envelopes: {:#?}", envelopes);
+
+    printer.print_table(AccountEnvelopes(envelopes), PrintTableOpts { max_width })
+}
+
 /// Parse and edit a message from a [mailto] URL string.
 ///
 /// [mailto]: https://en.wikipedia.org/wiki/Mailto
@@ -128,11 +468,20 @@ pub fn mailto<
     SmtpService: SmtpServiceInterface,
 >(
     url: &Url,
+    force: bool,
+    yes: bool,
     account: &Account,
     printer: &mut Printer,
     imap: &mut ImapService,
     smtp: &mut SmtpService,
 ) -> Result<()> {
+    mailto_msg(url, account)?.edit_with_editor(force, yes, account, printer, imap, smtp)
+}
+
+/// Build a message from a [mailto] URL string.
+///
+/// [mailto]: https://en.wikipedia.org/wiki/Mailto
+fn mailto_msg(url: &Url, account: &Account) -> Result<Msg> {
     let to: Vec<lettre::message::Mailbox> = url
         .path()
         .split(";")
@@ -170,52 +519,561 @@ pub fn mailto<
     msg.subject = subject.into();
     msg.parts.push(Part::TextPlain(TextPlainPart {
         content: body.into(),
+        flowed: false,
     }));
-    msg.edit_with_editor(account, printer, imap, smtp)
+    Ok(msg)
 }
 
-/// Move a message from a mailbox to another.
+/// Move messages matching the given selection from a mailbox to another.
 pub fn move_<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
-    // The sequence number of the message to move
-    seq: &str,
-    // The mailbox to move the message in
+    // The messages to move
+    selection: Selection,
+    // The mailbox to move the messages in
     mbox: &str,
+    // Prints what would be done without moving anything
+    dry_run: bool,
+    // Skips the confirmation
+    yes: bool,
+    account: &Account,
     printer: &mut Printer,
     imap: &mut ImapService,
 ) -> Result<()> {
-    // Copy the message to targetted mailbox
     let mbox = Mbox::new(mbox);
-    let msg = imap.find_raw_msg(&seq)?;
-    let flags = Flags::try_from(vec![Flag::Seen])?;
-    imap.append_raw_msg_with_flags(&mbox, &msg, flags)?;
+    let seq_range = selection.to_seq_range(imap)?;
+    if dry_run {
+        return printer.print(tr_args(
+            "msgs-would-move",
+            &[("seqs", &seq_range), ("mbox", &mbox.to_string())],
+        ));
+    }
+    if !yes && !confirm_deletion(&seq_range, account, printer, imap)? {
+        return printer.print(tr("move-aborted"));
+    }
+
+    // Copy the messages to targetted mailbox
+    imap.copy_msgs(&seq_range, &mbox)?;
 
-    // Delete the original message
+    // Delete the original messages
     let flags = Flags::try_from(vec![Flag::Seen, Flag::Deleted])?;
-    imap.add_flags(seq, &flags)?;
+    imap.add_flags(&seq_range, &flags)?;
     imap.expunge()?;
 
-    printer.print(format!(
-        r#"Message {} successfully moved to folder "{}""#,
-        seq, mbox
+    printer.print(tr_args(
+        "msgs-moved",
+        &[("seqs", &seq_range), ("mbox", &mbox.to_string())],
     ))
 }
 
-/// Read a message by its sequence number.
-pub fn read<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+/// Pipe a message, or one of its MIME parts, to the given command.
+pub fn pipe<'a, ImapService: ImapServiceInterface<'a>>(
     seq: &str,
+    part: Option<&str>,
+    cmd: Vec<&str>,
+    imap: &mut ImapService,
+) -> Result<()> {
+    let raw_msg = imap.find_raw_msg(seq)?;
+    let input = match part {
+        Some(part) => msg_utils::find_raw_part(&raw_msg, part)
+            .context(format!(r#"cannot pipe message "{}""#, seq))?,
+        None => raw_msg,
+    };
+    pipe_cmd(&cmd, &input)
+}
+
+/// Read messages by their sequence numbers or ranges, printed one after the other with a separator
+/// or exported to a file.
+#[allow(clippy::too_many_arguments)]
+pub fn read<
+    'a,
+    Printer: PrinterService,
+    ImapService: ImapServiceInterface<'a>,
+    SmtpService: SmtpServiceInterface,
+>(
+    seqs: Vec<&str>,
     text_mime: &str,
     raw: bool,
+    concat_output: Option<&str>,
+    export: Option<ExportFormat>,
+    print: bool,
+    load_remote: bool,
+    envelope_only: bool,
+    preserve_flags: bool,
+    account: &Account,
     printer: &mut Printer,
     imap: &mut ImapService,
+    smtp: &mut SmtpService,
 ) -> Result<()> {
+    // Single sequence numbers are read as is, ranges are resolved first
+    let seqs: Vec<String> = match seqs.as_slice() {
+        [seq] if seq.parse::<u32>().is_ok() => vec![seq.to_string()],
+        seqs => imap
+            .search_seqs(&seqs.join(","))?
+            .iter()
+            .map(|seq| seq.to_string())
+            .collect(),
+    };
+    if seqs.is_empty() {
+        return Err(anyhow!("cannot find messages to read"));
+    }
+
+    if export.is_some() || print {
+        let mut reports = vec![];
+        for seq in seqs.iter() {
+            let msg = imap.find_msg(seq)?;
+            if let Some(format) = export {
+                let path = export_msg(seq, &msg, format, account)?;
+                reports.push(tr_args(
+                    "msg-exported",
+                    &[("seq", seq), ("path", &path.to_string_lossy())],
+                ));
+            }
+            if print {
+                let cmd: Vec<&str> = account.print_cmd.split_whitespace().collect();
+                pipe_cmd(&cmd, msg.to_printable_text().as_bytes())
+                    .context(format!("cannot print message {}", seq))?;
+                reports.push(tr_args("msg-printed", &[("seq", seq)]));
+            }
+        }
+        return printer.print(reports.join("\n"));
+    }
+
+    // Messages moved or deleted by the post_read hook are expunged once all of them are read,
+    // which keeps the sequence numbers valid meanwhile.
+    let hooks = Hooks::load(account)?;
+    let mut expunge = false;
+    let mut msgs = Vec::with_capacity(seqs.len());
+    for seq in seqs.iter() {
+        let msg = if envelope_only {
+            read_envelope(seq, account, imap)?
+        } else {
+            let (msg, deleted) = read_text(
+                seq,
+                text_mime,
+                raw,
+                load_remote,
+                preserve_flags,
+                hooks.as_ref(),
+                account,
+                printer,
+                imap,
+                smtp,
+            )?;
+            expunge |= deleted;
+            msg
+        };
+        msgs.push(match (seqs.len(), concat_output) {
+            (1, None) => msg,
+            _ => format!("{}\n{}", msg_separator(seq), msg),
+        });
+    }
+
+    match concat_output {
+        Some(path) => {
+            fs::write(path, msgs.join("\n\n"))
+                .context(format!(r#"cannot export messages to "{}""#, path))?;
+            printer.print(tr_args(
+                "msgs-exported",
+                &[("count", &msgs.len().to_string()), ("path", path)],
+            ))
+        }
+        None => printer.print(msgs.join("\n\n")),
+    }?;
+
+    if expunge {
+        imap.expunge()?;
+    }
+    Ok(())
+}
+
+/// Export the given message to the downloads directory, returning the path of the export. PDF
+/// documents are converted from the HTML rendering by the export PDF command of the account.
+fn export_msg(seq: &str, msg: &Msg, format: ExportFormat, account: &Account) -> Result<PathBuf> {
+    let html = msg.to_html_document();
+    let path = account
+        .downloads_dir
+        .join(format!("message-{}.{}", seq, format.extension()));
+    match format {
+        ExportFormat::Html => {
+            fs::write(&path, html).context(format!("cannot export message to {:?}", path))?;
+        }
+        ExportFormat::Pdf => {
+            // The temporary file is private to the user and removed once dropped
+            let mut html_file = tempfile::Builder::new()
+                .prefix("himalaya-message-")
+                .suffix(".html")
+                .tempfile()
+                .context("cannot create temporary file for the HTML rendering")?;
+            html_file
+                .write_all(html.as_bytes())
+                .and_then(|_| html_file.flush())
+                .context(format!(
+                    "cannot write HTML rendering to {:?}",
+                    html_file.path()
+                ))?;
+            // A previous export would hide a failing command
+            let _ = fs::remove_file(&path);
+            debug!("export message {} to PDF", seq);
+            let cmd = &account.export_pdf_cmd;
+            run_cmd_with_args(
+                cmd,
+                &[
+                    html_file.path().to_string_lossy().as_ref(),
+                    path.to_string_lossy().as_ref(),
+                ],
+            )
+            .context(format!(r#"cannot run export PDF command "{}""#, cmd))?;
+            if !path.exists() {
+                return Err(anyhow!(
+                    r#"export PDF command "{}" did not create {:?}"#,
+                    cmd,
+                    path
+                ));
+            }
+        }
+    }
+    Ok(path)
+}
+
+/// Builds the line separating the messages read at once.
+fn msg_separator(seq: &str) -> String {
+    let title = format!(" Message {} ", seq);
+    format!("{:=^1$}", title, title.len() + 40)
+}
+
+/// Read the envelope headers of the given message, without downloading its body nor marking it
+/// as seen.
+fn read_envelope<'a, ImapService: ImapServiceInterface<'a>>(
+    seq: &str,
+    account: &Account,
+    imap: &mut ImapService,
+) -> Result<String> {
+    let raw_headers = imap
+        .fetch_raw_headers(seq)?
+        .into_iter()
+        .next()
+        .map(|(_, raw_headers)| raw_headers)
+        .ok_or_else(|| ExitCode::NotFound.err(format!(r#"cannot find message "{}""#, seq)))?;
+    msg_utils::format_envelope_headers(&raw_headers, &account.date_fmt)
+}
+
+/// Read the text bodies of the given message, or the whole raw message. HTML bodies are sanitized,
+/// their remote content being blocked unless asked otherwise. The `post_read` hook then runs on
+/// the message, returning whether it moved or deleted it. When the flags are preserved, the
+/// message is left untouched: the read receipt request is not handled and only the notifications
+/// of the hook apply.
+#[allow(clippy::too_many_arguments)]
+fn read_text<
+    'a,
+    Printer: PrinterService,
+    ImapService: ImapServiceInterface<'a>,
+    SmtpService: SmtpServiceInterface,
+>(
+    seq: &str,
+    text_mime: &str,
+    raw: bool,
+    load_remote: bool,
+    preserve_flags: bool,
+    hooks: Option<&Hooks>,
+    account: &Account,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+    smtp: &mut SmtpService,
+) -> Result<(String, bool)> {
+    let mut deleted = false;
     let msg = if raw {
         // Emails don't always have valid utf8. Using "lossy" to display what we can.
         String::from_utf8_lossy(&imap.find_raw_msg(&seq)?).into_owned()
     } else {
-        imap.find_msg(&seq)?.fold_text_parts(text_mime)
+        let mut msg = imap.find_msg(&seq)?;
+        if !preserve_flags {
+            mdn(seq, &msg, account, printer, imap, smtp)?;
+        }
+        // Re-flowed paragraphs are wrapped to the width of the terminal.
+        if text_mime != "html" && !printer.is_structured() {
+            if let Some((width, _)) = terminal_size::terminal_size() {
+                msg.wrap_flowed_parts(width.0 as usize);
+            }
+        }
+        let mut text = msg.fold_text_parts(text_mime);
+        if text_mime == "html" {
+            let (html, blocked) = html_utils::sanitize_html(&text, load_remote);
+            text = match blocked {
+                0 => html,
+                blocked => format!(
+                    "[{} remote resource(s) blocked, use --load-remote to load them]\n{}",
+                    blocked, html
+                ),
+            };
+        }
+        let text = match msg.invite() {
+            Some(invite) => format!("{}\n{}", invite, text),
+            None => text,
+        };
+        let text = match find_note(seq, msg.message_id.as_deref(), account, imap)? {
+            Some(note) => format!("{}: {}\n\n{}", tr("note-label"), note, text),
+            None => text,
+        };
+        if let Some(hooks) = hooks {
+            let hook_msg = HookMsg {
+                seq: seq.parse().ok(),
+                ..HookMsg::from(&msg)
+            };
+            if preserve_flags {
+                hook_handler::run_notify(hooks, Hook::PostRead, &hook_msg, account)?;
+            } else {
+                let (_, moved) =
+                    hook_handler::run(hooks, Hook::PostRead, &hook_msg, false, account, imap)?;
+                deleted = moved;
+            }
+        }
+        text
     };
 
-    printer.print(msg)
+    Ok((msg, deleted))
+}
+
+/// Find the note of the given message, in the local cache first then in the server annotations.
+fn find_note<'a, ImapService: ImapServiceInterface<'a>>(
+    seq: &str,
+    message_id: Option<&str>,
+    account: &Account,
+    imap: &mut ImapService,
+) -> Result<Option<String>> {
+    if let (Some(path), Some(message_id)) = (account.notes_path.as_ref(), message_id) {
+        if let Some(note) = NotesCache::load(path)?.get(message_id.trim()) {
+            return Ok(Some(note.note.to_owned()));
+        }
+    }
+    if imap.has_annotate()? {
+        return imap.fetch_note(seq);
+    }
+    Ok(None)
+}
+
+/// Show the note of the given message, or attach the given one, or delete it. Notes are cached
+/// locally by `Message-ID`, and stored as annotations when the server supports them.
+pub fn note<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    seq: &str,
+    note: Option<String>,
+    delete: bool,
+    account: &Account,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+) -> Result<()> {
+    let raw_headers = imap
+        .fetch_raw_headers(seq)?
+        .into_iter()
+        .next()
+        .map(|(_, raw_headers)| raw_headers)
+        .ok_or_else(|| ExitCode::NotFound.err(format!(r#"cannot find message "{}""#, seq)))?;
+    let entry = Note::from_raw_headers(&raw_headers, note.as_deref().unwrap_or_default())?;
+    let message_id = Some(entry.message_id.as_str()).filter(|id| !id.is_empty());
+
+    if note.is_none() && !delete {
+        return match find_note(seq, message_id, account, imap)? {
+            Some(note) => printer.print(note),
+            None => printer.print(tr_args("msg-has-no-note", &[("seq", seq)])),
+        };
+    }
+
+    let annotate = imap.has_annotate()?;
+    let cache = match (account.notes_path.as_ref(), message_id) {
+        (Some(path), Some(_)) => Some(NotesCache::load(path)?),
+        _ => None,
+    };
+    if !annotate && cache.is_none() {
+        return Err(anyhow!(
+            r#"cannot attach note to message "{}": it has no Message-ID and the server does not support annotations"#,
+            seq
+        ));
+    }
+
+    if annotate {
+        imap.store_note(seq, note.as_deref())?;
+    }
+    if let Some(mut cache) = cache {
+        match note {
+            Some(_) => cache.set(entry),
+            None => {
+                cache.remove(&entry.message_id);
+            }
+        }
+        cache.save()?;
+    }
+
+    if delete {
+        printer.print(format!(r#"Note of message "{}" successfully deleted"#, seq))
+    } else {
+        printer.print(format!(
+            r#"Note successfully attached to message "{}""#,
+            seq
+        ))
+    }
+}
+
+/// List the cached notes containing the given query, or all of them.
+pub fn notes<Printer: PrinterService>(
+    query: Option<&str>,
+    max_width: Option<usize>,
+    account: &Account,
+    printer: &mut Printer,
+) -> Result<()> {
+    let cache = match account.notes_path.as_ref() {
+        Some(path) => NotesCache::load(path)?,
+        None => NotesCache::default(),
+    };
+    printer.print_table(
+        cache.search(query.unwrap_or_default()),
+        PrintTableOpts { max_width },
+    )
+}
+
+/// Collect the messages of the thread the given message belongs to, in the current mailbox or in
+/// all mailboxes, then print them chronologically as a conversation. Mailboxes are searched again
+/// with the ids of the messages found, so replies referencing only part of the thread are found
+/// too.
+pub fn thread<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    seq: &str,
+    mbox: &str,
+    all_mboxes: bool,
+    collapse: bool,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+) -> Result<()> {
+    let raw_headers = imap
+        .fetch_raw_headers(seq)?
+        .into_iter()
+        .next()
+        .map(|(_, raw_headers)| raw_headers)
+        .ok_or_else(|| ExitCode::NotFound.err(format!(r#"cannot find message "{}""#, seq)))?;
+    let (headers, _) =
+        mailparse::parse_headers(&raw_headers).context("cannot parse message headers")?;
+    let mut ids = thread_ids(&headers);
+    if ids.is_empty() {
+        return Err(anyhow!(
+            r#"cannot find thread of message "{}": it has no Message-ID nor References"#,
+            seq
+        ));
+    }
+
+    let mboxes = if all_mboxes {
+        imap.fetch_mbox_names()?
+    } else {
+        vec![mbox.to_owned()]
+    };
+    let mut msgs: HashMap<String, ThreadMsg> = HashMap::new();
+    for _ in 0..THREAD_SEARCH_PASSES {
+        let query = thread_query(&ids);
+        for mbox in mboxes.iter() {
+            let raw_msgs = match imap.search_raw_msgs(mbox, &query) {
+                Ok(raw_msgs) => raw_msgs,
+                Err(err) if all_mboxes => {
+                    warn!(r#"skipping mailbox "{}": {}"#, mbox, err);
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            for (seq, raw) in raw_msgs {
+                let msg = ThreadMsg::from_raw(mbox, seq, &raw)?;
+                // Messages without id cannot be deduplicated, so they are keyed by location
+                let key = Some(msg.message_id.to_owned())
+                    .filter(|id| !id.is_empty())
+                    .unwrap_or_else(|| format!("{}:{}", mbox, seq));
+                msgs.entry(key).or_insert(msg);
+            }
+        }
+
+        let len = ids.len();
+        for msg in msgs.values() {
+            for id in msg.thread_ids.iter() {
+                if !ids.contains(id) {
+                    ids.push(id.to_owned());
+                }
+            }
+        }
+        if ids.len() == len {
+            break;
+        }
+    }
+
+    let mut msgs: Vec<ThreadMsg> = msgs.into_iter().map(|(_, msg)| msg).collect();
+    msgs.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.seq.cmp(&b.seq)));
+    if collapse {
+        for msg in msgs.iter_mut() {
+            msg.text = collapse_quotes(&msg.text);
+        }
+    }
+    printer.print(Thread(msgs))
+}
+
+/// Handle the read receipt request of the given message according to the account MDN policy.
+/// The message is flagged `$MDNSent` once the receipt is sent or denied, so it is handled once.
+fn mdn<
+    'a,
+    Printer: PrinterService,
+    ImapService: ImapServiceInterface<'a>,
+    SmtpService: SmtpServiceInterface,
+>(
+    seq: &str,
+    msg: &Msg,
+    account: &Account,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+    smtp: &mut SmtpService,
+) -> Result<()> {
+    let to = match msg
+        .disposition_notification_to
+        .as_ref()
+        .and_then(|addrs| addrs.first())
+    {
+        Some(addr) => addr,
+        None => return Ok(()),
+    };
+    let mdn_sent_flag = Flag::Custom(MDN_SENT_FLAG.into());
+    if msg.flags.contains(&mdn_sent_flag) {
+        return Ok(());
+    }
+
+    let can_ask = printer.is_interactive() && !printer.is_structured();
+    let send = match account
+        .mdn_policy
+        .decide(can_ask, || choice::send_mdn(&to.to_string()))?
+    {
+        Some(send) => send,
+        // Nobody can be asked, the request stays pending without `$MDNSent`.
+        None => return Ok(()),
+    };
+
+    if send {
+        // Send the receipt with the identity the message was sent to.
+        let recipients: Vec<String> = msg
+            .to
+            .iter()
+            .chain(msg.cc.iter())
+            .flatten()
+            .map(|addr| addr.email.to_string())
+            .collect();
+        let account = account.with_matching_identity(recipients.iter().map(String::as_str));
+        let raw_mdn = Mdn {
+            from: &account.address(),
+            final_recipient: &account.email,
+            to: &to.to_string(),
+            subject: &msg.subject,
+            original_message_id: msg.message_id.as_deref(),
+            automatic: account.mdn_policy == MdnPolicy::Always,
+        }
+        .to_raw();
+        let envelope = lettre::address::Envelope::new(
+            Some(account.email.parse::<lettre::Address>()?),
+            vec![to.email.to_owned()],
+        )
+        .context("cannot create read receipt envelope")?;
+        smtp.send_raw_msg(&envelope, raw_mdn.as_bytes())?;
+        debug!("read receipt sent!");
+    }
+
+    let flags = Flags::try_from(vec![mdn_sent_flag])?;
+    imap.add_flags(seq, &flags)
 }
 
 /// Reply to the given message UID.
@@ -228,15 +1086,34 @@ pub fn reply<
     seq: &str,
     all: bool,
     attachments_paths: Vec<&str>,
+    identity: Option<&str>,
+    ask_receipt: bool,
+    force: bool,
+    yes: bool,
     account: &Account,
     printer: &mut Printer,
     imap: &mut ImapService,
     smtp: &mut SmtpService,
 ) -> Result<()> {
-    imap.find_msg(seq)?
-        .into_reply(all, account)?
-        .add_attachments(attachments_paths)?
-        .edit_with_editor(account, printer, imap, smtp)?;
+    let msg = imap.find_msg(seq)?;
+    let account = match identity {
+        Some(email) => account.with_identity(email)?,
+        None => {
+            // Reply with the identity the original message was sent to.
+            let recipients: Vec<String> = msg
+                .to
+                .iter()
+                .chain(msg.cc.iter())
+                .flatten()
+                .map(|addr| addr.email.to_string())
+                .collect();
+            account.with_matching_identity(recipients.iter().map(String::as_str))
+        }
+    };
+    msg.into_reply(all, &account)?
+        .ask_receipt(ask_receipt, &account)?
+        .add_attachments(attachments_paths, &account)?
+        .edit_with_editor(force, yes, &account, printer, imap, smtp)?;
     let flags = Flags::try_from(vec![Flag::Answered])?;
     imap.add_flags(seq, &flags)
 }
@@ -248,7 +1125,7 @@ pub fn save<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
     printer: &mut Printer,
     imap: &mut ImapService,
 ) -> Result<()> {
-    let raw_msg = if atty::is(Stream::Stdin) || printer.is_json() {
+    let raw_msg = if atty::is(Stream::Stdin) || printer.is_structured() {
         raw_msg.replace("\r", "").replace("\n", "\r\n")
     } else {
         io::stdin()
@@ -277,12 +1154,54 @@ pub fn search<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>
     let page_size = page_size.unwrap_or(account.default_page_size);
     trace!("page size: {}", page_size);
 
-    let msgs = imap.fetch_envelopes_with(&query, &page_size, &page)?;
+    let msgs = imap
+        .fetch_envelopes_with(&query, &page_size, &page)?
+        .with_highlight(account.envelope_highlight)
+        .with_date(account.list_date)
+        .with_date_fmt(&account.date_fmt);
     trace!("messages: {:#?}", msgs);
     printer.print_table(msgs, PrintTableOpts { max_width })
 }
 
-/// Send a raw message.
+/// Search messages in all the mailboxes. Envelopes are cached by Message-ID, so a message living
+/// in several mailboxes is listed once, along with the mailboxes it lives in.
+pub fn search_all_mboxes<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    query: String,
+    max_width: Option<usize>,
+    page_size: Option<usize>,
+    page: usize,
+    account: &Account,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+) -> Result<()> {
+    let page_size = page_size.unwrap_or(account.default_page_size);
+    trace!("page size: {}", page_size);
+
+    let mut envelopes = MboxEnvelopes::default();
+    for mbox in imap.fetch_mbox_names()? {
+        let mbox_envelopes = match imap.search_envelopes_in(&mbox, &query) {
+            Ok(envelopes) => envelopes,
+            Err(err) => {
+                warn!(r#"skipping mailbox "{}": {}"#, mbox, err);
+                continue;
+            }
+        };
+        for envelope in Envelopes(mbox_envelopes)
+            .with_highlight(account.envelope_highlight)
+            .with_date(account.list_date)
+            .with_date_fmt(&account.date_fmt)
+            .0
+        {
+            envelopes.insert(&mbox, envelope);
+        }
+    }
+    let envelopes = envelopes.paginate(page_size, page);
+    trace!("envelopes: {:#?}", envelopes);
+    printer.print_table(envelopes, PrintTableOpts { max_width })
+}
+
+/// Send a raw message, or queue it in the outbox folder until the given time. Messages above the
+/// size limit of the account are refused unless forced.
 pub fn send<
     'a,
     Printer: PrinterService,
@@ -290,11 +1209,14 @@ pub fn send<
     SmtpService: SmtpServiceInterface,
 >(
     raw_msg: &str,
+    send_at: Option<&str>,
+    force: bool,
+    account: &Account,
     printer: &mut Printer,
     imap: &mut ImapService,
     smtp: &mut SmtpService,
 ) -> Result<()> {
-    let raw_msg = if atty::is(Stream::Stdin) || printer.is_json() {
+    let raw_msg = if atty::is(Stream::Stdin) || printer.is_structured() {
         raw_msg.replace("\r", "").replace("\n", "\r\n")
     } else {
         io::stdin()
@@ -306,15 +1228,301 @@ pub fn send<
             .join("\r\n")
     };
 
-    let msg = Msg::from_tpl(&raw_msg.to_string())?;
+    if let Some(send_at) = send_at {
+        let send_at = parse_schedule_time(send_at, Local::now())?;
+        // The message is checked now, rather than when the queue is flushed
+        let msg = Msg::from_tpl(&raw_msg)?;
+        let warnings = msg_utils::check_recipients(&msg, force, account)?;
+        let _: lettre::address::Envelope = msg.try_into()?;
+        msg_utils::check_msg_size(raw_msg.len(), force, account)?;
+
+        let flag = schedule_flag(SEND_AT_FLAG_PREFIX, send_at.with_timezone(&Utc));
+        let mut flags = Flags::from(vec![flag.as_str()]);
+        flags.insert(Flag::Seen);
+        let mbox = Mbox::new(&account.outbox_folder);
+        imap.append_raw_msg_with_flags(&mbox, raw_msg.as_bytes(), flags)?;
+        let info = format!(
+            "Message successfully scheduled for {}",
+            send_at.format("%Y-%m-%d %H:%M")
+        );
+        return printer.print([warnings, vec![info]].concat().join("\n"));
+    }
+
+    let warnings = msg_utils::check_recipients(&Msg::from_tpl(&raw_msg)?, force, account)?;
+    let info = msg_utils::check_msg_size(raw_msg.len(), force, account)?;
+    printer.print([warnings, vec![info]].concat().join("\n"))?;
+    deliver(raw_msg.as_bytes(), account, imap, smtp)
+}
+
+/// Send a raw message unchanged, then save it to the sent folder of the identity used. A message
+/// delivered to some recipient domains only is saved as well, before returning the error.
+fn deliver<'a, ImapService: ImapServiceInterface<'a>, SmtpService: SmtpServiceInterface>(
+    raw_msg: &[u8],
+    account: &Account,
+    imap: &mut ImapService,
+    smtp: &mut SmtpService,
+) -> Result<()> {
+    let msg = Msg::from_tpl(&String::from_utf8_lossy(raw_msg))?;
+    let senders: Vec<String> = msg
+        .from
+        .iter()
+        .flatten()
+        .map(|addr| addr.email.to_string())
+        .collect();
     let envelope: lettre::address::Envelope = msg.try_into()?;
-    smtp.send_raw_msg(&envelope, raw_msg.as_bytes())?;
-    debug!("message sent!");
+    let res = smtp.send_raw_msg(&envelope, raw_msg);
+    match res.as_ref() {
+        Err(err) if err.downcast_ref::<PartialDelivery>().is_none() => return res,
+        _ => debug!("message sent!"),
+    }
 
-    // Save message to sent folder
-    let mbox = Mbox::new("Sent");
+    // Save message to the sent folder of the identity used
+    let account = account.with_matching_identity(senders.iter().map(String::as_str));
+    let mbox = Mbox::new(&account.sent_folder);
     let flags = Flags::try_from(vec![Flag::Seen])?;
-    imap.append_raw_msg_with_flags(&mbox, raw_msg.as_bytes(), flags)
+    imap.append_raw_msg_with_flags(&mbox, raw_msg, flags)?;
+    res
+}
+
+/// Send the messages queued in the outbox folder, or only the ones whose scheduled time has come.
+/// Sent messages are removed from the queue, even if a following one fails. So are the messages
+/// delivered to some recipient domains only, since sending them again would make duplicates. The
+/// given IMAP service is bound to the outbox folder, which may not exist yet.
+pub fn flush_queue<
+    'a,
+    Printer: PrinterService,
+    ImapService: ImapServiceInterface<'a>,
+    SmtpService: SmtpServiceInterface,
+>(
+    due: bool,
+    account: &Account,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+    smtp: &mut SmtpService,
+) -> Result<()> {
+    if !imap.fetch_mbox_names()?.contains(&account.outbox_folder) {
+        return printer.print("No queued message to send");
+    }
+
+    let now = Utc::now();
+    let seqs: Vec<u32> = imap
+        .fetch_filter_headers(None)?
+        .into_iter()
+        .filter(|headers| !headers.flags.contains(&Flag::Deleted))
+        .filter(|headers| {
+            !due || headers.flags.iter().any(|flag| match flag {
+                Flag::Custom(flag) => parse_schedule_flag(SEND_AT_FLAG_PREFIX, flag)
+                    .map_or(false, |send_at| send_at <= now),
+                _ => false,
+            })
+        })
+        .map(|headers| headers.seq)
+        .collect();
+    if seqs.is_empty() {
+        return printer.print("No queued message to send");
+    }
+
+    // The connection is opened once and reused for all the queued messages
+    smtp.check()?;
+    let mut sent = vec![];
+    let res = seqs.iter().try_for_each(|seq| -> Result<()> {
+        let raw_msg = imap.find_raw_msg(&seq.to_string())?;
+        let res = deliver(&raw_msg, account, imap, smtp);
+        if let Err(err) = res.as_ref() {
+            if err.downcast_ref::<PartialDelivery>().is_none() {
+                return res.context(format!("cannot send queued message {}", seq));
+            }
+        }
+        sent.push(*seq);
+        res.context(format!("cannot send queued message {}", seq))
+    });
+
+    if !sent.is_empty() {
+        let seq_range = compress_seqs(&sent);
+        imap.add_flags(&seq_range, &Flags::try_from(vec![Flag::Deleted])?)?;
+        imap.expunge()?;
+    }
+    if let Err(err) = res {
+        return Err(match sent.len() {
+            0 => err,
+            n => err
+                .context(ExitCode::PartialFailure)
+                .context(format!("{} queued message(s) already sent", n)),
+        });
+    }
+
+    printer.print(format!("{} queued message(s) sent", sent.len()))
+}
+
+/// Print the delivery status of the last sent messages, found in the delivery status
+/// notifications of the current mailbox. The sent messages are fetched through `sent_imap`, which
+/// targets the sent folder of the account.
+pub fn sent_status<
+    'a,
+    'b,
+    Printer: PrinterService,
+    ImapService: ImapServiceInterface<'a>,
+    SentImapService: ImapServiceInterface<'b>,
+>(
+    max: usize,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+    sent_imap: &mut SentImapService,
+) -> Result<()> {
+    let mut dsns: HashMap<String, DsnStatus> = HashMap::new();
+    let seqs = imap.search_seqs(DSN_QUERY)?;
+    debug!("found {} delivery status notifications", seqs.len());
+    if !seqs.is_empty() {
+        for dsn in imap
+            .fetch_raw_msgs(&compress_seqs(&seqs))?
+            .iter()
+            .filter_map(|(_, raw)| Dsn::from_raw(raw))
+        {
+            trace!("dsn: {:?}", dsn);
+            let status = dsns.entry(dsn.message_id).or_default();
+            *status = (*status).max(dsn.status);
+        }
+    }
+
+    let seqs = sent_imap.search_seqs("ALL")?;
+    let seqs = &seqs[seqs.len().saturating_sub(max)..];
+    let mut statuses = vec![];
+    if !seqs.is_empty() {
+        for raw_headers in sent_imap
+            .fetch_raw_headers(&compress_seqs(seqs))?
+            .iter()
+            .rev()
+            .map(|(_, raw_headers)| raw_headers)
+        {
+            let mut status = SentStatus::from_raw_headers(raw_headers)?;
+            if let Some(dsn_status) = dsns.get(&status.message_id) {
+                status.status = *dsn_status;
+            }
+            statuses.push(status);
+        }
+    }
+
+    printer.print_table(SentStatuses(statuses), PrintTableOpts { max_width: None })
+}
+
+/// Snooze a message: the message is tagged with its wake up time then moved to the snoozed
+/// folder, where `wake` finds it once the time has come.
+pub fn snooze<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    seq: &str,
+    until: &str,
+    account: &Account,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+) -> Result<()> {
+    let until = parse_schedule_time(until, Local::now())?;
+    let flag = schedule_flag(SNOOZED_FLAG_PREFIX, until.with_timezone(&Utc));
+    debug!(r#"snooze message {} with flag "{}""#, seq, flag);
+    imap.add_flags(seq, &Flags::from(vec![flag.as_str()]))?;
+
+    let snoozed_mbox = Mbox::new(&account.snoozed_folder);
+    imap.copy_msgs(seq, &snoozed_mbox)?;
+    let flags = Flags::try_from(vec![Flag::Seen, Flag::Deleted])?;
+    imap.add_flags(seq, &flags)?;
+    imap.expunge()?;
+
+    printer.print(tr_args(
+        "msg-snoozed",
+        &[
+            ("seq", seq),
+            ("until", &until.format("%Y-%m-%d %H:%M").to_string()),
+        ],
+    ))
+}
+
+/// Move back the snoozed messages whose time has come to the inbox, marked as unseen. The given
+/// IMAP service is bound to the snoozed folder, which may not exist yet.
+pub fn wake<'a, Printer: PrinterService, ImapService: ImapServiceInterface<'a>>(
+    account: &Account,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+) -> Result<()> {
+    if !imap.fetch_mbox_names()?.contains(&account.snoozed_folder) {
+        return printer.print(tr("no-msg-to-wake"));
+    }
+
+    let now = Utc::now();
+    let mut seqs = vec![];
+    for headers in imap.fetch_filter_headers(None)? {
+        let due_flags: Vec<Flag> = headers
+            .flags
+            .iter()
+            .filter(|flag| match flag {
+                Flag::Custom(flag) => parse_schedule_flag(SNOOZED_FLAG_PREFIX, flag)
+                    .map_or(false, |until| until <= now),
+                _ => false,
+            })
+            .cloned()
+            .collect();
+        if due_flags.is_empty() {
+            continue;
+        }
+        let seq = headers.seq.to_string();
+        debug!("wake message {} up", seq);
+        let mut flags = due_flags;
+        flags.push(Flag::Seen);
+        imap.remove_flags(&seq, &Flags::try_from(flags)?)?;
+        seqs.push(headers.seq);
+    }
+
+    if seqs.is_empty() {
+        return printer.print(tr("no-msg-to-wake"));
+    }
+
+    let seq_range = compress_seqs(&seqs);
+    imap.copy_msgs(&seq_range, &Mbox::new("INBOX"))?;
+    imap.add_flags(&seq_range, &Flags::try_from(vec![Flag::Deleted])?)?;
+    imap.expunge()?;
+
+    printer.print(tr_args("msgs-woken", &[("count", &seqs.len().to_string())]))
+}
+
+/// Unsubscribe from the mailing list of the given message using its `List-Unsubscribe` header.
+/// HTTP(S) URLs are opened in the browser, otherwise the unsubscribe message is sent and saved to
+/// the sent folder.
+pub fn unsubscribe<
+    'a,
+    Printer: PrinterService,
+    ImapService: ImapServiceInterface<'a>,
+    SmtpService: SmtpServiceInterface,
+>(
+    seq: &str,
+    account: &Account,
+    printer: &mut Printer,
+    imap: &mut ImapService,
+    smtp: &mut SmtpService,
+) -> Result<()> {
+    let urls = msg_utils::list_unsubscribe_urls(&imap.find_raw_msg(seq)?)
+        .context(format!(r#"cannot unsubscribe from message "{}""#, seq))?;
+    trace!("unsubscribe urls: {:?}", urls);
+
+    if let Some(url) = urls
+        .iter()
+        .find(|url| url.scheme() == "https" || url.scheme() == "http")
+    {
+        open_url(url.as_str())?;
+        return printer.print(format!("Unsubscribe page {} opened", url));
+    }
+
+    let url = urls
+        .iter()
+        .find(|url| url.scheme() == "mailto")
+        .ok_or_else(|| anyhow!(r#"cannot find unsubscribe URL in message "{}""#, seq))?;
+    let mut msg = mailto_msg(url, account)?;
+    if msg.subject.is_empty() {
+        msg.subject = String::from("unsubscribe");
+    }
+    let sent_msg = smtp.send_msg(&msg)?;
+    debug!("unsubscribe message sent!");
+
+    let mbox = Mbox::new(&account.sent_folder);
+    let flags = Flags::try_from(vec![Flag::Seen])?;
+    imap.append_raw_msg_with_flags(&mbox, &sent_msg.formatted(), flags)?;
+    printer.print(format!("Unsubscribe message sent to {}", url.path()))
 }
 
 /// Compose a new message.
@@ -325,12 +1533,21 @@ pub fn write<
     SmtpService: SmtpServiceInterface,
 >(
     attachments_paths: Vec<&str>,
+    identity: Option<&str>,
+    ask_receipt: bool,
+    force: bool,
+    yes: bool,
     account: &Account,
     printer: &mut Printer,
     imap: &mut ImapService,
     smtp: &mut SmtpService,
 ) -> Result<()> {
+    let account = match identity {
+        Some(email) => account.with_identity(email)?,
+        None => account.clone(),
+    };
     Msg::default()
-        .add_attachments(attachments_paths)?
-        .edit_with_editor(account, printer, imap, smtp)
+        .ask_receipt(ask_receipt, &account)?
+        .add_attachments(attachments_paths, &account)?
+        .edit_with_editor(force, yes, &account, printer, imap, smtp)
 }
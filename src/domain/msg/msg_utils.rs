@@ -1,6 +1,19 @@
-use anyhow::{Context, Result};
-use log::{debug, trace};
-use std::{env, fs, path::PathBuf};
+use anyhow::{anyhow, Context, Result};
+use chrono::{Local, Utc};
+use lettre::Address;
+use log::{debug, trace, warn};
+use mailparse::MailHeaderMap;
+use std::{env, fs, path::PathBuf, process};
+use url::Url;
+
+use crate::{
+    config::Account,
+    domain::{
+        msg::{parse_date_header, DateFmt, Msg},
+        smtp::has_mx,
+    },
+    output::{format_size, ExitCode},
+};
 
 pub fn local_draft_path() -> PathBuf {
     let path = env::temp_dir().join("himalaya-draft.mail");
@@ -13,3 +26,664 @@ pub fn remove_local_draft() -> Result<()> {
     debug!("remove draft path at {:?}", path);
     fs::remove_file(&path).context(format!("cannot remove local draft at {:?}", path))
 }
+
+/// Parse the URLs of the `List-Unsubscribe` header ([RFC2369]) of the given raw message.
+///
+/// [RFC2369]: https://datatracker.ietf.org/doc/html/rfc2369#section-3.2
+pub fn list_unsubscribe_urls(raw_msg: &[u8]) -> Result<Vec<Url>> {
+    let (headers, _) = mailparse::parse_headers(raw_msg).context("cannot parse message headers")?;
+    let header = headers
+        .get_first_value("List-Unsubscribe")
+        .ok_or_else(|| anyhow!("cannot find List-Unsubscribe header"))?;
+    trace!("List-Unsubscribe header: {}", header);
+
+    Ok(header
+        .split(',')
+        .filter_map(|url| url.trim().strip_prefix('<')?.strip_suffix('>'))
+        .filter_map(|url| Url::parse(url).ok())
+        .collect())
+}
+
+/// Find the decoded content of a MIME part of the given raw message. The part is either its
+/// position among the leaf parts (starting from 1) or its MIME type, in which case the first
+/// matching part is taken.
+pub fn find_raw_part(raw_msg: &[u8], part: &str) -> Result<Vec<u8>> {
+    fn collect_leaves<'a>(
+        parsed: &'a mailparse::ParsedMail<'a>,
+        leaves: &mut Vec<&'a mailparse::ParsedMail<'a>>,
+    ) {
+        if parsed.subparts.is_empty() {
+            leaves.push(parsed)
+        } else {
+            parsed
+                .subparts
+                .iter()
+                .for_each(|part| collect_leaves(part, leaves))
+        }
+    }
+
+    let parsed = mailparse::parse_mail(raw_msg).context("cannot parse message")?;
+    let mut leaves = vec![];
+    collect_leaves(&parsed, &mut leaves);
+    trace!(
+        "parts: {:?}",
+        leaves
+            .iter()
+            .map(|leaf| &leaf.ctype.mimetype)
+            .collect::<Vec<_>>()
+    );
+
+    let leaf = match part.parse::<usize>() {
+        Ok(pos) => pos.checked_sub(1).and_then(|i| leaves.get(i)),
+        Err(_) => leaves
+            .iter()
+            .find(|leaf| leaf.ctype.mimetype.eq_ignore_ascii_case(part)),
+    }
+    .ok_or_else(|| ExitCode::NotFound.err(format!(r#"cannot find part "{}""#, part)))?;
+
+    leaf.get_body_raw()
+        .context(format!(r#"cannot decode part "{}""#, part))
+}
+
+/// Format the envelope headers of the given raw headers, one `Key: value` line per header found.
+/// Valid dates are shown with the given format.
+pub fn format_envelope_headers(raw_headers: &[u8], date_fmt: &DateFmt) -> Result<String> {
+    let (headers, _) =
+        mailparse::parse_headers(raw_headers).context("cannot parse message headers")?;
+    Ok([
+        "From",
+        "Reply-To",
+        "To",
+        "Cc",
+        "Date",
+        "Subject",
+        "Message-ID",
+    ]
+    .iter()
+    .filter_map(|key| {
+        let val = headers.get_first_value(key)?;
+        let val = match *key {
+            "Date" => parse_date_header(val.as_bytes())
+                .map(|date| date_fmt.format_full(&date.naive_local()))
+                .unwrap_or(val),
+            _ => val,
+        };
+        Some(format!("{}: {}", key, val))
+    })
+    .collect::<Vec<_>>()
+    .join("\n"))
+}
+
+/// Generate a new message identifier using the domain of the given email.
+pub fn gen_message_id(email: &str) -> String {
+    let domain = email.rsplit('@').next().unwrap_or("localhost");
+    format!(
+        "<{}.{}@{}>",
+        Utc::now().timestamp_nanos(),
+        process::id(),
+        domain
+    )
+}
+
+/// Encode the given header value with UTF-8 encoded words ([RFC2047]) when it contains non-ASCII
+/// or control characters, otherwise return it unchanged. Encoded words are limited to 75
+/// characters and separated by folding white space.
+///
+/// [RFC2047]: https://datatracker.ietf.org/doc/html/rfc2047#section-2
+pub fn encode_header_value(value: &str) -> String {
+    if value.chars().all(|c| c == ' ' || c.is_ascii_graphic()) {
+        return value.to_owned();
+    }
+
+    // 45 bytes give 60 base64 characters, so a word fits in 75 characters
+    let mut chunks: Vec<String> = vec![String::new()];
+    for c in value.chars() {
+        let chunk = chunks.last_mut().unwrap();
+        if chunk.len() + c.len_utf8() > 45 {
+            chunks.push(String::new());
+        }
+        chunks.last_mut().unwrap().push(c);
+    }
+    chunks
+        .iter()
+        .map(|chunk| format!("=?utf-8?b?{}?=", base64::encode(chunk)))
+        .collect::<Vec<_>>()
+        .join("\r\n ")
+}
+
+/// Return true if the given text is a valid header field name ([RFC5322]): printable ASCII
+/// characters except the colon.
+///
+/// [RFC5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.6.8
+pub fn is_header_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| (33..=126).contains(&b) && b != b':')
+}
+
+/// Prepend the `Resent-*` headers ([RFC5322]) to the given raw message, so it can be bounced
+/// unchanged to new recipients.
+///
+/// [RFC5322]: https://datatracker.ietf.org/doc/html/rfc5322#section-3.6.6
+pub fn prepend_resent_headers(raw_msg: &[u8], from: &str, email: &str, to: &[&str]) -> Vec<u8> {
+    let headers = format!(
+        "Resent-From: {}\r\nResent-To: {}\r\nResent-Date: {}\r\nResent-Message-ID: {}\r\n",
+        from,
+        to.join(", "),
+        Local::now().to_rfc2822(),
+        gen_message_id(email),
+    );
+    let mut resent_msg = headers.into_bytes();
+    resent_msg.extend_from_slice(raw_msg);
+    resent_msg
+}
+
+/// Represents common email domains, used to detect typos in recipient domains.
+const COMMON_DOMAINS: &[&str] = &[
+    "aol.com",
+    "fastmail.com",
+    "gmail.com",
+    "gmx.com",
+    "gmx.de",
+    "googlemail.com",
+    "hotmail.com",
+    "icloud.com",
+    "live.com",
+    "mail.com",
+    "me.com",
+    "msn.com",
+    "outlook.com",
+    "posteo.de",
+    "proton.me",
+    "protonmail.com",
+    "web.de",
+    "yahoo.com",
+    "yahoo.fr",
+    "yandex.com",
+];
+
+/// Computes the optimal string alignment distance between the given strings: the number of
+/// insertions, deletions, substitutions and transpositions of adjacent chars needed to turn one
+/// into the other.
+fn osa_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Returns the common domain the given domain is likely a typo of, like `gmail.com` for
+/// `gmial.com`.
+pub fn domain_typo(domain: &str) -> Option<&'static str> {
+    let domain = domain.to_lowercase();
+    if COMMON_DOMAINS.contains(&domain.as_str()) {
+        return None;
+    }
+    COMMON_DOMAINS
+        .iter()
+        .find(|common| osa_distance(&domain, common) == 1)
+        .copied()
+}
+
+/// Checks the recipients of an outgoing message. Domains looking like typos of common domains
+/// are reported as warnings to print before sending. Domains without dot, and domains not
+/// accepting mail when the account verifies their MX records, are rejected unless forced. Failed
+/// MX lookups are reported as warnings only.
+pub fn check_recipients(msg: &Msg, force: bool, account: &Account) -> Result<Vec<String>> {
+    let addrs: Vec<&Address> = [&msg.to, &msg.cc, &msg.bcc]
+        .iter()
+        .filter_map(|addrs| addrs.as_ref())
+        .flatten()
+        .map(|addr| &addr.email)
+        .collect();
+    if addrs.is_empty() {
+        return Err(anyhow!("cannot send message without recipient"));
+    }
+
+    let mut warnings = vec![];
+    for addr in addrs {
+        let domain = addr.domain();
+        let err = if !domain.contains('.') {
+            Some(format!(r#"recipient "{}" has an incomplete domain"#, addr))
+        } else if account.verify_recipient_mx {
+            match has_mx(domain) {
+                Ok(true) => None,
+                Ok(false) => Some(format!(
+                    r#"domain of recipient "{}" does not accept mail"#,
+                    addr
+                )),
+                Err(err) => {
+                    warn!("{:?}", err);
+                    warnings.push(format!(
+                        r#"Warning: cannot verify the domain of recipient "{}": {}"#,
+                        addr, err
+                    ));
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        match err {
+            Some(err) if !force => {
+                return Err(anyhow!("{}, use --force to send it anyway", err));
+            }
+            Some(err) => {
+                warn!("{}", err);
+                warnings.push(format!("Warning: {}", err));
+            }
+            None => (),
+        }
+        if let Some(common) = domain_typo(domain) {
+            warnings.push(format!(
+                r#"Warning: recipient "{}" may be misspelled, did you mean "{}"?"#,
+                addr, common
+            ));
+        }
+    }
+    Ok(warnings)
+}
+
+/// Returns true if the given text contains the given keyword as a whole word, case insensitively.
+fn mentions_keyword(text: &str, keyword: &str) -> bool {
+    let text = text.to_lowercase();
+    let keyword = keyword.to_lowercase();
+    if keyword.is_empty() {
+        return false;
+    }
+    text.match_indices(&keyword).any(|(i, _)| {
+        let before = text[..i].chars().next_back();
+        let after = text[i + keyword.len()..].chars().next();
+        !before.map_or(false, char::is_alphanumeric) && !after.map_or(false, char::is_alphanumeric)
+    })
+}
+
+/// Checks whether an outgoing message announces an attachment it does not have, through one of
+/// the attachment keywords of the account. Quoted lines are ignored, since they belong to the
+/// replied message. Returns the warning to print before sending.
+pub fn check_attachments(msg: &Msg, account: &Account) -> Option<String> {
+    if !msg.attachments().is_empty() {
+        return None;
+    }
+    let body = msg.fold_text_plain_parts();
+    let body: Vec<&str> = body
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('>'))
+        .collect();
+    let body = body.join("\n");
+    account
+        .attachment_keywords
+        .iter()
+        .find(|keyword| mentions_keyword(&body, keyword))
+        .map(|keyword| {
+            format!(
+                r#"Warning: the message mentions "{}" but has no attachment"#,
+                keyword
+            )
+        })
+}
+
+/// Checks the size of an outgoing message against the size limit of the account. Returns the
+/// line to print before sending, or an error if the message is too large and not forced.
+pub fn check_msg_size(size: usize, force: bool, account: &Account) -> Result<String> {
+    let limit = account.msg_size_limit * 1_000_000;
+    if limit == 0 || size <= limit {
+        return Ok(format!("Sending message ({})", format_size(size)));
+    }
+    if !force {
+        return Err(anyhow!(
+            "message size ({}) exceeds the limit of {}, use --force to send it anyway",
+            format_size(size),
+            format_size(limit)
+        ));
+    }
+    warn!("message size ({}) exceeds the limit", size);
+    Ok(format!(
+        "Sending message ({}, above the limit of {})",
+        format_size(size),
+        format_size(limit)
+    ))
+}
+
+/// Formats the summary shown before sending a message, so that a wrong identity or a missing
+/// attachment can be caught before it is too late.
+pub fn format_send_summary(msg: &Msg) -> String {
+    let from = msg
+        .from
+        .as_ref()
+        .map(|addrs| {
+            addrs
+                .iter()
+                .map(|addr| addr.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default();
+    let count = |addrs: &Option<Vec<lettre::message::Mailbox>>| {
+        addrs.as_ref().map(|addrs| addrs.len()).unwrap_or_default()
+    };
+
+    let mut lines = vec![
+        format!("From: {}", from),
+        format!(
+            "To: {}, Cc: {}, Bcc: {} recipient(s)",
+            count(&msg.to),
+            count(&msg.cc),
+            count(&msg.bcc)
+        ),
+        format!("Subject: {}", msg.subject),
+    ];
+    let atts = msg.attachments();
+    if atts.is_empty() {
+        lines.push(String::from("Attachments: none"));
+    } else {
+        lines.push(format!("Attachments: {}", atts.len()));
+        for att in atts {
+            lines.push(format!(
+                "  - {} ({})",
+                att.filename,
+                format_size(att.content.len())
+            ));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Matches the given text against a glob pattern, case insensitively: `*` matches any sequence
+/// of characters and `?` matches a single character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last star in the pattern, and of the text it matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::msg::{BinaryPart, Part, Parts, TextPlainPart};
+
+    #[test]
+    fn it_should_match_globs() {
+        assert!(glob_match("image/*", "image/png"));
+        assert!(glob_match("*.pdf", "Invoice 2021.PDF"));
+        assert!(glob_match("report-??.csv", "report-01.csv"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("image/*", "application/pdf"));
+        assert!(!glob_match("*.pdf", "invoice.pdf.exe"));
+        assert!(!glob_match("report-??.csv", "report-1.csv"));
+    }
+
+    #[test]
+    fn it_should_detect_domain_typos() {
+        assert_eq!(Some("gmail.com"), domain_typo("gmial.com"));
+        assert_eq!(Some("gmail.com"), domain_typo("GMAIL.con"));
+        assert_eq!(Some("hotmail.com"), domain_typo("hotmal.com"));
+        assert_eq!(None, domain_typo("gmail.com"));
+        assert_eq!(None, domain_typo("mail.com"));
+        assert_eq!(None, domain_typo("example.com"));
+    }
+
+    #[test]
+    fn it_should_format_send_summaries() {
+        let msg = Msg {
+            subject: String::from("Report"),
+            from: Some(vec!["Alice <alice@example.com>".parse().unwrap()]),
+            to: Some(vec![
+                "bob@example.com".parse().unwrap(),
+                "carol@example.com".parse().unwrap(),
+            ]),
+            bcc: Some(vec!["dave@example.com".parse().unwrap()]),
+            parts: Parts(vec![Part::Binary(BinaryPart {
+                filename: String::from("report.pdf"),
+                mime: String::from("application/pdf"),
+                content: vec![0; 1_500],
+                inline: false,
+            })]),
+            ..Msg::default()
+        };
+        assert_eq!(
+            concat!(
+                "From: Alice <alice@example.com>\n",
+                "To: 2, Cc: 0, Bcc: 1 recipient(s)\n",
+                "Subject: Report\n",
+                "Attachments: 1\n",
+                "  - report.pdf (1.5 kB)",
+            ),
+            format_send_summary(&msg)
+        );
+    }
+
+    #[test]
+    fn it_should_check_attachments() {
+        let account = Account {
+            attachment_keywords: vec![String::from("attached"), String::from("pièce jointe")],
+            ..Account::default()
+        };
+        let msg = |body: &str| Msg {
+            parts: Parts(vec![Part::TextPlain(TextPlainPart {
+                content: body.to_owned(),
+                flowed: false,
+            })]),
+            ..Msg::default()
+        };
+
+        assert!(check_attachments(&msg("Please find the report Attached."), &account).is_some());
+        assert!(check_attachments(&msg("Voir la Pièce jointe."), &account).is_some());
+        assert!(check_attachments(&msg("I am unattached."), &account).is_none());
+        assert!(check_attachments(&msg("Hi,\n> see attached\nThanks"), &account).is_none());
+
+        let mut with_att = msg("See attached.");
+        with_att.parts.push(Part::Binary(BinaryPart {
+            filename: String::from("report.pdf"),
+            mime: String::from("application/pdf"),
+            content: vec![],
+            inline: false,
+        }));
+        assert!(check_attachments(&with_att, &account).is_none());
+    }
+
+    #[test]
+    fn it_should_check_recipients() {
+        let account = Account::default();
+        let msg = |to: &str| Msg {
+            to: Some(vec![to.parse().unwrap()]),
+            ..Msg::default()
+        };
+
+        assert!(check_recipients(&Msg::default(), false, &account).is_err());
+        assert!(check_recipients(&msg("bob@example.com"), false, &account)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            vec![
+                r#"Warning: recipient "bob@gmial.com" may be misspelled, did you mean "gmail.com"?"#
+            ],
+            check_recipients(&msg("bob@gmial.com"), false, &account).unwrap()
+        );
+        assert!(check_recipients(&msg("bob@localhost"), false, &account).is_err());
+        assert_eq!(
+            1,
+            check_recipients(&msg("bob@localhost"), true, &account)
+                .unwrap()
+                .len()
+        );
+    }
+
+    #[test]
+    fn it_should_parse_list_unsubscribe_urls() {
+        let raw_msg = concat![
+            "From: news@example.com\r\n",
+            "List-Unsubscribe: <mailto:leave@example.com?subject=unsubscribe>,\r\n",
+            " <https://example.com/unsubscribe?id=42>\r\n",
+            "\r\n",
+            "Hello!\r\n",
+        ];
+        let urls = list_unsubscribe_urls(raw_msg.as_bytes()).unwrap();
+        assert_eq!(
+            vec![
+                "mailto:leave@example.com?subject=unsubscribe",
+                "https://example.com/unsubscribe?id=42"
+            ],
+            urls.iter().map(Url::as_str).collect::<Vec<_>>()
+        );
+
+        let raw_msg = "From: news@example.com\r\n\r\nHello!\r\n";
+        assert!(list_unsubscribe_urls(raw_msg.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn it_should_encode_header_values() {
+        assert_eq!("Report", encode_header_value("Report"));
+        assert_eq!("=?utf-8?b?Q2Fmw6k=?=", encode_header_value("Café"));
+        assert_eq!(
+            "=?utf-8?b?YQ0KQmNjOiB4?=",
+            encode_header_value("a\r\nBcc: x")
+        );
+
+        let encoded = encode_header_value(&"é".repeat(40));
+        let words: Vec<&str> = encoded.split("\r\n ").collect();
+        assert_eq!(2, words.len());
+        assert!(words.iter().all(|word| word.len() <= 75));
+    }
+
+    #[test]
+    fn it_should_check_header_names() {
+        assert!(is_header_name("List-Id"));
+        assert!(is_header_name("X-Spam-Status!"));
+        assert!(!is_header_name(""));
+        assert!(!is_header_name("List Id"));
+        assert!(!is_header_name("List-Id:"));
+        assert!(!is_header_name("Liste-Identifié"));
+        assert!(!is_header_name("X-Tag\r\n"));
+    }
+
+    #[test]
+    fn it_should_format_envelope_headers() {
+        let raw_headers = concat![
+            "Received: from mx.example.com\r\n",
+            "Subject: =?utf-8?q?Caf=C3=A9?=\r\n",
+            "From: bob@example.com\r\n",
+            "Date: Sat, 16 Oct 2021 10:00:00 +0200\r\n",
+            "\r\n",
+        ];
+        let date_fmt = DateFmt::new(Some("C"), Some("%d/%m/%Y %H:%M"), false, None);
+        assert_eq!(
+            "From: bob@example.com\nDate: 16/10/2021 10:00\nSubject: Café",
+            format_envelope_headers(raw_headers.as_bytes(), &date_fmt).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_should_find_raw_part() {
+        let raw_msg = concat![
+            "From: bob@example.com\r\n",
+            "Content-Type: multipart/mixed; boundary=\"b\"\r\n",
+            "\r\n",
+            "--b\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "Hello!\r\n",
+            "--b\r\n",
+            "Content-Type: application/json\r\n",
+            "Content-Transfer-Encoding: base64\r\n",
+            "\r\n",
+            "eyJoZWxsbyI6IHRydWV9\r\n",
+            "--b--\r\n",
+        ];
+
+        assert!(find_raw_part(raw_msg.as_bytes(), "1")
+            .unwrap()
+            .starts_with(b"Hello!"));
+        assert_eq!(
+            br#"{"hello": true}"#.to_vec(),
+            find_raw_part(raw_msg.as_bytes(), "2").unwrap()
+        );
+        assert_eq!(
+            br#"{"hello": true}"#.to_vec(),
+            find_raw_part(raw_msg.as_bytes(), "application/JSON").unwrap()
+        );
+        assert!(find_raw_part(raw_msg.as_bytes(), "0").is_err());
+        assert!(find_raw_part(raw_msg.as_bytes(), "3").is_err());
+        assert!(find_raw_part(raw_msg.as_bytes(), "text/html").is_err());
+    }
+
+    #[test]
+    fn it_should_prepend_resent_headers() {
+        let raw_msg = "From: bob@example.com\r\nSubject: Hi\r\n\r\nHello!\r\n";
+        let resent_msg = prepend_resent_headers(
+            raw_msg.as_bytes(),
+            "Alice <alice@example.com>",
+            "alice@example.com",
+            &["carol@example.com", "dave@example.com"],
+        );
+        let resent_msg = String::from_utf8(resent_msg).unwrap();
+
+        assert!(resent_msg.starts_with(concat![
+            "Resent-From: Alice <alice@example.com>\r\n",
+            "Resent-To: carol@example.com, dave@example.com\r\n",
+            "Resent-Date: ",
+        ]));
+        assert!(resent_msg.contains("@example.com>\r\nFrom: bob@example.com\r\n"));
+        assert!(resent_msg.ends_with(raw_msg));
+    }
+
+    #[test]
+    fn it_should_check_msg_size() {
+        let account = Account {
+            msg_size_limit: 25,
+            ..Account::default()
+        };
+
+        assert_eq!(
+            "Sending message (1.2 MB)",
+            check_msg_size(1_200_000, false, &account).unwrap()
+        );
+        assert!(check_msg_size(30_000_000, false, &account).is_err());
+        assert_eq!(
+            "Sending message (30.0 MB, above the limit of 25.0 MB)",
+            check_msg_size(30_000_000, true, &account).unwrap()
+        );
+
+        let account = Account::default();
+        assert!(check_msg_size(30_000_000, false, &account).is_ok());
+    }
+}
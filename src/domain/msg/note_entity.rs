@@ -0,0 +1,267 @@
+//! Note entity module.
+//!
+//! This module contains the definition of the private notes attached to messages. Notes are kept
+//! in a local cache next to the config file with the `.notes` extension, indexed by `Message-ID`,
+//! and stored as `/comment` annotations ([RFC5257]) when the server supports them.
+//!
+//! [RFC5257]: https://datatracker.ietf.org/doc/html/rfc5257
+
+use anyhow::{Context, Result};
+use mailparse::MailHeaderMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    output::{PrintTable, PrintTableOpts, WriteColor},
+    ui::{Cell, Row, Table},
+};
+
+/// Represents the IMAP capability of the servers supporting annotations.
+pub const ANNOTATE_CAPABILITY: &str = "ANNOTATE-EXPERIMENT-1";
+
+/// Represents the annotation entry notes are stored in.
+pub const NOTE_ANNOTATION_ENTRY: &str = "/comment";
+
+/// Represents a note attached to a message.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Note {
+    /// The `Message-ID` of the message, used as key of the local cache.
+    #[serde(skip_deserializing)]
+    pub message_id: String,
+    /// The subject of the message, kept for listings.
+    pub subject: String,
+    /// The note itself.
+    pub note: String,
+}
+
+impl Note {
+    /// Builds the note of the message with the given raw headers.
+    pub fn from_raw_headers(raw_headers: &[u8], note: &str) -> Result<Self> {
+        let (headers, _) =
+            mailparse::parse_headers(raw_headers).context("cannot parse message headers")?;
+        Ok(Self {
+            message_id: headers
+                .get_first_value("Message-ID")
+                .unwrap_or_default()
+                .trim()
+                .to_owned(),
+            subject: headers.get_first_value("Subject").unwrap_or_default(),
+            note: note.to_owned(),
+        })
+    }
+}
+
+/// Makes the note printable in a table.
+impl Table for Note {
+    fn head() -> Row {
+        Row::new()
+            .cell(Cell::new("SUBJECT").shrinkable().bold().underline().white())
+            .cell(Cell::new("NOTE").shrinkable().bold().underline().white())
+    }
+
+    fn row(&self) -> Row {
+        Row::new()
+            .cell(Cell::new(&self.subject).shrinkable().green())
+            .cell(Cell::new(&self.note).shrinkable().white())
+    }
+}
+
+/// Represents the notes matching a search.
+#[derive(Debug, Default, Serialize)]
+pub struct Notes(pub Vec<Note>);
+
+impl Deref for Notes {
+    type Target = Vec<Note>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Makes the notes printable.
+impl PrintTable for Notes {
+    fn print_table(&self, writter: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        writeln!(writter)?;
+        Table::print(writter, &self, opts)?;
+        writeln!(writter)?;
+        Ok(())
+    }
+}
+
+/// Represents the local cache of the notes.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NotesCache {
+    /// The file the cache is stored in.
+    path: PathBuf,
+    /// The notes, indexed by `Message-ID`.
+    notes: BTreeMap<String, Note>,
+}
+
+impl NotesCache {
+    /// Builds the path of the cache from the path of the config file.
+    pub fn path_from_config(config_path: &Path) -> PathBuf {
+        config_path.with_extension("notes")
+    }
+
+    /// Loads the cache from the given file. A missing file gives an empty cache.
+    pub fn load<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                return Err(err).context(format!("cannot read notes {:?}", path));
+            }
+        };
+        let mut notes: BTreeMap<String, Note> =
+            toml::from_str(&content).context(format!("cannot parse notes {:?}", path))?;
+        for (message_id, note) in notes.iter_mut() {
+            note.message_id = message_id.to_owned();
+        }
+        Ok(Self { path, notes })
+    }
+
+    /// Saves the cache to its file.
+    pub fn save(&self) -> Result<()> {
+        let content = toml::to_string(&self.notes).context("cannot serialize notes")?;
+        fs::write(&self.path, content).context(format!("cannot write notes {:?}", self.path))
+    }
+
+    /// Returns the note of the given message.
+    pub fn get(&self, message_id: &str) -> Option<&Note> {
+        self.notes.get(message_id)
+    }
+
+    /// Attaches the note to its message, replacing the previous one.
+    pub fn set(&mut self, note: Note) {
+        self.notes.insert(note.message_id.to_owned(), note);
+    }
+
+    /// Removes the note of the given message. Returns false if the message had no note.
+    pub fn remove(&mut self, message_id: &str) -> bool {
+        self.notes.remove(message_id).is_some()
+    }
+
+    /// Returns the notes containing the given query, ignoring case. Subjects are searched too.
+    pub fn search(&self, query: &str) -> Notes {
+        let query = query.to_lowercase();
+        Notes(
+            self.notes
+                .values()
+                .filter(|note| {
+                    note.note.to_lowercase().contains(&query)
+                        || note.subject.to_lowercase().contains(&query)
+                })
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+/// Quotes a note so it can be sent as an annotation value. Quoted strings cannot hold line
+/// breaks, so they are replaced by spaces.
+pub fn quote_note(note: &str) -> String {
+    let note = note
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace("\r\n", " ")
+        .replace(|c| c == '\r' || c == '\n', " ");
+    format!("\"{}\"", note)
+}
+
+/// Parses the note out of the raw response of an annotation fetch, like
+/// `* 1 FETCH (ANNOTATION (/comment (value.priv "note")))`. The value can be a quoted string, a
+/// literal or `NIL` when the message has no note.
+pub fn parse_note_annotation(res: &[u8]) -> Option<String> {
+    let res = String::from_utf8_lossy(res);
+    let start = res.to_lowercase().find("value.priv")? + "value.priv".len();
+    let value = res[start..].trim_start();
+
+    if let Some(quoted) = value.strip_prefix('"') {
+        let mut note = String::new();
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => note.push(chars.next()?),
+                '"' => return Some(note).filter(|note| !note.is_empty()),
+                c => note.push(c),
+            }
+        }
+        None
+    } else if let Some(literal) = value.strip_prefix('{') {
+        let end = literal.find('}')?;
+        let len: usize = literal[..end].parse().ok()?;
+        let note = literal[end + 1..].as_bytes();
+        let note = note.strip_prefix(b"\r\n").unwrap_or(note);
+        let note = String::from_utf8_lossy(note.get(..len)?).into_owned();
+        Some(note).filter(|note| !note.is_empty())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_search_notes() {
+        let mut cache = NotesCache::default();
+        cache.set(Note {
+            message_id: "<1@example.com>".into(),
+            subject: "Invoice".into(),
+            note: "Pay before Friday".into(),
+        });
+        cache.set(Note {
+            message_id: "<2@example.com>".into(),
+            subject: "Holidays".into(),
+            note: "Ask Bob".into(),
+        });
+
+        assert_eq!(
+            "Pay before Friday",
+            cache.get("<1@example.com>").unwrap().note
+        );
+        assert_eq!(1, cache.search("friday").len());
+        assert_eq!(1, cache.search("HOLIDAYS").len());
+        assert_eq!(0, cache.search("nothing").len());
+        assert!(cache.remove("<2@example.com>"));
+        assert!(!cache.remove("<2@example.com>"));
+    }
+
+    #[test]
+    fn it_should_quote_notes() {
+        assert_eq!(r#""Call \"Bob\"""#, quote_note(r#"Call "Bob""#));
+        assert_eq!(
+            r#""C:\\tmp then later""#,
+            quote_note("C:\\tmp\r\nthen\nlater")
+        );
+    }
+
+    #[test]
+    fn it_should_parse_note_annotations() {
+        let parse = |res: &str| parse_note_annotation(res.as_bytes());
+        assert_eq!(
+            Some(String::from(r#"Call "Bob""#)),
+            parse("* 1 FETCH (ANNOTATION (/comment (value.priv \"Call \\\"Bob\\\"\")))\r\n")
+        );
+        assert_eq!(
+            Some(String::from("Pay\r\nnow")),
+            parse("* 1 FETCH (ANNOTATION (/comment (value.priv {8}\r\nPay\r\nnow)))\r\n")
+        );
+        assert_eq!(
+            None,
+            parse("* 1 FETCH (ANNOTATION (/comment (value.priv NIL)))\r\n")
+        );
+        assert_eq!(
+            None,
+            parse("* 1 FETCH (ANNOTATION (/comment (value.priv \"\")))\r\n")
+        );
+        assert_eq!(None, parse("* 1 FETCH (FLAGS (\\Seen))\r\n"));
+    }
+}
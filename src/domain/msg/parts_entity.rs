@@ -2,9 +2,15 @@ use mailparse::MailHeaderMap;
 use serde::Serialize;
 use std::ops::{Deref, DerefMut};
 
+use crate::domain::msg::flowed_utils;
+
 #[derive(Debug, Clone, Default, Serialize)]
 pub struct TextPlainPart {
     pub content: String,
+    /// Whether the part was sent as format=flowed, its paragraphs being unflowed then, so they
+    /// can be wrapped again to the width of the terminal.
+    #[serde(skip)]
+    pub flowed: bool,
 }
 
 #[derive(Debug, Clone, Default, Serialize)]
@@ -12,11 +18,18 @@ pub struct TextHtmlPart {
     pub content: String,
 }
 
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TextCalendarPart {
+    pub content: String,
+}
+
 #[derive(Debug, Clone, Default, Serialize)]
 pub struct BinaryPart {
     pub filename: String,
     pub mime: String,
     pub content: Vec<u8>,
+    /// Whether the part is displayed inline, like images embedded in the body.
+    pub inline: bool,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -24,12 +37,16 @@ pub struct BinaryPart {
 pub enum Part {
     TextPlain(TextPlainPart),
     TextHtml(TextHtmlPart),
+    TextCalendar(TextCalendarPart),
     Binary(BinaryPart),
 }
 
 impl Part {
     pub fn new_text_plain(content: String) -> Self {
-        Self::TextPlain(TextPlainPart { content })
+        Self::TextPlain(TextPlainPart {
+            content,
+            flowed: false,
+        })
     }
 }
 
@@ -49,6 +66,18 @@ impl Parts {
         self.push(Part::TextPlain(part));
     }
 
+    /// Wraps the plain text parts sent as format=flowed to the given width. The other parts are
+    /// left as is, their line breaks being the ones of the sender.
+    pub fn wrap_flowed(&mut self, width: usize) {
+        for part in self.iter_mut() {
+            if let Part::TextPlain(part) = part {
+                if part.flowed {
+                    part.content = flowed_utils::wrap(&part.content, width);
+                }
+            }
+        }
+    }
+
     pub fn replace_text_html_parts_with(&mut self, part: TextHtmlPart) {
         self.retain(|part| {
             if let Part::TextHtml(_) = part {
@@ -75,6 +104,18 @@ impl DerefMut for Parts {
     }
 }
 
+impl Parts {
+    /// Picks the displayable parts of the given message from its structure: text parts and
+    /// inline images, only the best alternative of each `multipart/alternative` being kept. Plain
+    /// text alternatives are preferred, HTML ones are kept when no plain text alternative is
+    /// found.
+    pub fn best_of(part: &mailparse::ParsedMail) -> Self {
+        let mut parts = vec![];
+        build_best_parts_rec(part, &mut parts);
+        Self(parts)
+    }
+}
+
 impl<'a> From<&'a mailparse::ParsedMail<'a>> for Parts {
     fn from(part: &'a mailparse::ParsedMail<'a>) -> Self {
         let mut parts = vec![];
@@ -86,19 +127,35 @@ impl<'a> From<&'a mailparse::ParsedMail<'a>> for Parts {
 fn build_parts_map_rec(part: &mailparse::ParsedMail, parts: &mut Vec<Part>) {
     if part.subparts.is_empty() {
         let content_disp = part.get_content_disposition();
+        let filename = content_disp
+            .params
+            .get("filename")
+            .or_else(|| part.ctype.params.get("name"))
+            .map(String::from);
+        // Inline parts with a file name (like the images and documents embedded by Apple Mail)
+        // are attachments as well, except text parts which are rendered as the body
+        let inline_attachment = content_disp.disposition == mailparse::DispositionType::Inline
+            && filename.is_some()
+            && !part.ctype.mimetype.starts_with("text/");
         match content_disp.disposition {
             mailparse::DispositionType::Attachment => {
-                let filename = content_disp
-                    .params
-                    .get("filename")
-                    .map(String::from)
-                    .unwrap_or(String::from("noname"));
                 let content = part.get_body_raw().unwrap_or_default();
                 let mime = tree_magic::from_u8(&content);
                 parts.push(Part::Binary(BinaryPart {
-                    filename,
+                    filename: filename.unwrap_or_else(|| String::from("noname")),
                     mime,
                     content,
+                    inline: false,
+                }));
+            }
+            mailparse::DispositionType::Inline if inline_attachment => {
+                let content = part.get_body_raw().unwrap_or_default();
+                let mime = tree_magic::from_u8(&content);
+                parts.push(Part::Binary(BinaryPart {
+                    filename: filename.unwrap_or_default(),
+                    mime,
+                    content,
+                    inline: true,
                 }));
             }
             // TODO: manage other use cases
@@ -108,9 +165,25 @@ fn build_parts_map_rec(part: &mailparse::ParsedMail, parts: &mut Vec<Part>) {
                     .map(|ctype| {
                         let content = part.get_body().unwrap_or_default();
                         if ctype.starts_with("text/plain") {
-                            parts.push(Part::TextPlain(TextPlainPart { content }))
+                            let params = &part.ctype.params;
+                            let flowed = params
+                                .get("format")
+                                .map(|format| format.eq_ignore_ascii_case("flowed"))
+                                .unwrap_or_default();
+                            let content = if flowed {
+                                let delsp = params
+                                    .get("delsp")
+                                    .map(|delsp| delsp.eq_ignore_ascii_case("yes"))
+                                    .unwrap_or_default();
+                                flowed_utils::unflow(&content, delsp)
+                            } else {
+                                content
+                            };
+                            parts.push(Part::TextPlain(TextPlainPart { content, flowed }))
                         } else if ctype.starts_with("text/html") {
                             parts.push(Part::TextHtml(TextHtmlPart { content }))
+                        } else if ctype.starts_with("text/calendar") {
+                            parts.push(Part::TextCalendar(TextCalendarPart { content }))
                         }
                     });
             }
@@ -121,3 +194,111 @@ fn build_parts_map_rec(part: &mailparse::ParsedMail, parts: &mut Vec<Part>) {
             .for_each(|part| build_parts_map_rec(part, parts));
     }
 }
+
+fn build_best_parts_rec(part: &mailparse::ParsedMail, parts: &mut Vec<Part>) {
+    if part.subparts.is_empty() {
+        let mut leaf = vec![];
+        build_parts_map_rec(part, &mut leaf);
+        parts.extend(leaf.into_iter().filter(|part| match part {
+            Part::TextPlain(_) | Part::TextHtml(_) => true,
+            Part::Binary(part) => part.inline && part.mime.starts_with("image/"),
+            Part::TextCalendar(_) => false,
+        }));
+    } else if part
+        .ctype
+        .mimetype
+        .eq_ignore_ascii_case("multipart/alternative")
+    {
+        let alternatives: Vec<Vec<Part>> = part
+            .subparts
+            .iter()
+            .map(|part| {
+                let mut alternative = vec![];
+                build_best_parts_rec(part, &mut alternative);
+                alternative
+            })
+            .collect();
+        let has_text = |alternative: &&Vec<Part>, plain_only: bool| {
+            alternative.iter().any(|part| match part {
+                Part::TextPlain(part) => !part.content.trim().is_empty(),
+                Part::TextHtml(part) => !plain_only && !part.content.trim().is_empty(),
+                _ => false,
+            })
+        };
+        let best = alternatives
+            .iter()
+            .find(|alternative| has_text(alternative, true))
+            .or_else(|| {
+                alternatives
+                    .iter()
+                    .find(|alternative| has_text(alternative, false))
+            });
+        if let Some(best) = best {
+            parts.extend(best.iter().cloned());
+        }
+    } else {
+        part.subparts
+            .iter()
+            .for_each(|part| build_best_parts_rec(part, parts));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_pick_best_parts() {
+        let raw = concat!(
+            "Content-Type: multipart/mixed; boundary=mixed\r\n\r\n",
+            "--mixed\r\n",
+            "Content-Type: multipart/alternative; boundary=alt\r\n\r\n",
+            "--alt\r\n",
+            "Content-Type: text/plain\r\n\r\n",
+            "Hello\r\n",
+            "--alt\r\n",
+            "Content-Type: text/html\r\n\r\n",
+            "<p>Hello</p>\r\n",
+            "--alt--\r\n",
+            "--mixed\r\n",
+            "Content-Type: multipart/alternative; boundary=alt2\r\n\r\n",
+            "--alt2\r\n",
+            "Content-Type: text/plain\r\n\r\n",
+            "\r\n",
+            "--alt2\r\n",
+            "Content-Type: text/html\r\n\r\n",
+            "<p>Forwarded</p>\r\n",
+            "--alt2--\r\n",
+            "--mixed--\r\n",
+        );
+        let parsed = mailparse::parse_mail(raw.as_bytes()).unwrap();
+        let parts = Parts::best_of(&parsed);
+
+        assert_eq!(2, parts.len());
+        assert!(matches!(&parts[0], Part::TextPlain(part) if part.content.trim() == "Hello"));
+        assert!(matches!(&parts[1], Part::TextHtml(part) if part.content.contains("Forwarded")));
+    }
+
+    #[test]
+    fn it_should_wrap_flowed_parts_only() {
+        let raw = concat!(
+            "Content-Type: multipart/mixed; boundary=mixed\r\n\r\n",
+            "--mixed\r\n",
+            "Content-Type: text/plain; format=flowed\r\n\r\n",
+            "aaaa bbbb \r\ncccc dddd\r\n",
+            "--mixed\r\n",
+            "Content-Type: text/plain\r\n\r\n",
+            "aaaa bbbb cccc dddd\r\n",
+            "--mixed--\r\n",
+        );
+        let parsed = mailparse::parse_mail(raw.as_bytes()).unwrap();
+        let mut parts = Parts::from(&parsed);
+        parts.wrap_flowed(10);
+
+        assert_eq!(2, parts.len());
+        assert!(matches!(&parts[0], Part::TextPlain(part)
+            if part.flowed && part.content.lines().all(|line| line.chars().count() <= 10)));
+        assert!(matches!(&parts[1], Part::TextPlain(part)
+            if !part.flowed && part.content.trim() == "aaaa bbbb cccc dddd"));
+    }
+}
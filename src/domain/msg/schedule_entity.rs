@@ -0,0 +1,186 @@
+//! Schedule entity module.
+//!
+//! This module contains the definitions used to schedule actions on messages, like snoozing them
+//! or sending them later. The time of the action is stored on the server, in a keyword like
+//! `$Snoozed-20211017T070000Z`, so no local state is needed.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+
+/// Represents the prefix of the keywords holding the wake up time of snoozed messages.
+pub const SNOOZED_FLAG_PREFIX: &str = "$Snoozed-";
+
+/// Represents the prefix of the keywords holding the sending time of queued messages.
+pub const SEND_AT_FLAG_PREFIX: &str = "$SendAt-";
+
+/// Represents the format of the time in the keywords, always in UTC.
+const SCHEDULE_FLAG_DATE_FMT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Represents the hour of the actions scheduled on a day without time.
+const DEFAULT_SCHEDULE_HOUR: u32 = 8;
+
+/// Builds the keyword holding the given time, starting with the given prefix.
+pub fn schedule_flag(prefix: &str, time: DateTime<Utc>) -> String {
+    format!("{}{}", prefix, time.format(SCHEDULE_FLAG_DATE_FMT))
+}
+
+/// Parses the time of a keyword, or returns `None` if it does not start with the given prefix.
+pub fn parse_schedule_flag(prefix: &str, flag: &str) -> Option<DateTime<Utc>> {
+    let date = flag.strip_prefix(prefix)?;
+    Utc.datetime_from_str(date, SCHEDULE_FLAG_DATE_FMT).ok()
+}
+
+/// Parses a duration made of a number followed by a unit: `m` (minutes), `h` (hours), `d` (days)
+/// or `w` (weeks).
+fn parse_duration(duration: &str) -> Option<Duration> {
+    let unit = duration.chars().last()?;
+    let count: i64 = duration[..duration.len() - unit.len_utf8()].parse().ok()?;
+    match unit {
+        'm' => Some(Duration::minutes(count)),
+        'h' => Some(Duration::hours(count)),
+        'd' => Some(Duration::days(count)),
+        'w' => Some(Duration::weeks(count)),
+        _ => None,
+    }
+}
+
+/// Parses a time like `9am`, `9:30pm` or `14:30`.
+fn parse_time(time: &str) -> Option<NaiveTime> {
+    let (time, offset) = if let Some(time) = time.strip_suffix("am") {
+        (time, Some(0))
+    } else if let Some(time) = time.strip_suffix("pm") {
+        (time, Some(12))
+    } else {
+        (time, None)
+    };
+    let mut parts = time.splitn(2, ':');
+    let hour: u32 = parts.next()?.parse().ok()?;
+    let min: u32 = match parts.next() {
+        Some(min) => min.parse().ok()?,
+        None if offset.is_some() => 0,
+        None => return None,
+    };
+    let hour = match offset {
+        Some(_) if hour == 0 || hour > 12 => return None,
+        Some(offset) => hour % 12 + offset,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, min, 0)
+}
+
+/// Parses a day like `today`, `tomorrow` or a weekday (the next one, never today).
+fn parse_day(day: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match day {
+        "today" => Some(today),
+        "tomorrow" => Some(today.succ()),
+        day => {
+            let weekday: Weekday = day.parse().ok()?;
+            let days =
+                (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+            let days = if days == 0 { 7 } else { days };
+            Some(today + Duration::days(days as i64))
+        }
+    }
+}
+
+/// Parses the time of a scheduled action, relatively to the given time. The accepted formats
+/// are a duration (`+2h`, `3d`), a date with an optional time (`2021-12-01 08:00`), a day with an
+/// optional time (`tomorrow 9am`, `monday`) or a time (`14:30`, today or tomorrow if already
+/// passed). Days without time mean 8am.
+pub fn parse_schedule_time(input: &str, now: DateTime<Local>) -> Result<DateTime<Local>> {
+    let input = input.trim().to_lowercase();
+    let err = || anyhow!(r#"cannot parse time "{}""#, input);
+    let today = now.naive_local().date();
+    let default_time = NaiveTime::from_hms(DEFAULT_SCHEDULE_HOUR, 0, 0);
+
+    let until = if let Some(duration) = parse_duration(input.trim_start_matches('+')) {
+        now + duration
+    } else {
+        let words: Vec<&str> = input.split_whitespace().collect();
+        let (date, time) = match words.as_slice() {
+            [word] => match parse_time(word) {
+                Some(time) if today.and_time(time) > now.naive_local() => (today, time),
+                Some(time) => (today.succ(), time),
+                None => (
+                    NaiveDate::parse_from_str(word, "%Y-%m-%d")
+                        .ok()
+                        .or_else(|| parse_day(word, today))
+                        .ok_or_else(err)?,
+                    default_time,
+                ),
+            },
+            [day, time] => (
+                NaiveDate::parse_from_str(day, "%Y-%m-%d")
+                    .ok()
+                    .or_else(|| parse_day(day, today))
+                    .ok_or_else(err)?,
+                parse_time(time).ok_or_else(err)?,
+            ),
+            _ => return Err(err()),
+        };
+        Local
+            .from_local_datetime(&date.and_time(time))
+            .earliest()
+            .ok_or_else(err)?
+    };
+
+    if until <= now {
+        return Err(anyhow!(
+            r#"cannot schedule at "{}": time already passed"#,
+            input
+        ));
+    }
+    Ok(until)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_build_and_parse_schedule_flags() {
+        let time = Utc.ymd(2021, 10, 17).and_hms(7, 0, 0);
+        let flag = schedule_flag(SNOOZED_FLAG_PREFIX, time);
+        assert_eq!("$Snoozed-20211017T070000Z", flag);
+        assert_eq!(Some(time), parse_schedule_flag(SNOOZED_FLAG_PREFIX, &flag));
+        assert_eq!(None, parse_schedule_flag(SEND_AT_FLAG_PREFIX, &flag));
+        assert_eq!(None, parse_schedule_flag(SNOOZED_FLAG_PREFIX, "$label1"));
+        assert_eq!(
+            None,
+            parse_schedule_flag(SNOOZED_FLAG_PREFIX, "$Snoozed-soon")
+        );
+    }
+
+    #[test]
+    fn it_should_parse_schedule_times() {
+        // Saturday 16 October 2021, 10am
+        let now = Local.ymd(2021, 10, 16).and_hms(10, 0, 0);
+        let parse = |input| {
+            parse_schedule_time(input, now)
+                .map(|until| until.naive_local().to_string())
+                .ok()
+        };
+
+        assert_eq!(Some("2021-10-16 12:00:00".into()), parse("+2h"));
+        assert_eq!(Some("2021-10-16 10:30:00".into()), parse("30m"));
+        assert_eq!(Some("2021-10-19 10:00:00".into()), parse("3d"));
+        assert_eq!(Some("2021-10-17 09:00:00".into()), parse("tomorrow 9am"));
+        assert_eq!(Some("2021-10-17 08:00:00".into()), parse("Tomorrow"));
+        assert_eq!(Some("2021-10-16 14:30:00".into()), parse("14:30"));
+        assert_eq!(Some("2021-10-17 09:30:00".into()), parse("9:30am"));
+        assert_eq!(Some("2021-10-16 12:00:00".into()), parse("12pm"));
+        assert_eq!(Some("2021-10-18 08:00:00".into()), parse("monday"));
+        assert_eq!(Some("2021-10-23 18:00:00".into()), parse("saturday 6pm"));
+        assert_eq!(
+            Some("2021-12-01 08:00:00".into()),
+            parse("2021-12-01 08:00")
+        );
+        assert_eq!(Some("2021-12-01 08:00:00".into()), parse("2021-12-01"));
+
+        assert_eq!(None, parse("2021-10-01"));
+        assert_eq!(None, parse("today 9am"));
+        assert_eq!(None, parse("13pm"));
+        assert_eq!(None, parse("someday"));
+        assert_eq!(None, parse("next week maybe"));
+    }
+}
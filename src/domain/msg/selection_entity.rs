@@ -0,0 +1,105 @@
+//! Message selection entity module.
+//!
+//! This module contains the definition of the messages targetted by batch commands.
+
+use anyhow::Result;
+use log::trace;
+
+use crate::{domain::imap::ImapServiceInterface, output::ExitCode};
+
+/// Represents the messages targetted by a batch command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection<'a> {
+    /// A sequence range following the [RFC3501] format, for example `3,5,10:20`.
+    ///
+    /// [RFC3501]: https://datatracker.ietf.org/doc/html/rfc3501#section-9
+    SeqRange(&'a str),
+    /// An IMAP query, the targetted messages are the ones matching it.
+    Query(&'a str),
+}
+
+impl<'a> Selection<'a> {
+    /// Resolves the selection into a sequence range, so the command can be executed at once.
+    pub fn to_seq_range<'b, ImapService: ImapServiceInterface<'b>>(
+        &self,
+        imap: &mut ImapService,
+    ) -> Result<String> {
+        match self {
+            Self::SeqRange(seq_range) => Ok(seq_range.to_string()),
+            Self::Query(query) => {
+                let seqs = imap.search_seqs(query)?;
+                trace!("seqs matching query: {:?}", seqs);
+                if seqs.is_empty() {
+                    Err(ExitCode::NotFound.err(format!(
+                        r#"cannot find messages matching query "{}""#,
+                        query
+                    )))
+                } else {
+                    Ok(compress_seqs(&seqs))
+                }
+            }
+        }
+    }
+}
+
+/// Compresses the given sorted sequence numbers into a sequence range, for example `[1, 2, 3, 5]`
+/// becomes `1:3,5`.
+pub fn compress_seqs(seqs: &[u32]) -> String {
+    let mut ranges: Vec<(u32, u32)> = vec![];
+    for &seq in seqs {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == seq => *end = seq,
+            Some((_, end)) if *end == seq => (),
+            _ => ranges.push((seq, seq)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|(begin, end)| {
+            if begin == end {
+                begin.to_string()
+            } else {
+                format!("{}:{}", begin, end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Returns true if the given string is a sequence set following the [RFC3501] format, for example
+/// `3,5,10:*`, rather than a search query.
+///
+/// [RFC3501]: https://datatracker.ietf.org/doc/html/rfc3501#section-9
+pub fn is_seq_set(s: &str) -> bool {
+    !s.is_empty()
+        && s.split(',').all(|range| {
+            range.split(':').count() <= 2
+                && range
+                    .split(':')
+                    .all(|n| n == "*" || (!n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_compress_seqs() {
+        assert_eq!("", compress_seqs(&[]));
+        assert_eq!("4", compress_seqs(&[4]));
+        assert_eq!("1:3,5", compress_seqs(&[1, 2, 3, 5]));
+        assert_eq!("3,5,10:12", compress_seqs(&[3, 5, 10, 11, 11, 12]));
+    }
+
+    #[test]
+    fn it_should_recognize_seq_sets() {
+        assert!(is_seq_set("4"));
+        assert!(is_seq_set("3,5,10:*"));
+        assert!(!is_seq_set(""));
+        assert!(!is_seq_set("1:2:3"));
+        assert!(!is_seq_set("3,"));
+        assert!(!is_seq_set("UNSEEN"));
+        assert!(!is_seq_set("UID 3:5"));
+    }
+}
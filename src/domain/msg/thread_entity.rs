@@ -0,0 +1,204 @@
+//! Thread entity module.
+//!
+//! This module contains the definition of the conversations printed by the `thread` command:
+//! the messages linked together by their `Message-ID`, `In-Reply-To` and `References` headers.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset};
+use mailparse::MailHeaderMap;
+use regex::Regex;
+use serde::{Serialize, Serializer};
+
+use crate::{
+    domain::msg::{parse_date_header, Msg, Parts},
+    output::{Print, WriteColor},
+};
+
+/// Represents the maximum number of times mailboxes are searched for a thread, each search using
+/// the ids of the messages found by the previous one.
+pub const THREAD_SEARCH_PASSES: usize = 3;
+
+/// Returns the message ids found in the given header value, like `<a@host> <b@host>`.
+fn parse_msg_ids(val: &str) -> Vec<String> {
+    Regex::new(r"<[^<>\s]+>")
+        .unwrap()
+        .find_iter(val)
+        .map(|id| id.as_str().to_owned())
+        .collect()
+}
+
+/// Returns the ids linking the message with the given headers to its thread: its own
+/// `Message-ID`, then the ones of its `References` and `In-Reply-To` headers.
+pub fn thread_ids(headers: &[mailparse::MailHeader]) -> Vec<String> {
+    let mut ids = vec![];
+    for key in &["Message-ID", "References", "In-Reply-To"] {
+        for id in parse_msg_ids(&headers.get_first_value(key).unwrap_or_default()) {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+    ids
+}
+
+/// Builds the IMAP query matching the messages of the thread: the ones with one of the given ids
+/// as `Message-ID` or among their `References`.
+pub fn thread_query(ids: &[String]) -> String {
+    let keys: Vec<String> = ids
+        .iter()
+        .flat_map(|id| {
+            vec![
+                format!(r#"HEADER Message-ID "{}""#, id),
+                format!(r#"HEADER References "{}""#, id),
+            ]
+        })
+        .collect();
+    // Search keys are AND-ed, so they are OR-ed two by two from the end
+    keys.iter()
+        .rev()
+        .cloned()
+        .reduce(|query, key| format!("OR {} {}", key, query))
+        .unwrap_or_default()
+}
+
+/// Collapses the blocks of quoted lines into a single line giving their size.
+pub fn collapse_quotes(text: &str) -> String {
+    let mut lines: Vec<String> = vec![];
+    let mut quoted = 0;
+    for line in text.lines().chain(std::iter::once("")) {
+        if line.starts_with('>') {
+            quoted += 1;
+            continue;
+        }
+        if quoted > 0 {
+            lines.push(format!("[{} quoted line(s)]", quoted));
+            quoted = 0;
+        }
+        lines.push(line.to_owned());
+    }
+    lines.pop();
+    lines.join("\n")
+}
+
+fn serialize_date<S: Serializer>(
+    date: &Option<DateTime<FixedOffset>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match date {
+        Some(date) => serializer.serialize_some(&date.to_rfc3339()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Represents a message of a thread.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct ThreadMsg {
+    /// The mailbox the message belongs to.
+    pub mbox: String,
+    /// The sequence number of the message in its mailbox.
+    pub seq: u32,
+    pub message_id: String,
+    /// The ids linking the message to its thread, its own id included.
+    #[serde(skip)]
+    pub thread_ids: Vec<String>,
+    /// The `Date` header of the message.
+    #[serde(serialize_with = "serialize_date")]
+    pub date: Option<DateTime<FixedOffset>>,
+    pub from: String,
+    pub subject: String,
+    /// The text bodies of the message.
+    pub text: String,
+}
+
+impl ThreadMsg {
+    /// Builds the thread message from the given raw message.
+    pub fn from_raw(mbox: &str, seq: u32, raw: &[u8]) -> Result<Self> {
+        let parsed = mailparse::parse_mail(raw).context(format!("cannot parse message {}", seq))?;
+        let headers = &parsed.headers;
+        let thread_ids = thread_ids(headers);
+        let msg = Msg {
+            body_parts: Parts::best_of(&parsed),
+            ..Msg::default()
+        };
+        Ok(Self {
+            mbox: mbox.to_owned(),
+            seq,
+            message_id: parse_msg_ids(&headers.get_first_value("Message-ID").unwrap_or_default())
+                .into_iter()
+                .next()
+                .unwrap_or_default(),
+            thread_ids,
+            date: headers
+                .get_first_header("Date")
+                .and_then(|date| parse_date_header(date.get_value_raw())),
+            from: headers.get_first_value("From").unwrap_or_default(),
+            subject: headers.get_first_value("Subject").unwrap_or_default(),
+            text: msg.fold_text_parts("auto"),
+        })
+    }
+}
+
+/// Represents a thread, its messages being sorted chronologically.
+#[derive(Debug, Default, Serialize)]
+pub struct Thread(pub Vec<ThreadMsg>);
+
+/// Makes the thread printable, as a conversation.
+impl Print for Thread {
+    fn print(&self, writter: &mut dyn WriteColor) -> Result<()> {
+        for (i, msg) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(writter).context("cannot print thread")?;
+            }
+            let date = msg
+                .date
+                .map(|date| date.naive_local().to_string())
+                .unwrap_or_default();
+            let title = format!(" {} ({}) ", msg.from, date);
+            writeln!(writter, "{:=^1$}", title, title.len() + 40).context("cannot print thread")?;
+            writeln!(writter, "Subject: {}", msg.subject).context("cannot print thread")?;
+            writeln!(writter, "Message: {} in {}", msg.seq, msg.mbox)
+                .context("cannot print thread")?;
+            writeln!(writter, "\n{}", msg.text.trim_end()).context("cannot print thread")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_collect_thread_ids() {
+        let (headers, _) = mailparse::parse_headers(
+            b"Message-ID: <c@host>\r\nReferences: <a@host>\r\n <b@host>\r\nIn-Reply-To: <b@host>\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            vec!["<c@host>", "<a@host>", "<b@host>"],
+            thread_ids(&headers)
+        );
+    }
+
+    #[test]
+    fn it_should_build_thread_query() {
+        assert_eq!("", thread_query(&[]));
+        assert_eq!(
+            r#"OR HEADER Message-ID "<a@host>" HEADER References "<a@host>""#,
+            thread_query(&["<a@host>".into()])
+        );
+        assert_eq!(
+            r#"OR HEADER Message-ID "<a@host>" OR HEADER References "<a@host>" OR HEADER Message-ID "<b@host>" HEADER References "<b@host>""#,
+            thread_query(&["<a@host>".into(), "<b@host>".into()])
+        );
+    }
+
+    #[test]
+    fn it_should_collapse_quotes() {
+        assert_eq!(
+            "Sounds good.\n\nOn Monday, Alice wrote:\n[2 quoted line(s)]\nBye",
+            collapse_quotes("Sounds good.\n\nOn Monday, Alice wrote:\n> Shall we?\n> A.\nBye")
+        );
+        assert_eq!("Hi\n[1 quoted line(s)]", collapse_quotes("Hi\n> quoted"));
+    }
+}
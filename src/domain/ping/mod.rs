@@ -0,0 +1,10 @@
+//! Module related to health checks.
+//!
+//! This module contains everything related to the checking of the services configured for the
+//! accounts.
+
+pub mod ping_arg;
+pub mod ping_handler;
+
+pub mod ping_entity;
+pub use ping_entity::*;
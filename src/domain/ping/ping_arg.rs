@@ -0,0 +1,44 @@
+//! Module related to health check CLI.
+//!
+//! This module provides subcommands and a command matcher related to health checks.
+
+use anyhow::Result;
+use clap::{self, App, ArgMatches, SubCommand};
+use log::debug;
+
+/// Health check commands.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Cmd {
+    /// Check the services of all the configured accounts.
+    Ping,
+}
+
+/// Health check command matcher.
+pub fn matches(m: &ArgMatches) -> Result<Option<Cmd>> {
+    if m.subcommand_matches("ping").is_some() {
+        debug!("ping command matched");
+        return Ok(Some(Cmd::Ping));
+    }
+
+    Ok(None)
+}
+
+/// Health check subcommands.
+pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
+    vec![SubCommand::with_name("ping")
+        .about("Checks the services of all the accounts")
+        .long_about("Connects and authenticates to the IMAP and SMTP servers of all the configured accounts, and prints the latency of each service. Useful after changing passwords or certificates.")]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_match_cmds() {
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .get_matches_from(&["himalaya", "ping"]);
+        assert_eq!(Some(Cmd::Ping), matches(&arg).unwrap());
+    }
+}
@@ -0,0 +1,144 @@
+//! Health check entity module.
+//!
+//! This module contains the definition of the health checks and their traits implementations.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::{ops::Deref, time::Duration};
+
+use crate::{
+    output::{PrintTable, PrintTableOpts, WriteColor},
+    ui::{Cell, Row, Table},
+};
+
+/// Represents the health check of a service of an account.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Ping {
+    /// The name of the account.
+    pub account: String,
+    /// The name of the service, like IMAP or SMTP.
+    pub service: String,
+    /// The server of the service, as `host:port`.
+    pub server: String,
+    /// The time spent connecting and authenticating, in milliseconds.
+    pub latency: u128,
+    /// The error raised by the check, if any.
+    pub error: Option<String>,
+}
+
+impl Ping {
+    /// Builds the health check from the result of the check and the time it took.
+    pub fn new(
+        account: &str,
+        service: &str,
+        server: String,
+        res: Result<()>,
+        latency: Duration,
+    ) -> Self {
+        Self {
+            account: account.to_owned(),
+            service: service.to_owned(),
+            server,
+            latency: latency.as_millis(),
+            // The whole chain is joined on a single line, to fit in a table cell.
+            error: res.err().map(|err| {
+                err.chain()
+                    .map(|err| err.to_string())
+                    .collect::<Vec<_>>()
+                    .join(": ")
+            }),
+        }
+    }
+
+    /// Checks if the service is reachable.
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Makes the health check printable in a table.
+impl Table for Ping {
+    fn head() -> Row {
+        Row::new()
+            .cell(Cell::new("ACCOUNT").bold().underline().white())
+            .cell(Cell::new("SERVICE").bold().underline().white())
+            .cell(Cell::new("SERVER").bold().underline().white())
+            .cell(Cell::new("STATUS").bold().underline().white())
+            .cell(Cell::new("LATENCY").bold().underline().white())
+            .cell(Cell::new("ERROR").shrinkable().bold().underline().white())
+    }
+
+    fn row(&self) -> Row {
+        let status = if self.is_ok() {
+            Cell::new("ok").bold().green()
+        } else {
+            Cell::new("failed").bold().red()
+        };
+        Row::new()
+            .cell(Cell::new(&self.account).white())
+            .cell(Cell::new(&self.service).blue())
+            .cell(Cell::new(&self.server).white())
+            .cell(status)
+            .cell(Cell::new(format!("{} ms", self.latency)).yellow())
+            .cell(
+                Cell::new(self.error.as_deref().unwrap_or_default())
+                    .shrinkable()
+                    .red(),
+            )
+    }
+}
+
+/// Represents the health checks of several services.
+#[derive(Debug, Default, Serialize)]
+pub struct Pings(pub Vec<Ping>);
+
+impl Deref for Pings {
+    type Target = Vec<Ping>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Makes the health checks printable.
+impl PrintTable for Pings {
+    fn print_table(&self, writter: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        writeln!(writter)?;
+        Table::print(writter, &self, opts)?;
+        writeln!(writter)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Context};
+
+    use super::*;
+
+    #[test]
+    fn it_should_build_ping() {
+        let ping = Ping::new(
+            "work",
+            "IMAP",
+            "imap.example.com:993".into(),
+            Ok(()),
+            Duration::from_millis(120),
+        );
+        assert!(ping.is_ok());
+        assert_eq!(120, ping.latency);
+
+        let ping = Ping::new(
+            "work",
+            "SMTP",
+            "smtp.example.com:465".into(),
+            Err(anyhow!("authentication failed")).context("cannot connect to SMTP server"),
+            Duration::from_millis(80),
+        );
+        assert!(!ping.is_ok());
+        assert_eq!(
+            Some("cannot connect to SMTP server: authentication failed"),
+            ping.error.as_deref()
+        );
+    }
+}
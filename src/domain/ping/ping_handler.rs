@@ -0,0 +1,83 @@
+//! Health check handling module.
+//!
+//! This module gathers all health check actions triggered by the CLI.
+
+use anyhow::{anyhow, Result};
+use log::{debug, trace};
+use std::{
+    convert::TryFrom,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::{Account, Config},
+    domain::{
+        ImapService, ImapServiceInterface, Mbox, Ping, Pings, SmtpService, SmtpServiceInterface,
+    },
+    output::{PrintTableOpts, PrinterService},
+};
+
+/// Checks the IMAP and SMTP services of all the configured accounts: each service is connected
+/// to and authenticated against, and the time it takes is measured. An account that cannot be
+/// built from the configuration is reported as failed. Fails if any service is unreachable, once
+/// all of them have been checked.
+pub fn ping<Printer: PrinterService>(config: &Config, printer: &mut Printer) -> Result<()> {
+    let mut names: Vec<&String> = config.accounts.keys().collect();
+    names.sort();
+
+    let mut pings = vec![];
+    for name in names {
+        let account = match Account::try_from((config, Some(name.as_str()))) {
+            Ok(account) => account,
+            Err(err) => {
+                // None of the services can be checked, the error is reported as the result of
+                // the account configuration instead.
+                pings.push(Ping::new(
+                    name,
+                    "CONFIG",
+                    String::new(),
+                    Err(err),
+                    Duration::default(),
+                ));
+                continue;
+            }
+        };
+
+        debug!(r#"ping IMAP server of account "{}""#, account.name);
+        let mbox = Mbox::new("INBOX");
+        let mut imap = ImapService::from((&account, &mbox));
+        let now = Instant::now();
+        let res = imap.noop();
+        let latency = now.elapsed();
+        let res = res.and_then(|()| imap.logout());
+        pings.push(Ping::new(
+            &account.name,
+            "IMAP",
+            format!("{}:{}", account.imap_host, account.imap_port),
+            res,
+            latency,
+        ));
+
+        debug!(r#"ping SMTP server of account "{}""#, account.name);
+        let mut smtp = SmtpService::from(&account);
+        let now = Instant::now();
+        let res = smtp.check();
+        let latency = now.elapsed();
+        pings.push(Ping::new(
+            &account.name,
+            "SMTP",
+            format!("{}:{}", account.smtp_host, account.smtp_port),
+            res,
+            latency,
+        ));
+    }
+    trace!("pings: {:#?}", pings);
+
+    let failures = pings.iter().filter(|ping| !ping.is_ok()).count();
+    let total = pings.len();
+    printer.print_table(Pings(pings), PrintTableOpts { max_width: None })?;
+    if failures > 0 {
+        return Err(anyhow!("{} of {} services failed", failures, total));
+    }
+    Ok(())
+}
@@ -0,0 +1,15 @@
+//! Module related to ManageSieve.
+//!
+//! This module contains everything related to server-side filters managed with the ManageSieve
+//! protocol ([RFC5804]).
+//!
+//! [RFC5804]: https://datatracker.ietf.org/doc/html/rfc5804
+
+pub mod sieve_arg;
+pub mod sieve_handler;
+
+pub mod sieve_entity;
+pub use sieve_entity::*;
+
+pub mod sieve_service;
+pub use sieve_service::*;
@@ -0,0 +1,129 @@
+//! Module related to ManageSieve CLI.
+//!
+//! This module provides subcommands and a command matcher related to ManageSieve.
+
+use anyhow::Result;
+use clap::{self, App, AppSettings, Arg, ArgMatches, SubCommand};
+use log::{debug, trace};
+
+type Name<'a> = &'a str;
+type Path<'a> = Option<&'a str>;
+
+/// ManageSieve commands.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Cmd<'a> {
+    /// List the scripts stored on the server.
+    List,
+    /// Print the content of the given script.
+    Get(Name<'a>),
+    /// Upload the given script, read from the given path or from stdin.
+    Put(Name<'a>, Path<'a>),
+    /// Mark the given script as the active one.
+    Activate(Name<'a>),
+}
+
+/// ManageSieve command matcher.
+pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Cmd<'a>>> {
+    let m = match m.subcommand_matches("sieve") {
+        Some(m) => m,
+        None => return Ok(None),
+    };
+
+    if m.subcommand_matches("list").is_some() {
+        debug!("sieve list command matched");
+        return Ok(Some(Cmd::List));
+    }
+
+    if let Some(m) = m.subcommand_matches("get") {
+        debug!("sieve get command matched");
+        let name = m.value_of("name").unwrap();
+        trace!(r#"name: "{}""#, name);
+        return Ok(Some(Cmd::Get(name)));
+    }
+
+    if let Some(m) = m.subcommand_matches("put") {
+        debug!("sieve put command matched");
+        let name = m.value_of("name").unwrap();
+        trace!(r#"name: "{}""#, name);
+        let path = m.value_of("path");
+        trace!(r#"path: "{:?}""#, path);
+        return Ok(Some(Cmd::Put(name, path)));
+    }
+
+    if let Some(m) = m.subcommand_matches("activate") {
+        debug!("sieve activate command matched");
+        let name = m.value_of("name").unwrap();
+        trace!(r#"name: "{}""#, name);
+        return Ok(Some(Cmd::Activate(name)));
+    }
+
+    Ok(None)
+}
+
+/// Sieve script name argument.
+fn name_arg<'a>() -> Arg<'a, 'a> {
+    Arg::with_name("name")
+        .help("Specifies the script name")
+        .value_name("NAME")
+        .required(true)
+}
+
+/// ManageSieve subcommands.
+pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
+    vec![SubCommand::with_name("sieve")
+        .about("Manages server-side Sieve filters")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("list")
+                .aliases(&["lst", "l"])
+                .about("Lists Sieve scripts"),
+        )
+        .subcommand(
+            SubCommand::with_name("get")
+                .about("Prints a Sieve script")
+                .arg(name_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("put")
+                .about("Uploads a Sieve script")
+                .arg(name_arg())
+                .arg(
+                    Arg::with_name("path")
+                        .help("Reads the script from the given file instead of stdin")
+                        .value_name("PATH"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("activate")
+                .about("Activates a Sieve script")
+                .arg(name_arg()),
+        )]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_match_cmds() {
+        let app = || clap::App::new("himalaya").subcommands(subcmds());
+
+        let arg = app().get_matches_from(&["himalaya", "sieve", "list"]);
+        assert_eq!(Some(Cmd::List), matches(&arg).unwrap());
+
+        let arg = app().get_matches_from(&["himalaya", "sieve", "get", "main"]);
+        assert_eq!(Some(Cmd::Get("main")), matches(&arg).unwrap());
+
+        let arg = app().get_matches_from(&["himalaya", "sieve", "put", "main"]);
+        assert_eq!(Some(Cmd::Put("main", None)), matches(&arg).unwrap());
+
+        let arg = app().get_matches_from(&["himalaya", "sieve", "put", "main", "main.sieve"]);
+        assert_eq!(
+            Some(Cmd::Put("main", Some("main.sieve"))),
+            matches(&arg).unwrap()
+        );
+
+        let arg = app().get_matches_from(&["himalaya", "sieve", "activate", "main"]);
+        assert_eq!(Some(Cmd::Activate("main")), matches(&arg).unwrap());
+    }
+}
@@ -0,0 +1,107 @@
+//! Sieve entity module.
+//!
+//! This module contains the definition of the Sieve scripts and their traits implementations.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::ops::Deref;
+
+use crate::{
+    output::{PrintTable, PrintTableOpts, WriteColor},
+    ui::{Cell, Row, Table},
+};
+
+/// Represents a Sieve script stored on the server.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SieveScript {
+    /// The name of the script.
+    pub name: String,
+    /// Whether the script is the active one. Only one script can be active at a time.
+    pub active: bool,
+}
+
+/// Makes the script printable in a table.
+impl Table for SieveScript {
+    fn head() -> Row {
+        Row::new()
+            .cell(Cell::new("NAME").bold().underline().white())
+            .cell(Cell::new("ACTIVE").bold().underline().white())
+    }
+
+    fn row(&self) -> Row {
+        Row::new()
+            .cell(Cell::new(&self.name).bold_if(self.active).blue())
+            .cell(Cell::new(if self.active { "yes" } else { "" }).green())
+    }
+}
+
+/// Represents a list of Sieve scripts.
+#[derive(Debug, Default, Serialize)]
+pub struct SieveScripts(pub Vec<SieveScript>);
+
+impl Deref for SieveScripts {
+    type Target = Vec<SieveScript>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Makes the scripts printable.
+impl PrintTable for SieveScripts {
+    fn print_table(&self, writter: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()> {
+        writeln!(writter)?;
+        Table::print(writter, &self, opts)?;
+        writeln!(writter)?;
+        Ok(())
+    }
+}
+
+/// Parses a line of the `LISTSCRIPTS` response, for example `"vacation" ACTIVE`.
+impl From<&str> for SieveScript {
+    fn from(line: &str) -> Self {
+        let line = line.trim();
+        let (name, rest) = match line.strip_prefix('"') {
+            Some(line) => {
+                let end = line.find('"').unwrap_or(line.len());
+                (&line[..end], line[end..].trim_start_matches('"'))
+            }
+            None => line.split_at(line.find(' ').unwrap_or(line.len())),
+        };
+
+        Self {
+            name: name.to_owned(),
+            active: rest.trim().eq_ignore_ascii_case("ACTIVE"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_parse_listscripts_lines() {
+        assert_eq!(
+            SieveScript {
+                name: "vacation".into(),
+                active: true,
+            },
+            SieveScript::from(r#""vacation" ACTIVE"#)
+        );
+        assert_eq!(
+            SieveScript {
+                name: "lists and spam".into(),
+                active: false,
+            },
+            SieveScript::from(r#""lists and spam""#)
+        );
+        assert_eq!(
+            SieveScript {
+                name: "main".into(),
+                active: true,
+            },
+            SieveScript::from("main active")
+        );
+    }
+}
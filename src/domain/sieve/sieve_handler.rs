@@ -0,0 +1,70 @@
+//! Module related to ManageSieve handling.
+//!
+//! This module gathers all ManageSieve commands.
+
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    io::{self, Read},
+};
+
+use crate::{
+    domain::sieve::SieveServiceInterface,
+    output::{PrintTableOpts, PrinterService},
+};
+
+/// List the Sieve scripts stored on the server.
+pub fn list<Printer: PrinterService, SieveService: SieveServiceInterface>(
+    printer: &mut Printer,
+    sieve: &mut SieveService,
+) -> Result<()> {
+    let scripts = sieve.list_scripts()?;
+    sieve.logout()?;
+    printer.print_table(scripts, PrintTableOpts { max_width: None })
+}
+
+/// Print the content of the given Sieve script.
+pub fn get<Printer: PrinterService, SieveService: SieveServiceInterface>(
+    name: &str,
+    printer: &mut Printer,
+    sieve: &mut SieveService,
+) -> Result<()> {
+    let script = sieve.get_script(name)?;
+    sieve.logout()?;
+    printer.print(script)
+}
+
+/// Upload the given Sieve script, read from the given path or from stdin.
+pub fn put<Printer: PrinterService, SieveService: SieveServiceInterface>(
+    name: &str,
+    path: Option<&str>,
+    printer: &mut Printer,
+    sieve: &mut SieveService,
+) -> Result<()> {
+    let script = match path {
+        Some(path) => {
+            fs::read_to_string(path).context(format!("cannot read Sieve script at {:?}", path))?
+        }
+        None => {
+            let mut script = String::new();
+            io::stdin()
+                .read_to_string(&mut script)
+                .context("cannot read Sieve script from stdin")?;
+            script
+        }
+    };
+    sieve.put_script(name, &script)?;
+    sieve.logout()?;
+    printer.print(format!(r#"Sieve script "{}" successfully uploaded"#, name))
+}
+
+/// Mark the given Sieve script as the active one.
+pub fn activate<Printer: PrinterService, SieveService: SieveServiceInterface>(
+    name: &str,
+    printer: &mut Printer,
+    sieve: &mut SieveService,
+) -> Result<()> {
+    sieve.activate_script(name)?;
+    sieve.logout()?;
+    printer.print(format!(r#"Sieve script "{}" successfully activated"#, name))
+}
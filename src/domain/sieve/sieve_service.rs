@@ -0,0 +1,325 @@
+//! Module related to ManageSieve servicing.
+//!
+//! This module exposes a service that can interact with ManageSieve servers. The connection is
+//! upgraded with `STARTTLS` (unless TLS is negociated right away) before authenticating with
+//! SASL `PLAIN`, using the IMAP credentials.
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, trace};
+#[cfg(all(feature = "rustls-tls", not(feature = "default-tls")))]
+use std::sync::Arc;
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+};
+
+use crate::{
+    config::Account,
+    domain::{imap::ImapTlsStream, SieveScript, SieveScripts},
+};
+
+pub trait SieveServiceInterface {
+    fn list_scripts(&mut self) -> Result<SieveScripts>;
+    fn get_script(&mut self, name: &str) -> Result<String>;
+    fn put_script(&mut self, name: &str, script: &str) -> Result<()>;
+    fn activate_script(&mut self, name: &str) -> Result<()>;
+    fn logout(&mut self) -> Result<()>;
+}
+
+pub struct SieveService<'a> {
+    account: &'a Account,
+    stream: Option<BufReader<ImapTlsStream>>,
+}
+
+impl<'a> SieveService<'a> {
+    fn stream(&mut self) -> Result<&mut BufReader<ImapTlsStream>> {
+        if self.stream.is_none() {
+            debug!("create ManageSieve connection");
+            debug!("host: {}", self.account.sieve_host);
+            debug!("port: {}", self.account.sieve_port);
+            let tcp =
+                TcpStream::connect((self.account.sieve_host.as_str(), self.account.sieve_port))
+                    .context("cannot connect to ManageSieve server")?;
+
+            debug!("starttls: {}", self.account.sieve_starttls);
+            let tcp = if self.account.sieve_starttls {
+                // Capabilities are sent by the server as soon as the connection is open.
+                let mut plain = BufReader::new(tcp);
+                let caps =
+                    read_response(&mut plain).context("cannot read ManageSieve capabilities")?;
+                if capability(&caps, "STARTTLS").is_none() {
+                    return Err(anyhow!("ManageSieve server does not support STARTTLS"));
+                }
+                write!(plain.get_mut(), "STARTTLS\r\n").context("cannot start TLS")?;
+                read_response(&mut plain).context("cannot start TLS")?;
+                plain.into_inner()
+            } else {
+                tcp
+            };
+
+            debug!("negociate TLS");
+            let mut stream = BufReader::new(tls_handshake(self.account, tcp)?);
+            // Capabilities are sent once the TLS negociation is done.
+            let caps =
+                read_response(&mut stream).context("cannot read ManageSieve capabilities")?;
+            let mechanisms = capability(&caps, "SASL").unwrap_or_default();
+            trace!("SASL mechanisms: {}", mechanisms);
+            if !mechanisms
+                .split_whitespace()
+                .any(|mechanism| mechanism.eq_ignore_ascii_case("PLAIN"))
+            {
+                return Err(anyhow!(
+                    r#"ManageSieve server does not support SASL mechanism "PLAIN" (supported: "{}")"#,
+                    mechanisms
+                ));
+            }
+
+            debug!("authenticate");
+            debug!("login: {}", self.account.imap_login);
+            let creds = format!(
+                "\0{}\0{}",
+                self.account.imap_login,
+                self.account.imap_passwd()?
+            );
+            write!(
+                stream.get_mut(),
+                "AUTHENTICATE \"PLAIN\" \"{}\"\r\n",
+                base64::encode(creds)
+            )
+            .context("cannot authenticate to ManageSieve server")?;
+            read_response(&mut stream).context("cannot authenticate to ManageSieve server")?;
+
+            self.stream = Some(stream);
+        }
+
+        Ok(self.stream.as_mut().unwrap())
+    }
+
+    fn send(&mut self, cmd: &str) -> Result<Vec<String>> {
+        trace!("ManageSieve command: {}", cmd);
+        let stream = self.stream()?;
+        write!(stream.get_mut(), "{}\r\n", cmd)?;
+        stream.get_mut().flush()?;
+        read_response(stream)
+    }
+}
+
+impl<'a> SieveServiceInterface for SieveService<'a> {
+    fn list_scripts(&mut self) -> Result<SieveScripts> {
+        let lines = self
+            .send("LISTSCRIPTS")
+            .context("cannot list Sieve scripts")?;
+        Ok(SieveScripts(
+            lines
+                .iter()
+                .map(|line| SieveScript::from(line.as_str()))
+                .collect(),
+        ))
+    }
+
+    fn get_script(&mut self, name: &str) -> Result<String> {
+        let lines = self
+            .send(&format!("GETSCRIPT {}", quote(name)))
+            .context(format!(r#"cannot get Sieve script "{}""#, name))?;
+        Ok(lines.concat())
+    }
+
+    fn put_script(&mut self, name: &str, script: &str) -> Result<()> {
+        let cmd = format!(
+            "PUTSCRIPT {} {{{}+}}\r\n{}",
+            quote(name),
+            script.len(),
+            script
+        );
+        self.send(&cmd)
+            .context(format!(r#"cannot put Sieve script "{}""#, name))?;
+        Ok(())
+    }
+
+    fn activate_script(&mut self, name: &str) -> Result<()> {
+        self.send(&format!("SETACTIVE {}", quote(name)))
+            .context(format!(r#"cannot activate Sieve script "{}""#, name))?;
+        Ok(())
+    }
+
+    fn logout(&mut self) -> Result<()> {
+        if self.stream.is_some() {
+            debug!("logout from ManageSieve server");
+            self.send("LOGOUT")
+                .context("cannot logout from ManageSieve server")?;
+            self.stream = None;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> From<&'a Account> for SieveService<'a> {
+    fn from(account: &'a Account) -> Self {
+        debug!("init ManageSieve service");
+        Self {
+            account,
+            stream: None,
+        }
+    }
+}
+
+/// Quotes the given string as a ManageSieve quoted string.
+fn quote(s: &str) -> String {
+    format!(r#""{}""#, s.replace('\\', r"\\").replace('"', r#"\""#))
+}
+
+/// Finds the value of the given capability among the capability lines sent by the server, like
+/// `"SASL" "PLAIN LOGIN"`. Capabilities without value have an empty one.
+fn capability(lines: &[String], name: &str) -> Option<String> {
+    lines.iter().find_map(|line| {
+        let mut strings = unquote_all(line).into_iter();
+        match strings.next() {
+            Some(cap) if cap.eq_ignore_ascii_case(name) => Some(strings.next().unwrap_or_default()),
+            _ => None,
+        }
+    })
+}
+
+/// Parses the ManageSieve quoted strings of the given line, ignoring anything outside of them.
+fn unquote_all(line: &str) -> Vec<String> {
+    let mut strings = vec![];
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '"' {
+            continue;
+        }
+        let mut s = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => s.extend(chars.next()),
+                '"' => break,
+                c => s.push(c),
+            }
+        }
+        strings.push(s);
+    }
+    strings
+}
+
+/// Reads the data lines of a response until its status line. Literals (`{n}` followed by `n`
+/// bytes) are read as a whole data line. Returns an error if the status is not `OK`.
+fn read_response<R: Read>(reader: &mut BufReader<R>) -> Result<Vec<String>> {
+    let mut lines = vec![];
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(anyhow!("connection closed by the ManageSieve server"));
+        }
+        let line = line.trim_end_matches(|c| c == '\r' || c == '\n');
+        trace!("ManageSieve response line: {}", line);
+
+        let literal_len = line
+            .strip_prefix('{')
+            .and_then(|line| line.strip_suffix('}'))
+            .and_then(|len| len.trim_end_matches('+').parse::<usize>().ok());
+        if let Some(len) = literal_len {
+            let mut literal = vec![0; len];
+            reader.read_exact(&mut literal)?;
+            lines.push(String::from_utf8_lossy(&literal).into_owned());
+            continue;
+        }
+
+        let status = line.split(' ').next().unwrap_or_default();
+        match status.to_ascii_uppercase().as_str() {
+            "OK" => return Ok(lines),
+            "NO" | "BYE" => {
+                return Err(anyhow!(
+                    "ManageSieve server replied: {}",
+                    line[status.len()..].trim()
+                ))
+            }
+            _ if !line.is_empty() => lines.push(line.to_owned()),
+            _ => (),
+        }
+    }
+}
+
+/// Negociates TLS over the given stream using native-tls.
+#[cfg(feature = "default-tls")]
+fn tls_handshake(account: &Account, tcp: TcpStream) -> Result<ImapTlsStream> {
+    let tls = native_tls::TlsConnector::builder()
+        .danger_accept_invalid_certs(account.imap_insecure)
+        .danger_accept_invalid_hostnames(account.imap_insecure)
+        .build()
+        .context("cannot create TLS connector")?;
+    tls.connect(&account.sieve_host, tcp)
+        .map_err(|err| anyhow!(err.to_string()))
+        .context("cannot negociate TLS with ManageSieve server")
+}
+
+/// Negociates TLS over the given stream using rustls.
+#[cfg(all(feature = "rustls-tls", not(feature = "default-tls")))]
+fn tls_handshake(account: &Account, tcp: TcpStream) -> Result<ImapTlsStream> {
+    let mut tls = rustls::ClientConfig::new();
+    tls.root_store
+        .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+    if account.imap_insecure {
+        tls.dangerous()
+            .set_certificate_verifier(Arc::new(crate::domain::imap::InsecureCertVerifier));
+    }
+    let dns_name = webpki::DNSNameRef::try_from_ascii_str(&account.sieve_host).map_err(|_| {
+        anyhow!(
+            r#"cannot use ManageSieve host "{}" as DNS name"#,
+            account.sieve_host
+        )
+    })?;
+    let sess = rustls::ClientSession::new(&Arc::new(tls), dns_name);
+    Ok(rustls::StreamOwned::new(sess, tcp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_read_response() {
+        let mut reader = BufReader::new(
+            concat![
+                "\"IMPLEMENTATION\" \"Dovecot Pigeonhole\"\r\n",
+                "\"SASL\" \"PLAIN LOGIN\"\r\n",
+                "\"STARTTLS\"\r\n",
+                "OK \"Ready.\"\r\n",
+            ]
+            .as_bytes(),
+        );
+        let caps = read_response(&mut reader).unwrap();
+        assert_eq!(3, caps.len());
+        assert_eq!(Some("PLAIN LOGIN".into()), capability(&caps, "sasl"));
+        assert_eq!(Some("".into()), capability(&caps, "STARTTLS"));
+        assert_eq!(None, capability(&caps, "NOTIFY"));
+
+        // Literals may span several lines, and may contain what looks like a status line.
+        let mut reader = BufReader::new(
+            "{18}\r\nkeep;\r\nOK \"fake\"\r\n\r\nOK \"Getscript completed.\"\r\n".as_bytes(),
+        );
+        assert_eq!(
+            vec!["keep;\r\nOK \"fake\"\r\n"],
+            read_response(&mut reader).unwrap()
+        );
+
+        let mut reader = BufReader::new("NO \"Script does not exist.\"\r\n".as_bytes());
+        let err = read_response(&mut reader).unwrap_err();
+        assert_eq!(
+            r#"ManageSieve server replied: "Script does not exist.""#,
+            err.to_string()
+        );
+
+        let mut reader = BufReader::new("\"script\"\r\n".as_bytes());
+        assert!(read_response(&mut reader).is_err());
+    }
+
+    #[test]
+    fn it_should_unquote_strings() {
+        assert_eq!(
+            vec!["a \"b\"", "c\\d"],
+            unquote_all(r#""a \"b\"" "c\\d" ACTIVE"#)
+        );
+        assert_eq!(r#""a \"b\"""#, quote(r#"a "b""#));
+    }
+}
@@ -2,3 +2,9 @@
 
 pub mod smtp_service;
 pub use smtp_service::*;
+
+pub mod dkim_entity;
+pub use dkim_entity::*;
+
+pub mod mx_utils;
+pub use mx_utils::*;
@@ -0,0 +1,198 @@
+//! Module related to MX records.
+//!
+//! This module exposes the helpers of the direct MX delivery mode (`smtp-direct-mx`), where
+//! messages are delivered to the mail exchangers of the recipient domains instead of a relay.
+
+use anyhow::{anyhow, Context, Error, Result};
+use lettre::Address;
+use log::debug;
+use std::{collections::BTreeMap, fmt};
+use trust_dns_resolver::{
+    error::{ResolveError, ResolveErrorKind},
+    proto::op::ResponseCode,
+    Resolver,
+};
+
+use crate::output::ExitCode;
+
+/// Resolves the mail exchangers of the given domain, by order of preference. Domains without MX
+/// records are their own mail exchanger ([RFC5321]), while domains publishing a null MX record do
+/// not accept mail ([RFC7505]). Other lookup failures are errors, so a temporary DNS failure does
+/// not send the message to the wrong host.
+///
+/// [RFC5321]: https://datatracker.ietf.org/doc/html/rfc5321#section-5.1
+/// [RFC7505]: https://datatracker.ietf.org/doc/html/rfc7505
+pub fn lookup_mx(domain: &str) -> Result<Vec<String>> {
+    let resolver = Resolver::from_system_conf().context("cannot create DNS resolver")?;
+    let records: Vec<(u16, String)> = match resolver.mx_lookup(domain) {
+        Ok(mx) => mx
+            .iter()
+            .map(|mx| {
+                let host = mx.exchange().to_utf8();
+                (mx.preference(), host.trim_end_matches('.').to_owned())
+            })
+            .collect(),
+        Err(err) if is_no_records(&err) => {
+            debug!("cannot find MX records of {}: {}", domain, err);
+            vec![]
+        }
+        Err(err) => {
+            return Err(Error::new(err)
+                .context(ExitCode::Network)
+                .context(format!("cannot find mail exchangers of {}", domain)))
+        }
+    };
+    mx_hosts(domain, records)
+}
+
+/// Returns the mail exchangers of the given domain from its MX records (preference and host),
+/// by order of preference.
+fn mx_hosts(domain: &str, mut records: Vec<(u16, String)>) -> Result<Vec<String>> {
+    if records.iter().any(|(_, host)| host.is_empty()) {
+        return Err(anyhow!(
+            "domain {} does not accept mail (null MX record)",
+            domain
+        ));
+    }
+    records.sort();
+    if records.is_empty() {
+        return Ok(vec![domain.to_owned()]);
+    }
+    Ok(records.into_iter().map(|(_, host)| host).collect())
+}
+
+/// Returns true if the given lookup error means that the domain has no such records (including
+/// when the domain does not exist), as opposed to a failed lookup.
+fn is_no_records(err: &ResolveError) -> bool {
+    matches!(err.kind(), ResolveErrorKind::NoRecordsFound { .. })
+}
+
+/// Returns true if the given lookup error means that the domain does not exist (NXDOMAIN).
+fn is_nx_domain(err: &ResolveError) -> bool {
+    match err.kind() {
+        ResolveErrorKind::NoRecordsFound { response_code, .. } => {
+            *response_code == ResponseCode::NXDomain
+        }
+        _ => false,
+    }
+}
+
+/// Represents a message delivered to the mail exchangers of some recipient domains only. Retrying
+/// the whole delivery would send duplicates to the domains which already got the message.
+#[derive(Debug)]
+pub struct PartialDelivery {
+    /// The domains the message was delivered to.
+    pub delivered: Vec<String>,
+    /// The domains the message could not be delivered to, with the reason.
+    pub failed: Vec<(String, String)>,
+}
+
+impl fmt::Display for PartialDelivery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "message delivered to {} but not to ",
+            self.delivered.join(", ")
+        )?;
+        for (i, (domain, err)) in self.failed.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} ({})", domain, err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for PartialDelivery {}
+
+/// Returns true if the given domain accepts mail: it has MX records, or no MX record but an
+/// address, being then its own mail exchanger ([RFC5321]). Domains which do not exist or publish a
+/// null MX record ([RFC7505]) do not accept mail, while failed lookups are errors.
+///
+/// [RFC5321]: https://datatracker.ietf.org/doc/html/rfc5321#section-5.1
+/// [RFC7505]: https://datatracker.ietf.org/doc/html/rfc7505
+pub fn has_mx(domain: &str) -> Result<bool> {
+    let resolver = Resolver::from_system_conf().context("cannot create DNS resolver")?;
+    match resolver.mx_lookup(domain) {
+        Ok(mx) => {
+            return Ok(mx
+                .iter()
+                .any(|mx| !mx.exchange().to_utf8().trim_end_matches('.').is_empty()))
+        }
+        Err(err) if is_nx_domain(&err) => return Ok(false),
+        Err(err) if is_no_records(&err) => debug!("cannot find MX records of {}: {}", domain, err),
+        Err(err) => {
+            return Err(Error::new(err).context(format!("cannot find MX records of {}", domain)))
+        }
+    }
+    match resolver.lookup_ip(domain) {
+        Ok(ips) => Ok(ips.iter().next().is_some()),
+        Err(err) if is_no_records(&err) => Ok(false),
+        Err(err) => Err(Error::new(err).context(format!("cannot find address of {}", domain))),
+    }
+}
+
+/// Groups the given recipients by domain, domains being compared case-insensitively.
+pub fn group_by_domain(addrs: &[Address]) -> BTreeMap<String, Vec<Address>> {
+    let mut groups: BTreeMap<String, Vec<Address>> = BTreeMap::new();
+    for addr in addrs {
+        groups
+            .entry(addr.domain().to_lowercase())
+            .or_default()
+            .push(addr.to_owned());
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_sort_mx_hosts() {
+        let records = vec![
+            (20, String::from("mx2.example.com")),
+            (10, String::from("mx1.example.com")),
+        ];
+        assert_eq!(
+            vec!["mx1.example.com", "mx2.example.com"],
+            mx_hosts("example.com", records).unwrap()
+        );
+        assert_eq!(
+            vec!["example.com"],
+            mx_hosts("example.com", vec![]).unwrap()
+        );
+        assert!(mx_hosts("example.com", vec![(0, String::new())]).is_err());
+    }
+
+    #[test]
+    fn it_should_report_partial_deliveries() {
+        let delivery = PartialDelivery {
+            delivered: vec![String::from("example.com")],
+            failed: vec![(
+                String::from("example.org"),
+                String::from("connection refused"),
+            )],
+        };
+        assert_eq!(
+            "message delivered to example.com but not to example.org (connection refused)",
+            delivery.to_string()
+        );
+    }
+
+    #[test]
+    fn it_should_group_recipients_by_domain() {
+        let addrs: Vec<Address> = vec![
+            "alice@example.com".parse().unwrap(),
+            "bob@example.org".parse().unwrap(),
+            "carol@EXAMPLE.com".parse().unwrap(),
+        ];
+        let groups = group_by_domain(&addrs);
+        assert_eq!(
+            vec!["example.com", "example.org"],
+            groups.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(2, groups["example.com"].len());
+    }
+}
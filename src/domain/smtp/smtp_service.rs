@@ -1,20 +1,33 @@
-use anyhow::Result;
+use anyhow::{anyhow, Context, Error, Result};
 use lettre::{
     self,
     transport::smtp::{
         client::{Tls, TlsParameters},
-        SmtpTransport,
+        PoolConfig, SmtpTransport,
     },
     Transport,
 };
-use log::debug;
+use log::{debug, warn};
 use std::convert::TryInto;
 
-use crate::{config::Account, domain::msg::Msg};
+use crate::{
+    config::Account,
+    domain::{
+        hook::hook_handler,
+        msg::Msg,
+        smtp::{group_by_domain, lookup_mx, PartialDelivery},
+    },
+    output::ExitCode,
+};
+
+/// Represents the port mail exchangers are reached on.
+const DIRECT_MX_PORT: u16 = 25;
 
 pub trait SmtpServiceInterface {
     fn send_msg(&mut self, msg: &Msg) -> Result<lettre::Message>;
     fn send_raw_msg(&mut self, envelope: &lettre::address::Envelope, msg: &[u8]) -> Result<()>;
+    /// Connects and authenticates to the SMTP server, without sending anything.
+    fn check(&mut self) -> Result<()>;
 }
 
 pub struct SmtpService<'a> {
@@ -23,6 +36,8 @@ pub struct SmtpService<'a> {
 }
 
 impl<'a> SmtpService<'a> {
+    /// Returns the transport, built once. It keeps a single pooled connection, so successive
+    /// messages (like the ones of a queue flush) are sent through one authenticated connection.
     fn transport(&mut self) -> Result<&SmtpTransport> {
         if let Some(ref transport) = self.transport {
             Ok(transport)
@@ -48,27 +63,120 @@ impl<'a> SmtpService<'a> {
                     .tls(tls)
                     .port(self.account.smtp_port)
                     .credentials(self.account.smtp_creds()?)
+                    .pool_config(PoolConfig::new().max_size(1))
                     .build(),
             );
 
             Ok(self.transport.as_ref().unwrap())
         }
     }
+
+    /// Delivers the given message to the mail exchangers of the recipient domains, one domain
+    /// after the other. The mail exchangers of a domain are tried by order of preference, over
+    /// STARTTLS when they support it. A failing domain does not stop the delivery to the next
+    /// ones: when some domains got the message, the error is a [`PartialDelivery`].
+    fn send_raw_msg_direct(&self, envelope: &lettre::address::Envelope, msg: &[u8]) -> Result<()> {
+        let mut delivered = vec![];
+        let mut failed = vec![];
+        for (domain, to) in group_by_domain(envelope.to()) {
+            let envelope = lettre::address::Envelope::new(envelope.from().cloned(), to)?;
+            match Self::send_raw_msg_to_domain(&domain, &envelope, msg) {
+                Ok(()) => delivered.push(domain),
+                Err(err) => {
+                    warn!("cannot deliver message to {}: {:?}", domain, err);
+                    failed.push((domain, err));
+                }
+            }
+        }
+
+        if failed.is_empty() {
+            return Ok(());
+        }
+        if delivered.is_empty() && failed.len() == 1 {
+            let (domain, err) = failed.remove(0);
+            return Err(err.context(format!("cannot deliver message to {}", domain)));
+        }
+        let failed: Vec<(String, String)> = failed
+            .into_iter()
+            .map(|(domain, err)| (domain, format!("{:#}", err)))
+            .collect();
+        if delivered.is_empty() {
+            let failed: Vec<String> = failed
+                .iter()
+                .map(|(domain, err)| format!("{} ({})", domain, err))
+                .collect();
+            return Err(anyhow!("cannot deliver message to {}", failed.join(", ")));
+        }
+        Err(Error::new(PartialDelivery { delivered, failed }).context(ExitCode::PartialFailure))
+    }
+
+    /// Delivers the given message to the first mail exchanger of the given domain accepting it.
+    fn send_raw_msg_to_domain(
+        domain: &str,
+        envelope: &lettre::address::Envelope,
+        msg: &[u8],
+    ) -> Result<()> {
+        let mut res = Err(anyhow!("cannot find mail exchanger"));
+        for host in lookup_mx(domain)? {
+            debug!("delivering message to {} via {}…", domain, host);
+            let tls = TlsParameters::new(host.to_owned())?;
+            res = SmtpTransport::builder_dangerous(&host)
+                .port(DIRECT_MX_PORT)
+                .tls(Tls::Opportunistic(tls))
+                .build()
+                .send_raw(envelope, msg)
+                .map(|_| ())
+                .context(format!("cannot deliver message via {}", host));
+            if res.is_ok() {
+                break;
+            }
+        }
+        res
+    }
 }
 
 impl<'a> SmtpServiceInterface for SmtpService<'a> {
     fn send_msg(&mut self, msg: &Msg) -> Result<lettre::Message> {
         debug!("sending message…");
         let sendable_msg: lettre::Message = msg.try_into()?;
-        self.transport()?.send(&sendable_msg)?;
+        self.send_raw_msg(sendable_msg.envelope(), &sendable_msg.formatted())?;
         Ok(sendable_msg)
     }
 
     fn send_raw_msg(&mut self, envelope: &lettre::address::Envelope, msg: &[u8]) -> Result<()> {
         debug!("sending raw message…");
+        let account = self.account;
+        hook_handler::pre_send(account, msg)?;
+        let signed_msg = match account.dkim.as_ref() {
+            Some(dkim) => {
+                debug!("signing message with DKIM selector {}…", dkim.selector);
+                Some(dkim.sign(msg)?)
+            }
+            None => None,
+        };
+        let msg = signed_msg.as_deref().unwrap_or(msg);
+        if account.smtp_direct_mx {
+            return self.send_raw_msg_direct(envelope, msg);
+        }
         self.transport()?.send_raw(envelope, msg)?;
         Ok(())
     }
+
+    fn check(&mut self) -> Result<()> {
+        if self.account.smtp_direct_mx {
+            debug!("no SMTP relay to check in direct MX mode");
+            return Ok(());
+        }
+        debug!("checking SMTP connection…");
+        let connected = self
+            .transport()?
+            .test_connection()
+            .context("cannot connect to SMTP server")?;
+        if !connected {
+            return Err(anyhow!("cannot connect to SMTP server"));
+        }
+        Ok(())
+    }
 }
 
 impl<'a> From<&'a Account> for SmtpService<'a> {
@@ -1,27 +1,40 @@
 use anyhow::Result;
 use clap;
-use env_logger;
-use output::StdoutPrinter;
-use std::{convert::TryFrom, env};
+use log::debug;
+use output::{PrinterService, StdoutPrinter};
+use std::{collections::HashMap, convert::TryFrom, env, path::Path, process};
 use url::Url;
 
 mod compl;
 mod config;
+mod daemon;
 mod domain;
 mod output;
+mod repl;
+mod server;
 mod ui;
 
 use compl::{compl_arg, compl_handler};
-use config::{config_arg, Account, Config};
+use config::{config_arg, config_handler, Account, Config};
+use daemon::{daemon_arg, daemon_handler};
 use domain::{
-    imap::{imap_arg, imap_handler, ImapService, ImapServiceInterface},
+    filter::{filter_arg, filter_handler},
+    imap::{imap_arg, imap_handler, ImapService, ImapServiceInterface, SharedImapSession},
     mbox::{mbox_arg, mbox_handler, Mbox},
-    msg::{flag_arg, flag_handler, msg_arg, msg_handler, tpl_arg, tpl_handler},
+    mlist::{mlist_arg, mlist_handler},
+    msg::{
+        flag_arg, flag_handler, msg_arg, msg_handler, show_uids, tpl_arg, tpl_handler, Selection,
+    },
+    ping::{ping_arg, ping_handler},
+    sieve::{sieve_arg, sieve_handler, SieveService},
     smtp::SmtpService,
 };
-use output::{output_arg, OutputFmt};
+use output::{logger_service, output_arg, ExitCode, Logger, OutputFmt, WriteColor};
+use repl::{repl_arg, repl_handler};
+use server::{server_arg, server_handler};
+use ui::i18n::tr;
 
-fn create_app<'a>() -> clap::App<'a, 'a> {
+fn create_app<'a>(aliases: &'a HashMap<String, String>) -> clap::App<'a, 'a> {
     clap::App::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
         .about(env!("CARGO_PKG_DESCRIPTION"))
@@ -29,22 +42,48 @@ fn create_app<'a>() -> clap::App<'a, 'a> {
         .global_setting(clap::AppSettings::GlobalVersion)
         .args(&config_arg::args())
         .args(&output_arg::args())
+        .args(&imap_arg::args())
         .arg(mbox_arg::source_arg())
         .subcommands(compl_arg::subcmds())
+        .subcommands(filter_arg::subcmds())
         .subcommands(imap_arg::subcmds())
         .subcommands(mbox_arg::subcmds())
         .subcommands(msg_arg::subcmds())
+        .subcommands(mlist_arg::subcmds())
+        .subcommands(ping_arg::subcmds())
+        .subcommands(sieve_arg::subcmds())
+        .subcommands(daemon_arg::subcmds())
+        .subcommands(server_arg::subcmds())
+        .subcommands(repl_arg::subcmds())
+        .subcommands(config_arg::subcmds())
+        .subcommands(config_arg::alias_subcmds(aliases))
 }
 
-fn main() -> Result<()> {
-    // Init env logger
-    env_logger::init_from_env(
-        env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "off"),
-    );
+/// Parses the given raw arguments, exiting on usage errors.
+fn get_matches<'a>(app: clap::App<'a, 'a>, args: &[String]) -> clap::ArgMatches<'a> {
+    app.get_matches_from_safe(args)
+        .unwrap_or_else(|err| match err.kind {
+            clap::ErrorKind::HelpDisplayed | clap::ErrorKind::VersionDisplayed => err.exit(),
+            _ => {
+                eprintln!("{}", err.message);
+                process::exit(ExitCode::Usage as i32);
+            }
+        })
+}
 
+fn main() {
+    if let Err(err) = start() {
+        eprintln!("{}: {:?}", tr("error"), err);
+        process::exit(ExitCode::from(&err) as i32);
+    }
+}
+
+/// Starts the CLI. Errors are mapped to exit codes by the caller.
+fn start() -> Result<()> {
     // Check mailto command BEFORE app initialization.
     let raw_args: Vec<String> = env::args().collect();
     if raw_args.len() > 1 && raw_args[1].starts_with("mailto:") {
+        Logger::init(None, None, OutputFmt::Plain)?;
         let mbox = Mbox::new("INBOX");
         let config = Config::try_from(None)?;
         let account = Account::try_from((&config, None))?;
@@ -52,130 +91,530 @@ fn main() -> Result<()> {
         let url = Url::parse(&raw_args[1])?;
         let mut imap = ImapService::from((&account, &mbox));
         let mut smtp = SmtpService::from(&account);
-        return msg_handler::mailto(&url, &account, &mut printer, &mut imap, &mut smtp);
+        return msg_handler::mailto(
+            &url,
+            false,
+            false,
+            &account,
+            &mut printer,
+            &mut imap,
+            &mut smtp,
+        );
     }
 
-    let app = create_app();
-    let m = app.get_matches();
+    // The aliases are not known before the config is loaded, so they are parsed as external
+    // subcommands first.
+    let no_aliases = HashMap::default();
+    let app = create_app(&no_aliases).setting(clap::AppSettings::AllowExternalSubcommands);
+    let m = get_matches(app, &raw_args);
+
+    // Init logger.
+    Logger::init(
+        m.value_of("log-level"),
+        m.value_of("log-file").map(Path::new),
+        OutputFmt::try_from(m.value_of("log-format"))?,
+    )?;
 
     // Check completion command BEFORE entities and services initialization.
     // Related issue: https://github.com/soywod/himalaya/issues/115.
     match compl_arg::matches(&m)? {
         Some(compl_arg::Command::Generate(shell)) => {
-            return compl_handler::generate(create_app(), shell);
+            let aliases = Config::try_from(m.value_of("config"))
+                .ok()
+                .and_then(|config| config.aliases)
+                .unwrap_or_default();
+            return compl_handler::generate(create_app(&aliases), shell);
         }
         _ => (),
     }
 
-    // Init entities and services.
-    let mbox = Mbox::new(m.value_of("mbox-source").unwrap());
+    // Check config import command BEFORE config initialization, so a first config can be built
+    // from the imported accounts.
+    if let Some(config_arg::Cmd::Import(format, path)) = config_arg::matches(&m)? {
+        let mut printer = StdoutPrinter::from(output_arg::fmt(&m)?);
+        return config_handler::import(format, path, &mut printer);
+    }
+
+    // Init entities.
     let config = Config::try_from(m.value_of("config"))?;
+    let aliases = config.aliases.clone().unwrap_or_default();
+
+    // Expand the alias of the command, then parse the arguments again with the aliases known, so
+    // unknown subcommands are reported.
+    let raw_args = match m
+        .subcommand_name()
+        .and_then(|name| Some((name, aliases.get(name)?)))
+    {
+        Some((alias, cmd)) => {
+            let args = config_arg::expand_alias(&raw_args, alias, cmd);
+            debug!("args with alias expanded: {:?}", args);
+            args
+        }
+        None => raw_args,
+    };
+    let m = get_matches(create_app(&aliases), &raw_args);
+    if let Some(alias) = m
+        .subcommand_name()
+        .filter(|name| aliases.contains_key(*name))
+    {
+        return Err(ExitCode::Usage.err(format!(
+            r#"cannot expand alias "{}": an alias cannot refer to another alias"#,
+            alias
+        )));
+    }
+
     let account = Account::try_from((&config, m.value_of("account")))?;
-    let mut printer = StdoutPrinter::try_from(m.value_of("output"))?;
-    let mut imap = ImapService::from((&account, &mbox));
-    let mut smtp = SmtpService::from(&account);
+
+    // Insert the default arguments of the command from the config, then parse them again.
+    let (m, raw_args) = match m
+        .subcommand_name()
+        .and_then(|cmd| account.default_args.get(cmd))
+    {
+        Some(defaults) => {
+            let args = config_arg::with_default_args(&raw_args, defaults);
+            debug!("args with defaults: {:?}", args);
+            (get_matches(create_app(&aliases), &args), args)
+        }
+        None => (m, raw_args),
+    };
+
+    // The daemon, the server and the REPL run the commands they receive with a shared IMAP
+    // session. Only the REPL has a user at the terminal to answer prompts.
+    let run_args = |args: &[String],
+                    writter: Box<dyn WriteColor>,
+                    sess: SharedImapSession,
+                    interactive: bool|
+     -> Result<()> {
+        let m = create_app(&aliases).get_matches_from_safe(args)?;
+        let mut printer = StdoutPrinter {
+            writter,
+            fmt: output_arg::fmt(&m)?,
+            interactive,
+        };
+        run(&m, &config, &account, &mut printer, sess)
+    };
+
+    // Check REPL command.
+    if let Some(repl_arg::Cmd::Start) = repl_arg::matches(&m)? {
+        return repl_handler::start(output_arg::fmt(&m)?, |args, writter, sess| {
+            let m = create_app(&aliases).get_matches_from_safe(args)?;
+            if !repl_arg::is_allowed(&m) {
+                return Err(ExitCode::Usage.err(format!(
+                    r#"cannot run command "{}" in the REPL"#,
+                    m.subcommand_name().unwrap_or_default()
+                )));
+            }
+            run_args(args, writter, sess, true)
+        });
+    }
+
+    // Check server command.
+    if let Some(server_arg::Cmd::Start(addr)) = server_arg::matches(&m)? {
+        return server_handler::start(
+            addr,
+            config.serve_token.as_deref(),
+            &account,
+            |args, writter, sess| run_args(args, writter, sess, false),
+        );
+    }
+
+    // Check daemon command, or forward the command to the daemon if one is running.
+    if let Some(daemon_arg::Cmd::Start) = daemon_arg::matches(&m)? {
+        return daemon_handler::start(&account, |args, writter, sess| {
+            run_args(args, writter, sess, false)
+        });
+    }
+
+    if daemon_arg::is_forwardable(&m) && daemon_handler::forward(&account, &raw_args)? {
+        return Ok(());
+    }
+
+    let mut printer = StdoutPrinter::from(output_arg::fmt(&m)?);
+    run(
+        &m,
+        &config,
+        &account,
+        &mut printer,
+        SharedImapSession::default(),
+    )
+}
+
+/// Runs the command matching the given arguments.
+fn run(
+    m: &clap::ArgMatches,
+    config: &Config,
+    account: &Account,
+    printer: &mut StdoutPrinter,
+    sess: SharedImapSession,
+) -> Result<()> {
+    logger_service::set_span(Some(m.subcommand_name().unwrap_or("list")));
+
+    // Some filter and mailbox commands can target another mailbox than the source one.
+    let filter_cmd = filter_arg::matches(m)?;
+    let mbox_cmd = mbox_arg::matches(m)?;
+    let mbox = match (&filter_cmd, &mbox_cmd) {
+        (Some(filter_arg::Cmd::Run(Some(mbox))), _)
+        | (_, Some(mbox_arg::Cmd::Dedupe(Some(mbox), _, _)))
+        | (_, Some(mbox_arg::Cmd::Expunge(Some(mbox))))
+        | (_, Some(mbox_arg::Cmd::Stats(Some(mbox), _))) => Mbox::new(mbox),
+        (_, Some(mbox_arg::Cmd::EmptyTrash)) => Mbox::new(&account.trash_folder),
+        (_, Some(mbox_arg::Cmd::EmptyJunk)) => Mbox::new(&account.junk_folder),
+        _ => Mbox::new(
+            m.value_of("mbox-source")
+                .unwrap_or(&account.default_mailbox),
+        ),
+    };
+
+    let dry_run = m.is_present("dry-run");
+    let yes = m.is_present("yes");
+    let force = m.is_present("force");
+    let uid = imap_arg::uid(m).unwrap_or(account.uid);
+    show_uids(uid);
+
+    // Init services.
+    let mut imap = ImapService::from((account, &mbox, sess.clone()))
+        .with_trace(imap_arg::trace(m))
+        .with_uid(uid)
+        .with_gmail_ids(printer.is_structured());
+    let mut smtp = SmtpService::from(account);
+    let mut sieve = SieveService::from(account);
+
+    // Check dynamic completion command.
+    if let Some(compl_arg::Command::Values(kind)) = compl_arg::matches(m)? {
+        return compl_handler::values(kind, config, printer, &mut imap);
+    }
 
     // Check IMAP commands.
-    match imap_arg::matches(&m)? {
-        Some(imap_arg::Command::Notify(keepalive)) => {
-            return imap_handler::notify(keepalive, &config, &mut imap);
+    match imap_arg::matches(m)? {
+        Some(imap_arg::Command::Bench(batch_sizes)) => {
+            return imap_handler::bench(batch_sizes, account, printer, &mut imap);
+        }
+        Some(imap_arg::Command::Exec(cmd, select)) => {
+            return imap_handler::exec(cmd, select, printer, &mut imap);
+        }
+        Some(imap_arg::Command::Notify(keepalive, all_accounts)) => {
+            if all_accounts || !account.notify_mboxes.is_empty() {
+                return imap_handler::notify_all(keepalive, all_accounts, config, account);
+            }
+            return imap_handler::notify(keepalive, config, &mut imap);
         }
         Some(imap_arg::Command::Watch(keepalive)) => {
-            return imap_handler::watch(keepalive, &mut imap);
+            return imap_handler::watch(keepalive, account, &mut imap);
+        }
+        _ => (),
+    }
+
+    // Check filter commands.
+    match filter_cmd {
+        Some(filter_arg::Cmd::Run(_)) => {
+            return filter_handler::run(dry_run, account, printer, &mut imap);
+        }
+        Some(filter_arg::Cmd::Block(addr)) => {
+            return filter_handler::block(addr, config, printer);
+        }
+        Some(filter_arg::Cmd::Unblock(addr)) => {
+            return filter_handler::unblock(addr, config, printer);
         }
         _ => (),
     }
 
     // Check mailbox commands.
-    match mbox_arg::matches(&m)? {
+    match mbox_cmd {
         Some(mbox_arg::Cmd::List(max_width)) => {
-            return mbox_handler::list(max_width, &mut printer, &mut imap);
+            return mbox_handler::list(max_width, printer, &mut imap);
+        }
+        Some(mbox_arg::Cmd::Count) => {
+            return mbox_handler::count(printer, &mut imap);
+        }
+        Some(mbox_arg::Cmd::Dedupe(_, key, target)) => {
+            return mbox_handler::dedupe(key, target, dry_run, yes, account, printer, &mut imap);
+        }
+        Some(mbox_arg::Cmd::Stats(_, all)) => {
+            return mbox_handler::stats(&mbox, all, printer, &mut imap);
+        }
+        Some(mbox_arg::Cmd::Clean) => {
+            let mut reports = vec![];
+            for (name, policy) in &account.retention {
+                let mbox = Mbox::new(name);
+                let mut imap = ImapService::from((account, &mbox, sess.clone()))
+                    .with_trace(imap_arg::trace(m))
+                    .with_uid(uid);
+                reports.push(mbox_handler::clean(&mbox, policy, dry_run, &mut imap)?);
+            }
+            if reports.is_empty() {
+                return printer.print(tr("no-retention-policy"));
+            }
+            return printer.print(reports.join("\n"));
+        }
+        Some(mbox_arg::Cmd::Expunge(_)) => {
+            return mbox_handler::expunge(&mbox, dry_run, yes, account, printer, &mut imap);
+        }
+        Some(mbox_arg::Cmd::EmptyTrash) | Some(mbox_arg::Cmd::EmptyJunk) => {
+            return mbox_handler::empty(&mbox, dry_run, yes, account, printer, &mut imap);
+        }
+        _ => (),
+    }
+
+    // Check health check commands.
+    if let Some(ping_arg::Cmd::Ping) = ping_arg::matches(m)? {
+        return ping_handler::ping(config, printer);
+    }
+
+    // Check mailing list commands.
+    match mlist_arg::matches(m)? {
+        Some(mlist_arg::Cmd::List(max_width)) => {
+            return mlist_handler::list(max_width, printer, &mut imap);
+        }
+        Some(mlist_arg::Cmd::Read(list_id, max_width, page_size, page)) => {
+            return mlist_handler::read(
+                list_id, max_width, page_size, page, account, printer, &mut imap,
+            );
+        }
+        _ => (),
+    }
+
+    // Check ManageSieve commands.
+    match sieve_arg::matches(m)? {
+        Some(sieve_arg::Cmd::List) => {
+            return sieve_handler::list(printer, &mut sieve);
+        }
+        Some(sieve_arg::Cmd::Get(name)) => {
+            return sieve_handler::get(name, printer, &mut sieve);
+        }
+        Some(sieve_arg::Cmd::Put(name, path)) => {
+            return sieve_handler::put(name, path, printer, &mut sieve);
+        }
+        Some(sieve_arg::Cmd::Activate(name)) => {
+            return sieve_handler::activate(name, printer, &mut sieve);
         }
         _ => (),
     }
 
     // Check message commands.
-    match msg_arg::matches(&m)? {
-        Some(msg_arg::Command::Attachments(seq)) => {
-            return msg_handler::attachments(seq, &account, &mut printer, &mut imap);
+    match msg_arg::matches(m)? {
+        Some(msg_arg::Command::Attachments(seq, mime, name)) => {
+            return msg_handler::attachments(seq, mime, name, account, printer, &mut imap);
         }
-        Some(msg_arg::Command::Copy(seq, mbox)) => {
-            return msg_handler::copy(seq, mbox, &mut printer, &mut imap);
+        Some(msg_arg::Command::AttachmentCat(seq, name)) => {
+            return msg_handler::attachment_cat(seq, name, &mut imap);
         }
-        Some(msg_arg::Command::Delete(seq)) => {
-            return msg_handler::delete(seq, &mut printer, &mut imap);
+        Some(msg_arg::Command::Bounce(seq, to)) => {
+            return msg_handler::bounce(seq, to, account, printer, &mut imap, &mut smtp);
         }
-        Some(msg_arg::Command::Forward(seq, atts)) => {
-            return msg_handler::forward(seq, atts, &account, &mut printer, &mut imap, &mut smtp);
+        Some(msg_arg::Command::Copy(selection, mbox)) => {
+            return msg_handler::copy(selection, mbox, printer, &mut imap);
         }
-        Some(msg_arg::Command::List(max_width, page_size, page)) => {
-            return msg_handler::list(
-                max_width,
-                page_size,
-                page,
-                &account,
-                &mut printer,
+        Some(msg_arg::Command::Delete(selection)) => {
+            return msg_handler::delete(selection, dry_run, yes, account, printer, &mut imap);
+        }
+        Some(msg_arg::Command::Edit(seq)) => {
+            return msg_handler::edit(seq, force, yes, account, printer, &mut imap, &mut smtp);
+        }
+        Some(msg_arg::Command::Forward(seq, atts, identity, ask_receipt)) => {
+            return msg_handler::forward(
+                seq,
+                atts,
+                identity,
+                ask_receipt,
+                force,
+                yes,
+                account,
+                printer,
                 &mut imap,
+                &mut smtp,
+            );
+        }
+        Some(msg_arg::Command::Invite(seq, reply)) => {
+            return msg_handler::invite(seq, reply, account, printer, &mut imap, &mut smtp);
+        }
+        Some(msg_arg::Command::List(max_width, page_size, page)) => {
+            return msg_handler::list(max_width, page_size, page, account, printer, &mut imap);
+        }
+        Some(msg_arg::Command::ListAllAccounts(max_width, page_size, page)) => {
+            return msg_handler::list_all_accounts(
+                max_width, page_size, page, &mbox, config, printer,
             );
         }
-        Some(msg_arg::Command::Move(seq, mbox)) => {
-            return msg_handler::move_(seq, mbox, &mut printer, &mut imap);
+        Some(msg_arg::Command::Move(selection, mbox)) => {
+            return msg_handler::move_(selection, mbox, dry_run, yes, account, printer, &mut imap);
+        }
+        Some(msg_arg::Command::Pick(query, page_size, page, action)) => {
+            let mut pick_imap = ImapService::from((account, &mbox, sess.clone()))
+                .with_trace(imap_arg::trace(m))
+                .with_uid(uid);
+            let seq_range =
+                match msg_handler::pick(query, page_size, page, account, &mut pick_imap)? {
+                    Some(seq_range) => seq_range,
+                    None => return printer.print("No message picked"),
+                };
+            let selection = Selection::SeqRange(&seq_range);
+            return match action {
+                msg_arg::PickAction::Print => printer.print(seq_range.as_str()),
+                msg_arg::PickAction::Read => msg_handler::read(
+                    vec![&seq_range],
+                    "auto",
+                    false,
+                    None,
+                    None,
+                    false,
+                    false,
+                    false,
+                    false,
+                    account,
+                    printer,
+                    &mut imap,
+                    &mut smtp,
+                ),
+                msg_arg::PickAction::Delete => {
+                    msg_handler::delete(selection, dry_run, yes, account, printer, &mut imap)
+                }
+                msg_arg::PickAction::Move(target) => {
+                    msg_handler::move_(selection, target, dry_run, yes, account, printer, &mut imap)
+                }
+            };
         }
-        Some(msg_arg::Command::Read(seq, text_mime, raw)) => {
-            return msg_handler::read(seq, text_mime, raw, &mut printer, &mut imap);
+        Some(msg_arg::Command::Pipe(seq, part, cmd)) => {
+            return msg_handler::pipe(seq, part, cmd, &mut imap);
+        }
+        Some(msg_arg::Command::Read(
+            seqs,
+            text_mime,
+            raw,
+            concat_output,
+            export,
+            print,
+            load_remote,
+            envelope_only,
+            preserve_flags,
+        )) => {
+            let preserve_flags = preserve_flags || account.peek;
+            let mut imap = imap.with_peek(preserve_flags);
+            return msg_handler::read(
+                seqs,
+                text_mime,
+                raw,
+                concat_output,
+                export,
+                print,
+                load_remote,
+                envelope_only,
+                preserve_flags,
+                account,
+                printer,
+                &mut imap,
+                &mut smtp,
+            );
         }
-        Some(msg_arg::Command::Reply(seq, all, atts)) => {
+        Some(msg_arg::Command::Reply(seq, all, atts, identity, ask_receipt)) => {
             return msg_handler::reply(
                 seq,
                 all,
                 atts,
-                &account,
-                &mut printer,
+                identity,
+                ask_receipt,
+                force,
+                yes,
+                account,
+                printer,
                 &mut imap,
                 &mut smtp,
             );
         }
         Some(msg_arg::Command::Save(raw_msg)) => {
-            return msg_handler::save(&mbox, raw_msg, &mut printer, &mut imap);
+            return msg_handler::save(&mbox, raw_msg, printer, &mut imap);
+        }
+        Some(msg_arg::Command::Search(query, max_width, page_size, page, true)) => {
+            return msg_handler::search_all_mboxes(
+                query, max_width, page_size, page, account, printer, &mut imap,
+            );
         }
-        Some(msg_arg::Command::Search(query, max_width, page_size, page)) => {
+        Some(msg_arg::Command::Search(query, max_width, page_size, page, false)) => {
             return msg_handler::search(
-                query,
-                max_width,
-                page_size,
-                page,
-                &account,
-                &mut printer,
+                query, max_width, page_size, page, account, printer, &mut imap,
+            );
+        }
+        Some(msg_arg::Command::SentStatus(max)) => {
+            let sent_mbox = Mbox::new(&account.sent_folder);
+            let mut sent_imap = ImapService::from((account, &sent_mbox, sess))
+                .with_trace(imap_arg::trace(m))
+                .with_uid(uid);
+            return msg_handler::sent_status(max, printer, &mut imap, &mut sent_imap);
+        }
+        Some(msg_arg::Command::QueueFlush(due)) => {
+            let outbox_mbox = Mbox::new(&account.outbox_folder);
+            let mut outbox_imap = ImapService::from((account, &outbox_mbox, sess))
+                .with_trace(imap_arg::trace(m))
+                .with_uid(uid);
+            return msg_handler::flush_queue(due, account, printer, &mut outbox_imap, &mut smtp);
+        }
+        Some(msg_arg::Command::Send(raw_msg, send_at)) => {
+            return msg_handler::send(
+                raw_msg, send_at, force, account, printer, &mut imap, &mut smtp,
+            );
+        }
+        Some(msg_arg::Command::Note(seq, note, delete)) => {
+            return msg_handler::note(seq, note, delete, account, printer, &mut imap);
+        }
+        Some(msg_arg::Command::Notes(query, max_width)) => {
+            return msg_handler::notes(query, max_width, account, printer);
+        }
+        Some(msg_arg::Command::Snooze(seq, until)) => {
+            return msg_handler::snooze(seq, until, account, printer, &mut imap);
+        }
+        Some(msg_arg::Command::Thread(seq, all_mboxes, collapse_quotes)) => {
+            return msg_handler::thread(
+                seq,
+                &mbox.name,
+                all_mboxes,
+                collapse_quotes,
+                printer,
                 &mut imap,
             );
         }
-        Some(msg_arg::Command::Send(raw_msg)) => {
-            return msg_handler::send(raw_msg, &mut printer, &mut imap, &mut smtp);
+        Some(msg_arg::Command::Unsubscribe(seq)) => {
+            return msg_handler::unsubscribe(seq, account, printer, &mut imap, &mut smtp);
+        }
+        Some(msg_arg::Command::Wake) => {
+            let snoozed_mbox = Mbox::new(&account.snoozed_folder);
+            let mut snoozed_imap = ImapService::from((account, &snoozed_mbox, sess))
+                .with_trace(imap_arg::trace(m))
+                .with_uid(uid);
+            return msg_handler::wake(account, printer, &mut snoozed_imap);
         }
-        Some(msg_arg::Command::Write(atts)) => {
-            return msg_handler::write(atts, &account, &mut printer, &mut imap, &mut smtp);
+        Some(msg_arg::Command::Write(atts, identity, ask_receipt)) => {
+            return msg_handler::write(
+                atts,
+                identity,
+                ask_receipt,
+                force,
+                yes,
+                account,
+                printer,
+                &mut imap,
+                &mut smtp,
+            );
         }
         Some(msg_arg::Command::Flag(m)) => match m {
-            Some(flag_arg::Command::Set(seq_range, flags)) => {
-                return flag_handler::set(seq_range, flags, &mut printer, &mut imap);
+            Some(flag_arg::Command::Set(selection, flags)) => {
+                return flag_handler::set(selection, flags, printer, &mut imap);
             }
-            Some(flag_arg::Command::Add(seq_range, flags)) => {
-                return flag_handler::add(seq_range, flags, &mut printer, &mut imap);
+            Some(flag_arg::Command::Add(selection, flags)) => {
+                return flag_handler::add(selection, flags, printer, &mut imap);
             }
-            Some(flag_arg::Command::Remove(seq_range, flags)) => {
-                return flag_handler::remove(seq_range, flags, &mut printer, &mut imap);
+            Some(flag_arg::Command::Remove(selection, flags)) => {
+                return flag_handler::remove(selection, flags, printer, &mut imap);
             }
             _ => (),
         },
         Some(msg_arg::Command::Tpl(m)) => match m {
             Some(tpl_arg::Command::New(tpl)) => {
-                return tpl_handler::new(tpl, &account, &mut printer);
+                return tpl_handler::new(tpl, account, printer);
             }
             Some(tpl_arg::Command::Reply(seq, all, tpl)) => {
-                return tpl_handler::reply(seq, all, tpl, &account, &mut printer, &mut imap);
+                return tpl_handler::reply(seq, all, tpl, account, printer, &mut imap);
             }
             Some(tpl_arg::Command::Forward(seq, tpl)) => {
-                return tpl_handler::forward(seq, tpl, &account, &mut printer, &mut imap);
+                return tpl_handler::forward(seq, tpl, account, printer, &mut imap);
             }
             _ => (),
         },
@@ -0,0 +1,130 @@
+//! Module related to exit codes.
+//!
+//! This module exposes the exit codes of the CLI, so scripts can branch on the failure type.
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display},
+    io,
+};
+
+/// Represents the exit code of the CLI. A code can be attached to an error chain as an error
+/// itself, for example `ExitCode::NotFound.err("cannot find message")`, otherwise it is guessed
+/// from the errors of the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExitCode {
+    /// The command succeeded (0).
+    Success = 0,
+    /// The command failed for any other reason (1).
+    Failure = 1,
+    /// The arguments of the command are invalid (2).
+    Usage = 2,
+    /// The IMAP server rejected the credentials (3).
+    Auth = 3,
+    /// The connection to a server failed or was lost (4).
+    Network = 4,
+    /// The message, part, account… targeted by the command does not exist (5).
+    NotFound = 5,
+    /// A config file, a template or a message could not be parsed (6).
+    Parse = 6,
+    /// A batch command failed after having processed some messages (7).
+    PartialFailure = 7,
+}
+
+impl ExitCode {
+    /// Builds an error with the given message, caused by this exit code.
+    pub fn err<C: Display + Send + Sync + 'static>(self, msg: C) -> Error {
+        Error::new(self).context(msg)
+    }
+}
+
+impl Display for ExitCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let code = match self {
+            Self::Success => "success",
+            Self::Failure => "failure",
+            Self::Usage => "invalid usage",
+            Self::Auth => "authentication failure",
+            Self::Network => "network failure",
+            Self::NotFound => "not found",
+            Self::Parse => "parse failure",
+            Self::PartialFailure => "partial failure",
+        };
+        write!(f, "{}", code)
+    }
+}
+
+impl std::error::Error for ExitCode {}
+
+impl From<&Error> for ExitCode {
+    fn from(err: &Error) -> Self {
+        if let Some(code) = err.downcast_ref::<ExitCode>() {
+            return *code;
+        }
+
+        for cause in err.chain() {
+            if cause.is::<clap::Error>() {
+                return Self::Usage;
+            }
+
+            if let Some(err) = cause.downcast_ref::<imap::Error>() {
+                return match err {
+                    imap::Error::Io(_) | imap::Error::ConnectionLost => Self::Network,
+                    imap::Error::Parse(_) => Self::Parse,
+                    _ => Self::Failure,
+                };
+            }
+
+            if let Some(err) = cause.downcast_ref::<io::Error>() {
+                return match err.kind() {
+                    io::ErrorKind::NotFound => Self::NotFound,
+                    io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::NotConnected
+                    | io::ErrorKind::AddrNotAvailable
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::TimedOut => Self::Network,
+                    _ => Self::Failure,
+                };
+            }
+
+            if cause.is::<mailparse::MailParseError>()
+                || cause.is::<toml::de::Error>()
+                || cause.is::<serde_json::Error>()
+            {
+                return Self::Parse;
+            }
+        }
+
+        Self::Failure
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Context};
+
+    use super::*;
+
+    #[test]
+    fn it_should_get_exit_code_from_error() {
+        let err = ExitCode::NotFound
+            .err(r#"cannot find message "1""#)
+            .context("cannot read message");
+        assert_eq!(ExitCode::NotFound, ExitCode::from(&err));
+
+        let err = Error::new(imap::Error::ConnectionLost).context("cannot select mailbox");
+        assert_eq!(ExitCode::Network, ExitCode::from(&err));
+
+        let err = Error::new(io::Error::from(io::ErrorKind::NotFound)).context("cannot read file");
+        assert_eq!(ExitCode::NotFound, ExitCode::from(&err));
+
+        let err = toml::from_str::<toml::Value>("key = ").context("cannot parse config file");
+        assert_eq!(ExitCode::Parse, ExitCode::from(&err.unwrap_err()));
+
+        let err = anyhow!("cannot do something");
+        assert_eq!(ExitCode::Failure, ExitCode::from(&err));
+    }
+}
@@ -0,0 +1,168 @@
+//! Module related to logging.
+//!
+//! This module exposes a logger built on top of `env_logger` filters. Records can be written
+//! either to stderr or to a file, in plain text or in JSON, and are tagged with the span of the
+//! command being run.
+
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use log::{Log, Metadata, Record};
+use std::{
+    cell::RefCell,
+    fs::OpenOptions,
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use crate::output::OutputFmt;
+
+thread_local! {
+    static SPAN: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Sets the span the next records of the current thread are tagged with, usually the name of
+/// the command being run.
+pub fn set_span<S: ToString>(span: Option<S>) {
+    SPAN.with(|s| *s.borrow_mut() = span.map(|span| span.to_string()));
+}
+
+fn span() -> Option<String> {
+    SPAN.with(|s| s.borrow().clone())
+}
+
+/// Represents the logger. Records are filtered by the `RUST_LOG` env var (defaulting to `off`),
+/// whose global level can be overridden by the given level.
+pub struct Logger {
+    filter: env_logger::Logger,
+    fmt: OutputFmt,
+    sink: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Logger {
+    /// Initializes the global logger.
+    pub fn init(level: Option<&str>, path: Option<&Path>, fmt: OutputFmt) -> Result<()> {
+        let mut builder = env_logger::Builder::from_env(
+            env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "off"),
+        );
+        if let Some(level) = level {
+            builder.filter_level(level.parse().context("cannot parse log level")?);
+        }
+        let filter = builder.build();
+
+        let sink: Box<dyn Write + Send> = match path {
+            Some(path) => Box::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .context(format!("cannot open log file {:?}", path))?,
+            ),
+            None => Box::new(io::stderr()),
+        };
+
+        log::set_max_level(filter.filter());
+        log::set_boxed_logger(Box::new(Self {
+            filter,
+            fmt,
+            sink: Mutex::new(sink),
+        }))
+        .context("cannot init logger")
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.filter.matches(record) {
+            return;
+        }
+
+        let ts = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+        let line = format_record(&self.fmt, &ts, span().as_deref(), record);
+        if let Ok(mut sink) = self.sink.lock() {
+            // Logging must never break the command, errors are ignored.
+            let _ = writeln!(sink, "{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut sink) = self.sink.lock() {
+            let _ = sink.flush();
+        }
+    }
+}
+
+fn format_record(fmt: &OutputFmt, ts: &str, span: Option<&str>, record: &Record) -> String {
+    match fmt {
+        // S-expressions are not offered for logs
+        OutputFmt::Plain | OutputFmt::Sexp => {
+            let span = span.map(|span| format!(" {}", span)).unwrap_or_default();
+            format!(
+                "[{} {:<5} {}{}] {}",
+                ts,
+                record.level(),
+                record.target(),
+                span,
+                record.args()
+            )
+        }
+        OutputFmt::Json | OutputFmt::JsonStream => serde_json::json!({
+            "ts": ts,
+            "level": record.level().to_string(),
+            "target": record.target(),
+            "span": span,
+            "msg": record.args().to_string(),
+        })
+        .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+
+    use super::*;
+
+    #[test]
+    fn it_should_format_records() {
+        let ts = "2021-10-16T10:00:00.000Z";
+        let format = |fmt, span| {
+            format_record(
+                fmt,
+                ts,
+                span,
+                &Record::builder()
+                    .args(format_args!("select mailbox {}", "INBOX"))
+                    .level(Level::Debug)
+                    .target("himalaya::domain::imap")
+                    .build(),
+            )
+        };
+
+        assert_eq!(
+            "[2021-10-16T10:00:00.000Z DEBUG himalaya::domain::imap list] select mailbox INBOX",
+            format(&OutputFmt::Plain, Some("list"))
+        );
+        assert_eq!(
+            "[2021-10-16T10:00:00.000Z DEBUG himalaya::domain::imap] select mailbox INBOX",
+            format(&OutputFmt::Plain, None)
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&format(&OutputFmt::Json, Some("list"))).unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "ts": ts,
+                "level": "DEBUG",
+                "target": "himalaya::domain::imap",
+                "span": "list",
+                "msg": "select mailbox INBOX",
+            }),
+            json
+        );
+    }
+}
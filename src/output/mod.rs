@@ -5,9 +5,18 @@ pub mod output_arg;
 pub mod output_utils;
 pub use output_utils::*;
 
+pub mod sexp_utils;
+pub use sexp_utils::*;
+
 pub mod output_entity;
 pub use output_entity::*;
 
+pub mod exit_code_entity;
+pub use exit_code_entity::*;
+
+pub mod logger_service;
+pub use logger_service::*;
+
 pub mod print;
 pub use print::*;
 
@@ -2,7 +2,11 @@
 //!
 //! This module provides arguments related to output.
 
-use clap::Arg;
+use anyhow::Result;
+use clap::{Arg, ArgMatches};
+use std::convert::TryFrom;
+
+use crate::output::OutputFmt;
 
 /// Output arguments.
 pub fn args<'a>() -> Vec<Arg<'a, 'a>> {
@@ -11,16 +15,49 @@ pub fn args<'a>() -> Vec<Arg<'a, 'a>> {
             .long("output")
             .short("o")
             .help("Defines the output format")
+            .long_help("Defines the output format: plain text, JSON, or S-expressions (property lists readable by Emacs Lisp).")
             .value_name("FMT")
-            .possible_values(&["plain", "json"])
+            .possible_values(&["plain", "json", "sexp"])
             .default_value("plain"),
+        Arg::with_name("stream")
+            .long("stream")
+            .help("Prints JSON lines")
+            .long_help("Prints JSON lines instead of a single JSON document: lists are printed one item per line (`{\"type\":\"item\",\"data\":…}`), other data as a single response line (`{\"type\":\"response\",\"data\":…}`). Requires `--output json`."),
         Arg::with_name("log-level")
             .long("log-level")
             .alias("log")
             .short("l")
             .help("Defines the logs level")
+            .long_help("Defines the logs level. Overrides the global level of the RUST_LOG env var, which defaults to off.")
             .value_name("LEVEL")
-            .possible_values(&["error", "warn", "info", "debug", "trace"])
-            .default_value("info"),
+            .possible_values(&["off", "error", "warn", "info", "debug", "trace"]),
+        Arg::with_name("log-file")
+            .long("log-file")
+            .help("Writes logs to the given file instead of stderr")
+            .value_name("PATH"),
+        Arg::with_name("log-format")
+            .long("log-format")
+            .help("Defines the logs format")
+            .value_name("FMT")
+            .possible_values(&["plain", "json"])
+            .default_value("plain"),
+        Arg::with_name("dry-run")
+            .long("dry-run")
+            .help("Prints what destructive commands would do without doing it")
+            .long_help("Prints what the delete, move, expunge, empty, dedupe and filters commands would do (which messages, which folders) without sending any mutating IMAP command."),
+        Arg::with_name("yes")
+            .long("yes")
+            .short("y")
+            .help("Skips confirmation prompts")
+            .long_help("Skips the confirmation asked before deleting or moving more messages than the confirm-threshold of the account."),
+        Arg::with_name("force")
+            .long("force")
+            .help("Sends messages above the size limit")
+            .long_help("Sends messages larger than the msg-size-limit of the account (25 MB by default) instead of refusing them."),
     ]
 }
+
+/// Returns the output format matching the output arguments.
+pub fn fmt(m: &ArgMatches) -> Result<OutputFmt> {
+    OutputFmt::try_from(m.value_of("output"))?.with_stream(m.is_present("stream"))
+}
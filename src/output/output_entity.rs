@@ -1,21 +1,43 @@
 use anyhow::{anyhow, Error, Result};
 use serde::Serialize;
+use serde_json::Value;
 use std::{
     convert::TryFrom,
     fmt::{self, Display},
 };
 
+use crate::output::ExitCode;
+
 /// Represents the available output formats.
 #[derive(Debug, PartialEq)]
 pub enum OutputFmt {
     Plain,
     Json,
+    /// JSON lines, for editor plugins reading the output as it comes.
+    JsonStream,
+    /// S-expressions, for Emacs Lisp front ends.
+    Sexp,
+}
+
+impl OutputFmt {
+    /// Switches JSON to JSON lines when streaming. Other formats cannot be streamed.
+    pub fn with_stream(self, stream: bool) -> Result<Self> {
+        match (self, stream) {
+            (fmt, false) => Ok(fmt),
+            (Self::Json, true) | (Self::JsonStream, true) => Ok(Self::JsonStream),
+            (fmt, true) => Err(ExitCode::Usage.err(format!(
+                "cannot stream {} output, only JSON can be streamed",
+                fmt
+            ))),
+        }
+    }
 }
 
 impl From<&str> for OutputFmt {
     fn from(fmt: &str) -> Self {
         match fmt {
             slice if slice.eq_ignore_ascii_case("json") => Self::Json,
+            slice if slice.eq_ignore_ascii_case("sexp") => Self::Sexp,
             _ => Self::Plain,
         }
     }
@@ -27,6 +49,7 @@ impl TryFrom<Option<&str>> for OutputFmt {
     fn try_from(fmt: Option<&str>) -> Result<Self, Self::Error> {
         match fmt {
             Some(fmt) if fmt.eq_ignore_ascii_case("json") => Ok(Self::Json),
+            Some(fmt) if fmt.eq_ignore_ascii_case("sexp") => Ok(Self::Sexp),
             Some(fmt) if fmt.eq_ignore_ascii_case("plain") => Ok(Self::Plain),
             None => Ok(Self::Plain),
             Some(fmt) => Err(anyhow!(r#"cannot parse output format "{}""#, fmt)),
@@ -38,13 +61,15 @@ impl Display for OutputFmt {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let fmt = match self {
             &OutputFmt::Json => "JSON",
+            &OutputFmt::JsonStream => "JSON stream",
+            &OutputFmt::Sexp => "S-expression",
             &OutputFmt::Plain => "Plain",
         };
         write!(f, "{}", fmt)
     }
 }
 
-/// Defines a struct-wrapper to provide a JSON output.
+/// Defines a struct-wrapper to provide a JSON (or S-expression) output.
 #[derive(Debug, Serialize, Clone)]
 pub struct OutputJson<T: Serialize> {
     response: T,
@@ -55,3 +80,79 @@ impl<T: Serialize> OutputJson<T> {
         Self { response }
     }
 }
+
+/// Represents a line of the JSON stream output. Lists are printed one item per line, other data
+/// as a single response line. The REPL ends the output of each command with a done or an error
+/// line.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum StreamLine {
+    /// An element of a list printed by the command.
+    Item { data: Value },
+    /// Any other data printed by the command.
+    Response { data: Value },
+    /// The success of a command run by the REPL.
+    Done,
+    /// The failure of a command run by the REPL.
+    Error { code: ExitCode, error: String },
+}
+
+impl StreamLine {
+    /// Builds the lines of the given data.
+    pub fn from_data<T: Serialize>(data: &T) -> Result<Vec<Self>> {
+        Ok(match serde_json::to_value(data)? {
+            Value::Array(items) => items.into_iter().map(|data| Self::Item { data }).collect(),
+            data => vec![Self::Response { data }],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_switch_to_stream() {
+        assert_eq!(
+            OutputFmt::JsonStream,
+            OutputFmt::Json.with_stream(true).unwrap()
+        );
+        assert_eq!(OutputFmt::Json, OutputFmt::Json.with_stream(false).unwrap());
+        assert!(OutputFmt::Plain.with_stream(true).is_err());
+    }
+
+    #[test]
+    fn it_should_split_lists_into_lines() {
+        let to_string = |lines: Vec<StreamLine>| {
+            lines
+                .iter()
+                .map(|line| serde_json::to_string(line).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec![
+                r#"{"type":"item","data":{"id":1}}"#,
+                r#"{"type":"item","data":{"id":2}}"#,
+            ],
+            to_string(
+                StreamLine::from_data(&vec![
+                    serde_json::json!({"id": 1}),
+                    serde_json::json!({"id": 2}),
+                ])
+                .unwrap()
+            )
+        );
+        assert_eq!(
+            vec![r#"{"type":"response","data":"Message successfully sent"}"#],
+            to_string(StreamLine::from_data(&"Message successfully sent").unwrap())
+        );
+        assert_eq!(
+            r#"{"type":"error","code":"NotFound","error":"cannot find message"}"#,
+            serde_json::to_string(&StreamLine::Error {
+                code: ExitCode::NotFound,
+                error: String::from("cannot find message"),
+            })
+            .unwrap()
+        );
+    }
+}
@@ -1,5 +1,10 @@
-use anyhow::Result;
-use std::process::Command;
+use anyhow::{anyhow, Context, Result};
+use std::{
+    io::{self, Write},
+    process::{Command, Stdio},
+};
+
+use crate::output::ExitCode;
 
 /// TODO: move this in a more approriate place.
 pub fn run_cmd(cmd: &str) -> Result<String> {
@@ -11,3 +16,227 @@ pub fn run_cmd(cmd: &str) -> Result<String> {
 
     Ok(String::from_utf8(output.stdout)?)
 }
+
+/// Runs the given command with the given arguments appended, and fails if the command exits with
+/// a non-zero status. Unlike [`run_cmd`], the arguments are never interpreted by a shell, so they
+/// can safely come from messages: on Unix they are passed to `sh` as positional parameters, on
+/// Windows the command is split on whitespace and its program is spawned directly, `cmd /C`
+/// re-parsing `&`, `|` and `^` in its arguments.
+pub fn run_cmd_with_args(cmd: &str, args: &[&str]) -> Result<String> {
+    let output = if cfg!(target_os = "windows") {
+        let mut words = cmd.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| anyhow!("cannot run an empty command"))?;
+        Command::new(program).args(words).args(args).output()
+    } else {
+        Command::new("sh")
+            .arg("-c")
+            .arg(format!(r#"{} "$@""#, cmd))
+            .arg("sh")
+            .args(args)
+            .output()
+    }
+    .context(format!(r#"cannot run command "{}""#, cmd))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            r#"command "{}" failed ({}): {}"#,
+            cmd,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Opens the given HTTP(S) URL with the default application of the system. The URL is passed as
+/// a single argument and never goes through a shell.
+pub fn open_url(url: &str) -> Result<()> {
+    let scheme = url.split(':').next().unwrap_or_default().to_lowercase();
+    if scheme != "http" && scheme != "https" {
+        return Err(anyhow!(r#"cannot open URL "{}": not an HTTP(S) URL"#, url));
+    }
+
+    let status = if cfg!(target_os = "windows") {
+        Command::new("rundll32")
+            .args(&["url.dll,FileProtocolHandler", url])
+            .status()
+    } else if cfg!(target_os = "macos") {
+        Command::new("open").arg(url).status()
+    } else {
+        Command::new("xdg-open").arg(url).status()
+    }
+    .context(format!(r#"cannot open URL "{}""#, url))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!(r#"cannot open URL "{}": {}"#, url, status))
+    }
+}
+
+/// Runs the given command with its arguments, streaming the given input to its stdin. The output
+/// of the command is not captured.
+pub fn pipe_cmd(cmd: &[&str], input: &[u8]) -> Result<()> {
+    let (program, args) = cmd
+        .split_first()
+        .ok_or_else(|| anyhow!("cannot pipe to an empty command"))?;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .context(format!(r#"cannot run command "{}""#, program))?;
+
+    // A command may not read all of its input (eg. `head`), a broken pipe is not an error then.
+    if let Some(mut stdin) = child.stdin.take() {
+        match stdin.write_all(input) {
+            Err(err) if err.kind() != io::ErrorKind::BrokenPipe => {
+                return Err(err).context(format!(r#"cannot write to command "{}""#, program));
+            }
+            _ => (),
+        }
+    }
+
+    let status = child
+        .wait()
+        .context(format!(r#"cannot wait for command "{}""#, program))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!(r#"command "{}" failed: {}"#, program, status))
+    }
+}
+
+/// Runs the given picker command (like fzf) with its arguments, streaming the given lines to its
+/// stdin, and returns the picked lines. The command draws on the terminal itself. Aborting the
+/// pick (exit codes 1 and 130) gives no line.
+pub fn pick_lines(cmd: &[&str], lines: &[String]) -> Result<Vec<String>> {
+    let (program, args) = cmd
+        .split_first()
+        .ok_or_else(|| anyhow!("cannot pick with an empty command"))?;
+    let mut child = match Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        // The default picker is an external program, not shipped with himalaya
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(ExitCode::NotFound.err(format!(
+                r#"cannot find picker command "{}", install it or set the picker-cmd option"#,
+                program
+            )));
+        }
+        Err(err) => {
+            return Err(err).context(format!(r#"cannot run picker command "{}""#, program));
+        }
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        match stdin.write_all(lines.join("\n").as_bytes()) {
+            Err(err) if err.kind() != io::ErrorKind::BrokenPipe => {
+                return Err(err).context(format!(r#"cannot write to command "{}""#, program));
+            }
+            _ => (),
+        }
+    }
+
+    let output = child
+        .wait_with_output()
+        .context(format!(r#"cannot wait for command "{}""#, program))?;
+    match output.status.code() {
+        Some(0) => Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect()),
+        Some(1) | Some(130) => Ok(vec![]),
+        _ => Err(anyhow!(
+            r#"command "{}" failed: {}"#,
+            program,
+            output.status
+        )),
+    }
+}
+
+/// Formats the given size in bytes using decimal units, for example `26.3 MB`.
+pub fn format_size(size: usize) -> String {
+    match size {
+        size if size < 1_000 => format!("{} B", size),
+        size if size < 1_000_000 => format!("{:.1} kB", size as f64 / 1e3),
+        size if size < 1_000_000_000 => format!("{:.1} MB", size as f64 / 1e6),
+        size => format!("{:.1} GB", size as f64 / 1e9),
+    }
+}
+
+/// Parses the given size, in bytes or followed by a decimal unit like `500K`, `10M` or `1.5GB`.
+/// Units are case-insensitive, the same as [`format_size`].
+pub fn parse_size(size: &str) -> Result<usize> {
+    let size = size.trim();
+    let unit_pos = size
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(size.len());
+    let (num, unit) = size.split_at(unit_pos);
+    let factor = match unit.to_lowercase().as_str() {
+        "" | "b" => 1e0,
+        "k" | "kb" => 1e3,
+        "m" | "mb" => 1e6,
+        "g" | "gb" => 1e9,
+        _ => {
+            return Err(
+                ExitCode::Usage.err(format!(r#"cannot parse size "{}": unknown unit"#, size))
+            )
+        }
+    };
+    let num = num
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|num| *num >= 0.0)
+        .ok_or_else(|| {
+            ExitCode::Usage.err(format!(r#"cannot parse size "{}": not a number"#, size))
+        })?;
+    Ok((num * factor).round() as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_not_open_non_http_urls() {
+        assert!(open_url("file:///etc/passwd").is_err());
+        assert!(open_url("javascript:alert(1)").is_err());
+    }
+
+    #[test]
+    fn it_should_format_size() {
+        assert_eq!("512 B", format_size(512));
+        assert_eq!("1.5 kB", format_size(1_500));
+        assert_eq!("26.3 MB", format_size(26_300_000));
+        assert_eq!("2.0 GB", format_size(2_000_000_000));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_should_not_interpret_cmd_args() {
+        assert_eq!(
+            "$(echo injected)|a b",
+            run_cmd_with_args("printf '%s|%s'", &["$(echo injected)", "a b"]).unwrap()
+        );
+        assert!(run_cmd_with_args("false", &["arg"]).is_err());
+    }
+
+    #[test]
+    fn it_should_parse_size() {
+        assert_eq!(512, parse_size("512").unwrap());
+        assert_eq!(500_000, parse_size("500K").unwrap());
+        assert_eq!(10_000_000, parse_size("10mb").unwrap());
+        assert_eq!(1_500_000_000, parse_size("1.5 GB").unwrap());
+        assert!(parse_size("10T").is_err());
+        assert!(parse_size("-1M").is_err());
+        assert!(parse_size("M").is_err());
+    }
+}
@@ -1,11 +1,13 @@
 use anyhow::Result;
 use std::io;
-use termcolor::{self, StandardStream};
+use termcolor::{self, NoColor, StandardStream};
 
 pub trait WriteColor: io::Write + termcolor::WriteColor {}
 
 impl WriteColor for StandardStream {}
 
+impl<W: io::Write> WriteColor for NoColor<W> {}
+
 pub trait PrintTable {
     fn print_table(&self, writter: &mut dyn WriteColor, opts: PrintTableOpts) -> Result<()>;
 }
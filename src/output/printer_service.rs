@@ -1,10 +1,12 @@
 use anyhow::{Context, Error, Result};
 use atty::Stream;
 use serde::Serialize;
-use std::{convert::TryFrom, fmt::Debug};
+use std::{convert::TryFrom, fmt::Debug, io::Write};
 use termcolor::{ColorChoice, StandardStream};
 
-use crate::output::{OutputFmt, OutputJson, Print, PrintTable, PrintTableOpts, WriteColor};
+use crate::output::{
+    to_sexp, OutputFmt, OutputJson, Print, PrintTable, PrintTableOpts, StreamLine, WriteColor,
+};
 
 pub trait PrinterService {
     fn print<T: Debug + Print + Serialize>(&mut self, data: T) -> Result<()>;
@@ -13,12 +15,18 @@ pub trait PrinterService {
         data: T,
         opts: PrintTableOpts,
     ) -> Result<()>;
-    fn is_json(&self) -> bool;
+    /// Returns true if the output is meant for programs (JSON or S-expressions), so commands
+    /// skip prompts and notices.
+    fn is_structured(&self) -> bool;
+    /// Returns true if a user can answer prompts on the terminal, which is not the case of
+    /// commands run by the daemon or the server.
+    fn is_interactive(&self) -> bool;
 }
 
 pub struct StdoutPrinter {
     pub writter: Box<dyn WriteColor>,
     pub fmt: OutputFmt,
+    pub interactive: bool,
 }
 
 impl PrinterService for StdoutPrinter {
@@ -27,6 +35,9 @@ impl PrinterService for StdoutPrinter {
             OutputFmt::Plain => data.print(self.writter.as_mut()),
             OutputFmt::Json => serde_json::to_writer(self.writter.as_mut(), &OutputJson::new(data))
                 .context("cannot write JSON to writter"),
+            OutputFmt::JsonStream => write_stream(self.writter.as_mut(), &data),
+            OutputFmt::Sexp => write!(self.writter, "{}", to_sexp(&OutputJson::new(data))?)
+                .context("cannot write S-expression to writter"),
         }
     }
 
@@ -39,12 +50,37 @@ impl PrinterService for StdoutPrinter {
             OutputFmt::Plain => data.print_table(self.writter.as_mut(), opts),
             OutputFmt::Json => serde_json::to_writer(self.writter.as_mut(), &OutputJson::new(data))
                 .context("cannot write JSON to writter"),
+            OutputFmt::JsonStream => write_stream(self.writter.as_mut(), &data),
+            OutputFmt::Sexp => write!(self.writter, "{}", to_sexp(&OutputJson::new(data))?)
+                .context("cannot write S-expression to writter"),
         }
     }
 
-    fn is_json(&self) -> bool {
-        self.fmt == OutputFmt::Json
+    fn is_structured(&self) -> bool {
+        matches!(
+            self.fmt,
+            OutputFmt::Json | OutputFmt::JsonStream | OutputFmt::Sexp
+        )
     }
+
+    fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+}
+
+/// Writes the given data as JSON lines, flushed so readers get them at once.
+pub fn write_stream<T: Serialize>(writter: &mut dyn WriteColor, data: &T) -> Result<()> {
+    for line in StreamLine::from_data(data)? {
+        write_stream_line(writter, &line)?;
+    }
+    Ok(())
+}
+
+/// Writes the given JSON line.
+pub fn write_stream_line(writter: &mut dyn WriteColor, line: &StreamLine) -> Result<()> {
+    serde_json::to_writer(&mut *writter, line).context("cannot write JSON line to writter")?;
+    writeln!(writter).context("cannot write JSON line to writter")?;
+    writter.flush().context("cannot flush writter")
 }
 
 impl From<OutputFmt> for StdoutPrinter {
@@ -62,7 +98,12 @@ impl From<OutputFmt> for StdoutPrinter {
             ColorChoice::Auto
         });
         let writter = Box::new(writter);
-        Self { writter, fmt }
+        let interactive = atty::is(Stream::Stdin);
+        Self {
+            writter,
+            fmt,
+            interactive,
+        }
     }
 }
 
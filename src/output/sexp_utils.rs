@@ -0,0 +1,99 @@
+//! Module related to the S-expression output.
+//!
+//! This module converts the serialized data to S-expressions readable by Emacs Lisp: objects
+//! become property lists (`(:id 1 :subject "Hello")`), arrays become lists, and `null` and
+//! `false` become `nil`.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Writes the given symbol name, escaping the characters the Lisp reader gives a meaning to.
+fn write_symbol(name: &str, out: &mut String) {
+    for c in name.chars() {
+        if !c.is_alphanumeric() && !"-_+*/<>=!$%&~^.@".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+}
+
+/// Writes the given string, escaping backslashes and double quotes.
+fn write_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+}
+
+/// Writes the given value as an S-expression.
+fn write_value(value: &Value, out: &mut String) {
+    match value {
+        Value::Null | Value::Bool(false) => out.push_str("nil"),
+        Value::Bool(true) => out.push('t'),
+        Value::Number(n) => out.push_str(&n.to_string()),
+        Value::String(s) => write_string(s, out),
+        Value::Array(values) if values.is_empty() => out.push_str("nil"),
+        Value::Array(values) => {
+            out.push('(');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(' ');
+                }
+                write_value(value, out);
+            }
+            out.push(')');
+        }
+        Value::Object(entries) if entries.is_empty() => out.push_str("nil"),
+        Value::Object(entries) => {
+            out.push('(');
+            for (i, (key, value)) in entries.iter().enumerate() {
+                if i > 0 {
+                    out.push(' ');
+                }
+                out.push(':');
+                write_symbol(key, out);
+                out.push(' ');
+                write_value(value, out);
+            }
+            out.push(')');
+        }
+    }
+}
+
+/// Serializes the given data as an S-expression.
+pub fn to_sexp<T: Serialize>(data: &T) -> Result<String> {
+    let value = serde_json::to_value(data).context("cannot serialize data")?;
+    let mut out = String::new();
+    write_value(&value, &mut out);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn it_should_serialize_sexp() {
+        let data = json!({
+            "response": [
+                {"id": 1, "subject": "Say \"hi\"", "flags": [], "seen": true, "date": null},
+                {"id": 2, "subject": "C:\\", "flags": ["Seen"], "seen": false, "key with space": 1},
+            ]
+        });
+
+        assert_eq!(
+            concat!(
+                r#"(:response ((:date nil :flags nil :id 1 :seen t :subject "Say \"hi\"")"#,
+                r#" (:flags ("Seen") :id 2 :key\ with\ space 1 :seen nil :subject "C:\\")))"#,
+            ),
+            to_sexp(&data).unwrap()
+        );
+    }
+}
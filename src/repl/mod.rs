@@ -0,0 +1,10 @@
+//! Module related to the REPL.
+//!
+//! The REPL reads commands on stdin, one per line, and runs them with the IMAP session kept open
+//! between them. Editor plugins drive it instead of spawning a process per action.
+
+pub mod repl_arg;
+pub mod repl_handler;
+
+pub mod repl_entity;
+pub use repl_entity::*;
@@ -0,0 +1,83 @@
+//! Module related to REPL CLI.
+//!
+//! This module provides subcommands and a command matcher related to the REPL.
+
+use anyhow::Result;
+use clap::{self, App, ArgMatches, SubCommand};
+use log::debug;
+
+/// Subcommands that cannot run in the REPL: the ones opening the editor, which needs the
+/// terminal, and the ones never returning. `attachments cat` is not available either.
+const BLOCKING_CMDS: &[&str] = &[
+    "daemon", "edit", "forward", "notify", "repl", "reply", "serve", "watch", "write",
+];
+
+/// REPL commands.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Cmd {
+    /// Start the REPL for the selected account.
+    Start,
+}
+
+/// REPL command matcher.
+pub fn matches(m: &ArgMatches) -> Result<Option<Cmd>> {
+    if m.subcommand_matches("repl").is_some() {
+        debug!("repl command matched");
+        return Ok(Some(Cmd::Start));
+    }
+
+    Ok(None)
+}
+
+/// Checks if the matched command can run in the REPL.
+pub fn is_allowed(m: &ArgMatches) -> bool {
+    // Attachments are written as is to stdout, which would mix binary with the REPL output.
+    let is_cat = m
+        .subcommand_matches("attachments")
+        .and_then(|m| m.subcommand_name())
+        == Some("cat");
+    !is_cat
+        && m.subcommand_name()
+            .map(|name| !BLOCKING_CMDS.contains(&name))
+            .unwrap_or(true)
+}
+
+/// REPL subcommands.
+pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
+    vec![SubCommand::with_name("repl")
+        .about("Reads commands on stdin, one per line")
+        .long_about("Reads commands on stdin, one per line, like `list --page 2` or `read 42`, and runs them with the IMAP session kept open. The output options of the REPL apply to the commands not setting their own. With `--output json --stream`, the output of each command ends with a `{\"type\":\"done\"}` line, or a `{\"type\":\"error\",\"code\":…,\"error\":…}` line when it fails. Commands opening the editor are not available. Type `quit` or close stdin to exit.")]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_match_cmds() {
+        let arg = clap::App::new("himalaya")
+            .subcommands(subcmds())
+            .subcommand(SubCommand::with_name("list"))
+            .subcommand(SubCommand::with_name("write"))
+            .get_matches_from(&["himalaya", "repl"]);
+        assert_eq!(Some(Cmd::Start), matches(&arg).unwrap());
+        assert!(!is_allowed(&arg));
+
+        let arg = clap::App::new("himalaya")
+            .subcommand(SubCommand::with_name("list"))
+            .get_matches_from(&["himalaya", "list"]);
+        assert!(is_allowed(&arg));
+
+        let attachments = || {
+            clap::App::new("himalaya").subcommand(
+                SubCommand::with_name("attachments")
+                    .subcommand(SubCommand::with_name("cat"))
+                    .subcommand(SubCommand::with_name("list")),
+            )
+        };
+        let arg = attachments().get_matches_from(&["himalaya", "attachments", "cat"]);
+        assert!(!is_allowed(&arg));
+        let arg = attachments().get_matches_from(&["himalaya", "attachments"]);
+        assert!(is_allowed(&arg));
+    }
+}
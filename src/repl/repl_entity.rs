@@ -0,0 +1,140 @@
+//! REPL entity module.
+//!
+//! This module contains the parsing of the lines read by the REPL.
+
+use anyhow::Result;
+
+use crate::output::{ExitCode, OutputFmt};
+
+/// Represents a line read by the REPL.
+#[derive(Debug, PartialEq)]
+pub enum ReplLine {
+    /// A blank line or a comment, starting with `#`.
+    Empty,
+    /// The `quit` or `exit` command.
+    Quit,
+    /// Any other command, split into words.
+    Cmd(Vec<String>),
+}
+
+impl ReplLine {
+    /// Parses the given line. Words are split like a shell does: on whitespaces, except within
+    /// single or double quotes, a backslash escaping the following character.
+    pub fn parse(line: &str) -> Result<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(Self::Empty);
+        }
+        if line == "quit" || line == "exit" {
+            return Ok(Self::Quit);
+        }
+
+        let mut words = vec![];
+        let mut word: Option<String> = None;
+        let mut quote: Option<char> = None;
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some('"'), '\\') | (None, '\\') => {
+                    let c = chars.next().ok_or_else(|| {
+                        ExitCode::Usage.err(format!(
+                            r#"cannot parse line "{}": trailing backslash"#,
+                            line
+                        ))
+                    })?;
+                    word.get_or_insert_with(String::new).push(c);
+                }
+                (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+                (None, '\'') | (None, '"') => {
+                    quote = Some(c);
+                    word.get_or_insert_with(String::new);
+                }
+                (None, c) if c.is_whitespace() => words.extend(word.take()),
+                (None, c) => word.get_or_insert_with(String::new).push(c),
+            }
+        }
+        if let Some(q) = quote {
+            return Err(ExitCode::Usage.err(format!(
+                r#"cannot parse line "{}": unclosed quote {}"#,
+                line, q
+            )));
+        }
+        words.extend(word);
+
+        Ok(Self::Cmd(words))
+    }
+}
+
+/// Builds the raw arguments of the given command words, prefixed by the output arguments of the
+/// REPL unless the command sets its own output format.
+pub fn repl_args(words: &[String], fmt: &OutputFmt) -> Vec<String> {
+    let has_output = words
+        .iter()
+        .any(|word| word == "-o" || word == "--output" || word.starts_with("--output="));
+    let output_args: &[&str] = match fmt {
+        _ if has_output => &[],
+        OutputFmt::Plain => &[],
+        OutputFmt::Json => &["--output", "json"],
+        OutputFmt::JsonStream => &["--output", "json", "--stream"],
+        OutputFmt::Sexp => &["--output", "sexp"],
+    };
+    std::iter::once(env!("CARGO_PKG_NAME"))
+        .chain(output_args.iter().copied())
+        .map(String::from)
+        .chain(words.iter().cloned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmd(words: &[&str]) -> ReplLine {
+        ReplLine::Cmd(words.iter().map(|word| word.to_string()).collect())
+    }
+
+    #[test]
+    fn it_should_parse_lines() {
+        assert_eq!(ReplLine::Empty, ReplLine::parse("  ").unwrap());
+        assert_eq!(ReplLine::Empty, ReplLine::parse("# list").unwrap());
+        assert_eq!(ReplLine::Quit, ReplLine::parse("quit\n").unwrap());
+        assert_eq!(
+            cmd(&["list", "--page", "2"]),
+            ReplLine::parse("list  --page 2\n").unwrap()
+        );
+        assert_eq!(
+            cmd(&["-m", "Lists/Rust Users", "search", "subject", "it's", ""]),
+            ReplLine::parse(r#"-m "Lists/Rust Users" search subject it\'s """#).unwrap()
+        );
+        assert_eq!(
+            cmd(&["send", "--", "To: a@b\r\n"]),
+            ReplLine::parse("send -- 'To: a@b\r\n'").unwrap()
+        );
+        assert!(ReplLine::parse(r#"search "subject"#).is_err());
+        assert!(ReplLine::parse(r"search \").is_err());
+    }
+
+    #[test]
+    fn it_should_prefix_output_args() {
+        let words = vec![String::from("list")];
+        assert_eq!(
+            vec!["himalaya", "--output", "json", "--stream", "list"],
+            repl_args(&words, &OutputFmt::JsonStream)
+        );
+        assert_eq!(
+            vec!["himalaya", "list"],
+            repl_args(&words, &OutputFmt::Plain)
+        );
+
+        let words = vec![
+            String::from("-o"),
+            String::from("plain"),
+            String::from("list"),
+        ];
+        assert_eq!(
+            vec!["himalaya", "-o", "plain", "list"],
+            repl_args(&words, &OutputFmt::Json)
+        );
+    }
+}
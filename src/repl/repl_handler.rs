@@ -0,0 +1,85 @@
+//! Module related to REPL handling.
+//!
+//! This module gathers the REPL loop.
+
+use anyhow::{Context, Result};
+use atty::Stream;
+use log::{debug, trace};
+use std::io::{self, Write};
+
+use crate::{
+    domain::SharedImapSession,
+    output::{write_stream_line, ExitCode, OutputFmt, StdoutPrinter, StreamLine, WriteColor},
+    repl::{repl_args, ReplLine},
+    ui::i18n::tr,
+};
+
+/// Represents the prompt shown when stdin is a terminal.
+const PROMPT: &str = "himalaya> ";
+
+/// Starts the REPL, the commands being printed in the given output format. Each command is
+/// executed by the `run` closure, which receives the raw arguments of the command, the writter
+/// collecting its output and the IMAP session shared between commands. Failing commands do not
+/// stop the REPL.
+pub fn start<F>(fmt: OutputFmt, mut run: F) -> Result<()>
+where
+    F: FnMut(&[String], Box<dyn WriteColor>, SharedImapSession) -> Result<()>,
+{
+    let sess = SharedImapSession::default();
+    let interactive = atty::is(Stream::Stdin) && fmt == OutputFmt::Plain;
+
+    loop {
+        if interactive {
+            print!("{}", PROMPT);
+            io::stdout().flush().context("cannot flush stdout")?;
+        }
+        // Stdin is not locked between lines, commands asking for a confirmation read it too.
+        let mut line = String::new();
+        if io::stdin()
+            .read_line(&mut line)
+            .context("cannot read command from stdin")?
+            == 0
+        {
+            break;
+        }
+        trace!("repl line: {:?}", line);
+
+        let words = match ReplLine::parse(&line) {
+            Ok(ReplLine::Empty) => continue,
+            Ok(ReplLine::Quit) => break,
+            Ok(ReplLine::Cmd(words)) => Ok(words),
+            Err(err) => Err(err),
+        };
+        let res = words.and_then(|words| {
+            let args = repl_args(&words, &fmt);
+            debug!("repl args: {:?}", args);
+            let writter = StdoutPrinter::from(OutputFmt::Plain).writter;
+            let res = run(&args, writter, sess.clone());
+            if res.is_err() {
+                // Same as the daemon: the session may be broken, a new one is safer.
+                debug!("reset IMAP session");
+                sess.replace(None);
+            }
+            res
+        });
+
+        let mut writter = StdoutPrinter::from(OutputFmt::Plain).writter;
+        match (res, &fmt) {
+            (Ok(()), OutputFmt::JsonStream) => {
+                write_stream_line(writter.as_mut(), &StreamLine::Done)?;
+            }
+            (Err(err), OutputFmt::JsonStream) => {
+                let line = StreamLine::Error {
+                    code: ExitCode::from(&err),
+                    error: format!("{:#}", err),
+                };
+                write_stream_line(writter.as_mut(), &line)?;
+            }
+            // Outputs have no trailing newline.
+            (Ok(()), _) => writeln!(writter).context("cannot write to stdout")?,
+            (Err(err), _) => eprintln!("{}: {:?}", tr("error"), err),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,10 @@
+//! Module related to the HTTP server.
+//!
+//! The server exposes the commands of the selected account as a JSON API, for web front ends and
+//! mobile shortcuts. Like the daemon, it keeps the IMAP session open between requests.
+
+pub mod server_arg;
+pub mod server_handler;
+
+pub mod server_entity;
+pub use server_entity::*;
@@ -0,0 +1,59 @@
+//! Module related to server CLI.
+//!
+//! This module provides subcommands and a command matcher related to the HTTP server.
+
+use anyhow::Result;
+use clap::{self, App, Arg, ArgMatches, SubCommand};
+use log::{debug, trace};
+
+/// Represents the address the server listens on by default.
+const DEFAULT_ADDR: &str = "127.0.0.1:8080";
+
+/// Server commands.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Cmd<'a> {
+    /// Start the server for the selected account, on the given address.
+    Start(&'a str),
+}
+
+/// Server command matcher.
+pub fn matches<'a>(m: &'a ArgMatches) -> Result<Option<Cmd<'a>>> {
+    if let Some(m) = m.subcommand_matches("serve") {
+        debug!("serve command matched");
+        let addr = m.value_of("addr").unwrap_or(DEFAULT_ADDR);
+        trace!("address: {}", addr);
+        return Ok(Some(Cmd::Start(addr)));
+    }
+
+    Ok(None)
+}
+
+/// Server subcommands.
+pub fn subcmds<'a>() -> Vec<App<'a, 'a>> {
+    vec![SubCommand::with_name("serve")
+        .about("Starts a HTTP server exposing a JSON API")
+        .long_about("Starts a HTTP server exposing the mailboxes and the messages of the selected account as a JSON API: `GET /mailboxes`, `GET /mailboxes/<mailbox>/envelopes` (with the `page`, `size` and `query` parameters), `GET /mailboxes/<mailbox>/messages/<seq>` (with the `mime` parameter) and `POST /messages` (with a `{\"message\": <raw message>}` JSON body). Requests need an `Authorization: Bearer <token>` header, the token being taken from the HIMALAYA_SERVE_TOKEN environment variable or the serve-token config option, or generated and printed at start. Requests from other web origins are rejected.")
+        .arg(
+            Arg::with_name("addr")
+                .help("Address to listen on")
+                .long("addr")
+                .value_name("ADDR")
+                .default_value(DEFAULT_ADDR),
+        )]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_match_cmds() {
+        let app = || clap::App::new("himalaya").subcommands(subcmds());
+
+        let m = app().get_matches_from(&["himalaya", "serve"]);
+        assert_eq!(Some(Cmd::Start("127.0.0.1:8080")), matches(&m).unwrap());
+
+        let m = app().get_matches_from(&["himalaya", "serve", "--addr", "0.0.0.0:3000"]);
+        assert_eq!(Some(Cmd::Start("0.0.0.0:3000")), matches(&m).unwrap());
+    }
+}
@@ -0,0 +1,314 @@
+//! Server entity module.
+//!
+//! This module contains the routes of the JSON API, each one translated into the arguments of a
+//! command run with the JSON output.
+
+use anyhow::{Error, Result};
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use url::form_urlencoded;
+
+use crate::output::ExitCode;
+
+/// Represents the maximum size of the request bodies, in bytes.
+pub const MAX_BODY_SIZE: u64 = 50_000_000;
+
+/// Represents a request rejected before being served, along with its HTTP status code.
+#[derive(Debug)]
+pub struct Rejection {
+    pub code: u16,
+    pub reason: String,
+}
+
+impl Rejection {
+    pub fn new<S: Into<String>>(code: u16, reason: S) -> Self {
+        Self {
+            code,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+/// Represents the headers checked before serving a request.
+#[derive(Debug, Default)]
+pub struct RequestHeaders<'a> {
+    pub authorization: Option<&'a str>,
+    pub origin: Option<&'a str>,
+    pub content_type: Option<&'a str>,
+}
+
+/// Checks that the request can be served: it must come from no origin (like a script) or from the
+/// origin of the server, and carry the bearer token of the server. Bodies must be JSON, so
+/// cross-site requests from browsers always need a CORS preflight, which the server never
+/// answers.
+pub fn check_request(
+    method: &str,
+    headers: &RequestHeaders,
+    token: &str,
+    origin: &str,
+) -> Result<()> {
+    if let Some(req_origin) = headers.origin.filter(|req_origin| *req_origin != origin) {
+        return Err(Rejection::new(
+            403,
+            format!(r#"cannot serve requests from origin "{}""#, req_origin),
+        )
+        .into());
+    }
+
+    let req_token = headers
+        .authorization
+        .and_then(|auth| auth.trim().strip_prefix("Bearer "))
+        .map(str::trim);
+    if !req_token.map_or(false, |req_token| eq_secret(req_token, token)) {
+        return Err(Rejection::new(
+            401,
+            "cannot authenticate request: missing or invalid bearer token",
+        )
+        .into());
+    }
+
+    let is_json = headers
+        .content_type
+        .and_then(|content_type| content_type.split(';').next())
+        .map_or(false, |mime| {
+            mime.trim().eq_ignore_ascii_case("application/json")
+        });
+    if method != "GET" && !is_json {
+        return Err(Rejection::new(
+            415,
+            "cannot parse request body: expected application/json content type",
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Compares the given secrets in a time independent of their content.
+fn eq_secret(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Represents the body of the requests sending a message.
+#[derive(Debug, Deserialize)]
+struct SendBody {
+    /// The raw message to send.
+    message: String,
+}
+
+/// Represents a request to the API.
+#[derive(Debug, PartialEq)]
+pub struct ServerRequest {
+    /// The raw arguments of the command serving the request, including the binary name.
+    pub args: Vec<String>,
+}
+
+impl ServerRequest {
+    /// Builds the request from the method, the URL (path and query) and the body of a HTTP
+    /// request.
+    pub fn parse(method: &str, url: &str, body: String) -> Result<Self> {
+        let (path, query) = match url.find('?') {
+            Some(i) => (&url[..i], &url[i + 1..]),
+            None => (url, ""),
+        };
+        let segments = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| percent_decode_str(segment).decode_utf8_lossy().into_owned())
+            .collect::<Vec<_>>();
+        let segments = segments.iter().map(String::as_str).collect::<Vec<_>>();
+        let params: Vec<(String, String)> = form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect();
+        let param = |key: &str| {
+            params
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, val)| val.as_str())
+        };
+
+        let send_body: SendBody;
+        let mut args = vec![env!("CARGO_PKG_NAME"), "--output", "json"];
+        match (method, segments.as_slice()) {
+            ("GET", ["mailboxes"]) => args.push("mailboxes"),
+            ("GET", ["mailboxes", mbox, "envelopes"]) => {
+                args.extend(&["--mailbox", *mbox]);
+                let query = param("query").filter(|query| !query.trim().is_empty());
+                args.push(if query.is_some() { "search" } else { "list" });
+                if let Some(page) = param("page") {
+                    args.extend(&["--page", parse_number(page, "page")?]);
+                }
+                if let Some(size) = param("size") {
+                    args.extend(&["--size", parse_number(size, "size")?]);
+                }
+                if let Some(query) = query {
+                    args.push("--");
+                    args.extend(query.split_whitespace());
+                }
+            }
+            ("GET", ["mailboxes", mbox, "messages", seq]) => {
+                args.extend(&["--mailbox", *mbox, "read", parse_number(seq, "seq")?]);
+                if let Some(mime) = param("mime") {
+                    args.extend(&["--mime-type", mime]);
+                }
+            }
+            ("POST", ["messages"]) => {
+                send_body = serde_json::from_str(&body).map_err(|err| {
+                    ExitCode::Usage.err(format!("cannot parse request body: {}", err))
+                })?;
+                args.extend(&["send", "--", send_body.message.as_str()]);
+            }
+            _ => {
+                return Err(
+                    ExitCode::NotFound.err(format!(r#"cannot find route "{} {}""#, method, path))
+                )
+            }
+        }
+
+        Ok(Self {
+            args: args.into_iter().map(String::from).collect(),
+        })
+    }
+}
+
+/// Checks that the given parameter is a number.
+fn parse_number<'a>(val: &'a str, name: &str) -> Result<&'a str> {
+    match val.parse::<u32>() {
+        Ok(_) => Ok(val),
+        Err(_) => {
+            Err(ExitCode::Usage.err(format!(r#"cannot parse {} "{}": not a number"#, name, val)))
+        }
+    }
+}
+
+/// Represents the body of the responses of failed requests.
+#[derive(Debug, Serialize)]
+pub struct ServerError {
+    pub error: String,
+}
+
+impl From<&Error> for ServerError {
+    fn from(err: &Error) -> Self {
+        Self {
+            error: format!("{:#}", err),
+        }
+    }
+}
+
+/// Returns the HTTP status code matching the given error.
+pub fn status_code(err: &Error) -> u16 {
+    if let Some(rejection) = err.downcast_ref::<Rejection>() {
+        return rejection.code;
+    }
+    match ExitCode::from(err) {
+        ExitCode::Usage | ExitCode::Parse => 400,
+        ExitCode::NotFound => 404,
+        ExitCode::Auth | ExitCode::Network => 502,
+        _ => 500,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(method: &str, url: &str, body: &str) -> Vec<String> {
+        let req = ServerRequest::parse(method, url, body.to_owned()).unwrap();
+        req.args.into_iter().skip(3).collect()
+    }
+
+    #[test]
+    fn it_should_route_requests() {
+        assert_eq!(vec!["mailboxes"], args("GET", "/mailboxes", ""));
+        assert_eq!(
+            vec!["--mailbox", "INBOX", "list", "--page", "2", "--size", "20"],
+            args("GET", "/mailboxes/INBOX/envelopes?page=2&size=20", "")
+        );
+        assert_eq!(
+            vec!["--mailbox", "Lists/Rust", "search", "--", "from", "alice"],
+            args(
+                "GET",
+                "/mailboxes/Lists%2FRust/envelopes?query=from+alice",
+                ""
+            )
+        );
+        assert_eq!(
+            vec!["--mailbox", "INBOX", "read", "42", "--mime-type", "html"],
+            args("GET", "/mailboxes/INBOX/messages/42?mime=html", "")
+        );
+        assert_eq!(
+            vec!["send", "--", "To: you@localhost\r\n\r\nHi"],
+            args(
+                "POST",
+                "/messages",
+                r#"{"message":"To: you@localhost\r\n\r\nHi"}"#
+            )
+        );
+    }
+
+    #[test]
+    fn it_should_check_requests() {
+        let origin = "http://127.0.0.1:8080";
+        let check = |method, authorization, req_origin, content_type| {
+            let headers = RequestHeaders {
+                authorization,
+                origin: req_origin,
+                content_type,
+            };
+            check_request(method, &headers, "secret", origin).map_err(|err| status_code(&err))
+        };
+
+        assert!(check("GET", Some("Bearer secret"), None, None).is_ok());
+        assert!(check("GET", Some("Bearer secret"), Some(origin), None).is_ok());
+        assert_eq!(Err(401), check("GET", None, None, None));
+        assert_eq!(Err(401), check("GET", Some("Bearer secre"), None, None));
+        assert_eq!(Err(401), check("GET", Some("Basic secret"), None, None));
+        assert_eq!(
+            Err(403),
+            check(
+                "GET",
+                Some("Bearer secret"),
+                Some("https://evil.example"),
+                None
+            )
+        );
+        assert_eq!(
+            Err(415),
+            check("POST", Some("Bearer secret"), None, Some("text/plain"))
+        );
+        assert!(check(
+            "POST",
+            Some("Bearer secret"),
+            None,
+            Some("application/json; charset=utf-8")
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn it_should_reject_invalid_requests() {
+        let err = ServerRequest::parse("GET", "/unknown", String::new()).unwrap_err();
+        assert_eq!(404, status_code(&err));
+        let err = ServerRequest::parse("DELETE", "/mailboxes", String::new()).unwrap_err();
+        assert_eq!(404, status_code(&err));
+        let err = ServerRequest::parse("GET", "/mailboxes/INBOX/messages/1:4", String::new())
+            .unwrap_err();
+        assert_eq!(400, status_code(&err));
+        let err = ServerRequest::parse("POST", "/messages", String::from("To: you@localhost"))
+            .unwrap_err();
+        assert_eq!(400, status_code(&err));
+    }
+}
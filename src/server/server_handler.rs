@@ -0,0 +1,149 @@
+//! Module related to server handling.
+//!
+//! This module gathers the HTTP server loop.
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, trace, warn};
+use std::{env, io::Read};
+use termcolor::NoColor;
+use tiny_http::{Header, Request, Response, Server};
+use uuid::Uuid;
+
+use crate::{
+    config::Account,
+    daemon::DaemonBuf,
+    domain::SharedImapSession,
+    output::WriteColor,
+    server::{
+        check_request, status_code, Rejection, RequestHeaders, ServerError, ServerRequest,
+        MAX_BODY_SIZE,
+    },
+};
+
+/// Represents the environment variable holding the bearer token of the server.
+const TOKEN_ENV_VAR: &str = "HIMALAYA_SERVE_TOKEN";
+
+/// Starts the server for the given account on the given address. Each request is translated into
+/// a command executed by the `run` closure, which receives the raw arguments of the command, the
+/// writter collecting its JSON output and the IMAP session shared between requests. Requests need
+/// the bearer token taken from the environment, from the config, or generated and printed at
+/// start.
+pub fn start<F>(addr: &str, token: Option<&str>, account: &Account, mut run: F) -> Result<()>
+where
+    F: FnMut(&[String], Box<dyn WriteColor>, SharedImapSession) -> Result<()>,
+{
+    let token = match env::var(TOKEN_ENV_VAR)
+        .ok()
+        .or_else(|| token.map(String::from))
+    {
+        Some(token) if !token.trim().is_empty() => token.trim().to_owned(),
+        _ => {
+            let token = Uuid::new_v4().to_simple().to_string();
+            eprintln!("Bearer token: {}", token);
+            token
+        }
+    };
+
+    let server = Server::http(addr).map_err(|err| anyhow!("cannot listen on {}: {}", addr, err))?;
+    let origin = format!("http://{}", server.server_addr());
+    info!(r#"serving account "{}" on {}"#, account.name, origin);
+    let sess = SharedImapSession::default();
+
+    for req in server.incoming_requests() {
+        if let Err(err) = serve(req, &token, &origin, &mut run, &sess) {
+            warn!("cannot serve request: {:?}", err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the value of the given header of the request.
+fn header<'a>(req: &'a Request, name: &str) -> Option<&'a str> {
+    req.headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str())
+}
+
+/// Reads the body of the request, up to the maximum size.
+fn read_body(req: &mut Request) -> Result<String> {
+    let mut body = String::new();
+    req.as_reader()
+        .take(MAX_BODY_SIZE + 1)
+        .read_to_string(&mut body)
+        .context("cannot read request body")?;
+    if body.len() as u64 > MAX_BODY_SIZE {
+        return Err(Rejection::new(
+            413,
+            format!(
+                "cannot read request body: larger than {} bytes",
+                MAX_BODY_SIZE
+            ),
+        )
+        .into());
+    }
+    Ok(body)
+}
+
+/// Serves a single request.
+fn serve<F>(
+    mut req: Request,
+    token: &str,
+    origin: &str,
+    run: &mut F,
+    sess: &SharedImapSession,
+) -> Result<()>
+where
+    F: FnMut(&[String], Box<dyn WriteColor>, SharedImapSession) -> Result<()>,
+{
+    let method = req.method().to_string();
+    debug!("request: {} {}", method, req.url());
+    let headers = RequestHeaders {
+        authorization: header(&req, "Authorization"),
+        origin: header(&req, "Origin"),
+        content_type: header(&req, "Content-Type"),
+    };
+    let checked = check_request(&method, &headers, token, origin);
+    // The body is only read once the request is known to be legit.
+    let body = checked.and_then(|()| read_body(&mut req));
+
+    let buf = DaemonBuf::default();
+    let res = body
+        .and_then(|body| ServerRequest::parse(&method, req.url(), body))
+        .and_then(|server_req| {
+            trace!("request args: {:?}", server_req.args);
+            let res = run(
+                &server_req.args,
+                Box::new(NoColor::new(buf.clone())),
+                sess.clone(),
+            );
+            if res.is_err() {
+                // Same as the daemon: the session may be broken, a new one is safer.
+                debug!("reset IMAP session");
+                sess.replace(None);
+            }
+            res
+        });
+
+    let (code, body) = match res {
+        Ok(()) => (200, buf.to_string_lossy()),
+        Err(ref err) => {
+            debug!("request failed: {:?}", err);
+            let body =
+                serde_json::to_string(&ServerError::from(err)).context("cannot serialize error")?;
+            (status_code(err), body)
+        }
+    };
+    let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+        .map_err(|_| anyhow!("cannot build content type header"))?;
+    let mut res = Response::from_string(body)
+        .with_status_code(code)
+        .with_header(content_type);
+    if code == 401 {
+        let authenticate = Header::from_bytes(&b"WWW-Authenticate"[..], &b"Bearer"[..])
+            .map_err(|_| anyhow!("cannot build authenticate header"))?;
+        res = res.with_header(authenticate);
+    }
+    req.respond(res).context("cannot write response")
+}
@@ -2,6 +2,8 @@ use anyhow::{anyhow, Context, Result};
 use log::{debug, error};
 use std::io::{self, Write};
 
+use crate::ui::i18n::{tr, tr_args};
+
 pub enum PreEditChoice {
     Edit,
     Discard,
@@ -9,8 +11,8 @@ pub enum PreEditChoice {
 }
 
 pub fn pre_edit() -> Result<PreEditChoice> {
-    println!("A draft was found:");
-    print!("(e)dit, (d)iscard or (q)uit? ");
+    println!("{}", tr("draft-found"));
+    print!("{} ", tr("pre-edit-choice"));
     io::stdout().flush().context("cannot flush stdout")?;
 
     let mut buf = String::new();
@@ -32,12 +34,13 @@ pub fn pre_edit() -> Result<PreEditChoice> {
             Ok(PreEditChoice::Quit)
         }
         Some(choice) => {
-            error!(r#"invalid choice "{}""#, choice);
-            Err(anyhow!(r#"invalid choice "{}""#, choice))
+            let err = tr_args("invalid-choice", &[("choice", &choice.to_string())]);
+            error!("{}", err);
+            Err(anyhow!(err))
         }
         None => {
-            error!("empty choice");
-            Err(anyhow!("empty choice"))
+            error!("{}", tr("empty-choice"));
+            Err(anyhow!(tr("empty-choice")))
         }
     }
 }
@@ -51,7 +54,7 @@ pub enum PostEditChoice {
 }
 
 pub fn post_edit() -> Result<PostEditChoice> {
-    print!("(s)end, (e)dit, (l)ocal/(r)emote draft or (d)iscard? ");
+    print!("{} ", tr("post-edit-choice"));
     io::stdout().flush().context("cannot flush stdout")?;
 
     let mut buf = String::new();
@@ -81,12 +84,74 @@ pub fn post_edit() -> Result<PostEditChoice> {
             Ok(PostEditChoice::Discard)
         }
         Some(choice) => {
-            error!(r#"invalid choice "{}""#, choice);
-            Err(anyhow!(r#"invalid choice "{}""#, choice))
+            let err = tr_args("invalid-choice", &[("choice", &choice.to_string())]);
+            error!("{}", err);
+            Err(anyhow!(err))
+        }
+        None => {
+            error!("{}", tr("empty-choice"));
+            Err(anyhow!(tr("empty-choice")))
+        }
+    }
+}
+
+pub fn send_mdn(to: &str) -> Result<bool> {
+    println!("{}", tr_args("mdn-request", &[("to", to)]));
+    print!("{} ", tr("mdn-choice"));
+    io::stdout().flush().context("cannot flush stdout")?;
+
+    let mut buf = String::new();
+    io::stdin()
+        .read_line(&mut buf)
+        .context("cannot read stdin")?;
+
+    match buf.bytes().next().map(|bytes| bytes as char) {
+        Some('s') => {
+            debug!("send read receipt choice matched");
+            Ok(true)
+        }
+        Some('i') => {
+            debug!("ignore read receipt choice matched");
+            Ok(false)
+        }
+        Some(choice) => {
+            let err = tr_args("invalid-choice", &[("choice", &choice.to_string())]);
+            error!("{}", err);
+            Err(anyhow!(err))
+        }
+        None => {
+            error!("{}", tr("empty-choice"));
+            Err(anyhow!(tr("empty-choice")))
+        }
+    }
+}
+
+pub fn confirm(question: &str) -> Result<bool> {
+    print!("{} {} ", question, tr("confirm-choice"));
+    io::stdout().flush().context("cannot flush stdout")?;
+
+    let mut buf = String::new();
+    io::stdin()
+        .read_line(&mut buf)
+        .context("cannot read stdin")?;
+
+    match buf.bytes().next().map(|bytes| bytes as char) {
+        Some('y') => {
+            debug!("yes choice matched");
+            Ok(true)
+        }
+        Some('n') => {
+            debug!("no choice matched");
+            Ok(false)
+        }
+        Some(choice) => {
+            let err = tr_args("invalid-choice", &[("choice", &choice.to_string())]);
+            error!("{}", err);
+            Err(anyhow!(err))
         }
         None => {
-            error!("empty choice");
-            Err(anyhow!("empty choice"))
+            error!("{}", tr("empty-choice"));
+            Err(anyhow!(tr("empty-choice")))
         }
     }
 }
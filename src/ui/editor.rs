@@ -1,4 +1,4 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use log::debug;
 use std::{env, fs, process::Command};
 
@@ -11,10 +11,23 @@ pub fn open_with_tpl(tpl: String) -> Result<String> {
     fs::write(&path, tpl.as_bytes()).context(format!("cannot write local draft at {:?}", path))?;
 
     debug!("open editor");
-    Command::new(env::var("EDITOR").context(r#"cannot find "$EDITOR" env var"#)?)
-        .arg(&path)
-        .status()
-        .context("cannot launch editor")?;
+    // The editor may come with quoted arguments, like `code --wait`, so it is run by the shell
+    // while the draft path is passed as a positional parameter
+    let editor = editor()?;
+    if cfg!(target_os = "windows") {
+        Command::new("cmd")
+            .args(&["/C", &editor])
+            .arg(&path)
+            .status()
+    } else {
+        Command::new("sh")
+            .arg("-c")
+            .arg(format!(r#"{} "$@""#, editor))
+            .arg("sh")
+            .arg(&path)
+            .status()
+    }
+    .context(format!(r#"cannot launch editor "{}""#, editor))?;
 
     debug!("read draft");
     let content =
@@ -29,3 +42,15 @@ pub fn open_with_draft() -> Result<String> {
         fs::read_to_string(&path).context(format!("cannot read local draft at {:?}", path))?;
     open_with_tpl(tpl)
 }
+
+/// Returns the editor of the user, Notepad being the default one on Windows.
+fn editor() -> Result<String> {
+    match env::var("EDITOR")
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+    {
+        Some(editor) => Ok(editor),
+        None if cfg!(target_os = "windows") => Ok(String::from("notepad")),
+        None => Err(anyhow!(r#"cannot find "$EDITOR" env var"#)),
+    }
+}
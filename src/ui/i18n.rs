@@ -0,0 +1,147 @@
+//! Module related to the localization of the user interface.
+//!
+//! This module gathers the translated prompts, messages and errors, written with [Fluent] in the
+//! `locales` directory. The locale is taken from the `LC_ALL`, `LC_MESSAGES` and `LANG`
+//! environment variables, English being the fallback of missing locales and messages.
+//!
+//! [Fluent]: https://projectfluent.org
+
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource};
+use log::warn;
+use once_cell::sync::Lazy;
+use std::env;
+use unic_langid::LanguageIdentifier;
+
+/// Represents the translations, by language.
+const LOCALES: &[(&str, &str)] = &[
+    ("en", include_str!("locales/en.ftl")),
+    ("fr", include_str!("locales/fr.ftl")),
+];
+
+/// Represents the locale used when the one of the user is not supported.
+const DEFAULT_LOCALE: &str = "en";
+
+/// Represents the bundles of the supported languages, parsed once on first use.
+static BUNDLES: Lazy<Vec<(&str, FluentBundle<FluentResource>)>> = Lazy::new(|| {
+    LOCALES
+        .iter()
+        .filter_map(|(lang, _)| Some((*lang, bundle(lang)?)))
+        .collect()
+});
+
+/// Represents the language of the user, read once from the environment.
+static USER_LOCALE: Lazy<String> = Lazy::new(locale_from_env);
+
+/// Extracts the language of the given locale, like `fr` for `fr_FR.UTF-8`.
+fn parse_locale(locale: &str) -> String {
+    locale
+        .split(|c| c == '_' || c == '-' || c == '.' || c == '@')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Finds the language of the user from the environment.
+fn locale_from_env() -> String {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|locale| !locale.is_empty())
+        .map(|locale| parse_locale(&locale))
+        .unwrap_or_else(|| DEFAULT_LOCALE.to_owned())
+}
+
+/// Builds the bundle of the given language, if supported.
+fn bundle(lang: &str) -> Option<FluentBundle<FluentResource>> {
+    let (lang, src) = LOCALES.iter().find(|(name, _)| *name == lang)?;
+    let langid: LanguageIdentifier = lang.parse().ok()?;
+    let res = FluentResource::try_new(src.to_string())
+        .map_err(|(_, errs)| warn!("cannot parse {} translations: {:?}", lang, errs))
+        .ok()?;
+    let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+    // Isolation marks around arguments are meant for bidirectional texts, terminals show them
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(res)
+        .map_err(|errs| warn!("cannot load {} translations: {:?}", lang, errs))
+        .ok()?;
+    Some(bundle)
+}
+
+/// Formats the given message of the given bundle, if it exists.
+fn format(bundle: &FluentBundle<FluentResource>, id: &str, args: &FluentArgs) -> Option<String> {
+    let pattern = bundle.get_message(id)?.value()?;
+    let mut errs = vec![];
+    let msg = bundle.format_pattern(pattern, Some(args), &mut errs);
+    if !errs.is_empty() {
+        warn!(r#"cannot format message "{}": {:?}"#, id, errs);
+    }
+    Some(msg.into_owned())
+}
+
+/// Returns the cached bundle of the given language, if supported.
+fn cached_bundle(lang: &str) -> Option<&'static FluentBundle<FluentResource>> {
+    BUNDLES
+        .iter()
+        .find(|(name, _)| *name == lang)
+        .map(|(_, bundle)| bundle)
+}
+
+/// Translates the given message with the given arguments in the given language. Missing
+/// messages are taken from English, or replaced by their id as a last resort.
+pub fn tr_in(lang: &str, id: &str, args: &[(&str, &str)]) -> String {
+    let mut fluent_args = FluentArgs::new();
+    for (key, val) in args {
+        fluent_args.set(*key, val.to_string());
+    }
+    cached_bundle(lang)
+        .and_then(|bundle| format(bundle, id, &fluent_args))
+        .or_else(|| cached_bundle(DEFAULT_LOCALE).and_then(|b| format(b, id, &fluent_args)))
+        .unwrap_or_else(|| id.to_owned())
+}
+
+/// Translates the given message with the given arguments in the language of the user.
+pub fn tr_args(id: &str, args: &[(&str, &str)]) -> String {
+    tr_in(&USER_LOCALE, id, args)
+}
+
+/// Translates the given message in the language of the user.
+pub fn tr(id: &str) -> String {
+    tr_args(id, &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_should_parse_locales() {
+        assert_eq!("fr", parse_locale("fr_FR.UTF-8"));
+        assert_eq!("en", parse_locale("en-US"));
+        assert_eq!("c", parse_locale("C"));
+    }
+
+    #[test]
+    fn it_should_translate_messages() {
+        assert_eq!("Send this message?", tr_in("en", "confirm-send", &[]));
+        assert_eq!("Envoyer ce message ?", tr_in("fr", "confirm-send", &[]));
+        assert_eq!(
+            "3 messages will be expunged, continue?",
+            tr_in("en", "confirm-expunge", &[("count", "3")])
+        );
+        assert_eq!("Send this message?", tr_in("c", "confirm-send", &[]));
+        assert_eq!("unknown-message", tr_in("fr", "unknown-message", &[]));
+    }
+
+    #[test]
+    fn it_should_translate_all_messages() {
+        for (lang, _) in LOCALES {
+            let bundle = bundle(lang).unwrap();
+            let ids = LOCALES.iter().flat_map(|(_, src)| src.lines());
+            let ids = ids.filter(|line| !line.starts_with('#') && line.contains(" = "));
+            for id in ids.filter_map(|line| line.split(" = ").next()) {
+                assert!(bundle.has_message(id), "{} misses message {}", lang, id);
+            }
+        }
+    }
+}
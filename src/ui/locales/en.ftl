@@ -0,0 +1,59 @@
+# Prompts of the interactive choices. The letters in parentheses are the keys to type, they are
+# the same in all locales.
+draft-found = A draft was found:
+pre-edit-choice = (e)dit, (d)iscard or (q)uit?
+post-edit-choice = (s)end, (e)dit, (l)ocal/(r)emote draft or (d)iscard?
+mdn-request = The sender asks for a read receipt to { $to }.
+mdn-choice = (s)end or (i)gnore?
+confirm-choice = (y)es or (n)o?
+invalid-choice = invalid choice "{ $choice }"
+empty-choice = empty choice
+
+# Confirmations.
+confirm-expunge = { $count } messages will be expunged, continue?
+confirm-send = Send this message?
+
+# Messages of the editor flow.
+msg-sent = Message successfully sent
+msg-saved-locally = Message successfully saved locally
+msg-saved-drafts = Message successfully saved to Drafts
+
+# Messages of the commands.
+attachments-downloaded = { $count } attachment(s) successfully downloaded to { $dir }
+msg-bounced = Message { $seq } successfully bounced to { $to }
+msgs-copied = Message(s) { $seqs } successfully copied to folder "{ $mbox }"
+msgs-would-move = Message(s) { $seqs } would be moved to folder "{ $mbox }"
+msgs-moved = Message(s) { $seqs } successfully moved to folder "{ $mbox }"
+move-aborted = Move aborted
+msgs-would-delete = Message(s) { $seqs } would be deleted
+msgs-deleted = Message(s) { $seqs } successfully deleted
+deletion-aborted = Deletion aborted
+flags-added = Flag(s) "{ $flags }" successfully added to message(s) "{ $seqs }"
+flags-removed = Flag(s) "{ $flags }" successfully removed from message(s) "{ $seqs }"
+flags-set = Flag(s) "{ $flags }" successfully set for message(s) "{ $seqs }"
+no-duplicate = No duplicate message found
+duplicates-would-move = Duplicate message(s) { $seqs } would be moved to folder "{ $mbox }"
+duplicates-would-delete = Duplicate message(s) { $seqs } would be deleted
+duplicates-moved = Duplicate message(s) { $seqs } successfully moved to folder "{ $mbox }"
+duplicates-deleted = Duplicate message(s) { $seqs } successfully deleted
+dedupe-aborted = Dedupe aborted
+no-msg-to-expunge = No message to expunge from folder "{ $mbox }"
+msgs-would-expunge = Message(s) { $seqs } would be expunged from folder "{ $mbox }"
+msgs-expunged = { $count } message(s) successfully expunged from folder "{ $mbox }"
+expunge-aborted = Expunge aborted
+mbox-already-empty = Folder "{ $mbox }" is already empty
+mbox-would-empty = { $count } message(s) would be removed from folder "{ $mbox }"
+mbox-emptied = { $count } message(s) successfully removed from folder "{ $mbox }"
+no-retention-policy = No retention policy defined
+msg-exported = Message { $seq } successfully exported to "{ $path }"
+msg-printed = Message { $seq } successfully sent to the printer
+msgs-exported = { $count } message(s) exported to "{ $path }"
+note-label = Note
+msg-has-no-note = Message "{ $seq }" has no note
+msg-snoozed = Message { $seq } successfully snoozed until { $until }
+no-msg-to-wake = No snoozed message to wake up
+msgs-woken = { $count } message(s) woken up
+
+# Errors.
+error = Error
+confirmation-required = cannot expunge { $count } messages without confirmation, use --yes to skip it
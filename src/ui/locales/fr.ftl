@@ -0,0 +1,59 @@
+# Prompts of the interactive choices. The letters in parentheses are the keys to type, they are
+# the same in all locales.
+draft-found = Un brouillon a été trouvé :
+pre-edit-choice = (e) éditer, (d) supprimer ou (q) quitter ?
+post-edit-choice = (s) envoyer, (e) éditer, brouillon (l) local/(r) distant ou (d) supprimer ?
+mdn-request = L'expéditeur demande un accusé de lecture à { $to }.
+mdn-choice = (s) envoyer ou (i) ignorer ?
+confirm-choice = (y) oui ou (n) non ?
+invalid-choice = choix invalide « { $choice } »
+empty-choice = choix vide
+
+# Confirmations.
+confirm-expunge = { $count } messages vont être supprimés définitivement, continuer ?
+confirm-send = Envoyer ce message ?
+
+# Messages of the editor flow.
+msg-sent = Message envoyé
+msg-saved-locally = Message enregistré localement
+msg-saved-drafts = Message enregistré dans les brouillons
+
+# Messages of the commands.
+attachments-downloaded = { $count } pièce(s) jointe(s) téléchargée(s) dans { $dir }
+msg-bounced = Message { $seq } redirigé vers { $to }
+msgs-copied = Message(s) { $seqs } copié(s) dans le dossier « { $mbox } »
+msgs-would-move = Le(s) message(s) { $seqs } serai(en)t déplacé(s) dans le dossier « { $mbox } »
+msgs-moved = Message(s) { $seqs } déplacé(s) dans le dossier « { $mbox } »
+move-aborted = Déplacement annulé
+msgs-would-delete = Le(s) message(s) { $seqs } serai(en)t supprimé(s)
+msgs-deleted = Message(s) { $seqs } supprimé(s)
+deletion-aborted = Suppression annulée
+flags-added = Drapeau(x) « { $flags } » ajouté(s) au(x) message(s) « { $seqs } »
+flags-removed = Drapeau(x) « { $flags } » retiré(s) du/des message(s) « { $seqs } »
+flags-set = Drapeau(x) « { $flags } » défini(s) pour le(s) message(s) « { $seqs } »
+no-duplicate = Aucun message en double
+duplicates-would-move = Le(s) message(s) en double { $seqs } serai(en)t déplacé(s) dans le dossier « { $mbox } »
+duplicates-would-delete = Le(s) message(s) en double { $seqs } serai(en)t supprimé(s)
+duplicates-moved = Message(s) en double { $seqs } déplacé(s) dans le dossier « { $mbox } »
+duplicates-deleted = Message(s) en double { $seqs } supprimé(s)
+dedupe-aborted = Dédoublonnage annulé
+no-msg-to-expunge = Aucun message à supprimer définitivement du dossier « { $mbox } »
+msgs-would-expunge = Le(s) message(s) { $seqs } serai(en)t supprimé(s) définitivement du dossier « { $mbox } »
+msgs-expunged = { $count } message(s) supprimé(s) définitivement du dossier « { $mbox } »
+expunge-aborted = Suppression définitive annulée
+mbox-already-empty = Le dossier « { $mbox } » est déjà vide
+mbox-would-empty = { $count } message(s) serai(en)t retiré(s) du dossier « { $mbox } »
+mbox-emptied = { $count } message(s) retiré(s) du dossier « { $mbox } »
+no-retention-policy = Aucune politique de rétention définie
+msg-exported = Message { $seq } exporté dans « { $path } »
+msg-printed = Message { $seq } envoyé à l'imprimante
+msgs-exported = { $count } message(s) exporté(s) dans « { $path } »
+note-label = Note
+msg-has-no-note = Le message « { $seq } » n'a pas de note
+msg-snoozed = Message { $seq } mis en attente jusqu'au { $until }
+no-msg-to-wake = Aucun message en attente à réveiller
+msgs-woken = { $count } message(s) réveillé(s)
+
+# Errors.
+error = Erreur
+confirmation-required = impossible de supprimer définitivement { $count } messages sans confirmation, utilisez --yes pour l'ignorer
@@ -7,3 +7,4 @@ pub use table::*;
 
 pub mod choice;
 pub mod editor;
+pub mod i18n;
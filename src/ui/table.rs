@@ -113,6 +113,14 @@ impl Cell {
         self.style.set_fg(Some(Color::Ansi256(code)));
         self
     }
+
+    /// Applies the given color to the cell conditionally.
+    pub fn color_if(mut self, color: Option<Color>, predicate: bool) -> Self {
+        if predicate && color.is_some() {
+            self.style.set_fg(color);
+        }
+        self
+    }
 }
 
 /// Makes the cell printable.